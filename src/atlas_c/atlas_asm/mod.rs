@@ -1,9 +1,24 @@
-//The codegen outputs a `Vec<Instruction>` and this crate will try to take it and output a `Vec<u8>` or a `Vec<u16>`
+//! The codegen outputs a `Vec<Instruction>` and this module assembles it into a compact `Vec<u8>`.
+//!
+//! Every instruction is one [`OpCode`] byte followed by its immediates (little-endian).
+//! Names (variables & fields) are replaced by an index in [`Assembly::symbols`],
+//! calls by the absolute position of the callee and relative jumps by a signed byte offset
+//! starting from the jump opcode itself.
+//!
+//! Only linked programs (see [`crate::atlas_c::atlas_linker`]) can be assembled.
+//! The result runs with [`crate::atlas_vm::Atlas77VM::run_assembly`], e.g. with `atlas_77 run --assembled`.
 
+use std::collections::HashMap;
+
+use heck::ToSnakeCase;
+use miette::Diagnostic;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::atlas_vm::runtime::instruction::{Instruction, Program, Type};
 
 #[repr(u8)]
-#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub enum OpCode {
     /// No operation
     Nop = 0x00,
@@ -67,6 +82,14 @@ pub enum OpCode {
     /// Store the value of a variable to the stack
     /// The variable index is stored in the next 4 bytes
     Store,
+    /// Push unit values to make room for the locals of the current function
+    ///
+    /// The number of locals is stored in the next 4 bytes
+    Reserve,
+    /// Cast the top of the stack value
+    ///
+    /// The target [`Type`] is stored in the next 1 byte
+    CastTo,
 
     // Arithmetics
 
@@ -181,24 +204,53 @@ pub enum OpCode {
     ///
     /// The number of arguments is stored in the next 1 byte
     Call,
+    /// Call a function by taking the value at a given stack position as the fn_ptr
+    ///
+    /// The stack position is stored in the next 4 bytes
+    /// The number of arguments is stored in the next 1 byte
+    DirectCall,
+    /// Call a function at an absolute position
+    ///
+    /// The position is stored in the next 4 bytes
+    /// The number of arguments is stored in the next 1 byte
+    FunctionCall,
     /// Call an external function and return the result to the top of the stack
     ///
-    /// The function name is a string stored in the constant pool. The function name index is stored in the next 4 bytes
+    /// The function index in the native pool is stored in the next 4 bytes
     /// The number of arguments is stored in the next 1 byte
     ExternCall,
     Return,
 
+    // Objects
+
+    /// Create a new object and push its pointer to the stack
+    ///
+    /// The class index in the class pool is stored in the next 4 bytes
+    NewObj,
+    /// Delete the object whose pointer is at the top of the stack
+    DeleteObj,
+    /// Stack:
+    /// - **[ClassPtr,] -> [FieldValue,]**
+    ///
+    /// The field name index in the symbol table is stored in the next 4 bytes
+    GetField,
+    /// Stack:
+    /// - **[ClassPtr, Value] -> []**
+    ///
+    /// The field name index in the symbol table is stored in the next 4 bytes
+    SetField,
+
     // List operations
 
-    /// Create a new empty list and push its pointer to the stack
+    /// Create a new list and push its pointer to the stack
     ///
-    /// The number of elements is stored in the next 4 bytes
+    /// The number of elements is popped from the stack
     NewList,
     /// Copy a list to another one 
     ListCopy,
     /// Stack state:
     ///
-    /// - **Bottom** `[Index, ListPointer]` **Top**
+    /// - **Bottom** `[ListPointer, Index]` **Top**
     ///
     /// Load a value from a list to the top of the stack
     ListLoad,
@@ -208,6 +260,584 @@ pub enum OpCode {
     ///
     /// Store a value in a given list
     ListStore,
+    /// The index is stored in the next 8 bytes
+    ListIndex,
+
+    // String operations
+
+    /// Stack state:
+    ///
+    /// - **Bottom** `[StrPointer, Index]` **Top**
+    ///
+    /// Load a char from a string to the top of the stack
+    StringLoad,
+    /// Stack state:
+    ///
+    /// - **Bottom** `[Index, StrPointer, Value]` **Top**
+    ///
+    /// Store a char in a given string
+    StringStore,
 
     Halt,
+
+    /// Pop an integer `v` & run the `v - min`th of the `RJmp` following, the last one if `v` is out of range
+    ///
+    /// `min` is stored in the next 8 bytes, the number of entries (the last one excluded) in the next 4 bytes
+    JmpTable,
+    /// Stack state:
+    ///
+    /// - **Bottom** `[Field1, ..., FieldN]` **Top**
+    ///
+    /// The tag is stored in the next 4 bytes, the number of fields in the next byte
+    NewUnion,
+    /// Replace the union on top of the stack by its tag
+    UnionTag,
+    /// Replace the union on top of the stack by one of its fields, whose index is stored in the next 4 bytes
+    UnionField,
+}
+
+impl OpCode {
+    const ALL: [OpCode; 62] = [
+        OpCode::Nop,
+        OpCode::PushInteger,
+        OpCode::PushFloat,
+        OpCode::PushUnsignedInteger,
+        OpCode::PushBoolean,
+        OpCode::PushChar,
+        OpCode::PushStr,
+        OpCode::PushFnPtr,
+        OpCode::PushList,
+        OpCode::PushUnit,
+        OpCode::Pop,
+        OpCode::Swap,
+        OpCode::Dup,
+        OpCode::Get,
+        OpCode::Store,
+        OpCode::Reserve,
+        OpCode::CastTo,
+        OpCode::IMul,
+        OpCode::FMul,
+        OpCode::UIMul,
+        OpCode::IDiv,
+        OpCode::FDiv,
+        OpCode::UIDiv,
+        OpCode::ISub,
+        OpCode::FSub,
+        OpCode::UISub,
+        OpCode::IMod,
+        OpCode::FMod,
+        OpCode::UIMod,
+        OpCode::IAdd,
+        OpCode::FAdd,
+        OpCode::UIAdd,
+        OpCode::Eq,
+        OpCode::Neq,
+        OpCode::Gt,
+        OpCode::Gte,
+        OpCode::Lt,
+        OpCode::Lte,
+        OpCode::DJmp,
+        OpCode::RJmp,
+        OpCode::JmpZ,
+        OpCode::Call,
+        OpCode::DirectCall,
+        OpCode::FunctionCall,
+        OpCode::ExternCall,
+        OpCode::Return,
+        OpCode::NewObj,
+        OpCode::DeleteObj,
+        OpCode::GetField,
+        OpCode::SetField,
+        OpCode::NewList,
+        OpCode::ListCopy,
+        OpCode::ListLoad,
+        OpCode::ListStore,
+        OpCode::ListIndex,
+        OpCode::StringLoad,
+        OpCode::StringStore,
+        OpCode::Halt,
+        OpCode::JmpTable,
+        OpCode::NewUnion,
+        OpCode::UnionTag,
+        OpCode::UnionField,
+    ];
+
+    /// Decode an opcode byte
+    #[inline(always)]
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        Self::ALL.get(byte as usize).copied()
+    }
+
+    /// Number of bytes of immediates following the opcode
+    pub fn immediate_size(&self) -> usize {
+        use OpCode::*;
+        match self {
+            PushInteger | PushFloat | PushUnsignedInteger | PushList | ListIndex => 8,
+            PushChar | PushStr | PushFnPtr | Get | Store | Reserve | DJmp | RJmp | JmpZ | NewObj
+            | GetField | SetField | UnionField => 4,
+            JmpTable => 12,
+            DirectCall | FunctionCall | ExternCall | NewUnion => 5,
+            PushBoolean | CastTo | Call => 1,
+            _ => 0,
+        }
+    }
+}
+
+impl Type {
+    pub fn from_byte(byte: u8) -> Option<Type> {
+        match byte {
+            0 => Some(Type::Integer),
+            1 => Some(Type::Float),
+            2 => Some(Type::UnsignedInteger),
+            3 => Some(Type::Boolean),
+            4 => Some(Type::String),
+            5 => Some(Type::Char),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Error, Diagnostic, Debug)]
+pub enum AsmError {
+    #[error("`{0}` is not linked")]
+    #[diagnostic(
+        code(asm::unlinked_symbol),
+        help("Run the program through `atlas_linker::link` before assembling it")
+    )]
+    UnlinkedSymbol(String),
+    #[error("unknown label #{0}")]
+    #[diagnostic(code(asm::unknown_label))]
+    UnknownLabel(usize),
+    #[error("jump target out of the program bounds at instruction {0}")]
+    #[diagnostic(code(asm::invalid_jump))]
+    InvalidJump(usize),
+}
+
+pub type AsmResult<T> = Result<T, AsmError>;
+
+/// A [`Program`] lowered to its byte encoding
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assembly<'run> {
+    pub code: Vec<u8>,
+    /// Names referenced by `GetField` & `SetField`
+    pub symbols: Vec<&'run str>,
+    /// Names of the native functions referenced by `ExternCall`
+    pub natives: Vec<&'run str>,
+    /// Name and byte position of every label, in program order
+    pub labels: Vec<(&'run str, usize)>,
+    /// Byte position of every function of the constant pool's `function_pool`
+    pub function_pool: Vec<usize>,
+    pub entry_point: Option<usize>,
+}
+
+impl Assembly<'_> {
+    /// Index in `labels` of the label containing the byte at `pos`
+    pub fn label_index_at(&self, pos: usize) -> Option<usize> {
+        let idx = self.labels.partition_point(|(_, start)| *start <= pos);
+        idx.checked_sub(1).filter(|_| pos < self.code.len())
+    }
+}
+
+/// Encode a whole program
+pub fn assemble<'run>(program: &Program<'run>) -> AsmResult<Assembly<'run>> {
+    let instructions = program
+        .labels
+        .iter()
+        .flat_map(|label| label.body.iter())
+        .collect::<Vec<_>>();
+
+    // First pass: byte position of every instruction (plus the end of the program)
+    let mut positions = Vec::with_capacity(instructions.len() + 1);
+    let mut size = 0;
+    for instr in instructions.iter() {
+        positions.push(size);
+        size += 1 + opcode_of(instr).immediate_size();
+    }
+    positions.push(size);
+
+    let mut labels = Vec::with_capacity(program.labels.len());
+    let mut index = 0;
+    for label in program.labels.iter() {
+        labels.push((label.name, positions[index]));
+        index += label.body.len();
+    }
+
+    let mut asm = Assembly {
+        code: Vec::with_capacity(size),
+        symbols: Vec::new(),
+        natives: program.global.native_pool.to_vec(),
+        entry_point: labels
+            .iter()
+            .find(|(name, _)| *name == program.entry_point)
+            .map(|(_, pos)| *pos),
+        function_pool: program
+            .global
+            .function_pool
+            .iter()
+            .map(|pos| positions.get(*pos).copied().unwrap_or(size))
+            .collect(),
+        labels,
+    };
+    let mut symbols = HashMap::new();
+    let mut symbol = |asm: &mut Assembly<'run>, name: &'run str| -> u32 {
+        *symbols.entry(name).or_insert_with(|| {
+            asm.symbols.push(name);
+            (asm.symbols.len() - 1) as u32
+        })
+    };
+    let label_positions = asm.labels.iter().map(|(_, pos)| *pos).collect::<Vec<_>>();
+    let call_target = |label: usize| -> AsmResult<u32> {
+        label_positions
+            .get(label)
+            .map(|pos| *pos as u32)
+            .ok_or(AsmError::UnknownLabel(label))
+    };
+    let jump = |from: usize, to: isize| -> AsmResult<i32> {
+        let target = positions
+            .get(usize::try_from(to).map_err(|_| AsmError::InvalidJump(from))?)
+            .ok_or(AsmError::InvalidJump(from))?;
+        Ok((*target as isize - positions[from] as isize) as i32)
+    };
+
+    for (i, instr) in instructions.iter().enumerate() {
+        let code = &mut asm.code;
+        code.push(opcode_of(instr) as u8);
+        match instr {
+            Instruction::PushInt(v) => code.extend_from_slice(&v.to_le_bytes()),
+            Instruction::PushFloat(v) => code.extend_from_slice(&v.to_le_bytes()),
+            Instruction::PushUnsignedInt(v) => code.extend_from_slice(&v.to_le_bytes()),
+            Instruction::PushBool(v) => code.push(*v as u8),
+            Instruction::PushChar(v) => code.extend_from_slice(&(*v as u32).to_le_bytes()),
+            Instruction::PushStr(v)
+            | Instruction::Get(v)
+            | Instruction::Store(v)
+            | Instruction::Reserve(v)
+            | Instruction::UnionField(v) => {
+                code.extend_from_slice(&(*v as u32).to_le_bytes())
+            }
+            Instruction::PushList(v) | Instruction::ListIndex(v) => {
+                code.extend_from_slice(&(*v as u64).to_le_bytes())
+            }
+            Instruction::CastTo(t) => code.push(*t as u8),
+            Instruction::NewUnion { tag, nb_fields } => {
+                code.extend_from_slice(&(*tag as u32).to_le_bytes());
+                code.push(*nb_fields as u8);
+            }
+            Instruction::Jmp { pos } => {
+                let offset = jump(i, i as isize + pos)?;
+                asm.code.extend_from_slice(&offset.to_le_bytes());
+            }
+            Instruction::JmpZ { pos } => {
+                let offset = jump(i, i as isize + pos + 1)?;
+                asm.code.extend_from_slice(&offset.to_le_bytes());
+            }
+            Instruction::JmpTable { min, len } => {
+                code.extend_from_slice(&min.to_le_bytes());
+                code.extend_from_slice(&(*len as u32).to_le_bytes());
+            }
+            Instruction::DirectCall { pos, args } => {
+                code.extend_from_slice(&(*pos as u32).to_le_bytes());
+                code.push(*args);
+            }
+            Instruction::Call { nb_args } => code.push(*nb_args),
+            Instruction::LinkedCall { label, nb_args } => {
+                let target = call_target(*label)?;
+                code.extend_from_slice(&target.to_le_bytes());
+                code.push(*nb_args);
+            }
+            Instruction::LinkedNativeCall { native, nb_args } => {
+                code.extend_from_slice(&(*native as u32).to_le_bytes());
+                code.push(*nb_args);
+            }
+            Instruction::FunctionCall { function_name: name, .. }
+            | Instruction::MethodCall { method_name: name, .. }
+            | Instruction::StaticCall { method_name: name, .. }
+            | Instruction::ExternCall { function_name: name, .. }
+            | Instruction::NewObj { class_name: name } => {
+                return Err(AsmError::UnlinkedSymbol(name.to_string()));
+            }
+            Instruction::GetField { field_name: name }
+            | Instruction::SetField { field_name: name } => {
+                let idx = symbol(&mut asm, name);
+                asm.code.extend_from_slice(&idx.to_le_bytes());
+            }
+            Instruction::LinkedNewObj { class } => {
+                code.extend_from_slice(&(*class as u32).to_le_bytes())
+            }
+            _ => {}
+        }
+    }
+    Ok(asm)
+}
+
+fn opcode_of(instr: &Instruction) -> OpCode {
+    match instr {
+        Instruction::PushInt(_) => OpCode::PushInteger,
+        Instruction::PushFloat(_) => OpCode::PushFloat,
+        Instruction::PushUnsignedInt(_) => OpCode::PushUnsignedInteger,
+        Instruction::PushBool(_) => OpCode::PushBoolean,
+        Instruction::PushChar(_) => OpCode::PushChar,
+        Instruction::PushStr(_) => OpCode::PushStr,
+        Instruction::PushList(_) => OpCode::PushList,
+        Instruction::PushUnit => OpCode::PushUnit,
+        Instruction::Get(_) => OpCode::Get,
+        Instruction::Pop => OpCode::Pop,
+        Instruction::Swap => OpCode::Swap,
+        Instruction::Dup => OpCode::Dup,
+        Instruction::Store(_) => OpCode::Store,
+        Instruction::Reserve(_) => OpCode::Reserve,
+        Instruction::ListLoad => OpCode::ListLoad,
+        Instruction::ListStore => OpCode::ListStore,
+        Instruction::NewList => OpCode::NewList,
+        Instruction::ListIndex(_) => OpCode::ListIndex,
+        Instruction::StringLoad => OpCode::StringLoad,
+        Instruction::StringStore => OpCode::StringStore,
+        Instruction::CastTo(_) => OpCode::CastTo,
+        Instruction::IAdd => OpCode::IAdd,
+        Instruction::FAdd => OpCode::FAdd,
+        Instruction::UIAdd => OpCode::UIAdd,
+        Instruction::ISub => OpCode::ISub,
+        Instruction::FSub => OpCode::FSub,
+        Instruction::UISub => OpCode::UISub,
+        Instruction::IMul => OpCode::IMul,
+        Instruction::FMul => OpCode::FMul,
+        Instruction::UIMul => OpCode::UIMul,
+        Instruction::IDiv => OpCode::IDiv,
+        Instruction::FDiv => OpCode::FDiv,
+        Instruction::UIDiv => OpCode::UIDiv,
+        Instruction::IMod => OpCode::IMod,
+        Instruction::FMod => OpCode::FMod,
+        Instruction::UIMod => OpCode::UIMod,
+        Instruction::Eq => OpCode::Eq,
+        Instruction::Neq => OpCode::Neq,
+        Instruction::Gt => OpCode::Gt,
+        Instruction::Gte => OpCode::Gte,
+        Instruction::Lt => OpCode::Lt,
+        Instruction::Lte => OpCode::Lte,
+        Instruction::Jmp { .. } => OpCode::RJmp,
+        Instruction::JmpZ { .. } => OpCode::JmpZ,
+        Instruction::JmpTable { .. } => OpCode::JmpTable,
+        Instruction::DirectCall { .. } => OpCode::DirectCall,
+        Instruction::Call { .. } => OpCode::Call,
+        Instruction::FunctionCall { .. }
+        | Instruction::MethodCall { .. }
+        | Instruction::StaticCall { .. }
+        | Instruction::LinkedCall { .. } => OpCode::FunctionCall,
+        Instruction::ExternCall { .. } | Instruction::LinkedNativeCall { .. } => OpCode::ExternCall,
+        Instruction::Return => OpCode::Return,
+        Instruction::DeleteObj => OpCode::DeleteObj,
+        Instruction::GetField { .. } => OpCode::GetField,
+        Instruction::SetField { .. } => OpCode::SetField,
+        Instruction::NewObj { .. } | Instruction::LinkedNewObj { .. } => OpCode::NewObj,
+        Instruction::Halt => OpCode::Halt,
+        Instruction::NewUnion { .. } => OpCode::NewUnion,
+        Instruction::UnionTag => OpCode::UnionTag,
+        Instruction::UnionField(_) => OpCode::UnionField,
+    }
+}
+
+/// Read the `N` bytes of immediate starting at `pos`
+#[inline(always)]
+pub fn read_imm<const N: usize>(code: &[u8], pos: usize) -> [u8; N] {
+    let mut buf = [0; N];
+    buf.copy_from_slice(&code[pos..pos + N]);
+    buf
+}
+
+/// Turn an assembly back into a human-readable listing
+pub fn disassemble(asm: &Assembly) -> String {
+    let label_at = asm
+        .labels
+        .iter()
+        .map(|(name, pos)| (*pos, *name))
+        .collect::<HashMap<_, _>>();
+    let code = &asm.code;
+    let mut out = String::new();
+    let mut pc = 0;
+    while pc < code.len() {
+        if let Some(name) = label_at.get(&pc) {
+            out.push_str(&format!("{}:\n", name));
+        }
+        let Some(op) = OpCode::from_byte(code[pc]) else {
+            out.push_str(&format!("    {:04x}  <invalid opcode {:#04x}>\n", pc, code[pc]));
+            pc += 1;
+            continue;
+        };
+        let imm = pc + 1;
+        let u32_at = |pos| u32::from_le_bytes(read_imm(code, pos));
+        let symbol = |pos| asm.symbols.get(u32_at(pos) as usize).copied().unwrap_or("?");
+        let operands = match op {
+            OpCode::PushInteger => i64::from_le_bytes(read_imm(code, imm)).to_string(),
+            OpCode::PushFloat => f64::from_le_bytes(read_imm(code, imm)).to_string(),
+            OpCode::PushUnsignedInteger | OpCode::PushList | OpCode::ListIndex => {
+                u64::from_le_bytes(read_imm(code, imm)).to_string()
+            }
+            OpCode::PushBoolean => (code[imm] != 0).to_string(),
+            OpCode::PushChar => format!("{:?}", char::from_u32(u32_at(imm)).unwrap_or('?')),
+            OpCode::PushStr
+            | OpCode::PushFnPtr
+            | OpCode::Get
+            | OpCode::Store
+            | OpCode::Reserve
+            | OpCode::NewObj
+            | OpCode::UnionField
+            | OpCode::DJmp => u32_at(imm).to_string(),
+            OpCode::GetField | OpCode::SetField => symbol(imm).to_string(),
+            OpCode::CastTo => match Type::from_byte(code[imm]) {
+                Some(t) => format!("{:?}", t),
+                None => String::from("?"),
+            },
+            OpCode::RJmp | OpCode::JmpZ => {
+                let offset = i32::from_le_bytes(read_imm(code, imm));
+                format!("{:+} ({:04x})", offset, pc as isize + offset as isize)
+            }
+            OpCode::JmpTable => format!("{}, {}", i64::from_le_bytes(read_imm(code, imm)), u32_at(imm + 8)),
+            OpCode::Call => code[imm].to_string(),
+            OpCode::DirectCall | OpCode::NewUnion => format!("{}, {}", u32_at(imm), code[imm + 4]),
+            OpCode::FunctionCall => {
+                let target = u32_at(imm) as usize;
+                match label_at.get(&target) {
+                    Some(name) => format!("{}, {}", name, code[imm + 4]),
+                    None => format!("{:04x}, {}", target, code[imm + 4]),
+                }
+            }
+            OpCode::ExternCall => {
+                let native = asm.natives.get(u32_at(imm) as usize).copied().unwrap_or("?");
+                format!("{}, {}", native, code[imm + 4])
+            }
+            _ => String::new(),
+        };
+        let name = format!("{:?}", op).to_snake_case();
+        if operands.is_empty() {
+            out.push_str(&format!("    {:04x}  {}\n", pc, name));
+        } else {
+            out.push_str(&format!("    {:04x}  {} {}\n", pc, name, operands));
+        }
+        pc = imm + op.immediate_size();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atlas_c::atlas_codegen::arena::CodeGenArena;
+    use crate::atlas_c::atlas_linker::link;
+    use crate::atlas_vm::runtime::instruction::Label;
+    use bumpalo::Bump;
+
+    /// Byte position of every instruction
+    fn positions(asm: &Assembly) -> Vec<usize> {
+        let mut positions = Vec::new();
+        let mut pc = 0;
+        while pc < asm.code.len() {
+            positions.push(pc);
+            pc += 1 + OpCode::from_byte(asm.code[pc]).unwrap().immediate_size();
+        }
+        positions
+    }
+
+    /// Where the relative jump at `pos` lands
+    fn jump_target(asm: &Assembly, pos: usize) -> usize {
+        (pos as isize + i32::from_le_bytes(read_imm(&asm.code, pos + 1)) as isize) as usize
+    }
+
+    #[test]
+    fn calls_and_jumps_target_byte_positions() {
+        let main = [
+            Instruction::Reserve(1),
+            Instruction::PushInt(5),
+            Instruction::FunctionCall {
+                function_name: "double",
+                nb_args: 1,
+            },
+            Instruction::Store(0),
+            Instruction::Get(0),
+            Instruction::Halt,
+        ];
+        let double = [
+            Instruction::Get(0),
+            Instruction::PushInt(0),
+            Instruction::Gt,
+            Instruction::JmpZ { pos: 4 },
+            Instruction::Get(0),
+            Instruction::Get(0),
+            Instruction::IAdd,
+            Instruction::Jmp { pos: 2 },
+            Instruction::PushInt(-1),
+            Instruction::Return,
+        ];
+        let bump = Bump::new();
+        let mut program = Program::new();
+        program.entry_point = String::from("main");
+        program.labels = vec![
+            Label {
+                name: "main",
+                position: 0,
+                body: &main,
+                spans: &[],
+                locals: &[],
+            },
+            Label {
+                name: "double",
+                position: main.len(),
+                body: &double,
+                spans: &[],
+                locals: &[],
+            },
+        ];
+
+        let program = link(&program, &CodeGenArena::new(&bump), &[]).unwrap();
+        let asm = assemble(&program).unwrap();
+        let listing = disassemble(&asm);
+        assert!(listing.contains("function_call double, 1"));
+        assert!(listing.contains("jmp_z"));
+
+        let pos = positions(&asm);
+        assert_eq!(pos.len(), main.len() + double.len());
+        assert_eq!(asm.labels, vec![("main", pos[0]), ("double", pos[6])]);
+        assert_eq!(asm.entry_point, Some(pos[0]));
+        assert_eq!(u32::from_le_bytes(read_imm(&asm.code, pos[2] + 1)) as usize, pos[6]);
+        //`JmpZ { pos: 4 }` skips the 4 following instructions, `Jmp { pos: 2 }` is relative to itself
+        assert_eq!(jump_target(&asm, pos[9]), pos[14]);
+        assert_eq!(jump_target(&asm, pos[13]), pos[15]);
+    }
+
+    #[test]
+    fn jump_tables_are_followed_by_their_jumps() {
+        let main = [
+            Instruction::PushInt(2),
+            Instruction::JmpTable { min: 1, len: 2 },
+            Instruction::Jmp { pos: 3 },
+            Instruction::Jmp { pos: 4 },
+            Instruction::Jmp { pos: 5 },
+            Instruction::PushInt(10),
+            Instruction::Halt,
+            Instruction::PushInt(20),
+            Instruction::Halt,
+            Instruction::PushInt(30),
+            Instruction::Halt,
+        ];
+        let bump = Bump::new();
+        let mut program = Program::new();
+        program.entry_point = String::from("main");
+        program.labels = vec![Label {
+            name: "main",
+            position: 0,
+            body: &main,
+            spans: &[],
+            locals: &[],
+        }];
+
+        let program = link(&program, &CodeGenArena::new(&bump), &[]).unwrap();
+        let asm = assemble(&program).unwrap();
+        assert!(disassemble(&asm).contains("jmp_table 1, 2"));
+
+        let pos = positions(&asm);
+        assert_eq!(OpCode::from_byte(asm.code[pos[1]]), Some(OpCode::JmpTable));
+        for (entry, target) in [(2, 5), (3, 7), (4, 9)] {
+            assert_eq!(OpCode::from_byte(asm.code[pos[entry]]), Some(OpCode::RJmp));
+            assert_eq!(jump_target(&asm, pos[entry]), pos[target]);
+        }
+    }
 }
@@ -4,14 +4,16 @@ mod table;
 
 use crate::atlas_c::atlas_hir::{
     error::{HirResult, UnsupportedExpr, UnsupportedStatement},
-    expr::HirExpr,
-    signature::HirFunctionParameterSignature,
+    expr::{HirExpr, HirMatchExpr, HirPattern, HirStaticAccessExpr, HirVariantPattern},
     stmt::{HirBlock, HirStatement},
     ty::HirTy,
     HirModule,
 };
-use crate::atlas_vm::runtime::instruction::{ConstantClass, ImportedLibrary, Instruction, Label, Program, Type};
-use std::collections::BTreeMap;
+use crate::atlas_vm::libraries;
+use crate::atlas_vm::runtime::instruction::{
+    ConstantClass, ImportedLibrary, Instruction, Label, Program, SpanEntry, Type,
+};
+use std::collections::{BTreeMap, HashMap};
 
 use crate::atlas_c::atlas_codegen::table::_Table;
 use crate::atlas_c::atlas_hir;
@@ -20,8 +22,12 @@ use crate::atlas_c::atlas_hir::expr::HirUnaryOp;
 use crate::atlas_c::atlas_hir::item::{HirClass, HirClassConstructor};
 use crate::atlas_c::atlas_hir::signature::{ConstantValue, HirClassMethodModifier};
 use arena::CodeGenArena;
+use logos::Span;
 use miette::{SourceOffset, SourceSpan};
 
+/// Fewest distinct values for an integer `match` to use a jump table
+const MIN_JUMP_TABLE_VALUES: usize = 4;
+
 /// Result of codegen
 pub type CodegenResult<T> = Result<T, HirError>;
 
@@ -33,8 +39,14 @@ where
     hir: HirModule<'hir>,
     program: Program<'gen>,
     arena: CodeGenArena<'gen>,
-    //simulate a var_map so the codegen can translate it into stack operations
-    _variables: _Table<&'hir str>,
+    //stack slot of every local of the function being generated, one map per lexical scope
+    locals: Vec<HashMap<&'hir str, usize>>,
+    //number of slots used by the function being generated, arguments included
+    nb_slots: usize,
+    //name of every slot of the function being generated
+    slot_names: Vec<&'hir str>,
+    //line table of the function being generated
+    spans: Vec<SpanEntry>,
     //store the function position
     _global: _Table<&'hir str>,
     current_pos: usize,
@@ -51,7 +63,10 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
             hir,
             program: Program::new(),
             arena,
-            _variables: _Table::new(),
+            locals: Vec::new(),
+            nb_slots: 0,
+            slot_names: Vec::new(),
+            spans: Vec::new(),
             _global: _Table::new(),
             current_pos: 0,
             string_pool: Vec::new(),
@@ -61,24 +76,28 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
     }
 
     /// Take the HIR and convert it to a VM representation
-    pub fn compile(&mut self) -> CodegenResult<Program> {
+    pub fn compile(&mut self) -> CodegenResult<Program<'gen>> {
         let mut labels: Vec<Label> = Vec::new();
         for (func_name, function) in self.hir.body.functions.clone() {
-            let mut bytecode = Vec::new();
+            let mut body = Vec::new();
 
-            let params = function.signature.params.clone();
-            self.generate_bytecode_args(params, &mut bytecode)?;
-            self.generate_bytecode_block(&function.body, &mut bytecode, self.src.clone())?;
+            self.begin_function(function.signature.params.iter().map(|p| p.name));
+            self.generate_bytecode_block(&function.body, &mut body, self.src.clone())?;
 
             if func_name == "main" {
-                bytecode.push(Instruction::Halt);
+                body.push(Instruction::Halt);
+            } else {
+                Self::implicit_return(&mut body);
             }
+            let bytecode = self.reserve_locals(function.signature.params.len(), body);
             let len = bytecode.len();
 
             labels.push(Label {
                 name: self.arena.alloc(func_name.to_string()),
                 position: self.current_pos,
                 body: self.arena.alloc(bytecode),
+                spans: self.arena.alloc(std::mem::take(&mut self.spans)),
+                locals: self.local_names(),
             });
 
             self.current_pos += len;
@@ -99,7 +118,7 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
             .iter()
             .map(|l| ImportedLibrary {
                 name: l.path.to_string(),
-                is_std: true,
+                is_std: libraries::std_library(l.path).is_some(),
             })
             .collect::<Vec<_>>();
         self.program.libraries = libraries;
@@ -114,15 +133,19 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
         src: String,
     ) -> HirResult<()> {
         for method in class.methods.iter() {
-            let mut bytecode = Vec::new();
-            let params = method.signature.params.clone();
-            self.generate_bytecode_args(params, &mut bytecode)?;
-            if method.signature.modifier != HirClassMethodModifier::Static {
-                bytecode.push(Instruction::Store {
-                    var_name: self.arena.alloc(String::from("self")),
-                });
-            }
-            self.generate_bytecode_block(&method.body, &mut bytecode, src.clone())?;
+            let mut body = Vec::new();
+            let params = method.signature.params.iter().map(|p| p.name);
+            //`self` is pushed before the arguments
+            let nb_params = if method.signature.modifier != HirClassMethodModifier::Static {
+                self.begin_function(std::iter::once("self").chain(params));
+                method.signature.params.len() + 1
+            } else {
+                self.begin_function(params);
+                method.signature.params.len()
+            };
+            self.generate_bytecode_block(&method.body, &mut body, src.clone())?;
+            Self::implicit_return(&mut body);
+            let bytecode = self.reserve_locals(nb_params, body);
             let len = bytecode.len();
             labels.push(Label {
                 name: self.arena.alloc(
@@ -134,6 +157,8 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
                 ),
                 position: self.current_pos,
                 body: self.arena.alloc(bytecode),
+                spans: self.arena.alloc(std::mem::take(&mut self.spans)),
+                locals: self.local_names(),
             });
             self.current_pos += len;
         }
@@ -165,28 +190,24 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
         labels: &mut Vec<Label<'gen>>,
         src: String,
     ) -> HirResult<()> {
-        let mut bytecode = Vec::new();
-        let params = constructor.params.clone();
+        let mut body = Vec::new();
+        //self reference of the object, pushed by `NewObj` before the arguments
+        self.begin_function(std::iter::once("self").chain(constructor.params.iter().map(|p| p.name)));
 
-        self.generate_bytecode_args(params, &mut bytecode)?;
-        //self reference of the object
-        bytecode.push(Instruction::Store {
-            var_name: self.arena.alloc(String::from("self")),
-        });
-
-        self.generate_bytecode_block(&constructor.body, &mut bytecode, src.clone())?;
+        self.generate_bytecode_block(&constructor.body, &mut body, src.clone())?;
 
         //Return the self reference
-        bytecode.push(Instruction::Load {
-            var_name: self.arena.alloc(String::from("self")),
-        });
-        bytecode.push(Instruction::Return);
+        body.push(Instruction::Get(0));
+        body.push(Instruction::Return);
+        let bytecode = self.reserve_locals(constructor.params.len() + 1, body);
 
         let len = bytecode.len();
         labels.push(Label {
             name: self.arena.alloc(format!("{}.{}", class_name, class_name)),
             position: self.current_pos,
             body: self.arena.alloc(bytecode),
+            spans: self.arena.alloc(std::mem::take(&mut self.spans)),
+            locals: self.local_names(),
         });
         self.current_pos += len;
 
@@ -199,9 +220,11 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
         bytecode: &mut Vec<Instruction<'gen>>,
         src: String,
     ) -> HirResult<()> {
+        self.locals.push(HashMap::new());
         for stmt in &block.statements {
             self.generate_bytecode_stmt(stmt, bytecode, src.clone())?;
         }
+        self.locals.pop();
         Ok(())
     }
 
@@ -211,6 +234,7 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
         bytecode: &mut Vec<Instruction<'gen>>,
         src: String,
     ) -> HirResult<()> {
+        self.mark_span(bytecode.len(), stmt.span());
         match stmt {
             HirStatement::Return(e) => {
                 self.generate_bytecode_expr(&e.value, bytecode, src)?;
@@ -218,56 +242,55 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
             }
             HirStatement::IfElse(i) => {
                 self.generate_bytecode_expr(&i.condition, bytecode, src.clone())?;
-                let mut then_body = Vec::new();
-                self.generate_bytecode_block(&i.then_branch, &mut then_body, src.clone())?;
+                //The jumps are patched once the size of the branches is known
+                let jmp_z = bytecode.len();
+                bytecode.push(Instruction::JmpZ { pos: 0 });
+                self.generate_bytecode_block(&i.then_branch, bytecode, src.clone())?;
 
-                bytecode.push(Instruction::JmpZ {
-                    pos: (then_body.len() + if i.else_branch.is_some() { 1 } else { 0 }) as isize,
-                });
-                bytecode.append(&mut then_body);
                 if let Some(e) = &i.else_branch {
-                    let mut else_body = Vec::new();
-                    self.generate_bytecode_block(e, &mut else_body, src)?;
-
-                    bytecode.push(Instruction::Jmp {
-                        pos: (else_body.len() + 1) as isize,
-                    });
-                    bytecode.append(&mut else_body);
+                    self.mark_span(bytecode.len(), stmt.span());
+                    let jmp = bytecode.len();
+                    bytecode.push(Instruction::Jmp { pos: 0 });
+                    bytecode[jmp_z] = Instruction::JmpZ {
+                        pos: (jmp - jmp_z) as isize,
+                    };
+                    self.generate_bytecode_block(e, bytecode, src)?;
+                    bytecode[jmp] = Instruction::Jmp {
+                        pos: (bytecode.len() - jmp) as isize,
+                    };
+                } else {
+                    bytecode[jmp_z] = Instruction::JmpZ {
+                        pos: (bytecode.len() - jmp_z - 1) as isize,
+                    };
                 }
             }
             HirStatement::While(w) => {
                 let start = bytecode.len() as isize;
                 self.generate_bytecode_expr(&w.condition, bytecode, src.clone())?;
-                let mut body = Vec::new();
+                let jmp_z = bytecode.len();
+                bytecode.push(Instruction::JmpZ { pos: 0 });
 
-                self.generate_bytecode_block(&w.body, &mut body, src)?;
-                //If the condition is false jump to the end of the loop
-                bytecode.push(Instruction::JmpZ {
-                    pos: (body.len() + 1) as isize,
-                });
-                bytecode.append(&mut body);
+                self.generate_bytecode_block(&w.body, bytecode, src)?;
                 //Jump back to the start of the loop
+                self.mark_span(bytecode.len(), stmt.span());
                 bytecode.push(Instruction::Jmp {
                     pos: start - bytecode.len() as isize,
                 });
+                //If the condition is false jump to the end of the loop
+                bytecode[jmp_z] = Instruction::JmpZ {
+                    pos: (bytecode.len() - jmp_z - 1) as isize,
+                };
             }
             HirStatement::Const(let_stmt) => {
-                println!("const {}: {}", let_stmt.name, let_stmt.ty.unwrap());
-                let mut value = Vec::new();
-                self.generate_bytecode_expr(&let_stmt.value, &mut value, src)?;
-                value.push(Instruction::Store {
-                    var_name: self.arena.alloc(let_stmt.name.to_string()),
-                });
-                bytecode.append(&mut value);
+                self.generate_bytecode_expr(&let_stmt.value, bytecode, src)?;
+                //The slot is declared after the value so `let x = x;` still reads the outer `x`
+                let slot = self.declare_local(let_stmt.name);
+                bytecode.push(Instruction::Store(slot));
             }
             HirStatement::Let(let_stmt) => {
-                println!("let {}: {}", let_stmt.name, let_stmt.ty.unwrap());
-                let mut value = Vec::new();
-                self.generate_bytecode_expr(&let_stmt.value, &mut value, src)?;
-                value.push(Instruction::Store {
-                    var_name: self.arena.alloc(let_stmt.name.to_string()),
-                });
-                bytecode.append(&mut value);
+                self.generate_bytecode_expr(&let_stmt.value, bytecode, src)?;
+                let slot = self.declare_local(let_stmt.name);
+                bytecode.push(Instruction::Store(slot));
             }
             HirStatement::Expr(e) => {
                 self.generate_bytecode_expr(&e.expr, bytecode, src)?;
@@ -301,9 +324,8 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
                 match lhs {
                     HirExpr::Ident(i) => {
                         self.generate_bytecode_expr(&a.rhs, bytecode, src.clone())?;
-                        bytecode.push(Instruction::Store {
-                            var_name: self.arena.alloc(i.name.to_string()),
-                        });
+                        let slot = self.local_slot(i.name, lhs, &src)?;
+                        bytecode.push(Instruction::Store(slot));
                     }
                     HirExpr::Indexing(i) => {
                         match i.target.ty() {
@@ -318,7 +340,6 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
                                 bytecode.push(Instruction::ListStore);
                             }
                             HirTy::String(_) => {
-                                eprintln!("String store: {:?}", a);
                                 //Get the Index
                                 self.generate_bytecode_expr(&i.index, bytecode, src.clone())?;
                                 //Get the string pointer
@@ -576,12 +597,12 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
                 }
             }
             HirExpr::Call(f) => {
-                for arg in &f.args {
-                    self.generate_bytecode_expr(arg, bytecode, src.clone())?;
-                }
                 let callee = f.callee.as_ref();
                 match callee {
                     HirExpr::Ident(i) => {
+                        for arg in &f.args {
+                            self.generate_bytecode_expr(arg, bytecode, src.clone())?;
+                        }
                         let func = self.hir.signature.functions.get(i.name).unwrap();
                         if func.is_external {
                             bytecode.push(Instruction::ExternCall {
@@ -627,6 +648,13 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
                         for arg in f.args.iter() {
                             self.generate_bytecode_expr(arg, bytecode, src.clone())?;
                         }
+                        if self.hir.signature.unions.contains_key(static_access.target.name) {
+                            bytecode.push(Instruction::NewUnion {
+                                tag: self.discriminant(static_access) as usize,
+                                nb_fields: f.args.len(),
+                            });
+                            return Ok(());
+                        }
                         bytecode.push(Instruction::StaticCall {
                             method_name: self.arena.alloc(format!("{}::{}", static_access.target.name, static_access.field.name)),
                             nb_args: f.args.len() as u8,
@@ -646,6 +674,7 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
                     }
                 }
             }
+            HirExpr::Match(m) => self.generate_bytecode_match(m, bytecode, src)?,
             HirExpr::IntegerLiteral(i) => bytecode.push(Instruction::PushInt(i.value)),
             HirExpr::FloatLiteral(f) => bytecode.push(Instruction::PushFloat(f.value)),
             HirExpr::BooleanLiteral(b) => bytecode.push(Instruction::PushBool(b.value)),
@@ -678,9 +707,17 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
             HirExpr::Delete(d) => {
                 self.generate_bytecode_expr(&d.expr, bytecode, src)?;
                 bytecode.push(Instruction::DeleteObj);
+                //`delete` is a unit expression
+                bytecode.push(Instruction::PushUnit);
+            }
+            HirExpr::Ident(i) => {
+                let slot = self.local_slot(i.name, expr, &src)?;
+                bytecode.push(Instruction::Get(slot));
+            }
+            HirExpr::SelfLiteral(_) => {
+                let slot = self.local_slot("self", expr, &src)?;
+                bytecode.push(Instruction::Get(slot));
             }
-            HirExpr::Ident(i) => bytecode.push(Instruction::Load { var_name: self.arena.alloc(i.name.to_string()) }),
-            HirExpr::SelfLiteral(_) => bytecode.push(Instruction::Load { var_name: self.arena.alloc(String::from("self")) }),
             HirExpr::FieldAccess(field_access) => {
                 self.generate_bytecode_expr(field_access.target.as_ref(), bytecode, src.clone())?;
                 bytecode.push(Instruction::GetField {
@@ -689,6 +726,11 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
             }
             HirExpr::StaticAccess(static_access) => {
                 match static_access.field.ty {
+                    HirTy::Enum(_) => bytecode.push(Instruction::PushInt(self.discriminant(static_access))),
+                    HirTy::Union(_) => bytecode.push(Instruction::NewUnion {
+                        tag: self.discriminant(static_access) as usize,
+                        nb_fields: 0,
+                    }),
                     HirTy::String(_) => {
                         let target_name = static_access.target.name;
                         let class_signature = self.hir.signature.classes.get(target_name).unwrap();
@@ -831,17 +873,561 @@ impl<'hir, 'gen> CodeGenUnit<'hir, 'gen> {
         Ok(())
     }
 
-    fn generate_bytecode_args(
-        &self,
-        args: Vec<&HirFunctionParameterSignature<'hir>>,
+    /// The value is kept in a hidden local & tested against the patterns of each arm in order,
+    /// the last arm is taken if none of the others matched as the match is exhaustive
+    ///
+    /// Unions are matched on their tag, the bindings of an arm are copied out of the payload before its body
+    fn generate_bytecode_match(
+        &mut self,
+        m: &HirMatchExpr<'hir>,
         bytecode: &mut Vec<Instruction<'gen>>,
+        src: String,
     ) -> HirResult<()> {
-        let args = args.iter().rev().cloned().collect::<Vec<_>>();
-        for arg in args {
-            bytecode.push(Instruction::Store {
-                var_name: self.arena.alloc(arg.name.to_string()),
-            });
+        self.locals.push(HashMap::new());
+        self.generate_bytecode_expr(&m.value, bytecode, src.clone())?;
+        //Not an identifier, so it can't shadow a variable
+        let slot = self.declare_local("<match>");
+        bytecode.push(Instruction::Store(slot));
+
+        let is_union = matches!(m.value.ty(), HirTy::Union(_));
+        //The value patterns are compared with
+        let load = |bytecode: &mut Vec<Instruction<'gen>>| {
+            bytecode.push(Instruction::Get(slot));
+            if is_union {
+                bytecode.push(Instruction::UnionTag);
+            }
+        };
+
+        let last = m.arms.len() - 1;
+        //`Jmp` to patch with the position of an arm's body, by arm
+        let mut to_arm: Vec<(usize, usize)> = vec![];
+        match self.jump_table(m) {
+            Some((min, targets)) => {
+                load(bytecode);
+                bytecode.push(Instruction::JmpTable {
+                    min,
+                    len: targets.len(),
+                });
+                let default = m
+                    .arms
+                    .iter()
+                    .position(|arm| arm.patterns.iter().any(|p| matches!(p, HirPattern::Wildcard(_))))
+                    .unwrap_or(last);
+                for arm in targets {
+                    to_arm.push((bytecode.len(), arm.unwrap_or(default)));
+                    bytecode.push(Instruction::Jmp { pos: 0 });
+                }
+                to_arm.push((bytecode.len(), default));
+                bytecode.push(Instruction::Jmp { pos: 0 });
+            }
+            None => {
+                for (i, arm) in m.arms[..last].iter().enumerate() {
+                    self.mark_span(bytecode.len(), arm.span.clone());
+                    for pattern in arm.patterns.iter() {
+                        match pattern {
+                            HirPattern::Wildcard(_) => {}
+                            HirPattern::Literal(literal) => {
+                                bytecode.push(Instruction::Get(slot));
+                                self.generate_bytecode_expr(literal, bytecode, src.clone())?;
+                                bytecode.push(Instruction::Eq);
+                                bytecode.push(Instruction::JmpZ { pos: 1 });
+                            }
+                            HirPattern::Variant(v) => {
+                                load(bytecode);
+                                bytecode.push(Instruction::PushInt(self.discriminant(&v.access)));
+                                bytecode.push(Instruction::Eq);
+                                bytecode.push(Instruction::JmpZ { pos: 1 });
+                            }
+                        }
+                        to_arm.push((bytecode.len(), i));
+                        bytecode.push(Instruction::Jmp { pos: 0 });
+                    }
+                }
+                to_arm.push((bytecode.len(), last));
+                bytecode.push(Instruction::Jmp { pos: 0 });
+            }
+        }
+
+        let mut to_end = vec![];
+        for (i, arm) in m.arms.iter().enumerate() {
+            let start = bytecode.len() as isize;
+            for (jmp, _) in to_arm.iter().filter(|(_, arm)| *arm == i) {
+                bytecode[*jmp] = Instruction::Jmp {
+                    pos: start - *jmp as isize,
+                };
+            }
+            self.mark_span(bytecode.len(), arm.span.clone());
+            self.locals.push(HashMap::new());
+            if let [HirPattern::Variant(HirVariantPattern { bindings: Some(bindings), .. })] = arm.patterns.as_slice() {
+                for (field, binding) in bindings.iter().enumerate().filter(|(_, b)| b.name != "_") {
+                    bytecode.push(Instruction::Get(slot));
+                    bytecode.push(Instruction::UnionField(field));
+                    let binding_slot = self.declare_local(binding.name);
+                    bytecode.push(Instruction::Store(binding_slot));
+                }
+            }
+            self.generate_bytecode_block(&arm.body, bytecode, src.clone())?;
+            match &arm.value {
+                Some(value) => self.generate_bytecode_expr(value, bytecode, src.clone())?,
+                None => bytecode.push(Instruction::PushUnit),
+            }
+            self.locals.pop();
+            if i != last {
+                to_end.push(bytecode.len());
+                bytecode.push(Instruction::Jmp { pos: 0 });
+            }
         }
+        for jmp in to_end {
+            bytecode[jmp] = Instruction::Jmp {
+                pos: (bytecode.len() - jmp) as isize,
+            };
+        }
+        self.locals.pop();
         Ok(())
     }
+
+    /// Lowest value & arm of every value up to the highest one, if the integer patterns are dense enough for a jump table
+    ///
+    /// Values without an arm go to the `_` arm
+    fn jump_table(&self, m: &HirMatchExpr<'hir>) -> Option<(i64, Vec<Option<usize>>)> {
+        if !matches!(m.value.ty(), HirTy::Int64(_) | HirTy::Enum(_) | HirTy::Union(_)) {
+            return None;
+        }
+        let mut arms: BTreeMap<i64, usize> = BTreeMap::new();
+        'arms: for (i, arm) in m.arms.iter().enumerate() {
+            for pattern in arm.patterns.iter() {
+                let value = match pattern {
+                    HirPattern::Literal(HirExpr::IntegerLiteral(int)) => int.value,
+                    HirPattern::Variant(v) => self.discriminant(&v.access),
+                    //The arms after it can't be reached
+                    HirPattern::Wildcard(_) => break 'arms,
+                    HirPattern::Literal(_) => return None,
+                };
+                arms.entry(value).or_insert(i);
+            }
+        }
+        let (min, max) = (*arms.first_key_value()?.0, *arms.last_key_value()?.0);
+        let len = max.checked_sub(min)?.checked_add(1)?;
+        if arms.len() < MIN_JUMP_TABLE_VALUES || len > 2 * arms.len() as i64 {
+            return None;
+        }
+        Some((min, (min..=max).map(|value| arms.get(&value).copied()).collect()))
+    }
+
+    /// The enum variant's discriminant, or the union variant's tag
+    fn discriminant(&self, access: &HirStaticAccessExpr<'hir>) -> i64 {
+        if let Some(union_signature) = self.hir.signature.unions.get(access.target.name) {
+            return union_signature.variant(access.field.name).unwrap().0 as i64;
+        }
+        let enum_signature = self.hir.signature.enums.get(access.target.name).unwrap();
+        enum_signature.variant(access.field.name).unwrap().discriminant
+    }
+
+    /// Reset the locals for a new function, `params` take the first slots in order
+    fn begin_function(&mut self, params: impl Iterator<Item = &'hir str>) {
+        self.locals = vec![HashMap::new()];
+        self.nb_slots = 0;
+        self.slot_names.clear();
+        self.spans.clear();
+        for param in params {
+            self.declare_local(param);
+        }
+    }
+
+    /// Slot of `name` in the innermost scope, a new one is allocated if needed
+    ///
+    /// Slots aren't reused once their scope ends
+    fn declare_local(&mut self, name: &'hir str) -> usize {
+        let scope = self.locals.last_mut().expect("no scope to declare a local in");
+        match scope.get(name) {
+            Some(slot) => *slot,
+            None => {
+                scope.insert(name, self.nb_slots);
+                self.slot_names.push(name);
+                self.nb_slots += 1;
+                self.nb_slots - 1
+            }
+        }
+    }
+
+    fn local_slot(&self, name: &str, expr: &HirExpr<'hir>, src: &str) -> HirResult<usize> {
+        match self.locals.iter().rev().find_map(|scope| scope.get(name)) {
+            Some(slot) => Ok(*slot),
+            None => Err(HirError::UnsupportedExpr(UnsupportedExpr {
+                span: SourceSpan::new(
+                    SourceOffset::from(expr.span().start),
+                    expr.span().end - expr.span().start,
+                ),
+                expr: format!("Unknown variable `{}`", name),
+                src: src.to_string(),
+            })),
+        }
+    }
+
+    /// Names of the slots of the function being generated, in the program's arena
+    fn local_names(&mut self) -> &'gen [&'gen str] {
+        let names = std::mem::take(&mut self.slot_names)
+            .into_iter()
+            .map(|name| {
+                let name: &'gen String = self.arena.alloc(name.to_string());
+                name.as_str()
+            })
+            .collect::<Vec<_>>();
+        self.arena.alloc(names)
+    }
+
+    /// Return unit at the end of a body, so it doesn't run into the next label
+    fn implicit_return(body: &mut Vec<Instruction<'gen>>) {
+        if body.last() != Some(&Instruction::Return) {
+            body.push(Instruction::PushUnit);
+            body.push(Instruction::Return);
+        }
+    }
+
+    /// Map the instructions from `offset` to `span` in the line table
+    fn mark_span(&mut self, offset: usize, span: Span) {
+        let entry = SpanEntry {
+            offset,
+            start: span.start,
+            end: span.end,
+        };
+        match self.spans.last_mut() {
+            Some(last) if last.offset == offset => *last = entry,
+            Some(last) if (last.start, last.end) == (entry.start, entry.end) => {}
+            _ => self.spans.push(entry),
+        }
+    }
+
+    /// Prefix `body` with the room needed for the locals that aren't arguments
+    fn reserve_locals(&mut self, nb_params: usize, mut body: Vec<Instruction<'gen>>) -> Vec<Instruction<'gen>> {
+        let nb_locals = self.nb_slots - nb_params;
+        if nb_locals == 0 {
+            return body;
+        }
+        for entry in self.spans.iter_mut() {
+            entry.offset += 1;
+        }
+        let mut bytecode = Vec::with_capacity(body.len() + 1);
+        bytecode.push(Instruction::Reserve(nb_locals));
+        bytecode.append(&mut body);
+        bytecode
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::arena::CodeGenArena;
+    use super::CodeGenUnit;
+    use crate::atlas_vm::runtime::instruction::Instruction;
+    use crate::engine::Engine;
+    use bumpalo::Bump;
+
+    /// Sorted names of the functions of `source` whose body has a `JmpTable`
+    #[allow(clippy::result_large_err)]
+    fn with_jump_table(source: &str) -> Vec<String> {
+        Engine::new()
+            .analyse("test.atlas", source, |hir| {
+                let bump = Bump::new();
+                let mut codegen = CodeGenUnit::new(hir, CodeGenArena::new(&bump), source.to_string());
+                let program = codegen.compile()?;
+                let mut names = program
+                    .labels
+                    .iter()
+                    .filter(|label| label.body.iter().any(|i| matches!(i, Instruction::JmpTable { .. })))
+                    .map(|label| label.name.to_string())
+                    .collect::<Vec<_>>();
+                names.sort();
+                Ok(names)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn dense_integer_arms_use_a_jump_table() {
+        let source = r#"enum Color { Red, Green, Blue, Black }
+func dense(n: int64) -> int64 {
+    return match n {
+        -1 => 10,
+        0 => 11,
+        1 | 2 => 12,
+        _ => 13,
+    };
+}
+func sparse(n: int64) -> int64 {
+    return match n {
+        1 => 1,
+        10 => 2,
+        100 => 3,
+        1000 => 4,
+        _ => 5,
+    };
+}
+func few(n: int64) -> int64 {
+    return match n {
+        1 => 1,
+        2 => 2,
+        _ => 3,
+    };
+}
+func color(c: Color) -> int64 {
+    return match c {
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Blue => 3,
+        Color::Black => 4,
+    };
+}"#;
+        assert_eq!(with_jump_table(source), ["color", "dense"].map(String::from));
+    }
+
+    #[test]
+    fn enums_are_compared_by_variant() {
+        let script = Engine::new()
+            .compile(
+                "color.atlas",
+                r#"func rank(c: Color) -> int64 {
+    if c == Color::Red {
+        return 1;
+    }
+    if c != Color::Blue {
+        return 2;
+    }
+    return 3;
+}
+enum Color {
+    Red,
+    Green = 4,
+    Blue,
+}
+func blue() -> int64 {
+    let c: Color = Color::Blue;
+    return rank(c);
+}"#,
+            )
+            .unwrap();
+        assert_eq!(script.call::<_, i64>("blue", ()).unwrap(), 3);
+    }
+
+    #[test]
+    fn std_file_enum_and_class_compile() {
+        let script = Engine::new()
+            .compile(
+                "file.atlas",
+                r#"import "std/file"
+func flag(write: bool) -> Flag {
+    if write {
+        return Flag::Write;
+    }
+    return Flag::Read;
+}
+func is_read(write: bool) -> bool {
+    return flag(write) == Flag::Read;
+}
+func written(path: str) -> str {
+    let file = new File(path, flag(true));
+    file.write("content");
+    return file.content();
+}"#,
+            )
+            .unwrap();
+        assert!(script.call::<_, bool>("is_read", (false,)).unwrap());
+        assert!(!script.call::<_, bool>("is_read", (true,)).unwrap());
+        assert_eq!(script.call::<_, String>("written", ("a.txt",)).unwrap(), "content");
+    }
+
+    #[test]
+    fn match_arms_are_picked_by_pattern() {
+        let script = Engine::new()
+            .compile(
+                "match.atlas",
+                r#"enum Color {
+    Red,
+    Green = 4,
+    Blue,
+    Black,
+    White,
+}
+func color(c: Color) -> int64 {
+    return match c {
+        Color::Red => 1,
+        Color::Green | Color::Blue => 2,
+        Color::Black => 3,
+        Color::White => 4,
+    };
+}
+func digit(n: int64) -> int64 {
+    return match n {
+        -1 => 10,
+        0 => 11,
+        1 => 12,
+        2 | 3 => 13,
+        _ => 14,
+    };
+}
+func word(s: str) -> int64 {
+    return match s {
+        "one" => 1,
+        "two" => 2,
+        _ => 0,
+    };
+}
+func flag(b: bool) -> int64 {
+    let n = 0;
+    match b {
+        true => {
+            n = 1;
+        }
+        false => {
+            n = 2;
+        }
+    }
+    return n;
+}
+func blue() -> int64 {
+    return color(Color::Blue);
+}"#,
+            )
+            .unwrap();
+        assert_eq!(script.call::<_, i64>("blue", ()).unwrap(), 2);
+        let digits = [-2_i64, -1, 0, 2, 3, 4].map(|n| script.call::<_, i64>("digit", (n,)).unwrap());
+        assert_eq!(digits, [14, 10, 11, 13, 13, 14]);
+        assert_eq!(script.call::<_, i64>("word", ("two".to_string(),)).unwrap(), 2);
+        assert_eq!(script.call::<_, i64>("word", ("three".to_string(),)).unwrap(), 0);
+        assert_eq!(script.call::<_, i64>("flag", (false,)).unwrap(), 2);
+    }
+
+    #[test]
+    fn unions_carry_their_payload() {
+        let script = Engine::new()
+            .compile(
+                "union.atlas",
+                r#"union Shape {
+    Square(side: int64),
+    Rect(w: int64, h: int64),
+    Empty,
+}
+func area(s: Shape) -> int64 {
+    return match s {
+        Shape::Square(side) => side * side,
+        Shape::Rect(w, h) => w * h,
+        Shape::Empty => 0,
+    };
+}
+func total() -> int64 {
+    return area(Shape::Square(3)) + area(Shape::Rect(2, 5)) + area(Shape::Empty);
+}
+func half(n: int64) -> Option<int64> {
+    let rem = n % 2;
+    if rem == 0 {
+        return Option::Some(n / 2);
+    }
+    return Option::None;
+}
+func unwrap_or(o: Option<int64>, default: int64) -> int64 {
+    return match o {
+        Option::Some(value) => value,
+        Option::None => default,
+    };
+}
+func check(n: int64) -> Result<int64, str> {
+    if n < 0 {
+        return Result::Err("negative");
+    }
+    return Result::Ok(n);
+}"#,
+            )
+            .unwrap();
+        assert_eq!(script.call::<_, i64>("total", ()).unwrap(), 19);
+        assert_eq!(script.call::<_, Option<i64>>("half", (4_i64,)).unwrap(), Some(2));
+        assert_eq!(script.call::<_, Option<i64>>("half", (3_i64,)).unwrap(), None);
+        assert_eq!(script.call::<_, i64>("unwrap_or", (None::<i64>, 7_i64)).unwrap(), 7);
+        assert_eq!(
+            script.call::<_, Result<i64, String>>("check", (-1_i64,)).unwrap(),
+            Err("negative".to_string())
+        );
+    }
+
+    #[test]
+    fn concept_methods_are_called() {
+        let script = Engine::new()
+            .compile(
+                "concepts.atlas",
+                r#"import "std/vec"
+concept Shape {
+    func area(self) -> int64;
+}
+class Square : impl Shape {
+public:
+    side: int64;
+    func area(self) -> int64 {
+        return self.side * self.side;
+    }
+}
+func total<S>(a: S, b: S) -> int64 where S: impl Shape {
+    return a.area() + b.area();
+}
+func squares() -> int64 {
+    return total(new Square(2), new Square(3));
+}
+func found() -> bool {
+    let v = new Vec([1, 2, 3]);
+    return contains(v, 3);
+}"#,
+            )
+            .unwrap();
+        assert_eq!(script.call::<_, i64>("squares", ()).unwrap(), 13);
+        assert!(script.call::<_, bool>("found", ()).unwrap());
+    }
+
+    #[test]
+    fn generics_are_monomorphized() {
+        let script = Engine::new()
+            .compile(
+                "generics.atlas",
+                r#"import "std/vec"
+import "std/listmap"
+func max<T>(a: T, b: T) -> T {
+    if a > b {
+        return a;
+    }
+    return b;
+}
+class Pair<A, B> {
+public:
+    left: A;
+    right: B;
+    func swap(self) -> Pair<B, A> {
+        return new Pair<B, A>(self.right, self.left);
+    }
+}
+func largest() -> float64 {
+    return max(max(1.5, 4.5), 2.5);
+}
+func swapped() -> str {
+    let p = new Pair(1, "one");
+    return p.swap().left;
+}
+func pushed() -> int64 {
+    let v = new Vec<int64>([1, 2]);
+    v.push(3);
+    v.push(4);
+    return v.len + v.get(3);
+}
+func counted() -> int64 {
+    let m = new ListMap<str, int64>(1);
+    m.insert("a", 1);
+    m.insert("b", 2);
+    m.insert("a", 3);
+    return match m.get("a") {
+        Option::Some(n) => n + m.len,
+        Option::None => 0,
+    };
+}"#,
+            )
+            .unwrap();
+        assert_eq!(script.call::<_, f64>("largest", ()).unwrap(), 4.5);
+        assert_eq!(script.call::<_, String>("swapped", ()).unwrap(), "one");
+        assert_eq!(script.call::<_, i64>("pushed", ()).unwrap(), 8);
+        assert_eq!(script.call::<_, i64>("counted", ()).unwrap(), 5);
+    }
 }
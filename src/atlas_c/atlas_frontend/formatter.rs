@@ -0,0 +1,893 @@
+//! Canonical formatting of Atlas source code, behind `atlas_77 fmt`.
+//!
+//! The program is parsed then printed back from its [`AstProgram`]. Comments aren't part of the AST,
+//! they are put back before the item, member or statement following them, or at the end of the line
+//! they were on.
+
+use bumpalo::Bump;
+
+use crate::atlas_c::atlas_frontend::lexer::token::{Token, TokenKind};
+use crate::atlas_c::atlas_frontend::lexer::AtlasLexer;
+use crate::atlas_c::atlas_frontend::parse;
+use crate::atlas_c::atlas_frontend::parser::arena::AstArena;
+use crate::atlas_c::atlas_frontend::parser::ast::{
+    AstBinaryOp, AstBlock, AstClass, AstConcept, AstConst, AstConstructor, AstDestructor, AstExpr, AstFunction, AstGeneric, AstGenericConstraint,
+    AstIfElseExpr, AstItem, AstLiteral, AstMatchExpr, AstMethod, AstMethodModifier, AstNamedType, AstNewObjExpr, AstObjField, AstOperatorOverload,
+    AstPattern, AstProgram, AstStatement, AstType, AstUnaryOp, AstVisibility,
+};
+use crate::atlas_c::atlas_frontend::parser::error::ParseResult;
+
+/// Lines longer than this are wrapped where possible
+pub const MAX_WIDTH: usize = 100;
+const INDENT: &str = "    ";
+
+/// Format `source`, fails if it doesn't parse
+pub fn format(path: &str, source: &str) -> ParseResult<String> {
+    let bump = Bump::new();
+    let arena = AstArena::new(&bump);
+    let program = parse(path, &arena, source.to_string())?;
+    //It parsed, so it can be tokenized
+    let tokens = AtlasLexer::new(path, source.to_string()).tokenize().unwrap_or_default();
+    let mut formatter = Formatter::new(source, tokens);
+    formatter.program(&program);
+    Ok(formatter.out)
+}
+
+struct Formatter<'src> {
+    source: &'src str,
+    /// Every token of the source, comments included
+    tokens: Vec<Token>,
+    comments: Vec<Token>,
+    /// First comment not printed yet
+    next_comment: usize,
+    /// Comments are left for later while printing an expression on the side
+    quiet: bool,
+    indent: usize,
+    /// Whether the last line opened a block or a class section, no blank line can follow it
+    opened: bool,
+    out: String,
+}
+
+/// Member of a class
+enum Member<'a, 'ast> {
+    Field(&'a AstObjField<'ast>),
+    Const(&'a AstConst<'ast>),
+    Constructor(&'a AstConstructor<'ast>),
+    Destructor(&'a AstDestructor<'ast>),
+    Method(&'a AstMethod<'ast>),
+    Operator(&'a AstOperatorOverload<'ast>),
+}
+
+impl<'src> Formatter<'src> {
+    fn new(source: &'src str, tokens: Vec<Token>) -> Self {
+        let tokens: Vec<Token> = tokens.into_iter().filter(|t| t.kind() != TokenKind::EoI).collect();
+        let comments = tokens
+            .iter()
+            .filter(|t| matches!(t.kind, TokenKind::Comments(_)))
+            .cloned()
+            .collect();
+        Self {
+            source,
+            tokens,
+            comments,
+            next_comment: 0,
+            quiet: false,
+            indent: 0,
+            opened: false,
+            out: String::new(),
+        }
+    }
+
+    fn program(&mut self, program: &AstProgram) {
+        let mut previous: Option<&AstItem> = None;
+        for &item in program.items.iter() {
+            let start = match item {
+                AstItem::Import(i) => i.span.start,
+                AstItem::Enum(e) => self.item_start(e.name.span.start),
+                AstItem::Union(u) => self.item_start(u.name.span.start),
+                AstItem::Class(c) => self.item_start(c.name.span.start),
+                AstItem::Concept(c) => self.item_start(c.name.span.start),
+                AstItem::Struct(s) => self.item_start(s.name.span.start),
+                AstItem::ExternFunction(e) => self.item_start(e.name.span.start),
+                AstItem::Func(f) => match f.attributes.first() {
+                    Some(attribute) => attribute.span.start,
+                    None => self.item_start(f.name.span.start),
+                },
+            };
+            //Functions & types are always separated by a blank line, imports & externs can be grouped
+            let grouped = matches!(
+                (previous, item),
+                (Some(AstItem::Import(_)), AstItem::Import(_))
+                    | (Some(AstItem::ExternFunction(_)), AstItem::ExternFunction(_))
+            );
+            if previous.is_some() && !grouped {
+                self.blank_line();
+            }
+            self.comments_before(start);
+            if self.gap(start) {
+                self.blank_line();
+            }
+            self.item(item);
+            previous = Some(item);
+        }
+        self.comments_before(usize::MAX);
+    }
+
+    fn item(&mut self, item: &AstItem) {
+        match item {
+            AstItem::Import(i) => {
+                let alias = i.alias.map(|a| format!(" as {}", a.name)).unwrap_or_default();
+                self.line(&format!("import \"{}\"{}", i.path, alias));
+            }
+            AstItem::ExternFunction(e) => {
+                let generics = e
+                    .generics
+                    .map(|g| format!("<{}>", g.iter().map(|g| g.name.name).collect::<Vec<_>>().join(", ")))
+                    .unwrap_or_default();
+                let params = e
+                    .args_name
+                    .iter()
+                    .zip(e.args_ty.iter())
+                    .map(|(name, ty)| format!("{}: {}", name.name, Self::ty(ty)))
+                    .collect::<Vec<_>>();
+                let head = format!("{}extern {}{}", vis(e.vis), e.name.name, generics);
+                self.signature(&head, &params, &format!(" -> {}", Self::ty(e.ret)));
+            }
+            AstItem::Func(f) => self.function(f),
+            AstItem::Class(c) => self.class(c),
+            AstItem::Concept(c) => self.concept(c),
+            AstItem::Struct(s) => {
+                self.line(&format!("{}struct {} {{", vis(s.vis), s.name.name));
+                self.indent += 1;
+                for field in s.fields.iter() {
+                    self.member_spacing(field.span.start);
+                    self.line(&format!("{};", Self::param(field)));
+                }
+                let from = s.fields.last().map_or(s.name.span.end, |f| f.span.start);
+                self.close(from);
+            }
+            AstItem::Enum(e) => {
+                self.line(&format!("{}enum {} {{", vis(e.vis), e.name.name));
+                self.indent += 1;
+                for variant in e.variants.iter() {
+                    self.member_spacing(variant.span.start);
+                    match variant.val {
+                        Some(val) => self.line(&format!("{} = {},", variant.name.name, val)),
+                        None => self.line(&format!("{},", variant.name.name)),
+                    }
+                }
+                let from = e.variants.last().map_or(e.name.span.end, |v| v.span.start);
+                self.close(from);
+            }
+            AstItem::Union(u) => {
+                let generics = Self::generics(u.generics);
+                self.line(&format!("{}union {}{} {{", vis(u.vis), u.name.name, generics));
+                self.indent += 1;
+                for variant in u.variants.iter() {
+                    self.member_spacing(variant.span.start);
+                    if variant.fields.is_empty() {
+                        self.line(&format!("{},", variant.name.name));
+                    } else {
+                        let fields = variant.fields.iter().map(|f| Self::param(f)).collect::<Vec<_>>();
+                        self.line(&format!("{}({}),", variant.name.name, fields.join(", ")));
+                    }
+                }
+                let from = u.variants.last().map_or(u.name.span.end, |v| v.span.start);
+                self.close(from);
+            }
+        }
+    }
+
+    fn function(&mut self, f: &AstFunction) {
+        for attribute in f.attributes.iter() {
+            self.line(&format!("#[{}]", attribute.name.name));
+        }
+        let head = format!("{}func {}{}", vis(f.vis), f.name.name, Self::generics(f.generics));
+        let params = f.args.iter().map(|a| Self::param(a)).collect::<Vec<_>>();
+        self.signature(&head, &params, &Self::ret(f.ret));
+        self.body(f.body);
+    }
+
+    fn class(&mut self, class: &AstClass) {
+        let generics = Self::generics(class.generics);
+        let concepts = class
+            .concepts
+            .iter()
+            .map(|c| format!("impl {}", Self::named_ty(c)))
+            .collect::<Vec<_>>();
+        let concepts = if concepts.is_empty() {
+            String::new()
+        } else {
+            format!(" : {}", concepts.join(", "))
+        };
+        self.line(&format!("{}class {}{}{} {{", vis(class.vis), class.name.name, generics, concepts));
+
+        let mut members = Vec::new();
+        members.extend(class.fields.iter().map(|f| (f.span.start, Member::Field(f))));
+        members.extend(class.constants.iter().map(|c| (c.span.start, Member::Const(c))));
+        if let Some(c) = class.constructor {
+            let name = TokenKind::Identifier(class.name.name.to_string());
+            members.push((self.token_before(c.span.start, &name), Member::Constructor(c)));
+        }
+        if let Some(d) = class.destructor {
+            members.push((self.token_before(d.span.start, &TokenKind::Tilde), Member::Destructor(d)));
+        }
+        members.extend(
+            class
+                .methods
+                .iter()
+                .map(|m| (self.token_before(m.span.start, &TokenKind::KwFunc), Member::Method(m))),
+        );
+        members.extend(
+            class
+                .operators
+                .iter()
+                .map(|o| (self.token_before(o.span.start, &TokenKind::KwOperator), Member::Operator(o))),
+        );
+        members.sort_by_key(|(start, _)| *start);
+
+        //A header is only printed when the section changes, members are private until the first one
+        let body_start = self.token_after(class.name.span.end, &TokenKind::LBrace);
+        let mut section = None;
+        self.indent += 1;
+        for (start, member) in members.iter() {
+            let header = self.section_header(body_start, *start);
+            let public = header.is_some_and(|(_, public)| public);
+            if section != Some(public) {
+                if section.is_some() {
+                    self.blank_line();
+                }
+                //Comments above a header stay above it
+                if let Some((header, _)) = header {
+                    self.comments_before(header);
+                }
+                self.line(if public { "public:" } else { "private:" });
+                self.opened = true;
+                section = Some(public);
+            }
+            self.indent += 1;
+            self.member_spacing(*start);
+            self.member(class.name.name, member);
+            self.indent -= 1;
+        }
+        let from = match members.last() {
+            Some((start, _)) => *start,
+            None => body_start + 1,
+        };
+        self.close(from);
+    }
+
+    fn concept(&mut self, concept: &AstConcept) {
+        let generics = Self::generics(concept.generics);
+        self.line(&format!("{}concept {}{} {{", vis(concept.vis), concept.name.name, generics));
+        self.indent += 1;
+        for method in concept.methods.iter() {
+            self.member_spacing(self.token_before(method.span.start, &TokenKind::KwFunc));
+            let params = Self::method_params(method.modifier, method.args);
+            self.signature(&format!("func {}", method.name.name), &params, &Self::ret(method.ret));
+            self.append(";");
+        }
+        let from = concept.methods.last().map_or(concept.name.span.end, |m| m.span.start);
+        self.close(from);
+    }
+
+    /// The parameters of a method, starting with `self` unless it's static
+    fn method_params(modifier: AstMethodModifier, args: &[&AstObjField]) -> Vec<String> {
+        let mut params = Vec::new();
+        let has_self = args.first().is_some_and(|a| a.name.name == "self");
+        if !matches!(modifier, AstMethodModifier::Static) && !has_self {
+            params.push(String::from("self"));
+        }
+        params.extend(args.iter().map(|a| Self::param(a)));
+        params
+    }
+
+    fn member(&mut self, class: &str, member: &Member) {
+        match member {
+            Member::Field(f) => self.line(&format!("{};", Self::param(f))),
+            Member::Const(c) => {
+                let head = format!("const {}: {} = ", c.name.name, Self::ty(c.ty));
+                let value = self.expr(c.value, self.column() + head.len(), self.indent);
+                self.line(&format!("{}{};", head, value));
+            }
+            Member::Constructor(c) => {
+                let params = c.args.iter().map(|a| Self::param(a)).collect::<Vec<_>>();
+                self.signature(class, &params, "");
+                self.body(c.body);
+            }
+            Member::Destructor(d) => {
+                let params = d.args.iter().map(|a| Self::param(a)).collect::<Vec<_>>();
+                self.signature(&format!("~{}", class), &params, "");
+                self.body(d.body);
+            }
+            Member::Method(m) => {
+                let params = Self::method_params(m.modifier, m.args);
+                self.signature(&format!("func {}", m.name.name), &params, &Self::ret(m.ret));
+                self.body(m.body);
+            }
+            Member::Operator(o) => {
+                let params = o.args.iter().map(|a| Self::param(a)).collect::<Vec<_>>();
+                let head = format!("operator {} ", binary(&o.op));
+                self.signature(&head, &params, &format!(" -> {}", Self::ty(o.ret)));
+                self.body(o.body);
+            }
+        }
+    }
+
+    /// Print the statements of `block` & its closing brace, the opening one ends the last line
+    fn body(&mut self, block: &AstBlock) {
+        let from = block.stmts.last().map_or(block.span.start + 1, |s| s.span().start);
+        let end = self.closing_brace(from);
+        let has_comments = !self.quiet && self.comments.get(self.next_comment).is_some_and(|c| c.start() < end);
+        if block.stmts.is_empty() && !has_comments {
+            self.append(" {}");
+            return;
+        }
+        self.append(" {");
+        self.block(block);
+    }
+
+    /// Print the statements of `block` & its closing brace, the opening one is already printed
+    fn block(&mut self, block: &AstBlock) {
+        let from = block.stmts.last().map_or(block.span.start + 1, |s| s.span().start);
+        self.opened = true;
+        self.indent += 1;
+        for stmt in block.stmts.iter() {
+            self.stmt(stmt);
+        }
+        self.close(from);
+    }
+
+    /// Print the comments left before the closing brace following `from` & the brace itself
+    fn close(&mut self, from: usize) {
+        let end = self.closing_brace(from);
+        self.comments_before(end);
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    fn stmt(&mut self, stmt: &AstStatement) {
+        let start = match stmt {
+            AstStatement::Return(r) => self.token_before(r.span.start, &TokenKind::KwReturn),
+            stmt => stmt.span().start,
+        };
+        self.comments_before(start);
+        if self.gap(start) {
+            self.blank_line();
+        }
+        match stmt {
+            AstStatement::Let(l) => {
+                let ty = l.ty.map(|t| format!(": {}", Self::ty(t))).unwrap_or_default();
+                let head = format!("let {}{} = ", l.name.name, ty);
+                let value = self.expr(l.value, self.column() + head.len(), self.indent);
+                self.line(&format!("{}{};", head, value));
+            }
+            AstStatement::Const(c) => {
+                let head = format!("const {}: {} = ", c.name.name, Self::ty(c.ty));
+                let value = self.expr(c.value, self.column() + head.len(), self.indent);
+                self.line(&format!("{}{};", head, value));
+            }
+            AstStatement::IfElse(i) => self.if_else(i),
+            AstStatement::Block(b) => {
+                self.line("{");
+                self.block(b);
+            }
+            AstStatement::While(w) => {
+                let condition = self.expr(w.condition, self.column() + 6, self.indent);
+                self.line(&format!("while {}", condition));
+                self.body(w.body);
+            }
+            AstStatement::Expr(AstExpr::Match(m)) => self.match_expr(m),
+            AstStatement::Expr(e) => {
+                let expr = self.expr(e, self.column(), self.indent);
+                self.line(&format!("{};", expr));
+            }
+            AstStatement::Break(_) => self.line("break;"),
+            AstStatement::Continue(_) => self.line("continue;"),
+            AstStatement::Return(r) => match r.value {
+                AstExpr::Literal(AstLiteral::Unit(_)) => self.line("return;"),
+                value => {
+                    let value = self.expr(value, self.column() + 7, self.indent);
+                    self.line(&format!("return {};", value));
+                }
+            },
+            AstStatement::_InnerFunc(f) => self.function(f),
+        }
+    }
+
+    fn if_else(&mut self, i: &AstIfElseExpr) {
+        let condition = self.expr(i.condition, self.column() + 3, self.indent);
+        self.line(&format!("if {}", condition));
+        self.body(i.body);
+        if let Some(else_body) = i.else_body {
+            self.append(" else");
+            self.body(else_body);
+        }
+    }
+
+    fn match_expr(&mut self, m: &AstMatchExpr) {
+        let value = self.expr(m.value, self.column() + 6, self.indent);
+        self.line(&format!("match {} {{", value));
+        self.opened = true;
+        self.indent += 1;
+        for arm in m.arms.iter() {
+            self.comments_before(arm.span.start);
+            if self.gap(arm.span.start) {
+                self.blank_line();
+            }
+            let patterns = arm.patterns.iter().map(|p| self.pattern(p)).collect::<Vec<_>>().join(" | ");
+            match arm.body {
+                AstExpr::Block(b) => {
+                    self.line(&format!("{} =>", patterns));
+                    self.body(b);
+                }
+                body => {
+                    let head = format!("{} => ", patterns);
+                    let body = self.expr(body, self.column() + head.len(), self.indent);
+                    self.line(&format!("{}{},", head, body));
+                }
+            }
+        }
+        self.close(m.arms.last().map_or(m.value.span().end, |a| a.span.start));
+    }
+
+    fn pattern(&self, pattern: &AstPattern) -> String {
+        match pattern {
+            AstPattern::Wildcard(_) => String::from("_"),
+            AstPattern::Literal(AstLiteral::Boolean(b)) => b.value.to_string(),
+            AstPattern::Literal(l) => self.source[l.span()].to_string(),
+            AstPattern::Variant(v) => match v.bindings {
+                Some(bindings) => {
+                    let bindings = bindings.iter().map(|b| b.name).collect::<Vec<_>>().join(", ");
+                    format!("{}::{}({})", v.target.name, v.variant.name, bindings)
+                }
+                None => format!("{}::{}", v.target.name, v.variant.name),
+            },
+        }
+    }
+
+    /// `expr` on one line if it fits after `column`, wrapped with continuation lines at `indent` otherwise
+    fn expr(&mut self, expr: &AstExpr, column: usize, indent: usize) -> String {
+        let flat = self.flat(expr, indent);
+        if column + flat.len() < MAX_WIDTH || flat.contains('\n') {
+            return flat;
+        }
+        match expr {
+            AstExpr::UnaryOp(u) => {
+                let op = unary(&u.op);
+                format!("{}{}", op, self.expr(u.expr, column + op.len(), indent))
+            }
+            AstExpr::BinaryOp(b) => {
+                let lhs = self.expr(b.lhs, column, indent);
+                let rhs = self.expr(b.rhs, (indent + 1) * INDENT.len(), indent);
+                format!("{} {}\n{}{}", lhs, binary(&b.op), INDENT.repeat(indent + 1), rhs)
+            }
+            AstExpr::Call(c) => {
+                let callee = self.flat(c.callee, indent);
+                self.wrapped_list(&callee, "(", c.args, ")", indent)
+            }
+            AstExpr::NewObj(n) => self.wrapped_list(&Self::new_obj(n), "(", n.args, ")", indent),
+            AstExpr::Literal(AstLiteral::List(l)) => self.wrapped_list("", "[", l.items, "]", indent),
+            AstExpr::Assign(a) => {
+                let target = self.flat(a.target, indent);
+                let value = self.expr(a.value, column + target.len() + 3, indent);
+                format!("{} = {}", target, value)
+            }
+            _ => flat,
+        }
+    }
+
+    /// One item per line, each followed by a comma
+    fn wrapped_list(&mut self, head: &str, open: &str, items: &[&AstExpr], close: &str, indent: usize) -> String {
+        let mut text = format!("{}{}\n", head, open);
+        for item in items.iter() {
+            let item = self.expr(item, (indent + 1) * INDENT.len(), indent + 1);
+            text.push_str(&format!("{}{},\n", INDENT.repeat(indent + 1), item));
+        }
+        text.push_str(&INDENT.repeat(indent));
+        text.push_str(close);
+        text
+    }
+
+    fn flat(&mut self, expr: &AstExpr, indent: usize) -> String {
+        match expr {
+            AstExpr::UnaryOp(u) => format!("{}{}", unary(&u.op), self.flat(u.expr, indent)),
+            AstExpr::BinaryOp(b) => {
+                let lhs = self.flat(b.lhs, indent);
+                format!("{} {} {}", lhs, binary(&b.op), self.flat(b.rhs, indent))
+            }
+            AstExpr::Call(c) => {
+                let callee = self.flat(c.callee, indent);
+                format!("{}({})", callee, self.flat_list(c.args, indent))
+            }
+            AstExpr::Literal(l) => match l {
+                AstLiteral::Unit(_) => String::from("()"),
+                AstLiteral::SelfLiteral(_) => String::from("self"),
+                AstLiteral::Boolean(b) => b.value.to_string(),
+                AstLiteral::List(l) => format!("[{}]", self.flat_list(l.items, indent)),
+                _ => self.source[l.span()].to_string(),
+            },
+            AstExpr::Identifier(i) => i.name.to_string(),
+            AstExpr::Indexing(i) => {
+                let target = self.flat(i.target, indent);
+                format!("{}[{}]", target, self.flat(i.index, indent))
+            }
+            AstExpr::FieldAccess(f) => format!("{}.{}", self.flat(f.target, indent), f.field.name),
+            AstExpr::StaticAccess(s) => format!("{}::{}", s.target.name, s.field.name),
+            AstExpr::NewObj(n) => format!("{}({})", Self::new_obj(n), self.flat_list(n.args, indent)),
+            AstExpr::Delete(d) => format!("delete {}", self.flat(d.target, indent)),
+            AstExpr::NewArray(n) => {
+                let ty = match n.ty {
+                    AstType::List(l) => Self::ty(l.inner),
+                    ty => Self::ty(ty),
+                };
+                format!("new [{}; {}]", ty, self.flat(n.size, indent))
+            }
+            AstExpr::Assign(a) => {
+                let target = self.flat(a.target, indent);
+                format!("{} = {}", target, self.flat(a.value, indent))
+            }
+            AstExpr::Casting(c) => format!("{} as {}", self.flat(c.value, indent), Self::ty(c.ty)),
+            AstExpr::IfElse(i) => self.aside(indent, |f| f.if_else(i)),
+            AstExpr::Block(b) => self.aside(indent, |f| {
+                f.line("{");
+                f.block(b)
+            }),
+            AstExpr::Match(m) => self.aside(indent, |f| f.match_expr(m)),
+            AstExpr::_Lambda(l) => {
+                let args = l.args.iter().map(|a| a.name).collect::<Vec<_>>().join(", ");
+                format!("|{}| {}", args, self.flat(l.body, indent))
+            }
+            AstExpr::_CompTime(c) => format!("comptime {}", self.flat(c.expr, indent)),
+        }
+    }
+
+    fn flat_list(&mut self, items: &[&AstExpr], indent: usize) -> String {
+        items.iter().map(|item| self.flat(item, indent)).collect::<Vec<_>>().join(", ")
+    }
+
+    /// Print something to its own buffer, at `indent`
+    ///
+    /// Comments met meanwhile are printed after the statement it belongs to
+    fn aside(&mut self, indent: usize, print: impl FnOnce(&mut Self)) -> String {
+        let out = std::mem::take(&mut self.out);
+        let saved = (self.indent, self.quiet, self.opened);
+        self.indent = indent;
+        self.quiet = true;
+        print(self);
+        (self.indent, self.quiet, self.opened) = saved;
+        let text = std::mem::replace(&mut self.out, out);
+        text.trim().to_string()
+    }
+
+    /// `head(params)tail` on one line if it fits, one parameter per line otherwise
+    fn signature(&mut self, head: &str, params: &[String], tail: &str) {
+        let flat = format!("{}({}){}", head, params.join(", "), tail);
+        //Room is left for the ` {` of a body
+        if self.column() + flat.len() + 2 <= MAX_WIDTH || params.is_empty() {
+            self.line(&flat);
+            return;
+        }
+        self.line(&format!("{}(", head));
+        self.indent += 1;
+        for param in params {
+            self.line(&format!("{},", param));
+        }
+        self.indent -= 1;
+        self.line(&format!("){}", tail));
+    }
+
+    fn param(field: &AstObjField) -> String {
+        match field.ty {
+            AstType::SelfTy(_) if field.name.name == "self" => String::from("self"),
+            ty => format!("{}: {}", field.name.name, Self::ty(ty)),
+        }
+    }
+
+    /// ` -> ty`, unless the return type was left out
+    fn ret(ty: &AstType) -> String {
+        if ty.span() == (0..0) {
+            String::new()
+        } else {
+            format!(" -> {}", Self::ty(ty))
+        }
+    }
+
+    /// `<T: impl Foo, U>`, or nothing when there are no generics
+    ///
+    /// The constraints of a `where` clause are merged with them by the parser, so they're printed here too
+    fn generics(generics: &[&AstGeneric]) -> String {
+        if generics.is_empty() {
+            return String::new();
+        }
+        let generics = generics
+            .iter()
+            .map(|g| {
+                let constraints = g
+                    .constraints
+                    .iter()
+                    .map(|c| match c {
+                        AstGenericConstraint::NamedType(t) => format!("impl {}", Self::named_ty(t)),
+                        AstGenericConstraint::Operator(op) => format!("operator::({})", binary(op)),
+                    })
+                    .collect::<Vec<_>>();
+                if constraints.is_empty() {
+                    g.name.name.to_string()
+                } else {
+                    format!("{}: {}", g.name.name, constraints.join(" + "))
+                }
+            })
+            .collect::<Vec<_>>();
+        format!("<{}>", generics.join(", "))
+    }
+
+    /// `new Name` or `new Name<A, B>`, without the arguments
+    fn new_obj(n: &AstNewObjExpr) -> String {
+        if n.type_args.is_empty() {
+            return format!("new {}", n.ty.name);
+        }
+        let args = n.type_args.iter().map(|a| Self::ty(a)).collect::<Vec<_>>().join(", ");
+        format!("new {}<{}>", n.ty.name, args)
+    }
+
+    fn named_ty(n: &AstNamedType) -> String {
+        if n.args.is_empty() {
+            return n.name.name.to_string();
+        }
+        let args = n.args.iter().map(|a| Self::ty(a)).collect::<Vec<_>>().join(", ");
+        format!("{}<{}>", n.name.name, args)
+    }
+
+    fn ty(ty: &AstType) -> String {
+        match ty {
+            AstType::Unit(_) => String::from("unit"),
+            AstType::Boolean(_) => String::from("bool"),
+            AstType::Integer(_) => String::from("int64"),
+            AstType::Float(_) => String::from("float64"),
+            AstType::UnsignedInteger(_) => String::from("uint64"),
+            AstType::Char(_) => String::from("char"),
+            AstType::SelfTy(_) => String::from("Self"),
+            AstType::String(_) => String::from("str"),
+            AstType::Named(n) => Self::named_ty(n),
+            AstType::Generic(g) => g.name.name.to_string(),
+            AstType::Pointer(p) => format!("&{}", Self::ty(p.inner)),
+            AstType::List(l) => format!("[{}]", Self::ty(l.inner)),
+            AstType::Function(f) => {
+                let args = f.args.iter().map(|a| Self::ty(a)).collect::<Vec<_>>().join(", ");
+                format!("({}) -> {}", args, Self::ty(f.ret))
+            }
+        }
+    }
+
+    /// Comments & blank line before a member of a class, struct or enum
+    fn member_spacing(&mut self, start: usize) {
+        self.comments_before(start);
+        if self.gap(start) {
+            self.blank_line();
+        }
+    }
+
+    /// Print the comments starting before `offset`
+    ///
+    /// A comment following code on its line stays at the end of the last printed line
+    fn comments_before(&mut self, offset: usize) {
+        if self.quiet {
+            return;
+        }
+        while let Some(comment) = self.comments.get(self.next_comment).filter(|c| c.start() < offset).cloned() {
+            self.next_comment += 1;
+            let text = self.source[comment.span()].trim_end();
+            let line_start = self.source[..comment.start()].rfind('\n').map_or(0, |i| i + 1);
+            let trailing = !self.source[line_start..comment.start()].trim().is_empty();
+            if trailing && !self.out.is_empty() {
+                self.append(&format!(" {}", text));
+            } else {
+                if self.gap(comment.start()) {
+                    self.blank_line();
+                }
+                self.line(text);
+            }
+        }
+    }
+
+    /// Whether there is a blank line right before `offset` in the source
+    fn gap(&self, offset: usize) -> bool {
+        let previous = self.tokens.partition_point(|t| t.end() <= offset);
+        match previous.checked_sub(1) {
+            Some(previous) => self.source[self.tokens[previous].end()..offset].matches('\n').count() >= 2,
+            None => false,
+        }
+    }
+
+    /// Start of the `}` closing the block `from` is in
+    fn closing_brace(&self, from: usize) -> usize {
+        let mut depth = 0usize;
+        for token in self.tokens.iter().filter(|t| t.start() >= from) {
+            match token.kind {
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace if depth == 0 => return token.start(),
+                TokenKind::RBrace => depth -= 1,
+                _ => {}
+            }
+        }
+        self.source.len()
+    }
+
+    /// Start of the last `kind` token starting at or before `offset`, `offset` if there is none
+    fn token_before(&self, offset: usize, kind: &TokenKind) -> usize {
+        self.tokens
+            .iter()
+            .rev()
+            .find(|t| t.start() <= offset && t.kind == *kind)
+            .map_or(offset, |t| t.start())
+    }
+
+    /// Start of the first `kind` token starting at or after `offset`
+    fn token_after(&self, offset: usize, kind: &TokenKind) -> usize {
+        self.tokens
+            .iter()
+            .find(|t| t.start() >= offset && t.kind == *kind)
+            .map_or(offset, |t| t.start())
+    }
+
+    /// Start of the last `public:` or `private:` between `from` & `to`, and whether it's `public:`
+    fn section_header(&self, from: usize, to: usize) -> Option<(usize, bool)> {
+        self.tokens
+            .windows(2)
+            .filter(|w| w[0].start() >= from && w[1].end() <= to && w[1].kind == TokenKind::Colon)
+            .filter_map(|w| match w[0].kind {
+                TokenKind::KwPublic => Some((w[0].start(), true)),
+                TokenKind::KwPrivate => Some((w[0].start(), false)),
+                _ => None,
+            })
+            .next_back()
+    }
+
+    /// Start of the item whose name starts at `name`, its keyword & visibility included
+    fn item_start(&self, name: usize) -> usize {
+        let keyword = self.tokens.partition_point(|t| t.start() < name).saturating_sub(1);
+        match keyword.checked_sub(1).map(|i| &self.tokens[i]) {
+            Some(vis) if matches!(vis.kind, TokenKind::KwPublic | TokenKind::KwPrivate) => vis.start(),
+            _ => self.tokens.get(keyword).map_or(name, |t| t.start()),
+        }
+    }
+
+    fn column(&self) -> usize {
+        self.indent * INDENT.len()
+    }
+
+    fn line(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.out.push_str(&INDENT.repeat(self.indent));
+            self.out.push_str(text);
+        }
+        self.out.push('\n');
+        self.opened = false;
+    }
+
+    /// Add `text` at the end of the last line
+    fn append(&mut self, text: &str) {
+        let content = self.out.trim_end_matches('\n').len();
+        let newlines = self.out.len() - content;
+        self.out.truncate(content);
+        self.out.push_str(text);
+        self.out.push_str(&"\n".repeat(newlines.max(1)));
+    }
+
+    fn blank_line(&mut self) {
+        if !(self.out.is_empty() || self.out.ends_with("\n\n") || self.opened) {
+            self.out.push('\n');
+        }
+    }
+}
+
+fn vis(vis: AstVisibility) -> &'static str {
+    if is_public(vis) {
+        "public "
+    } else {
+        ""
+    }
+}
+
+fn is_public(vis: AstVisibility) -> bool {
+    matches!(vis, AstVisibility::Public)
+}
+
+fn unary(op: &Option<AstUnaryOp>) -> &'static str {
+    match op {
+        Some(AstUnaryOp::Neg) => "-",
+        Some(AstUnaryOp::Not) => "!",
+        Some(AstUnaryOp::_Deref) => "*",
+        Some(AstUnaryOp::_AsRef) => "&",
+        None => "",
+    }
+}
+
+fn binary(op: &AstBinaryOp) -> &'static str {
+    match op {
+        AstBinaryOp::Add => "+",
+        AstBinaryOp::Sub => "-",
+        AstBinaryOp::Mul => "*",
+        AstBinaryOp::Div => "/",
+        AstBinaryOp::Mod => "%",
+        AstBinaryOp::Eq => "==",
+        AstBinaryOp::NEq => "!=",
+        AstBinaryOp::Lt => "<",
+        AstBinaryOp::Lte => "<=",
+        AstBinaryOp::Gt => ">",
+        AstBinaryOp::Gte => ">=",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format;
+
+    #[test]
+    fn comments_sections_and_wrapping() {
+        let source = "import \"std/io\"
+// Points
+public class Point {
+private:
+  x: int64; // abscissa
+    public:
+  y: int64;
+  Point(x: int64, y: int64) { self.x = x;
+      self.y = y; }
+  ~Point() {
+     // nothing to free
+  }
+  func norm(self) -> int64 { return self.x * self.x + self.y * self.y; }
+}
+func main() -> int64 {
+    let p = new Point(1, 2);
+
+
+    let a_very_long_variable_name = some_function_with_a_long_name(first_argument_value, second_argument_value, 3);
+    if p.norm() > 3 { println(\"far\"); } else { println(\"near\"); }
+    return 0; // done
+}
+// end";
+        let expected = "import \"std/io\"
+
+// Points
+public class Point {
+    private:
+        x: int64; // abscissa
+
+    public:
+        y: int64;
+        Point(x: int64, y: int64) {
+            self.x = x;
+            self.y = y;
+        }
+        ~Point() {
+            // nothing to free
+        }
+        func norm(self) -> int64 {
+            return self.x * self.x + self.y * self.y;
+        }
+}
+
+func main() -> int64 {
+    let p = new Point(1, 2);
+
+    let a_very_long_variable_name = some_function_with_a_long_name(
+        first_argument_value,
+        second_argument_value,
+        3,
+    );
+    if p.norm() > 3 {
+        println(\"far\");
+    } else {
+        println(\"near\");
+    }
+    return 0; // done
+}
+// end
+";
+        let formatted = format("point.atlas", source).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format("point.atlas", &formatted).unwrap(), formatted);
+    }
+}
@@ -164,6 +164,8 @@ pub enum TokenKind {
     RFatArrow,
     #[token("~")]
     Tilde,
+    #[token("#")]
+    HashTag,
     #[token("self")]
     KwSelf,
     #[token("operator")]
@@ -185,6 +187,9 @@ pub enum TokenKind {
     KwStruct,
     #[token("concept")]
     KwConcept,
+    #[token("impl")]
+    //Used for the concepts a class implements or a type parameter requires (i.e. class Foo : impl Bar)
+    KwImpl,
     #[token("enum")]
     KwEnum,
     #[token("union")]
@@ -1,10 +1,16 @@
+pub mod formatter;
 pub mod lexer;
 pub mod parser;
 
 use std::path::PathBuf;
 
 use lexer::AtlasLexer;
-use parser::{arena::AstArena, ast::AstProgram, error::ParseResult};
+use lexer::token::LexingError;
+use parser::{
+    arena::AstArena,
+    ast::AstProgram,
+    error::{InvalidTokenError, ParseError, ParseResult},
+};
 
 
 pub fn parse<'ast>(
@@ -16,7 +22,20 @@ pub fn parse<'ast>(
     let token_res = lex.tokenize();
     let tokens = match token_res {
         Ok(tokens) => tokens,
-        Err(e) => panic!("Error while lexing: {:?}", e),
+        Err((e, span)) => {
+            let reason = match e {
+                LexingError::InvalidInteger(e) => format!("invalid integer: {}", e),
+                LexingError::InvalidFloat(e) => format!("invalid float: {}", e),
+                LexingError::InvalidUnsignedInteger(e) => format!("invalid unsigned integer: {}", e),
+                LexingError::InvalidBool(e) => format!("invalid boolean: {}", e),
+                LexingError::NonAsciiChar => String::from("unknown character"),
+            };
+            return Err(ParseError::InvalidToken(InvalidTokenError {
+                reason,
+                span: span.into(),
+                src: source,
+            }));
+        }
     };
     let mut parser = parser::Parser::new(arena, tokens, PathBuf::from(path), source);
     parser.parse()
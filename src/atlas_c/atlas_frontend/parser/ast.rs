@@ -9,15 +9,14 @@ pub struct AstProgram<'ast> {
 }
 
 /// An `Item` is anything that can be declared at the top-level scope of a program.
-/// This currently means functions, classes & structs declarations
-///
-/// Enums & unions are also top-level items, but they are not yet supported
+/// This currently means functions, classes, concepts, enums, unions & structs declarations
 #[derive(Debug, Clone, Serialize)]
-//todo: Add classes and a trait-ish stuff
 pub enum AstItem<'ast> {
     Import(AstImport<'ast>),
     Enum(AstEnum<'ast>),
+    Union(AstUnion<'ast>),
     Class(AstClass<'ast>),
+    Concept(AstConcept<'ast>),
     Struct(AstStruct<'ast>),
     ExternFunction(AstExternFunction<'ast>),
     Func(AstFunction<'ast>),
@@ -28,7 +27,9 @@ impl AstItem<'_> {
         match self {
             AstItem::Import(_) => {}
             AstItem::Enum(v) => v.vis = vis,
+            AstItem::Union(v) => v.vis = vis,
             AstItem::Class(v) => v.vis = vis,
+            AstItem::Concept(v) => v.vis = vis,
             AstItem::Struct(v) => v.vis = vis,
             AstItem::ExternFunction(v) => v.vis = vis,
             AstItem::Func(v) => v.vis = vis,
@@ -38,7 +39,9 @@ impl AstItem<'_> {
         match self {
             AstItem::Import(v) => v.span.clone(),
             AstItem::Enum(v) => v.span.clone(),
+            AstItem::Union(v) => v.span.clone(),
             AstItem::Class(v) => v.span.clone(),
+            AstItem::Concept(v) => v.span.clone(),
             AstItem::Struct(v) => v.span.clone(),
             AstItem::ExternFunction(v) => v.span.clone(),
             AstItem::Func(v) => v.span.clone(),
@@ -89,12 +92,42 @@ pub struct AstClass<'ast> {
     pub constructor: Option<&'ast AstConstructor<'ast>>,
     pub destructor: Option<&'ast AstDestructor<'ast>>,
     pub generics: &'ast [&'ast AstGeneric<'ast>],
+    /// `: impl Foo, impl Bar<T>`
+    pub concepts: &'ast [&'ast AstNamedType<'ast>],
     pub operators: &'ast [&'ast AstOperatorOverload<'ast>],
     pub constants: &'ast [&'ast AstConst<'ast>],
     //todo: Add support for methods (AstFunction -> AstMethod)
     pub methods: &'ast [&'ast AstMethod<'ast>],
 }
 
+/// A set of methods a class must have to implement it
+///
+/// Example:
+/// ```cpp
+/// public concept Indexable<T> {
+///     func len(self) -> int64;
+///     func get(self, i: int64) -> T;
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize)]
+pub struct AstConcept<'ast> {
+    pub span: Span,
+    pub name: &'ast AstIdentifier<'ast>,
+    pub vis: AstVisibility,
+    pub generics: &'ast [&'ast AstGeneric<'ast>],
+    pub methods: &'ast [&'ast AstConceptMethod<'ast>],
+}
+
+/// A method declared by a concept, it has no body
+#[derive(Debug, Clone, Serialize)]
+pub struct AstConceptMethod<'ast> {
+    pub modifier: AstMethodModifier,
+    pub span: Span,
+    pub name: &'ast AstIdentifier<'ast>,
+    pub args: &'ast [&'ast AstObjField<'ast>],
+    pub ret: &'ast AstType<'ast>,
+}
+
 #[derive(Debug, Clone, Serialize, Default, Copy)]
 pub enum AstMethodModifier {
     Static,
@@ -141,10 +174,30 @@ pub struct AstMethod<'ast> {
 pub struct AstFunction<'ast> {
     pub span: Span,
     pub name: &'ast AstIdentifier<'ast>,
+    pub generics: &'ast [&'ast AstGeneric<'ast>],
     pub args: &'ast [&'ast AstObjField<'ast>],
     pub ret: &'ast AstType<'ast>,
     pub body: &'ast AstBlock<'ast>,
     pub vis: AstVisibility,
+    /// `#[name]` lines written above the function
+    pub attributes: &'ast [&'ast AstAttribute<'ast>],
+}
+
+/// `#[name]`, only functions can have some for now (e.g. `#[test]`)
+#[derive(Debug, Clone, Serialize)]
+pub struct AstAttribute<'ast> {
+    pub span: Span,
+    pub name: &'ast AstIdentifier<'ast>,
+}
+
+/// `union Option<T> { Some(value: T), None }`, a sum type whose variants can carry fields
+#[derive(Debug, Clone, Serialize)]
+pub struct AstUnion<'ast> {
+    pub span: Span,
+    pub name: &'ast AstIdentifier<'ast>,
+    pub generics: &'ast [&'ast AstGeneric<'ast>],
+    pub variants: &'ast [&'ast AstUnionVariant<'ast>],
+    pub vis: AstVisibility,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -291,7 +344,9 @@ pub enum AstExpr<'ast> {
     NewObj(AstNewObjExpr<'ast>),
     Delete(AstDeleteObjExpr<'ast>),
     NewArray(AstNewArrayExpr<'ast>),
-    _Block(AstBlock<'ast>),
+    /// Only reachable as the body of a match arm for now
+    Block(AstBlock<'ast>),
+    Match(AstMatchExpr<'ast>),
     Assign(AstAssignExpr<'ast>),
     Casting(AstCastingExpr<'ast>),
     //Tuple(AstTupleExpr<'ast>),
@@ -314,7 +369,8 @@ impl AstExpr<'_> {
             AstExpr::NewObj(e) => e.span.clone(),
             AstExpr::Delete(e) => e.span.clone(),
             AstExpr::NewArray(e) => e.span.clone(),
-            AstExpr::_Block(e) => e.span.clone(),
+            AstExpr::Block(e) => e.span.clone(),
+            AstExpr::Match(e) => e.span.clone(),
             AstExpr::Assign(e) => e.span.clone(),
             AstExpr::Casting(e) => e.span.clone(),
         }
@@ -351,6 +407,8 @@ pub struct AstBlock<'ast> {
 pub struct AstNewObjExpr<'ast> {
     pub span: Span,
     pub ty: &'ast AstIdentifier<'ast>,
+    /// `new Name<A, B>(...)`, empty when the type arguments are left to inference
+    pub type_args: &'ast [&'ast AstType<'ast>],
     pub args: &'ast [&'ast AstExpr<'ast>],
 }
 
@@ -462,6 +520,57 @@ pub struct AstIfElseExpr<'ast> {
     pub else_body: Option<&'ast AstBlock<'ast>>,
 }
 
+#[derive(Debug, Clone, Serialize)]
+/// i.e. ``match value { 0 | 1 => "small", _ => { ... } }``
+pub struct AstMatchExpr<'ast> {
+    pub span: Span,
+    pub value: &'ast AstExpr<'ast>,
+    pub arms: &'ast [&'ast AstMatchArm<'ast>],
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AstMatchArm<'ast> {
+    pub span: Span,
+    /// The arm is taken if any of them matches
+    pub patterns: &'ast [&'ast AstPattern<'ast>],
+    /// An [`AstExpr::Block`] for ``pattern => { ... }``
+    pub body: &'ast AstExpr<'ast>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum AstPattern<'ast> {
+    /// `_`
+    Wildcard(AstWildcardPattern),
+    /// Integers, chars, strings & booleans
+    Literal(AstLiteral<'ast>),
+    /// `Enum::Variant` or `Enum::Variant(a, b)` to bind its payload
+    Variant(AstVariantPattern<'ast>),
+}
+
+impl AstPattern<'_> {
+    pub(crate) fn span(&self) -> Span {
+        match self {
+            AstPattern::Wildcard(p) => p.span.clone(),
+            AstPattern::Literal(l) => l.span(),
+            AstPattern::Variant(p) => p.span.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AstWildcardPattern {
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AstVariantPattern<'ast> {
+    pub span: Span,
+    pub target: &'ast AstIdentifier<'ast>,
+    pub variant: &'ast AstIdentifier<'ast>,
+    /// `None` if the pattern has no parentheses
+    pub bindings: Option<&'ast [&'ast AstIdentifier<'ast>]>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct AstLet<'ast> {
     pub span: Span,
@@ -655,6 +764,8 @@ pub struct AstStringType {
 pub struct AstNamedType<'ast> {
     pub span: Span,
     pub name: &'ast AstIdentifier<'ast>,
+    /// `Name<A, B>`, empty when no type arguments were given
+    pub args: &'ast [&'ast AstType<'ast>],
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -11,6 +11,7 @@ declare_error_type! {
         UnexpectedToken(UnexpectedTokenError),
         OnlyOneConstructorAllowed(OnlyOneConstructorAllowedError),
         NoFieldInClass(NoFieldInClassError),
+        InvalidToken(InvalidTokenError),
     }
 }
 
@@ -56,6 +57,18 @@ pub struct UnexpectedEndOfFileError {
     pub src: String,
 }
 
+#[derive(Error, Diagnostic, Debug)]
+#[diagnostic(code(syntax::invalid_token))]
+#[error("Invalid token")]
+pub struct InvalidTokenError {
+    /// What the lexer couldn't make sense of
+    pub reason: String,
+    #[label("{reason}")]
+    pub span: SourceSpan,
+    #[source_code]
+    pub src: String,
+}
+
 #[derive(Error, Diagnostic, Debug)]
 #[diagnostic(code(syntax::unexpected_token))]
 #[error("Found unexpected token during parsing")]
@@ -19,7 +19,7 @@ use ast::{
 };
 
 use crate::atlas_c::atlas_frontend::lexer::{token::{Token, TokenKind}, Spanned, TokenVec};
-use crate::atlas_c::atlas_frontend::parser::ast::{AstCastingExpr, AstCharLiteral, AstCharType, AstClass, AstConstructor, AstDeleteObjExpr, AstDestructor, AstGeneric, AstGenericConstraint, AstListLiteral, AstListType, AstMethod, AstMethodModifier, AstNewArrayExpr, AstNewObjExpr, AstOperatorOverload, AstSelf, AstSelfType, AstStaticAccessExpr, AstUnitLiteral, AstVisibility};
+use crate::atlas_c::atlas_frontend::parser::ast::{AstAttribute, AstCastingExpr, AstCharLiteral, AstCharType, AstClass, AstConcept, AstConceptMethod, AstConstructor, AstDeleteObjExpr, AstDestructor, AstEnum, AstEnumVariant, AstGeneric, AstGenericConstraint, AstListLiteral, AstListType, AstMatchArm, AstMatchExpr, AstMethod, AstMethodModifier, AstNewArrayExpr, AstNewObjExpr, AstOperatorOverload, AstPattern, AstSelf, AstSelfType, AstStaticAccessExpr, AstUnion, AstUnionVariant, AstUnitLiteral, AstVariantPattern, AstVisibility, AstWildcardPattern};
 use arena::AstArena;
 use logos::Span;
 
@@ -108,6 +108,9 @@ impl<'ast> Parser<'ast> {
             TokenKind::KwExtern => Ok(AstItem::ExternFunction(self.parse_extern_function()?)),
             TokenKind::KwFunc => Ok(AstItem::Func(self.parse_func()?)),
             TokenKind::KwClass => Ok(AstItem::Class(self.parse_class()?)),
+            TokenKind::KwConcept => Ok(AstItem::Concept(self.parse_concept()?)),
+            TokenKind::KwEnum => Ok(AstItem::Enum(self.parse_enum()?)),
+            TokenKind::KwUnion => Ok(AstItem::Union(self.parse_union()?)),
             //This does allow for "private public private func foo() {}" which is bad... but it's a start!
             TokenKind::KwPublic => {
                 let _ = self.advance();
@@ -121,6 +124,27 @@ impl<'ast> Parser<'ast> {
                 item.set_vis(AstVisibility::Private);
                 Ok(item)
             }
+            TokenKind::HashTag => {
+                let tok = self.current().clone();
+                let attribute = self.parse_attribute()?;
+                match self.parse_item()? {
+                    AstItem::Func(mut func) => {
+                        let attributes = std::iter::once(attribute)
+                            .chain(func.attributes.iter().map(|a| (*a).clone()))
+                            .collect();
+                        func.attributes = self.arena.alloc_vec(attributes);
+                        Ok(AstItem::Func(func))
+                    }
+                    _ => Err(ParseError::UnexpectedToken(UnexpectedTokenError {
+                        span: SourceSpan::new(SourceOffset::from(tok.start()), tok.end() - tok.start()),
+                        token: tok,
+                        expected: TokenVec(vec![TokenKind::Identifier(
+                            "Function".to_string(),
+                        )]),
+                        src: self.src.clone(),
+                    })),
+                }
+            }
             //Handling comments
             _ => Err(ParseError::UnexpectedToken(UnexpectedTokenError {
                 token: self.current().clone(),
@@ -190,6 +214,22 @@ impl<'ast> Parser<'ast> {
             self.expect(TokenKind::RAngle)?;
         }
 
+        let mut concepts = vec![];
+        if self.current().kind() == TokenKind::Colon {
+            let _ = self.advance();
+            // example: `: impl Foo, impl Bar<T>`
+            loop {
+                self.expect(TokenKind::KwImpl)?;
+                concepts.push(self.parse_named_type()?);
+                if self.current().kind() == TokenKind::Comma {
+                    let _ = self.advance();
+                } else {
+                    break;
+                }
+            }
+        }
+        self.parse_where(&mut generics)?;
+
         self.expect(TokenKind::LBrace)?;
         let mut fields = vec![];
         let mut constructor: Option<&'ast AstConstructor<'ast>> = None;
@@ -287,7 +327,8 @@ impl<'ast> Parser<'ast> {
             //todo: Actually use/make the constructor and the destructor
             constructor,
             destructor,
-            generics: self.arena.alloc_vec(vec![]),
+            generics: self.arena.alloc_vec(generics),
+            concepts: self.arena.alloc_vec(concepts),
             methods: self.arena.alloc_vec(methods),
             operators: self.arena.alloc_vec(operators),
             constants: self.arena.alloc_vec(constants),
@@ -297,6 +338,22 @@ impl<'ast> Parser<'ast> {
     }
 
     fn parse_method(&mut self) -> ParseResult<AstMethod<'ast>> {
+        let signature = self.parse_method_signature()?;
+        let body = self.parse_block()?;
+        let node = AstMethod {
+            modifier: signature.modifier,
+            span: Span::union_span(&signature.span, &body.span),
+            name: signature.name,
+            args: signature.args,
+            ret: signature.ret,
+            body: self.arena.alloc(body),
+            vis: AstVisibility::default(),
+        };
+        Ok(node)
+    }
+
+    /// `func name(self, args) -> ret`, without the body
+    fn parse_method_signature(&mut self) -> ParseResult<AstConceptMethod<'ast>> {
         let _ = self.advance();
         let name = self.parse_identifier()?;
         self.expect(TokenKind::LParen)?;
@@ -344,19 +401,99 @@ impl<'ast> Parser<'ast> {
             let _ = self.advance();
             ret_ty = self.parse_type()?;
         }
-        let body = self.parse_block()?;
-        let node = AstMethod {
+        let node = AstConceptMethod {
             modifier,
-            span: Span::union_span(&name.span, &body.span),
+            span: Span::union_span(&name.span, &self.tokens[self.pos - 1].span()),
             name: self.arena.alloc(name),
             args: self.arena.alloc_vec(params),
             ret: self.arena.alloc(ret_ty),
-            body: self.arena.alloc(body),
+        };
+        Ok(node)
+    }
+
+    fn parse_concept(&mut self) -> ParseResult<AstConcept<'ast>> {
+        let start = self.expect(TokenKind::KwConcept)?.span();
+        let name = self.parse_identifier()?;
+
+        let mut generics = vec![];
+        if self.current().kind() == TokenKind::LAngle {
+            let _ = self.advance();
+            while self.current().kind() != TokenKind::RAngle {
+                generics.push(self.parse_generic()?);
+                if self.current().kind() == TokenKind::Comma {
+                    let _ = self.advance();
+                }
+            }
+            self.expect(TokenKind::RAngle)?;
+        }
+        self.parse_where(&mut generics)?;
+
+        self.expect(TokenKind::LBrace)?;
+        let mut methods = vec![];
+        while self.current().kind() != TokenKind::RBrace {
+            if self.current().kind() != TokenKind::KwFunc {
+                return Err(ParseError::UnexpectedToken(UnexpectedTokenError {
+                    token: self.current().clone(),
+                    expected: TokenVec(vec![TokenKind::KwFunc]),
+                    span: SourceSpan::new(
+                        SourceOffset::from(self.current().start()),
+                        self.current().end() - self.current().start(),
+                    ),
+                    src: self.src.clone(),
+                }));
+            }
+            methods.push(self.parse_method_signature()?);
+            self.expect(TokenKind::Semicolon)?;
+        }
+        let end = self.expect(TokenKind::RBrace)?.span();
+
+        let node = AstConcept {
+            span: Span::union_span(&start, &end),
+            name: self.arena.alloc(name),
             vis: AstVisibility::default(),
+            generics: self.arena.alloc_vec(generics),
+            methods: self.arena.alloc_vec(methods),
         };
         Ok(node)
     }
 
+    /// `where T: impl Foo, U: impl Bar`, the constraints are added to the matching type parameters
+    fn parse_where(&mut self, generics: &mut [AstGeneric<'ast>]) -> ParseResult<()> {
+        if self.current().kind() != TokenKind::KwWhere {
+            return Ok(());
+        }
+        let _ = self.advance();
+        loop {
+            let tok = self.current().clone();
+            let clause = self.parse_generic()?;
+            let generic = generics
+                .iter_mut()
+                .find(|g| g.name.name == clause.name.name)
+                .ok_or_else(|| {
+                    ParseError::UnexpectedToken(UnexpectedTokenError {
+                        span: SourceSpan::new(SourceOffset::from(tok.start()), tok.end() - tok.start()),
+                        token: tok,
+                        expected: TokenVec(vec![TokenKind::Identifier(
+                            "Type parameter".to_string(),
+                        )]),
+                        src: self.src.clone(),
+                    })
+                })?;
+            let constraints = generic
+                .constraints
+                .iter()
+                .chain(clause.constraints.iter())
+                .map(|c| (*c).clone())
+                .collect();
+            generic.constraints = self.arena.alloc_vec(constraints);
+            if self.current().kind() == TokenKind::Comma {
+                let _ = self.advance();
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
     fn parse_generic(&mut self) -> ParseResult<AstGeneric<'ast>> {
         let name = self.parse_identifier()?;
         let mut constraints = vec![];
@@ -389,23 +526,10 @@ impl<'ast> Parser<'ast> {
                         self.expect(TokenKind::RParen)?;
                         AstGenericConstraint::Operator(op)
                     }
-                    TokenKind::Identifier(_) => {
-                        let ast_ty = match self.parse_type()? {
-                            AstType::Named(ast_ty) => ast_ty,
-                            _ => return Err(ParseError::UnexpectedToken(UnexpectedTokenError {
-                                token: self.current().clone(),
-                                expected: TokenVec(vec![TokenKind::Identifier(
-                                    "Named Type".to_string(),
-                                )]),
-                                span: SourceSpan::new(
-                                    SourceOffset::from(self.current().start()),
-                                    self.current().end() - self.current().start(),
-                                ),
-                                src: self.src.clone(),
-                            })),
-                        };
-
-                        AstGenericConstraint::NamedType(ast_ty)
+                    TokenKind::Identifier(_) => AstGenericConstraint::NamedType(self.parse_named_type()?),
+                    TokenKind::KwImpl => {
+                        let _ = self.advance();
+                        AstGenericConstraint::NamedType(self.parse_named_type()?)
                     }
                     _ => return Err(ParseError::UnexpectedToken(UnexpectedTokenError {
                         token: self.current().clone(),
@@ -435,6 +559,23 @@ impl<'ast> Parser<'ast> {
         })
     }
 
+    fn parse_named_type(&mut self) -> ParseResult<AstNamedType<'ast>> {
+        match self.parse_type()? {
+            AstType::Named(ast_ty) => Ok(ast_ty),
+            _ => Err(ParseError::UnexpectedToken(UnexpectedTokenError {
+                token: self.current().clone(),
+                expected: TokenVec(vec![TokenKind::Identifier(
+                    "Named Type".to_string(),
+                )]),
+                span: SourceSpan::new(
+                    SourceOffset::from(self.current().start()),
+                    self.current().end() - self.current().start(),
+                ),
+                src: self.src.clone(),
+            })),
+        }
+    }
+
     fn parse_operator(&mut self) -> ParseResult<AstOperatorOverload<'ast>> {
         self.expect(TokenKind::KwOperator)?;
         let tok_op = self.current().clone();
@@ -496,6 +637,19 @@ impl<'ast> Parser<'ast> {
     fn parse_func(&mut self) -> ParseResult<AstFunction<'ast>> {
         let _ = self.advance();
         let name = self.parse_identifier()?;
+
+        let mut generics = vec![];
+        if self.current().kind() == TokenKind::LAngle {
+            let _ = self.advance();
+            while self.current().kind() != TokenKind::RAngle {
+                generics.push(self.parse_generic()?);
+                if self.current().kind() == TokenKind::Comma {
+                    let _ = self.advance();
+                }
+            }
+            self.expect(TokenKind::RAngle)?;
+        }
+
         self.expect(TokenKind::LParen)?;
         let mut params = vec![];
 
@@ -514,18 +668,32 @@ impl<'ast> Parser<'ast> {
             let _ = self.advance();
             ret_ty = self.parse_type()?;
         }
+        self.parse_where(&mut generics)?;
         let body = self.parse_block()?;
         let node = AstFunction {
             span: Span::union_span(&name.span, &body.span),
             name: self.arena.alloc(name),
+            generics: self.arena.alloc_vec(generics),
             args: self.arena.alloc_vec(params),
             ret: self.arena.alloc(ret_ty),
             body: self.arena.alloc(body),
             vis: AstVisibility::default(),
+            attributes: self.arena.alloc_vec(vec![]),
         };
         Ok(node)
     }
 
+    fn parse_attribute(&mut self) -> ParseResult<AstAttribute<'ast>> {
+        let start = self.expect(TokenKind::HashTag)?.span;
+        self.expect(TokenKind::LBracket)?;
+        let name = self.parse_identifier()?;
+        let end = self.expect(TokenKind::RBracket)?.span;
+        Ok(AstAttribute {
+            span: Span::union_span(&start, &end),
+            name: self.arena.alloc(name),
+        })
+    }
+
     fn parse_block(&mut self) -> ParseResult<AstBlock<'ast>> {
         let start = self.expect(TokenKind::LBrace)?.span;
         let mut stmts = vec![];
@@ -567,6 +735,14 @@ impl<'ast> Parser<'ast> {
                 let node = AstStatement::While(self.parse_while()?);
                 Ok(node)
             }
+            //Like `if`, a `match` statement doesn't need a semicolon
+            TokenKind::KwMatch => {
+                let node = AstStatement::Expr(AstExpr::Match(self.parse_match()?));
+                if self.current().kind() == TokenKind::Semicolon {
+                    let _ = self.advance();
+                }
+                Ok(node)
+            }
             TokenKind::KwBreak => {
                 let node = self.parse_break()?;
                 Ok(AstStatement::Break(node))
@@ -842,6 +1018,13 @@ impl<'ast> Parser<'ast> {
                 let _ = self.advance();
                 node
             }
+            TokenKind::LParen => {
+                let start = self.advance();
+                let end = self.expect(TokenKind::RParen)?;
+                AstExpr::Literal(AstLiteral::Unit(AstUnitLiteral {
+                    span: Span::union_span(&start.span(), &end.span()),
+                }))
+            }
             TokenKind::KwNew => {
                 self.parse_new_obj()?
             }
@@ -902,6 +1085,7 @@ impl<'ast> Parser<'ast> {
                 node
             }
             TokenKind::KwIf => AstExpr::IfElse(self.parse_if_expr()?),
+            TokenKind::KwMatch => AstExpr::Match(self.parse_match()?),
             _ => {
                 return Err(ParseError::UnexpectedToken(UnexpectedTokenError {
                     token: tok.clone(),
@@ -957,6 +1141,18 @@ impl<'ast> Parser<'ast> {
             TokenKind::Identifier(_) => {
                 let name = self.parse_identifier()?;
 
+                let mut type_args = vec![];
+                if self.current().kind() == TokenKind::LAngle {
+                    let _ = self.advance();
+                    while self.current().kind() != TokenKind::RAngle {
+                        type_args.push(self.parse_type()?);
+                        if self.current().kind() == TokenKind::Comma {
+                            let _ = self.advance();
+                        }
+                    }
+                    self.expect(TokenKind::RAngle)?;
+                }
+
                 self.expect(TokenKind::LParen)?;
                 let mut args = vec![];
 
@@ -971,6 +1167,7 @@ impl<'ast> Parser<'ast> {
                 let node = AstExpr::NewObj(AstNewObjExpr {
                     span: Span::union_span(&name.span, &self.current().span()),
                     ty: self.arena.alloc(name),
+                    type_args: self.arena.alloc_vec(type_args),
                     args: self.arena.alloc_vec(args),
                 });
 
@@ -1033,6 +1230,138 @@ impl<'ast> Parser<'ast> {
         Ok(node)
     }
 
+    fn parse_match(&mut self) -> ParseResult<AstMatchExpr<'ast>> {
+        let start = self.expect(TokenKind::KwMatch)?;
+        let value = self.parse_expr()?;
+        self.expect(TokenKind::LBrace)?;
+
+        let mut arms = vec![];
+        while self.current().kind() != TokenKind::RBrace {
+            let mut patterns = vec![self.parse_pattern()?];
+            while self.current().kind() == TokenKind::Pipe {
+                let _ = self.advance();
+                patterns.push(self.parse_pattern()?);
+            }
+            self.expect(TokenKind::RFatArrow)?;
+            let body = if self.current().kind() == TokenKind::LBrace {
+                AstExpr::Block(self.parse_block()?)
+            } else {
+                self.parse_expr()?
+            };
+            //The comma is optional after a block & after the last arm
+            if self.current().kind() == TokenKind::Comma {
+                let _ = self.advance();
+            } else if !matches!(body, AstExpr::Block(_)) && self.current().kind() != TokenKind::RBrace {
+                self.expect(TokenKind::Comma)?;
+            }
+            arms.push(AstMatchArm {
+                span: Span::union_span(&patterns[0].span(), &body.span()),
+                patterns: self.arena.alloc_vec(patterns),
+                body: self.arena.alloc(body),
+            });
+        }
+        let end = self.expect(TokenKind::RBrace)?;
+
+        let node = AstMatchExpr {
+            span: Span::union_span(&start.span(), &end.span()),
+            value: self.arena.alloc(value),
+            arms: self.arena.alloc_vec(arms),
+        };
+        Ok(node)
+    }
+
+    fn parse_pattern(&mut self) -> ParseResult<AstPattern<'ast>> {
+        let tok = self.current().clone();
+        let pattern = match tok.kind() {
+            TokenKind::Identifier(name) if name == "_" => {
+                let _ = self.advance();
+                AstPattern::Wildcard(AstWildcardPattern { span: tok.span() })
+            }
+            TokenKind::Identifier(_) => {
+                let target = self.parse_identifier()?;
+                self.expect(TokenKind::DoubleColon)?;
+                let variant = self.parse_identifier()?;
+                let mut span = Span::union_span(&target.span, &variant.span);
+                let bindings = if self.current().kind() == TokenKind::LParen {
+                    let _ = self.advance();
+                    let mut bindings = vec![];
+                    while self.current().kind() != TokenKind::RParen {
+                        bindings.push(self.parse_identifier()?);
+                        if self.current().kind() == TokenKind::Comma {
+                            let _ = self.advance();
+                        }
+                    }
+                    span = Span::union_span(&span, &self.expect(TokenKind::RParen)?.span());
+                    Some(self.arena.alloc_vec(bindings))
+                } else {
+                    None
+                };
+                AstPattern::Variant(AstVariantPattern {
+                    span,
+                    target: self.arena.alloc(target),
+                    variant: self.arena.alloc(variant),
+                    bindings,
+                })
+            }
+            TokenKind::Minus => {
+                let _ = self.advance();
+                let int = self.current().clone();
+                match int.kind() {
+                    TokenKind::Integer(i) => {
+                        let _ = self.advance();
+                        AstPattern::Literal(AstLiteral::Integer(AstIntegerLiteral {
+                            span: Span::union_span(&tok.span(), &int.span()),
+                            value: -i,
+                        }))
+                    }
+                    _ => {
+                        return Err(ParseError::UnexpectedToken(UnexpectedTokenError {
+                            span: SourceSpan::new(SourceOffset::from(int.start()), int.end() - int.start()),
+                            token: int,
+                            expected: TokenVec(vec![TokenKind::Identifier("Integer".to_string())]),
+                            src: self.src.clone(),
+                        }))
+                    }
+                }
+            }
+            TokenKind::Integer(i) => {
+                let _ = self.advance();
+                AstPattern::Literal(AstLiteral::Integer(AstIntegerLiteral { span: tok.span(), value: i }))
+            }
+            TokenKind::UnsignedInteger(u) => {
+                let _ = self.advance();
+                AstPattern::Literal(AstLiteral::UnsignedInteger(AstUnsignedIntegerLiteral {
+                    span: tok.span(),
+                    value: u,
+                }))
+            }
+            TokenKind::Char(c) => {
+                let _ = self.advance();
+                AstPattern::Literal(AstLiteral::Char(AstCharLiteral { span: tok.span(), value: c }))
+            }
+            TokenKind::Bool(b) => {
+                let _ = self.advance();
+                AstPattern::Literal(AstLiteral::Boolean(AstBooleanLiteral { span: tok.span(), value: b }))
+            }
+            TokenKind::StringLiteral(s) => {
+                let _ = self.advance();
+                AstPattern::Literal(AstLiteral::String(AstStringLiteral {
+                    span: tok.span(),
+                    value: self.arena.alloc(s),
+                }))
+            }
+            _ => {
+                return Err(ParseError::UnexpectedToken(UnexpectedTokenError {
+                    span: SourceSpan::new(SourceOffset::from(tok.start()), tok.end() - tok.start()),
+                    token: tok,
+                    expected: TokenVec(vec![TokenKind::Identifier("Pattern".to_string())]),
+                    src: self.src.clone(),
+                }))
+            }
+        };
+        Ok(pattern)
+    }
+
     fn parse_return(&mut self) -> ParseResult<AstReturnStmt<'ast>> {
         let _ = self.advance();
         if self.current().kind == TokenKind::Semicolon {
@@ -1068,6 +1397,7 @@ impl<'ast> Parser<'ast> {
                 generic_names.push(AstNamedType {
                     span: self.current().span(),
                     name: self.arena.alloc(generic_name),
+                    args: &[],
                 });
                 if self.current().kind == TokenKind::Comma {
                     let _ = self.advance();
@@ -1165,6 +1495,113 @@ impl<'ast> Parser<'ast> {
         Ok(node)
     }
 
+    /// `enum Name { A, B = 4, C }`, the trailing comma is optional
+    fn parse_enum(&mut self) -> ParseResult<AstEnum<'ast>> {
+        self.expect(TokenKind::KwEnum)?;
+        let ident = self.parse_identifier()?;
+        self.expect(TokenKind::LBrace)?;
+
+        let mut variants = vec![];
+        while self.current().kind() != TokenKind::RBrace {
+            let name = self.parse_identifier()?;
+            let mut span = name.span.clone();
+            let mut val = None;
+            if self.current().kind() == TokenKind::OpAssign {
+                let _ = self.advance();
+                let tok = self.current().clone();
+                match tok.kind() {
+                    TokenKind::Integer(i) if i32::try_from(i).is_ok() => {
+                        val = Some(i as i32);
+                        span = Span::union_span(&span, &tok.span());
+                        let _ = self.advance();
+                    }
+                    _ => {
+                        return Err(ParseError::UnexpectedToken(UnexpectedTokenError {
+                            span: SourceSpan::new(SourceOffset::from(tok.start()), tok.end() - tok.start()),
+                            token: tok,
+                            expected: TokenVec(vec![TokenKind::Identifier("Integer".to_string())]),
+                            src: self.src.clone(),
+                        }));
+                    }
+                }
+            }
+            variants.push(AstEnumVariant {
+                span,
+                name: self.arena.alloc(name),
+                val,
+            });
+            if self.current().kind() == TokenKind::Comma {
+                let _ = self.advance();
+            } else if self.current().kind() != TokenKind::RBrace {
+                self.expect(TokenKind::Comma)?;
+            }
+        }
+        let node = AstEnum {
+            span: Span::union_span(&ident.span, &self.current().span()),
+            name: self.arena.alloc(ident),
+            variants: self.arena.alloc_vec(variants),
+            vis: AstVisibility::default(),
+        };
+        self.expect(TokenKind::RBrace)?;
+        Ok(node)
+    }
+
+    /// `union Name<T> { A(field: T, ...), B }`, the trailing comma is optional
+    fn parse_union(&mut self) -> ParseResult<AstUnion<'ast>> {
+        self.expect(TokenKind::KwUnion)?;
+        let ident = self.parse_identifier()?;
+
+        let mut generics = vec![];
+        if self.current().kind() == TokenKind::LAngle {
+            let _ = self.advance();
+            while self.current().kind() != TokenKind::RAngle {
+                generics.push(self.parse_generic()?);
+                if self.current().kind() == TokenKind::Comma {
+                    let _ = self.advance();
+                }
+            }
+            self.expect(TokenKind::RAngle)?;
+        }
+        self.expect(TokenKind::LBrace)?;
+
+        let mut variants = vec![];
+        while self.current().kind() != TokenKind::RBrace {
+            let name = self.parse_identifier()?;
+            let mut span = name.span.clone();
+            let mut fields = vec![];
+            if self.current().kind() == TokenKind::LParen {
+                let _ = self.advance();
+                while self.current().kind() != TokenKind::RParen {
+                    fields.push(self.parse_obj_field()?);
+                    if self.current().kind() == TokenKind::Comma {
+                        let _ = self.advance();
+                    }
+                }
+                span = Span::union_span(&span, &self.current().span());
+                self.expect(TokenKind::RParen)?;
+            }
+            variants.push(AstUnionVariant {
+                span,
+                name: self.arena.alloc(name),
+                fields: self.arena.alloc_vec(fields),
+            });
+            if self.current().kind() == TokenKind::Comma {
+                let _ = self.advance();
+            } else if self.current().kind() != TokenKind::RBrace {
+                self.expect(TokenKind::Comma)?;
+            }
+        }
+        let node = AstUnion {
+            span: Span::union_span(&ident.span, &self.current().span()),
+            name: self.arena.alloc(ident),
+            generics: self.arena.alloc_vec(generics),
+            variants: self.arena.alloc_vec(variants),
+            vis: AstVisibility::default(),
+        };
+        self.expect(TokenKind::RBrace)?;
+        Ok(node)
+    }
+
     fn parse_obj_field(&mut self) -> ParseResult<AstObjField<'ast>> {
         if self.current().kind == TokenKind::KwSelf {
             self.expect(TokenKind::KwSelf)?;
@@ -1357,9 +1794,21 @@ impl<'ast> Parser<'ast> {
             }
             TokenKind::Identifier(_) => {
                 let name = self.parse_identifier()?;
+                let mut args = vec![];
+                if self.current().kind() == TokenKind::LAngle {
+                    let _ = self.advance();
+                    while self.current().kind() != TokenKind::RAngle {
+                        args.push(self.parse_type()?);
+                        if self.current().kind() == TokenKind::Comma {
+                            let _ = self.advance();
+                        }
+                    }
+                    self.expect(TokenKind::RAngle)?;
+                }
                 let node = AstType::Named(AstNamedType {
                     span: Span::union_span(&start, &self.current().span()),
                     name: self.arena.alloc(name),
+                    args: self.arena.alloc_vec(args),
                 });
                 Ok(node)
             }
@@ -1508,4 +1957,111 @@ mod tests {
             Err(e) => Err(e.into()),
         }
     }
+
+    #[test]
+    fn test_parse_match() {
+        let input = r#"func f(n: int64) -> int64 {
+    return match n {
+        -1 | 2 => 1,
+        Shape::Rect(w, h) => w,
+        Shape::Empty => 2,
+        _ => {
+            return 3;
+        }
+    };
+}"#;
+        let bump = Bump::new();
+        let arena = AstArena::new(&bump);
+        let program = crate::atlas_c::atlas_frontend::parse("match.atlas", &arena, input.to_string()).unwrap();
+        let AstItem::Func(f) = program.items[0] else {
+            panic!("expected a function");
+        };
+        //Operands are wrapped in a unary expression without operator
+        let AstStatement::Return(AstReturnStmt {
+            value: AstExpr::UnaryOp(AstUnaryOpExpr {
+                expr: AstExpr::Match(m),
+                op: None,
+                ..
+            }),
+            ..
+        }) = f.body.stmts[0]
+        else {
+            panic!("expected `return match`");
+        };
+        assert_eq!(m.arms.len(), 4);
+        let values = m.arms[0]
+            .patterns
+            .iter()
+            .map(|p| match p {
+                AstPattern::Literal(AstLiteral::Integer(i)) => i.value,
+                _ => panic!("expected an integer pattern"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, [-1, 2]);
+        let AstPattern::Variant(rect) = m.arms[1].patterns[0] else {
+            panic!("expected a variant pattern");
+        };
+        assert_eq!((rect.target.name, rect.variant.name), ("Shape", "Rect"));
+        let bindings = rect.bindings.unwrap().iter().map(|b| b.name).collect::<Vec<_>>();
+        assert_eq!(bindings, ["w", "h"]);
+        let AstPattern::Variant(empty) = m.arms[2].patterns[0] else {
+            panic!("expected a variant pattern");
+        };
+        assert!(empty.bindings.is_none());
+        assert!(matches!(m.arms[3].patterns, [AstPattern::Wildcard(_)]));
+        assert!(matches!(m.arms[3].body, AstExpr::Block(_)));
+    }
+
+    #[test]
+    fn test_parse_enum() {
+        let input = "enum Color {\n    Red,\n    Green = 4,\n    Blue,\n}";
+        let bump = Bump::new();
+        let arena = AstArena::new(&bump);
+        let program = crate::atlas_c::atlas_frontend::parse("enum.atlas", &arena, input.to_string()).unwrap();
+        let AstItem::Enum(e) = program.items[0] else {
+            panic!("expected an enum");
+        };
+        assert_eq!(e.name.name, "Color");
+        let variants = e.variants.iter().map(|v| (v.name.name, v.val)).collect::<Vec<_>>();
+        assert_eq!(variants, [("Red", None), ("Green", Some(4)), ("Blue", None)]);
+    }
+
+    #[test]
+    fn test_parse_concept() {
+        let input = r#"concept Shape {
+    func area(self) -> int64;
+    func scale(self, by: int64) -> unit;
+}
+class Square : impl Shape {
+public:
+    side: int64;
+}
+func total<S>(a: S, b: S) -> int64 where S: impl Shape {
+    return a.area() + b.area();
+}"#;
+        let bump = Bump::new();
+        let arena = AstArena::new(&bump);
+        let program = crate::atlas_c::atlas_frontend::parse("concept.atlas", &arena, input.to_string()).unwrap();
+        let AstItem::Concept(concept) = program.items[0] else {
+            panic!("expected a concept");
+        };
+        assert_eq!(concept.name.name, "Shape");
+        let methods = concept.methods.iter().map(|m| (m.name.name, m.args.len())).collect::<Vec<_>>();
+        //`self` isn't one of the arguments
+        assert_eq!(methods, [("area", 0), ("scale", 1)]);
+        let AstItem::Class(class) = program.items[1] else {
+            panic!("expected a class");
+        };
+        let concepts = class.concepts.iter().map(|c| c.name.name).collect::<Vec<_>>();
+        assert_eq!(concepts, ["Shape"]);
+        //`where` clauses are the constraints of the generic they name
+        let AstItem::Func(f) = program.items[2] else {
+            panic!("expected a function");
+        };
+        let [AstGenericConstraint::NamedType(shape)] = f.generics[0].constraints else {
+            panic!("expected `impl Shape`");
+        };
+        assert_eq!(shape.name.name, "Shape");
+    }
+
 }
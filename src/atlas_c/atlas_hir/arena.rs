@@ -5,7 +5,7 @@ use std::{
     rc::Rc,
 };
 
-use super::ty::{HirBooleanTy, HirCharTy, HirFloatTy, HirIntegerTy, HirListTy, HirNamedTy, HirStringTy, HirTy, HirTyId, HirUninitializedTy, HirUnitTy, HirUnsignedIntTy};
+use super::ty::{HirBooleanTy, HirCharTy, HirEnumTy, HirFloatTy, HirGenericTy, HirIntegerTy, HirListTy, HirNamedTy, HirStringTy, HirTy, HirTyId, HirUninitializedTy, HirUnionTy, HirUnitTy, HirUnsignedIntTy};
 use bumpalo::Bump;
 use logos::Span;
 
@@ -166,4 +166,30 @@ impl<'arena> TypeArena<'arena> {
             .entry(id)
             .or_insert_with(|| self.allocator.alloc(HirTy::Named(HirNamedTy { name, span })))
     }
+
+    pub fn get_enum_ty(&'arena self, name: &'arena str, span: Span) -> &'arena HirTy<'arena> {
+        let id = HirTyId::compute_enum_ty_id(name);
+        self.intern
+            .borrow_mut()
+            .entry(id)
+            .or_insert_with(|| self.allocator.alloc(HirTy::Enum(HirEnumTy { name, span })))
+    }
+
+    pub fn get_union_ty(&'arena self, name: &'arena str, span: Span, args: Vec<&'arena HirTy<'arena>>) -> &'arena HirTy<'arena> {
+        let ids = args.iter().map(|a| HirTyId::from(*a)).collect::<Vec<_>>();
+        let id = HirTyId::compute_union_ty_id(name, &ids);
+        self.intern
+            .borrow_mut()
+            .entry(id)
+            .or_insert_with(|| self.allocator.alloc(HirTy::Union(HirUnionTy { name, span, args })))
+    }
+
+    pub fn get_generic_ty(&'arena self, name: &'arena str, span: Span, args: Vec<&'arena HirTy<'arena>>) -> &'arena HirTy<'arena> {
+        let ids = args.iter().map(|a| HirTyId::from(*a)).collect::<Vec<_>>();
+        let id = HirTyId::compute_generic_ty_id(name, &ids);
+        self.intern
+            .borrow_mut()
+            .entry(id)
+            .or_insert_with(|| self.allocator.alloc(HirTy::Generic(HirGenericTy { name, span, args })))
+    }
 }
@@ -20,12 +20,142 @@ declare_error_type! {
         AccessingClassFieldOutsideClass(AccessingClassFieldOutsideClassError),
         AccessingPrivateField(AccessingPrivateFieldError),
         NonConstantValue(NonConstantValueError),
+        DuplicateEnumVariant(DuplicateEnumVariantError),
+        NonExhaustiveMatch(NonExhaustiveMatchError),
+        InvalidPattern(InvalidPatternError),
+        TypeArgumentCount(TypeArgumentCountError),
+        CannotInferTypeArgument(CannotInferTypeArgumentError),
+        MissingConceptMethod(MissingConceptMethodError),
+        ConceptMethodMismatch(ConceptMethodMismatchError),
+        UnsatisfiedConstraint(UnsatisfiedConstraintError),
     }
 }
 
 /// Handy type alias for all HIR-related errors.
 pub type HirResult<T> = Result<T, HirError>;
 
+/// Not an error, the name still lowers as written
+#[derive(Error, Diagnostic, Debug, Clone)]
+#[diagnostic(code(sema::naming_convention), severity(Warning), help("try `{suggestion}`"))]
+#[error("{kind} `{name}` isn't in {case}")]
+pub struct NamingConventionWarning {
+    /// e.g. `function` or `class`
+    pub kind: &'static str,
+    pub name: String,
+    /// `snake_case` or `PascalCase`
+    pub case: &'static str,
+    pub suggestion: String,
+    #[label("declared here")]
+    pub span: Span,
+    #[source_code]
+    pub src: String,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[diagnostic(code(sema::duplicate_enum_variant))]
+#[error("`{name}` is declared twice in `{item}`")]
+pub struct DuplicateEnumVariantError {
+    pub name: String,
+    /// The enum or union declaring it
+    pub item: String,
+    #[label("first declared here")]
+    pub first: Span,
+    #[label("declared again here")]
+    pub span: Span,
+    #[source_code]
+    pub src: String,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[diagnostic(code(sema::type_argument_count))]
+#[error("`{name}` expects {expected} type arguments, found {found}")]
+pub struct TypeArgumentCountError {
+    pub name: String,
+    pub expected: usize,
+    pub found: usize,
+    #[label("wrong number of type arguments")]
+    pub span: Span,
+    #[source_code]
+    pub src: String,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[diagnostic(code(sema::cannot_infer_type_argument))]
+#[error("can't infer the type argument `{name}` of `{item}`")]
+pub struct CannotInferTypeArgumentError {
+    pub name: String,
+    /// The generic function or class
+    pub item: String,
+    #[label("`{name}` isn't used by the arguments")]
+    pub span: Span,
+    #[source_code]
+    pub src: String,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[diagnostic(code(sema::missing_concept_method), help("add it to `{class}`"))]
+#[error("`{class}` doesn't implement `{expected}` from `{concept}`")]
+pub struct MissingConceptMethodError {
+    pub class: String,
+    pub concept: String,
+    /// The method as declared by the concept, e.g. `func len(self) -> int64`
+    pub expected: String,
+    #[label("`{concept}` is implemented here")]
+    pub span: Span,
+    #[source_code]
+    pub src: String,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[diagnostic(code(sema::concept_method_mismatch))]
+#[error("`{method}` doesn't match its declaration in `{concept}`")]
+pub struct ConceptMethodMismatchError {
+    /// e.g. `Vec.len`
+    pub method: String,
+    pub concept: String,
+    pub expected: String,
+    #[label("expected `{expected}`")]
+    pub span: Span,
+    #[source_code]
+    pub src: String,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[diagnostic(code(sema::unsatisfied_constraint))]
+#[error("`{ty}` doesn't implement `{concept}`")]
+pub struct UnsatisfiedConstraintError {
+    pub ty: String,
+    pub concept: String,
+    /// The generic function or class requiring it
+    pub item: String,
+    #[label("required by `{item}`")]
+    pub span: Span,
+    #[source_code]
+    pub src: String,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[diagnostic(code(sema::non_exhaustive_match), help("add the missing arms or a `_` arm"))]
+#[error("non-exhaustive match, {missing} not covered")]
+pub struct NonExhaustiveMatchError {
+    pub missing: String,
+    #[label("{missing} not covered")]
+    pub span: Span,
+    #[source_code]
+    pub src: String,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[diagnostic(code(sema::invalid_pattern))]
+#[error("invalid pattern: {reason}")]
+pub struct InvalidPatternError {
+    pub reason: String,
+    #[label("{reason}")]
+    pub span: Span,
+    #[source_code]
+    pub src: String,
+}
+
 #[derive(Error, Diagnostic, Debug)]
 #[diagnostic(code(sema::non_constant_value))]
 #[error("You can't assign a non-constant value to a constant field")]
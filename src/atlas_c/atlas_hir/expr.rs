@@ -1,3 +1,4 @@
+use super::stmt::HirBlock;
 use super::ty::{HirTy, HirUnitTy};
 use logos::Span;
 use serde::Serialize;
@@ -26,6 +27,7 @@ pub enum HirExpr<'hir> {
     Delete(HirDeleteExpr<'hir>),
     FieldAccess(HirFieldAccessExpr<'hir>),
     StaticAccess(HirStaticAccessExpr<'hir>),
+    Match(HirMatchExpr<'hir>),
 }
 
 pub fn is_self_access(field_access_expr: &HirFieldAccessExpr) -> bool {
@@ -56,6 +58,7 @@ impl HirExpr<'_> {
             HirExpr::Delete(expr) => expr.span.clone(),
             HirExpr::FieldAccess(expr) => expr.span.clone(),
             HirExpr::StaticAccess(expr) => expr.span.clone(),
+            HirExpr::Match(expr) => expr.span.clone(),
         }
     }
 }
@@ -84,6 +87,7 @@ impl<'hir> HirExpr<'hir> {
             HirExpr::Delete(_) => &HirTy::Unit(HirUnitTy {}),
             HirExpr::FieldAccess(expr) => expr.ty,
             HirExpr::StaticAccess(expr) => expr.ty,
+            HirExpr::Match(expr) => expr.ty,
         }
     }
 }
@@ -102,6 +106,49 @@ pub struct HirStaticAccessExpr<'hir> {
     pub ty: &'hir HirTy<'hir>,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct HirMatchExpr<'hir> {
+    pub span: Span,
+    pub value: Box<HirExpr<'hir>>,
+    pub arms: Vec<HirMatchArm<'hir>>,
+    /// The type shared by every arm
+    pub ty: &'hir HirTy<'hir>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HirMatchArm<'hir> {
+    pub span: Span,
+    pub patterns: Vec<HirPattern<'hir>>,
+    pub body: HirBlock<'hir>,
+    /// The arm's value, `None` for ``pattern => { ... }`` arms which are `unit`
+    pub value: Option<Box<HirExpr<'hir>>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum HirPattern<'hir> {
+    Wildcard(Span),
+    /// Always one of the literal expressions
+    Literal(HirExpr<'hir>),
+    Variant(HirVariantPattern<'hir>),
+}
+
+impl HirPattern<'_> {
+    pub fn span(&self) -> Span {
+        match self {
+            HirPattern::Wildcard(span) => span.clone(),
+            HirPattern::Literal(expr) => expr.span(),
+            HirPattern::Variant(pattern) => pattern.span.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HirVariantPattern<'hir> {
+    pub span: Span,
+    pub access: HirStaticAccessExpr<'hir>,
+    pub bindings: Option<Vec<HirIdentExpr<'hir>>>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct HirFieldAccessExpr<'hir> {
     pub span: Span,
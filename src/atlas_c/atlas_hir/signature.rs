@@ -8,21 +8,86 @@ use std::collections::BTreeMap;
 
 /// An HirModuleSignature represents the API of a module.
 ///
-/// Currently only functions exist in the language.
+/// Currently only functions, classes, concepts, enums & unions exist in the language.
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct HirModuleSignature<'hir> {
     pub functions: BTreeMap<&'hir str, &'hir HirFunctionSignature<'hir>>,
     pub classes: BTreeMap<&'hir str, &'hir HirClassSignature<'hir>>,
+    pub concepts: BTreeMap<&'hir str, &'hir HirConceptSignature<'hir>>,
+    pub enums: BTreeMap<&'hir str, &'hir HirEnumSignature<'hir>>,
+    pub unions: BTreeMap<&'hir str, &'hir HirUnionSignature<'hir>>,
 }
 
 #[derive(Debug, Clone, Serialize)]
-/// As of now, classes don't inherit from other classes or extend interfaces.
+/// Enum variants don't hold values, they're only their discriminant
+pub struct HirEnumSignature<'hir> {
+    pub span: Span,
+    pub vis: HirVisibility,
+    pub name: &'hir str,
+    pub name_span: Span,
+    /// In declaration order
+    pub variants: Vec<HirEnumVariantSignature<'hir>>,
+}
+
+impl<'hir> HirEnumSignature<'hir> {
+    pub fn variant(&self, name: &str) -> Option<&HirEnumVariantSignature<'hir>> {
+        self.variants.iter().find(|v| v.name == name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HirEnumVariantSignature<'hir> {
+    pub span: Span,
+    pub name: &'hir str,
+    /// The given value, or the previous variant's one + 1 (starting at 0)
+    pub discriminant: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// Variants are tagged by their index in declaration order
+pub struct HirUnionSignature<'hir> {
+    pub span: Span,
+    pub vis: HirVisibility,
+    pub name: &'hir str,
+    pub name_span: Span,
+    /// Type parameters, referred to as named types in the variants' fields
+    pub generics: Vec<&'hir str>,
+    pub variants: Vec<HirUnionVariantSignature<'hir>>,
+}
+
+impl<'hir> HirUnionSignature<'hir> {
+    /// The variant & its tag
+    pub fn variant(&self, name: &str) -> Option<(usize, &HirUnionVariantSignature<'hir>)> {
+        self.variants.iter().enumerate().find(|(_, v)| v.name == name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HirUnionVariantSignature<'hir> {
+    pub span: Span,
+    pub name: &'hir str,
+    pub fields: Vec<HirUnionFieldSignature<'hir>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HirUnionFieldSignature<'hir> {
+    pub span: Span,
+    pub name: &'hir str,
+    pub ty: &'hir HirTy<'hir>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// As of now, classes don't inherit from other classes, they can only implement concepts.
 ///
-/// Generic classes are not supported yet.
+/// A generic class is a template, only its instances (e.g. `Vec<int64>`) are checked & compiled.
 pub struct HirClassSignature<'hir> {
     pub span: Span,
     pub vis: HirVisibility,
     pub name: &'hir str,
+    /// Type parameters, empty for non-generic classes & instances
+    pub generics: Vec<&'hir HirTypeParameterItemSignature<'hir>>,
+    /// `: impl Foo, impl Bar<T>`, checked against the concepts' methods
+    pub concepts: Vec<&'hir HirConceptConstraint<'hir>>,
     pub methods: BTreeMap<&'hir str, &'hir HirClassMethodSignature<'hir>>,
     pub fields: BTreeMap<&'hir str, HirClassFieldSignature<'hir>>,
     /// This is enough to know if the class implement them or not
@@ -32,16 +97,32 @@ pub struct HirClassSignature<'hir> {
     pub destructor: HirClassConstructorSignature<'hir>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, Serialize)]
+/// The methods a class must have to implement the concept, it's never compiled
+pub struct HirConceptSignature<'hir> {
+    pub span: Span,
+    pub vis: HirVisibility,
+    pub name: &'hir str,
+    pub name_span: Span,
+    /// Replaced by the type arguments of `impl Name<A, B>` when checking a class
+    pub generics: Vec<&'hir HirTypeParameterItemSignature<'hir>>,
+    pub methods: BTreeMap<&'hir str, &'hir HirClassMethodSignature<'hir>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// `impl Name<A, B>`, implemented by a class or required from a type argument
+pub struct HirConceptConstraint<'hir> {
+    pub span: Span,
+    pub name: &'hir str,
+    pub args: Vec<&'hir HirTy<'hir>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Default)]
 pub enum HirVisibility {
+    #[default]
     Public,
     Private,
 }
-impl Default for HirVisibility {
-    fn default() -> Self {
-        Self::Public
-    }
-}
 impl From<AstVisibility> for HirVisibility {
     fn from(ast_vis: AstVisibility) -> Self {
         match ast_vis {
@@ -183,6 +264,8 @@ pub struct HirTypeParameterItemSignature<'hir> {
     pub span: Span,
     pub name: &'hir str,
     pub name_span: Span,
+    /// `T: impl Foo` or `where T: impl Foo`, the concepts a type argument must implement
+    pub constraints: Vec<&'hir HirConceptConstraint<'hir>>,
 }
 
 #[derive(Debug, Clone, Serialize)]
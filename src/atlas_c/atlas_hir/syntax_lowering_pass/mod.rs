@@ -1,17 +1,19 @@
 pub mod case;
 
 use heck::{ToPascalCase, ToSnakeCase};
+use logos::Span;
 use miette::{SourceOffset, SourceSpan};
-use std::collections::BTreeMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-use crate::atlas_c::atlas_frontend::parser::ast::{AstClass, AstConstructor, AstDestructor, AstIdentifier, AstMethod, AstMethodModifier, AstNamedType};
+use crate::atlas_c::atlas_frontend::parser::ast::{AstClass, AstConcept, AstConstructor, AstDestructor, AstEnum, AstGeneric, AstGenericConstraint, AstIdentifier, AstMatchArm, AstMethod, AstMethodModifier, AstNamedType, AstPattern, AstVisibility};
 use crate::atlas_c::atlas_frontend::{
     parse,
     parser::{
         arena::AstArena,
         ast::{
             AstBinaryOp, AstBlock, AstExpr, AstFunction, AstImport, AstItem, AstLiteral,
-            AstObjField, AstProgram, AstStatement, AstType, AstUnaryOp,
+            AstObjField, AstProgram, AstStatement, AstType, AstUnaryOp, AstUnion,
         },
     },
 };
@@ -19,14 +21,22 @@ use crate::atlas_c::atlas_frontend::{
 const FILE_ATLAS: &str = include_str!("../../../atlas_lib/std/fs.atlas");
 const IO_ATLAS: &str = include_str!("../../../atlas_lib/std/io.atlas");
 const LIST_ATLAS: &str = include_str!("../../../atlas_lib/std/list.atlas");
+const LISTMAP_ATLAS: &str = include_str!("../../../atlas_lib/std/listmap.atlas");
 const MATH_ATLAS: &str = include_str!("../../../atlas_lib/std/math.atlas");
+const PRELUDE_ATLAS: &str = include_str!("../../../atlas_lib/std/prelude.atlas");
+const PROCESS_ATLAS: &str = include_str!("../../../atlas_lib/std/process.atlas");
 const STRING_ATLAS: &str = include_str!("../../../atlas_lib/std/string.atlas");
+const TEST_ATLAS: &str = include_str!("../../../atlas_lib/std/test.atlas");
+const TIME_ATLAS: &str = include_str!("../../../atlas_lib/std/time.atlas");
+const VEC_ATLAS: &str = include_str!("../../../atlas_lib/std/vec.atlas");
 
-use crate::atlas_c::atlas_hir::error::NonConstantValueError;
-use crate::atlas_c::atlas_hir::expr::{HirCastExpr, HirCharLiteralExpr, HirDeleteExpr, HirFieldAccessExpr, HirIndexingExpr, HirListLiteralExpr, HirNewArrayExpr, HirNewObjExpr, HirSelfLiteral, HirStaticAccessExpr, HirStringLiteralExpr, HirUnitLiteralExpr};
+use crate::atlas_c::atlas_hir::error::{DuplicateEnumVariantError, NamingConventionWarning, NonConstantValueError, TypeArgumentCountError};
+use crate::atlas_vm::native::NativeModule;
+use crate::atlas_c::atlas_hir::expr::{HirCastExpr, HirCharLiteralExpr, HirDeleteExpr, HirFieldAccessExpr, HirIndexingExpr, HirListLiteralExpr, HirMatchArm, HirMatchExpr, HirNewArrayExpr, HirNewObjExpr, HirPattern, HirSelfLiteral, HirStaticAccessExpr, HirStringLiteralExpr, HirUnitLiteralExpr, HirVariantPattern};
 use crate::atlas_c::atlas_hir::item::{HirClass, HirClassConstructor, HirClassMethod};
-use crate::atlas_c::atlas_hir::signature::{ConstantValue, HirClassConstSignature, HirClassConstructorSignature, HirClassFieldSignature, HirClassMethodModifier, HirClassMethodSignature, HirClassSignature};
+use crate::atlas_c::atlas_hir::signature::{ConstantValue, HirClassConstSignature, HirConceptConstraint, HirConceptSignature, HirClassConstructorSignature, HirClassFieldSignature, HirClassMethodModifier, HirClassMethodSignature, HirClassSignature, HirEnumSignature, HirEnumVariantSignature, HirUnionFieldSignature, HirUnionSignature, HirUnionVariantSignature};
 use crate::atlas_c::atlas_hir::syntax_lowering_pass::case::Case;
+use crate::atlas_c::atlas_hir::type_check_pass::TypeChecker;
 use crate::atlas_c::atlas_hir::{
     arena::HirArena,
     error::{HirError, HirResult, UnsupportedExpr, UnsupportedStatement},
@@ -48,6 +58,16 @@ pub struct AstSyntaxLoweringPass<'ast, 'hir> {
     ast_arena: &'ast AstArena<'ast>,
     //source code
     src: String,
+    /// `extern` declarations of the native modules registered by the host, by module name
+    host_modules: HashMap<String, String>,
+    /// Enums declared in the module or imported so far, so their names lower to an enum type
+    enums: RefCell<HashSet<&'hir str>>,
+    /// Unions declared, imported or from the prelude, with their number of type parameters
+    unions: RefCell<HashMap<&'hir str, usize>>,
+    /// Whether `Option` & `Result` are brought in, false when lowering the prelude itself
+    prelude: bool,
+    /// Names not following the naming conventions, they don't stop the lowering
+    warnings: RefCell<Vec<NamingConventionWarning>>,
 }
 
 impl<'ast, 'hir> AstSyntaxLoweringPass<'ast, 'hir> {
@@ -62,18 +82,72 @@ impl<'ast, 'hir> AstSyntaxLoweringPass<'ast, 'hir> {
             ast,
             ast_arena,
             src,
+            host_modules: HashMap::new(),
+            enums: RefCell::new(HashSet::new()),
+            unions: RefCell::new(HashMap::new()),
+            prelude: true,
+            warnings: RefCell::new(Vec::new()),
         }
     }
+    /// Let the program import `modules` next to the standard libraries
+    pub fn with_natives(mut self, modules: &[NativeModule]) -> Self {
+        self.host_modules = modules
+            .iter()
+            .map(|module| (module.name().to_string(), module.declarations()))
+            .collect();
+        self
+    }
+    /// Warnings about the module lowered so far, the modules it imports aren't checked
+    pub fn take_warnings(&self) -> Vec<NamingConventionWarning> {
+        self.warnings.take()
+    }
+    /// Functions & variables are in snake_case
+    fn check_snake_case(&self, kind: &'static str, name: &str, span: &Span) {
+        if !name.is_snake_case() {
+            self.warn(kind, name, "snake_case", name.to_snake_case(), span);
+        }
+    }
+    /// Types & concepts are in PascalCase
+    fn check_pascal_case(&self, kind: &'static str, name: &str, span: &Span) {
+        if !name.is_pascal_case() {
+            self.warn(kind, name, "PascalCase", name.to_pascal_case(), span);
+        }
+    }
+    fn warn(&self, kind: &'static str, name: &str, case: &'static str, suggestion: String, span: &Span) {
+        self.warnings.borrow_mut().push(NamingConventionWarning {
+            kind,
+            name: name.to_string(),
+            case,
+            suggestion,
+            span: SourceSpan::new(SourceOffset::from(span.start), span.end - span.start),
+            src: self.src.clone(),
+        });
+    }
 }
 
 impl<'ast, 'hir> AstSyntaxLoweringPass<'ast, 'hir>
 where
     'ast: 'hir,
 {
-    pub fn lower(&self) -> HirResult<HirModule> {
+    pub fn lower(&self) -> HirResult<HirModule<'hir>> {
         let mut module_body = HirModuleBody::default();
         let mut module_signature = HirModuleSignature::default();
 
+        if self.prelude {
+            self.visit_prelude(&mut module_signature)?;
+        }
+        //Enums & unions can be used before being declared
+        for item in self.ast.items {
+            match item {
+                AstItem::Enum(e) => {
+                    self.enums.borrow_mut().insert(self.arena.names().get(e.name.name));
+                }
+                AstItem::Union(u) => {
+                    self.unions.borrow_mut().insert(self.arena.names().get(u.name.name), u.generics.len());
+                }
+                _ => {}
+            }
+        }
         let mut items = Vec::new();
         for item in self.ast.items {
             items.push(self.visit_item(&mut module_body, &mut module_signature, item)?);
@@ -93,10 +167,7 @@ where
             AstItem::Func(f) => {
                 let fun = self.visit_func(f)?;
                 let name = self.arena.names().get(f.name.name);
-                if !name.is_snake_case() {
-                    eprintln!("Warning: {} is not snake case", name);
-                    eprintln!("Try using snake_case for function names e.g. {}", name.to_snake_case());
-                }
+                self.check_snake_case("function", name, &f.name.span);
                 module_signature.functions.insert(name, fun.signature);
                 module_body.functions.insert(name, fun);
             }
@@ -108,27 +179,57 @@ where
             }
             AstItem::Import(i) => {
                 let hir = self.visit_import(i)?;
-                let allocated_hir: &'hir HirModule<'hir> = self.arena.intern(hir);
-                for (name, signature) in allocated_hir.signature.functions.iter() {
+                for (name, signature) in hir.signature.functions.iter() {
                     module_signature.functions.insert(name, *signature);
                 }
-                allocated_hir.body.imports.iter().for_each(|i| {
+                for (name, signature) in hir.signature.enums.iter() {
+                    self.enums.borrow_mut().insert(name);
+                    module_signature.enums.insert(name, *signature);
+                }
+                for (name, signature) in hir.signature.unions.iter() {
+                    self.unions.borrow_mut().insert(name, signature.generics.len());
+                    module_signature.unions.insert(name, *signature);
+                }
+                for (name, signature) in hir.signature.concepts.iter() {
+                    module_signature.concepts.insert(name, *signature);
+                }
+                //Generic functions are instantiated in the module importing them
+                for (name, function) in hir.body.functions {
+                    if function.signature.generics.is_some() {
+                        module_body.functions.insert(name, function);
+                    }
+                }
+                //Classes are compiled with the module importing them
+                for (name, class) in hir.body.classes {
+                    module_signature.classes.insert(name, class.signature);
+                    module_body.classes.insert(name, class);
+                }
+                hir.body.imports.iter().for_each(|i| {
                     module_body.imports.push(i);
                 });
             }
+            AstItem::Enum(e) => {
+                let signature = self.visit_enum(e)?;
+                module_signature.enums.insert(signature.name, signature);
+            }
+            AstItem::Union(u) => {
+                let signature = self.visit_union(u)?;
+                module_signature.unions.insert(signature.name, signature);
+            }
+            AstItem::Concept(c) => {
+                let signature = self.visit_concept(c)?;
+                module_signature.concepts.insert(signature.name, signature);
+            }
             AstItem::ExternFunction(e) => {
                 let name = self.arena.names().get(e.name.name);
-                if !name.is_snake_case() {
-                    eprintln!("Warning: {} is not snake case", name);
-                    eprintln!("Try using snake_case for function names e.g. {}", name.to_snake_case());
-                }
+                self.check_snake_case("function", name, &e.name.span);
                 let ty = self.visit_ty(e.ret)?;
 
                 let mut params: Vec<&HirFunctionParameterSignature<'hir>> = Vec::new();
                 let mut type_params: Vec<&HirTypeParameterItemSignature<'_>> = Vec::new();
 
-                let generics = if e.generics.is_some() {
-                    Some(e.generics.unwrap().iter().map(|g| self.visit_generic(g)).collect::<HirResult<Vec<_>>>()?)
+                let generics = if let Some(generics) = e.generics {
+                    Some(generics.iter().map(|g| self.visit_generic(g)).collect::<HirResult<Vec<_>>>()?)
                 } else {
                     None
                 };
@@ -149,6 +250,7 @@ where
                         span: arg_name.span.clone(),
                         name: hir_arg_name,
                         name_span: arg_name.span.clone(),
+                        constraints: Vec::new(),
                     }));
                 }
                 let hir = self.arena.intern(HirFunctionSignature {
@@ -168,6 +270,111 @@ where
         Ok(())
     }
 
+    fn visit_enum(&self, node: &'ast AstEnum<'ast>) -> HirResult<&'hir HirEnumSignature<'hir>> {
+        let name = self.arena.names().get(node.name.name);
+        self.check_pascal_case("enum", name, &node.name.span);
+        let mut variants: Vec<HirEnumVariantSignature<'hir>> = Vec::new();
+        let mut next = 0;
+        for variant in node.variants.iter() {
+            let variant_name = self.arena.names().get(variant.name.name);
+            if let Some(first) = variants.iter().find(|v| v.name == variant_name) {
+                return Err(HirError::DuplicateEnumVariant(DuplicateEnumVariantError {
+                    name: variant_name.to_string(),
+                    item: name.to_string(),
+                    first: SourceSpan::new(SourceOffset::from(first.span.start), first.span.end - first.span.start),
+                    span: SourceSpan::new(
+                        SourceOffset::from(variant.span.start),
+                        variant.span.end - variant.span.start,
+                    ),
+                    src: self.src.clone(),
+                }));
+            }
+            let discriminant = variant.val.map_or(next, i64::from);
+            next = discriminant + 1;
+            variants.push(HirEnumVariantSignature {
+                span: variant.span.clone(),
+                name: variant_name,
+                discriminant,
+            });
+        }
+        Ok(self.arena.intern(HirEnumSignature {
+            span: node.span.clone(),
+            vis: node.vis.into(),
+            name,
+            name_span: node.name.span.clone(),
+            variants,
+        }))
+    }
+
+    /// `Option` & `Result`, available in every module without an import
+    fn visit_prelude(&self, module_signature: &mut HirModuleSignature<'hir>) -> HirResult<()> {
+        let ast: AstProgram<'ast> = parse(
+            "atlas_stdlib/prelude.atlas",
+            self.ast_arena,
+            PRELUDE_ATLAS.to_string(),
+        )
+            .unwrap();
+        let allocated_ast = self.ast_arena.alloc(ast);
+        let mut pass = AstSyntaxLoweringPass::<'ast, 'hir>::new(
+            self.arena,
+            allocated_ast,
+            self.ast_arena,
+            PRELUDE_ATLAS.to_string(),
+        );
+        pass.prelude = false;
+        let prelude = pass.lower()?;
+        for (name, signature) in prelude.signature.unions.iter() {
+            self.unions.borrow_mut().insert(name, signature.generics.len());
+            module_signature.unions.insert(name, *signature);
+        }
+        Ok(())
+    }
+
+    fn visit_union(&self, node: &'ast AstUnion<'ast>) -> HirResult<&'hir HirUnionSignature<'hir>> {
+        let name = self.arena.names().get(node.name.name);
+        self.check_pascal_case("union", name, &node.name.span);
+        let mut variants: Vec<HirUnionVariantSignature<'hir>> = Vec::new();
+        for variant in node.variants.iter() {
+            let variant_name = self.arena.names().get(variant.name.name);
+            if let Some(first) = variants.iter().find(|v| v.name == variant_name) {
+                return Err(HirError::DuplicateEnumVariant(DuplicateEnumVariantError {
+                    name: variant_name.to_string(),
+                    item: name.to_string(),
+                    first: SourceSpan::new(SourceOffset::from(first.span.start), first.span.end - first.span.start),
+                    span: SourceSpan::new(
+                        SourceOffset::from(variant.span.start),
+                        variant.span.end - variant.span.start,
+                    ),
+                    src: self.src.clone(),
+                }));
+            }
+            let fields = variant
+                .fields
+                .iter()
+                .map(|f| {
+                    Ok(HirUnionFieldSignature {
+                        span: f.span.clone(),
+                        name: self.arena.names().get(f.name.name),
+                        ty: self.visit_ty(f.ty)?,
+                    })
+                })
+                .collect::<HirResult<Vec<_>>>()?;
+            variants.push(HirUnionVariantSignature {
+                span: variant.span.clone(),
+                name: variant_name,
+                fields,
+            });
+        }
+        Ok(self.arena.intern(HirUnionSignature {
+            span: node.span.clone(),
+            vis: node.vis.into(),
+            name,
+            name_span: node.name.span.clone(),
+            generics: node.generics.iter().map(|g| self.arena.names().get(g.name.name)).collect(),
+            variants,
+        }))
+    }
+
     //todo: Add constraints to generics
     fn visit_generic(&self, generics: &'ast AstNamedType) -> HirResult<&'hir HirTypeParameterItemSignature<'hir>> {
         let name = self.arena.names().get(generics.name.name);
@@ -175,16 +382,60 @@ where
             span: generics.span.clone(),
             name,
             name_span: generics.name.span.clone(),
+            constraints: Vec::new(),
         });
         Ok(hir)
     }
 
-    fn visit_class(&self, node: &'ast AstClass<'ast>) -> HirResult<HirClass<'hir>> {
+    /// Type parameter of a class, a function or a concept, e.g. the `T` of `class Vec<T>`
+    fn visit_type_parameter(&self, generic: &'ast AstGeneric<'ast>) -> HirResult<&'hir HirTypeParameterItemSignature<'hir>> {
+        let mut constraints = Vec::new();
+        for constraint in generic.constraints.iter() {
+            match constraint {
+                AstGenericConstraint::NamedType(concept) => constraints.push(self.visit_concept_constraint(concept)?),
+                //todo: Check operator constraints (i.e. `T: operator::(+)`)
+                AstGenericConstraint::Operator(_) => {}
+            }
+        }
+        Ok(self.arena.intern(HirTypeParameterItemSignature {
+            span: generic.span.clone(),
+            name: self.arena.names().get(generic.name.name),
+            name_span: generic.name.span.clone(),
+            constraints,
+        }))
+    }
+
+    /// `impl Name<A, B>`
+    fn visit_concept_constraint(&self, node: &'ast AstNamedType<'ast>) -> HirResult<&'hir HirConceptConstraint<'hir>> {
+        let args = node.args.iter().map(|a| self.visit_ty(a)).collect::<HirResult<Vec<_>>>()?;
+        Ok(self.arena.intern(HirConceptConstraint {
+            span: node.span.clone(),
+            name: self.arena.names().get(node.name.name),
+            args,
+        }))
+    }
+
+    fn visit_concept(&self, node: &'ast AstConcept<'ast>) -> HirResult<&'hir HirConceptSignature<'hir>> {
         let name = self.arena.names().get(node.name.name);
-        if !name.is_pascal_case() {
-            eprintln!("Warning: {} is not pascal case", name);
-            eprintln!("Try using PascalCase for class names e.g. {}", name.to_pascal_case());
+        self.check_pascal_case("concept", name, &node.name.span);
+        let mut methods = BTreeMap::new();
+        for method in node.methods.iter() {
+            let signature = self.visit_method_signature(method.modifier, node.vis, &method.span, method.args, method.ret)?;
+            methods.insert(self.arena.names().get(method.name.name), signature);
         }
+        Ok(self.arena.intern(HirConceptSignature {
+            span: node.span.clone(),
+            vis: node.vis.into(),
+            name,
+            name_span: node.name.span.clone(),
+            generics: node.generics.iter().map(|g| self.visit_type_parameter(g)).collect::<HirResult<_>>()?,
+            methods,
+        }))
+    }
+
+    fn visit_class(&self, node: &'ast AstClass<'ast>) -> HirResult<HirClass<'hir>> {
+        let name = self.arena.names().get(node.name.name);
+        self.check_pascal_case("class", name, &node.name.span);
 
         let mut methods = Vec::new();
         for method in node.methods.iter() {
@@ -256,6 +507,8 @@ where
             span: node.span.clone(),
             vis: node.vis.into(),
             name,
+            generics: node.generics.iter().map(|g| self.visit_type_parameter(g)).collect::<HirResult<_>>()?,
+            concepts: node.concepts.iter().map(|c| self.visit_concept_constraint(c)).collect::<HirResult<_>>()?,
             methods: {
                 let mut map = BTreeMap::new();
                 for method in methods.iter() {
@@ -289,43 +542,53 @@ where
     }
 
     fn visit_method(&self, node: &'ast AstMethod<'ast>) -> HirResult<HirClassMethod<'hir>> {
-        let type_parameters = node
-            .args
+        let signature = self.visit_method_signature(node.modifier, node.vis, &node.span, node.args, node.ret)?;
+        let body = self.visit_block(node.body)?;
+        let method = HirClassMethod {
+            span: node.span.clone(),
+            name: self.arena.names().get(node.name.name),
+            name_span: node.name.span.clone(),
+            signature,
+            body,
+        };
+        Ok(method)
+    }
+
+    /// Shared by class methods & the methods declared by concepts
+    fn visit_method_signature(
+        &self,
+        modifier: AstMethodModifier,
+        vis: AstVisibility,
+        span: &logos::Span,
+        args: &'ast [&'ast AstObjField<'ast>],
+        ret: &'ast AstType<'ast>,
+    ) -> HirResult<&'hir HirClassMethodSignature<'hir>> {
+        let type_parameters = args
             .iter()
             .map(|arg| self.visit_type_param_item(arg))
             .collect::<HirResult<Vec<_>>>();
-        let ret_type_span = node.ret.span();
-        let ret_type = self.visit_ty(node.ret)?;
-        let parameters = node
-            .args
+        let ret_type_span = ret.span();
+        let ret_type = self.visit_ty(ret)?;
+        let parameters = args
             .iter()
             .map(|arg| self.visit_func_param(arg))
             .collect::<HirResult<Vec<_>>>();
 
-        let body = self.visit_block(node.body)?;
-        let signature = self.arena.intern(HirClassMethodSignature {
-            modifier: match node.modifier {
+        Ok(self.arena.intern(HirClassMethodSignature {
+            modifier: match modifier {
                 AstMethodModifier::Const => HirClassMethodModifier::Const,
                 AstMethodModifier::Static => HirClassMethodModifier::Static,
                 AstMethodModifier::None => HirClassMethodModifier::None,
             },
-            span: node.span.clone(),
-            vis: node.vis.into(),
+            span: span.clone(),
+            vis: vis.into(),
             params: parameters?,
-            //Generics aren't supported yet for normal functions
+            //Generics aren't supported yet for methods
             generics: None,
             type_params: type_parameters?,
             return_ty: ret_type,
             return_ty_span: Some(ret_type_span),
-        });
-        let method = HirClassMethod {
-            span: node.span.clone(),
-            name: self.arena.names().get(node.name.name),
-            name_span: node.name.span.clone(),
-            signature,
-            body,
-        };
-        Ok(method)
+        }))
     }
 
     fn visit_constructor(&self, constructor: Option<&'ast AstConstructor<'ast>>, fields: &[HirClassFieldSignature<'hir>]) -> HirResult<HirClassConstructor<'hir>> {
@@ -348,15 +611,44 @@ where
                     span: type_param.span.clone(),
                     name: type_param.name,
                     name_span: type_param.name_span.clone(),
+                    constraints: Vec::new(),
                 }));
             }
+            //`self.field = field;` for each field, generated code has no span
+            let statements = fields
+                .iter()
+                .map(|field| {
+                    let ident = || HirIdentExpr {
+                        name: field.name,
+                        span: logos::Span::default(),
+                        ty: self.arena.types().get_uninitialized_ty(),
+                    };
+                    HirStatement::Expr(HirExprStmt {
+                        span: logos::Span::default(),
+                        expr: HirExpr::Assign(HirAssignExpr {
+                            span: logos::Span::default(),
+                            lhs: Box::new(HirExpr::FieldAccess(HirFieldAccessExpr {
+                                span: logos::Span::default(),
+                                target: Box::new(HirExpr::SelfLiteral(HirSelfLiteral {
+                                    span: logos::Span::default(),
+                                    ty: self.arena.types().get_uninitialized_ty(),
+                                })),
+                                field: Box::new(ident()),
+                                ty: self.arena.types().get_uninitialized_ty(),
+                            })),
+                            rhs: Box::new(HirExpr::Ident(ident())),
+                            ty: self.arena.types().get_uninitialized_ty(),
+                        }),
+                    })
+                })
+                .collect();
             let hir = HirClassConstructor {
                 span: logos::Span::default(),
                 params,
                 type_params,
                 body: HirBlock {
                     span: logos::Span::default(),
-                    statements: Vec::new(),
+                    statements,
                 },
             };
             return Ok(hir);
@@ -381,6 +673,7 @@ where
                 span: type_param.span.clone(),
                 name: type_param.name,
                 name_span: type_param.name_span.clone(),
+                constraints: Vec::new(),
             }));
         }
         let hir = HirClassConstructor {
@@ -425,6 +718,7 @@ where
                 span: type_param.span.clone(),
                 name: type_param.name,
                 name_span: type_param.name_span.clone(),
+                constraints: Vec::new(),
             }));
         }
         let hir = HirClassConstructor {
@@ -438,7 +732,7 @@ where
 
     //This needs to be generalized
     fn visit_import(&self, node: &'ast AstImport<'ast>) -> HirResult<HirModule<'hir>> {
-        match node.path.split("/").last().unwrap() {
+        match node.path.split('/').next_back().unwrap() {
             "io" => {
                 let ast: AstProgram<'ast> = parse(
                     "atlas_stdlib/io.atlas",
@@ -454,6 +748,7 @@ where
                     IO_ATLAS.to_string(),
                 ));
                 let mut lower = hir.lower()?;
+                self.check_import(&lower, IO_ATLAS)?;
 
                 let hir_import: &'hir HirImport<'_> = self.arena.intern(HirImport {
                     span: node.span.clone(),
@@ -482,6 +777,61 @@ where
                     MATH_ATLAS.to_string(),
                 ));
                 let mut lower = hir.lower()?;
+                self.check_import(&lower, MATH_ATLAS)?;
+                let hir_import: &'hir HirImport<'_> = self.arena.intern(HirImport {
+                    span: node.span.clone(),
+                    path: node.path,
+                    path_span: node.span.clone(),
+                    alias: None,
+                    alias_span: None,
+                });
+
+                lower.body.imports.push(hir_import);
+                Ok(lower)
+            }
+            "process" => {
+                let ast: AstProgram<'ast> = parse(
+                    "atlas_stdlib/process.atlas",
+                    self.ast_arena,
+                    PROCESS_ATLAS.to_string(),
+                )
+                    .unwrap();
+                let allocated_ast = self.ast_arena.alloc(ast);
+                let hir = self.arena.intern(AstSyntaxLoweringPass::<'ast, 'hir>::new(
+                    self.arena,
+                    allocated_ast,
+                    self.ast_arena,
+                    PROCESS_ATLAS.to_string(),
+                ));
+                let mut lower = hir.lower()?;
+                self.check_import(&lower, PROCESS_ATLAS)?;
+                let hir_import: &'hir HirImport<'_> = self.arena.intern(HirImport {
+                    span: node.span.clone(),
+                    path: node.path,
+                    path_span: node.span.clone(),
+                    alias: None,
+                    alias_span: None,
+                });
+
+                lower.body.imports.push(hir_import);
+                Ok(lower)
+            }
+            "test" => {
+                let ast: AstProgram<'ast> = parse(
+                    "atlas_stdlib/test.atlas",
+                    self.ast_arena,
+                    TEST_ATLAS.to_string(),
+                )
+                    .unwrap();
+                let allocated_ast = self.ast_arena.alloc(ast);
+                let hir = self.arena.intern(AstSyntaxLoweringPass::<'ast, 'hir>::new(
+                    self.arena,
+                    allocated_ast,
+                    self.ast_arena,
+                    TEST_ATLAS.to_string(),
+                ));
+                let mut lower = hir.lower()?;
+                self.check_import(&lower, TEST_ATLAS)?;
                 let hir_import: &'hir HirImport<'_> = self.arena.intern(HirImport {
                     span: node.span.clone(),
                     path: node.path,
@@ -507,7 +857,18 @@ where
                     self.ast_arena,
                     FILE_ATLAS.to_string(),
                 ));
-                hir.lower()
+                let mut lower = hir.lower()?;
+                self.check_import(&lower, FILE_ATLAS)?;
+                let hir_import: &'hir HirImport<'_> = self.arena.intern(HirImport {
+                    span: node.span.clone(),
+                    path: node.path,
+                    path_span: node.span.clone(),
+                    alias: None,
+                    alias_span: None,
+                });
+
+                lower.body.imports.push(hir_import);
+                Ok(lower)
             }
             "list" => {
                 let ast: AstProgram<'ast> = parse(
@@ -524,6 +885,7 @@ where
                     LIST_ATLAS.to_string(),
                 ));
                 let mut lower = hir.lower()?;
+                self.check_import(&lower, LIST_ATLAS)?;
 
                 let hir_import: &'hir HirImport<'_> = self.arena.intern(HirImport {
                     span: node.span.clone(),
@@ -552,6 +914,7 @@ where
                 ));
 
                 let mut lower = hir.lower()?;
+                self.check_import(&lower, STRING_ATLAS)?;
 
                 let hir_import: &'hir HirImport<'_> = self.arena.intern(HirImport {
                     span: node.span.clone(),
@@ -565,11 +928,49 @@ where
 
                 Ok(lower)
             }
+            "vec" => {
+                let ast: AstProgram<'ast> = parse(
+                    "atlas_stdlib/vec.atlas",
+                    self.ast_arena,
+                    VEC_ATLAS.to_string(),
+                )
+                    .unwrap();
+                let allocated_ast = self.ast_arena.alloc(ast);
+                let hir = self.arena.intern(AstSyntaxLoweringPass::<'ast, 'hir>::new(
+                    self.arena,
+                    allocated_ast,
+                    self.ast_arena,
+                    VEC_ATLAS.to_string(),
+                ));
+                //Written in Atlas only, there's no native library to link
+                let lower = hir.lower()?;
+                self.check_import(&lower, VEC_ATLAS)?;
+                Ok(lower)
+            }
+            "listmap" => {
+                let ast: AstProgram<'ast> = parse(
+                    "atlas_stdlib/listmap.atlas",
+                    self.ast_arena,
+                    LISTMAP_ATLAS.to_string(),
+                )
+                    .unwrap();
+                let allocated_ast = self.ast_arena.alloc(ast);
+                let hir = self.arena.intern(AstSyntaxLoweringPass::<'ast, 'hir>::new(
+                    self.arena,
+                    allocated_ast,
+                    self.ast_arena,
+                    LISTMAP_ATLAS.to_string(),
+                ));
+                //Written in Atlas only, there's no native library to link
+                let lower = hir.lower()?;
+                self.check_import(&lower, LISTMAP_ATLAS)?;
+                Ok(lower)
+            }
             "time" => {
                 let ast: AstProgram<'ast> = parse(
                     "atlas_stdlib/time.atlas",
                     self.ast_arena,
-                    STRING_ATLAS.to_string(),
+                    TIME_ATLAS.to_string(),
                 )
                     .unwrap();
                 let allocated_ast = self.ast_arena.alloc(ast);
@@ -577,21 +978,73 @@ where
                     self.arena,
                     allocated_ast,
                     self.ast_arena,
-                    IO_ATLAS.to_string(),
+                    TIME_ATLAS.to_string(),
                 ));
-                hir.lower()
+                let mut lower = hir.lower()?;
+                self.check_import(&lower, TIME_ATLAS)?;
+                let hir_import: &'hir HirImport<'_> = self.arena.intern(HirImport {
+                    span: node.span.clone(),
+                    path: node.path,
+                    path_span: node.span.clone(),
+                    alias: None,
+                    alias_span: None,
+                });
+
+                lower.body.imports.push(hir_import);
+                Ok(lower)
+            }
+            _ => {
+                let unsupported = |stmt: String| {
+                    HirError::UnsupportedStatement(UnsupportedStatement {
+                        span: SourceSpan::new(
+                            SourceOffset::from(node.span.start),
+                            node.span.end - node.span.start,
+                        ),
+                        stmt,
+                        src: self.src.clone(),
+                    })
+                };
+                let source = self
+                    .host_modules
+                    .get(node.path)
+                    .ok_or_else(|| unsupported(format!("{:?}", node)))?;
+                let ast: AstProgram<'ast> = parse(node.path, self.ast_arena, source.clone())
+                    .map_err(|e| unsupported(format!("Native module `{}` ({})", node.path, e)))?;
+                let allocated_ast = self.ast_arena.alloc(ast);
+                let pass = AstSyntaxLoweringPass::<'ast, 'hir>::new(
+                    self.arena,
+                    allocated_ast,
+                    self.ast_arena,
+                    source.clone(),
+                );
+                //The host may declare natives taking or returning the enums & unions of the importer
+                pass.enums.borrow_mut().extend(self.enums.borrow().iter());
+                pass.unions.borrow_mut().extend(self.unions.borrow().iter());
+                let hir = self.arena.intern(pass);
+                let mut lower = hir.lower()?;
+                self.check_import(&lower, source)?;
+
+                let hir_import: &'hir HirImport<'_> = self.arena.intern(HirImport {
+                    span: node.span.clone(),
+                    path: node.path,
+                    path_span: node.span.clone(),
+                    alias: None,
+                    alias_span: None,
+                });
+
+                lower.body.imports.push(hir_import);
+                Ok(lower)
             }
-            _ => Err(HirError::UnsupportedStatement(UnsupportedStatement {
-                span: SourceSpan::new(
-                    SourceOffset::from(node.span.start),
-                    node.span.end - node.span.start,
-                ),
-                stmt: format!("{:?}", node),
-                src: self.src.clone(),
-            })),
         }
     }
 
+    /// Imported modules are type-checked against their own source, so the diagnostics point into it.
+    /// A copy is checked, the importer checks the classes again & instantiates the generic items it uses
+    fn check_import(&self, module: &HirModule<'hir>, src: &str) -> HirResult<()> {
+        let mut module = module.clone();
+        TypeChecker::new(self.arena, src.to_string()).check(&mut module)
+    }
+
     fn visit_block(&self, node: &'ast AstBlock<'ast>) -> HirResult<HirBlock<'hir>> {
         let statements = node
             .stmts
@@ -618,10 +1071,7 @@ where
             }
             AstStatement::Const(c) => {
                 let name = self.arena.names().get(c.name.name);
-                if !name.is_snake_case() {
-                    eprintln!("Warning: {} is not snake case", name);
-                    eprintln!("Try using snake_case for variable names e.g. {}", name.to_snake_case());
-                }
+                self.check_snake_case("constant", name, &c.name.span);
                 let ty = self.visit_ty(c.ty)?;
 
                 let value = self.visit_expr(c.value)?;
@@ -637,10 +1087,7 @@ where
             }
             AstStatement::Let(l) => {
                 let name = self.arena.names().get(l.name.name);
-                if !name.is_snake_case() {
-                    eprintln!("Warning: {} is not snake case", name);
-                    eprintln!("Try using snake_case for variable names e.g. {}", name.to_snake_case());
-                }
+                self.check_snake_case("variable", name, &l.name.span);
                 let ty = l.ty.map(|ty| self.visit_ty(ty)).transpose()?;
 
                 let value = self.visit_expr(l.value)?;
@@ -813,9 +1260,17 @@ where
                 Ok(hir)
             }
             AstExpr::NewObj(obj) => {
+                let name = self.arena.names().get(obj.ty.name);
+                //Without type arguments, they're inferred from the constructor's arguments if the class is generic
+                let ty = if obj.type_args.is_empty() {
+                    self.arena.types().get_named_ty(name, obj.ty.span.clone())
+                } else {
+                    let args = obj.type_args.iter().map(|a| self.visit_ty(a)).collect::<HirResult<Vec<_>>>()?;
+                    self.arena.types().get_generic_ty(name, obj.ty.span.clone(), args)
+                };
                 let hir = HirExpr::NewObj(HirNewObjExpr {
                     span: node.span(),
-                    ty: self.arena.types().get_named_ty(obj.ty.name, obj.ty.span.clone()),
+                    ty,
                     args: obj
                         .args
                         .iter()
@@ -825,71 +1280,7 @@ where
                 });
                 Ok(hir)
             }
-            AstExpr::Literal(l) => {
-                let hir = match l {
-                    AstLiteral::Integer(i) => HirExpr::IntegerLiteral(HirIntegerLiteralExpr {
-                        span: l.span(),
-                        value: i.value,
-                        ty: self.arena.types().get_integer64_ty(),
-                    }),
-                    AstLiteral::Boolean(b) => HirExpr::BooleanLiteral(HirBooleanLiteralExpr {
-                        span: l.span(),
-                        value: b.value,
-                        ty: self.arena.types().get_boolean_ty(),
-                    }),
-                    AstLiteral::Float(f) => HirExpr::FloatLiteral(HirFloatLiteralExpr {
-                        span: l.span(),
-                        value: f.value,
-                        ty: self.arena.types().get_float64_ty(),
-                    }),
-                    AstLiteral::UnsignedInteger(u) => {
-                        HirExpr::UnsignedIntegerLiteral(HirUnsignedIntegerLiteralExpr {
-                            span: l.span(),
-                            value: u.value,
-                            ty: self.arena.types().get_uint64_ty(),
-                        })
-                    }
-                    AstLiteral::SelfLiteral(_) => {
-                        HirExpr::SelfLiteral(HirSelfLiteral {
-                            span: l.span(),
-                            ty: self.arena.types().get_uninitialized_ty(),
-                        })
-                    }
-                    AstLiteral::Char(c) => {
-                        HirExpr::CharLiteral(HirCharLiteralExpr {
-                            span: l.span(),
-                            value: c.value,
-                            ty: self.arena.types().get_char_ty(),
-                        })
-                    }
-                    AstLiteral::Unit(_) => {
-                        HirExpr::UnitLiteral(HirUnitLiteralExpr {
-                            span: l.span(),
-                            ty: self.arena.types().get_unit_ty(),
-                        })
-                    }
-                    AstLiteral::String(s) => {
-                        HirExpr::StringLiteral(HirStringLiteralExpr {
-                            span: l.span(),
-                            value: s.value,
-                            ty: self.arena.types().get_str_ty(),
-                        })
-                    }
-                    AstLiteral::List(l) => {
-                        let elements = l
-                            .items
-                            .iter()
-                            .map(|e| self.visit_expr(e))
-                            .collect::<HirResult<Vec<_>>>()?;
-                        HirExpr::ListLiteral(HirListLiteralExpr {
-                            span: l.span.clone(),
-                            items: elements,
-                            ty: self.arena.types().get_uninitialized_ty(),
-                        })
-                    }
-                };
-                Ok(hir)
-            }
+            AstExpr::Literal(l) => self.visit_literal(l),
             AstExpr::StaticAccess(s) => {
                 let hir = HirExpr::StaticAccess(HirStaticAccessExpr {
                     span: node.span(),
@@ -916,7 +1307,20 @@ where
                 });
                 Ok(hir)
             }
-
+            AstExpr::Match(m) => {
+                let arms = m
+                    .arms
+                    .iter()
+                    .map(|arm| self.visit_match_arm(arm))
+                    .collect::<HirResult<Vec<_>>>()?;
+                let hir = HirExpr::Match(HirMatchExpr {
+                    span: node.span(),
+                    value: Box::new(self.visit_expr(m.value)?),
+                    arms,
+                    ty: self.arena.types().get_uninitialized_ty(),
+                });
+                Ok(hir)
+            }
             _ => {
                 //todo: if/else as an expression
                 Err(HirError::UnsupportedExpr(UnsupportedExpr {
@@ -931,6 +1335,117 @@ where
         }
     }
 
+    fn visit_literal(&self, l: &'ast AstLiteral<'ast>) -> HirResult<HirExpr<'hir>> {
+        let hir = match l {
+            AstLiteral::Integer(i) => HirExpr::IntegerLiteral(HirIntegerLiteralExpr {
+                span: l.span(),
+                value: i.value,
+                ty: self.arena.types().get_integer64_ty(),
+            }),
+            AstLiteral::Boolean(b) => HirExpr::BooleanLiteral(HirBooleanLiteralExpr {
+                span: l.span(),
+                value: b.value,
+                ty: self.arena.types().get_boolean_ty(),
+            }),
+            AstLiteral::Float(f) => HirExpr::FloatLiteral(HirFloatLiteralExpr {
+                span: l.span(),
+                value: f.value,
+                ty: self.arena.types().get_float64_ty(),
+            }),
+            AstLiteral::UnsignedInteger(u) => {
+                HirExpr::UnsignedIntegerLiteral(HirUnsignedIntegerLiteralExpr {
+                    span: l.span(),
+                    value: u.value,
+                    ty: self.arena.types().get_uint64_ty(),
+                })
+            }
+            AstLiteral::SelfLiteral(_) => {
+                HirExpr::SelfLiteral(HirSelfLiteral {
+                    span: l.span(),
+                    ty: self.arena.types().get_uninitialized_ty(),
+                })
+            }
+            AstLiteral::Char(c) => {
+                HirExpr::CharLiteral(HirCharLiteralExpr {
+                    span: l.span(),
+                    value: c.value,
+                    ty: self.arena.types().get_char_ty(),
+                })
+            }
+            AstLiteral::Unit(_) => {
+                HirExpr::UnitLiteral(HirUnitLiteralExpr {
+                    span: l.span(),
+                    ty: self.arena.types().get_unit_ty(),
+                })
+            }
+            AstLiteral::String(s) => {
+                HirExpr::StringLiteral(HirStringLiteralExpr {
+                    span: l.span(),
+                    value: s.value,
+                    ty: self.arena.types().get_str_ty(),
+                })
+            }
+            AstLiteral::List(l) => {
+                let elements = l
+                    .items
+                    .iter()
+                    .map(|e| self.visit_expr(e))
+                    .collect::<HirResult<Vec<_>>>()?;
+                HirExpr::ListLiteral(HirListLiteralExpr {
+                    span: l.span.clone(),
+                    items: elements,
+                    ty: self.arena.types().get_uninitialized_ty(),
+                })
+            }
+        };
+        Ok(hir)
+    }
+
+    fn visit_match_arm(&self, node: &'ast AstMatchArm<'ast>) -> HirResult<HirMatchArm<'hir>> {
+        let patterns = node
+            .patterns
+            .iter()
+            .map(|pattern| self.visit_pattern(pattern))
+            .collect::<HirResult<Vec<_>>>()?;
+        let (body, value) = match node.body {
+            AstExpr::Block(b) => (self.visit_block(b)?, None),
+            expr => {
+                let body = HirBlock {
+                    span: expr.span(),
+                    statements: vec![],
+                };
+                (body, Some(Box::new(self.visit_expr(expr)?)))
+            }
+        };
+        Ok(HirMatchArm {
+            span: node.span.clone(),
+            patterns,
+            body,
+            value,
+        })
+    }
+
+    fn visit_pattern(&self, node: &'ast AstPattern<'ast>) -> HirResult<HirPattern<'hir>> {
+        let hir = match node {
+            AstPattern::Wildcard(w) => HirPattern::Wildcard(w.span.clone()),
+            AstPattern::Literal(l) => HirPattern::Literal(self.visit_literal(l)?),
+            AstPattern::Variant(v) => HirPattern::Variant(HirVariantPattern {
+                span: v.span.clone(),
+                access: HirStaticAccessExpr {
+                    span: v.span.clone(),
+                    target: Box::new(self.visit_identifier(v.target)?),
+                    field: Box::new(self.visit_identifier(v.variant)?),
+                    ty: self.arena.types().get_uninitialized_ty(),
+                },
+                bindings: v
+                    .bindings
+                    .map(|bindings| bindings.iter().map(|b| self.visit_identifier(b)).collect::<HirResult<Vec<_>>>())
+                    .transpose()?,
+            }),
+        };
+        Ok(hir)
+    }
+
     fn visit_identifier(&self, node: &'ast AstIdentifier<'ast>) -> HirResult<HirIdentExpr<'hir>> {
         Ok(HirIdentExpr {
             name: self.arena.names().get(node.name),
@@ -976,8 +1491,11 @@ where
             span: node.span.clone(),
             vis: node.vis.into(),
             params: parameters?,
-            //Generics aren't supported yet for normal functions
-            generics: None,
+            generics: if node.generics.is_empty() {
+                None
+            } else {
+                Some(node.generics.iter().map(|g| self.visit_type_parameter(g)).collect::<HirResult<_>>()?)
+            },
             type_params: type_parameters?,
             return_ty: ret_type,
             return_ty_span: Some(ret_type_span),
@@ -1020,6 +1538,7 @@ where
             span: node.span.clone(),
             name,
             name_span: node.name.span.clone(),
+            constraints: Vec::new(),
         });
         Ok(hir)
     }
@@ -1035,7 +1554,27 @@ where
             AstType::String(_) => self.arena.types().get_str_ty(),
             AstType::Named(n) => {
                 let name = self.arena.names().get(n.name.name);
-                self.arena.types().get_named_ty(name, n.span.clone())
+                let arity = self.unions.borrow().get(name).copied();
+                if let Some(arity) = arity {
+                    if arity != n.args.len() {
+                        return Err(HirError::TypeArgumentCount(TypeArgumentCountError {
+                            name: name.to_string(),
+                            expected: arity,
+                            found: n.args.len(),
+                            span: SourceSpan::new(SourceOffset::from(n.span.start), n.span.end - n.span.start),
+                            src: self.src.clone(),
+                        }));
+                    }
+                    let args = n.args.iter().map(|a| self.visit_ty(a)).collect::<HirResult<Vec<_>>>()?;
+                    self.arena.types().get_union_ty(name, n.span.clone(), args)
+                } else if self.enums.borrow().contains(name) {
+                    self.arena.types().get_enum_ty(name, n.span.clone())
+                } else if !n.args.is_empty() {
+                    let args = n.args.iter().map(|a| self.visit_ty(a)).collect::<HirResult<Vec<_>>>()?;
+                    self.arena.types().get_generic_ty(name, n.span.clone(), args)
+                } else {
+                    self.arena.types().get_named_ty(name, n.span.clone())
+                }
             }
             AstType::List(l) => {
                 let ty = self.visit_ty(l.inner)?;
@@ -1055,3 +1594,120 @@ where
         Ok(ty)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::AstSyntaxLoweringPass;
+    use crate::atlas_c::atlas_frontend::{parse, parser::arena::AstArena};
+    use crate::atlas_c::atlas_hir::{
+        arena::HirArena,
+        error::{HirError, HirResult},
+        HirModule,
+    };
+    use bumpalo::Bump;
+
+    /// Parse & lower `source`, then hand the module to `then`
+    #[allow(clippy::result_large_err)]
+    fn lower<T>(source: &str, then: impl for<'hir> FnOnce(&HirModule<'hir>) -> T) -> HirResult<T> {
+        let bump = Bump::new();
+        let ast_arena = AstArena::new(&bump);
+        let program = parse("test.atlas", &ast_arena, source.to_string()).unwrap();
+        let hir_arena = HirArena::new();
+        let pass = AstSyntaxLoweringPass::new(&hir_arena, &program, &ast_arena, source.to_string());
+        pass.lower().map(|hir| then(&hir))
+    }
+
+    /// Name, type parameters & fields of every variant of the union
+    fn union_shape(hir: &HirModule, name: &str) -> (Vec<String>, Vec<(String, Vec<String>)>) {
+        let union = hir.signature.unions.get(name).unwrap();
+        let variants = union
+            .variants
+            .iter()
+            .map(|v| (v.name.to_string(), v.fields.iter().map(|f| format!("{}: {}", f.name, f.ty)).collect()))
+            .collect();
+        (union.generics.iter().map(|g| g.to_string()).collect(), variants)
+    }
+
+    #[test]
+    fn the_prelude_is_in_every_module() {
+        let (option, result) = lower("func f() -> int64 { return 0; }", |hir| {
+            (union_shape(hir, "Option"), union_shape(hir, "Result"))
+        })
+        .unwrap();
+        assert_eq!(option.0, ["T"]);
+        assert_eq!(option.1[0], ("Some".to_string(), vec!["value: T".to_string()]));
+        assert_eq!(option.1[1], ("None".to_string(), vec![]));
+        assert_eq!(result.0, ["T", "E"]);
+        assert_eq!(result.1[1], ("Err".to_string(), vec!["error: E".to_string()]));
+    }
+
+    #[test]
+    fn union_variants_keep_their_fields() {
+        let source = "union Shape {\n    Square(side: int64),\n    Rect(w: int64, h: float64),\n    Empty,\n}";
+        let (generics, variants) = lower(source, |hir| union_shape(hir, "Shape")).unwrap();
+        assert!(generics.is_empty());
+        let names = variants.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["Square", "Rect", "Empty"]);
+        assert_eq!(variants[1].1, ["w: int64", "h: float64"]);
+
+        let duplicate = "union Shape { Empty, Empty }";
+        assert!(matches!(lower(duplicate, |_| ()), Err(HirError::DuplicateEnumVariant(_))));
+    }
+
+    #[test]
+    fn enum_variants_are_numbered() {
+        let source = "enum Color {\n    Red,\n    Green = 4,\n    Blue,\n}";
+        let variants = lower(source, |hir| {
+            let color = hir.signature.enums.get("Color").unwrap();
+            color.variants.iter().map(|v| (v.name.to_string(), v.discriminant)).collect::<Vec<_>>()
+        })
+        .unwrap();
+        assert_eq!(variants, [("Red".to_string(), 0), ("Green".to_string(), 4), ("Blue".to_string(), 5)]);
+
+        let duplicate = "enum A { X, X }";
+        assert!(matches!(lower(duplicate, |_| ()), Err(HirError::DuplicateEnumVariant(_))));
+    }
+
+    #[test]
+    fn union_type_arguments_are_counted() {
+        let arity = "func f(o: Option<int64, int64>) -> int64 { return 0; }";
+        assert!(matches!(lower(arity, |_| ()), Err(HirError::TypeArgumentCount(_))));
+        let missing = "func f() -> Result<int64> { return Result::Ok(1); }";
+        assert!(matches!(lower(missing, |_| ()), Err(HirError::TypeArgumentCount(_))));
+    }
+
+    #[test]
+    fn naming_conventions_are_warnings() {
+        let source = r#"enum color { Red }
+union shape { Empty }
+class point {
+public:
+    x: int64;
+}
+func Main() -> int64 {
+    let myValue = 1;
+    return myValue;
+}"#;
+        let bump = Bump::new();
+        let ast_arena = AstArena::new(&bump);
+        let program = parse("test.atlas", &ast_arena, source.to_string()).unwrap();
+        let hir_arena = HirArena::new();
+        let pass = AstSyntaxLoweringPass::new(&hir_arena, &program, &ast_arena, source.to_string());
+        pass.lower().unwrap();
+        let warnings = pass
+            .take_warnings()
+            .iter()
+            .map(|w| (w.to_string(), w.suggestion.clone()))
+            .collect::<Vec<_>>();
+        let expected = [
+            ("enum `color` isn't in PascalCase", "Color"),
+            ("union `shape` isn't in PascalCase", "Shape"),
+            ("class `point` isn't in PascalCase", "Point"),
+            ("variable `myValue` isn't in snake_case", "my_value"),
+            ("function `Main` isn't in snake_case", "main"),
+        ]
+        .map(|(warning, suggestion)| (warning.to_string(), suggestion.to_string()));
+        assert_eq!(warnings, expected);
+        assert!(pass.take_warnings().is_empty());
+    }
+}
@@ -76,6 +76,24 @@ impl HirTyId {
         (0x10, name).hash(&mut hasher);
         Self(hasher.finish())
     }
+
+    pub fn compute_enum_ty_id(name: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        (0x40, name).hash(&mut hasher);
+        Self(hasher.finish())
+    }
+
+    pub fn compute_union_ty_id(name: &str, args: &[HirTyId]) -> Self {
+        let mut hasher = DefaultHasher::new();
+        (0x60, name, args).hash(&mut hasher);
+        Self(hasher.finish())
+    }
+
+    pub fn compute_generic_ty_id(name: &str, args: &[HirTyId]) -> Self {
+        let mut hasher = DefaultHasher::new();
+        (0x70, name, args).hash(&mut hasher);
+        Self(hasher.finish())
+    }
 }
 
 impl<'hir> From<&'hir HirTy<'hir>> for HirTyId {
@@ -90,6 +108,15 @@ impl<'hir> From<&'hir HirTy<'hir>> for HirTyId {
             HirTy::String(_) => Self::compute_str_ty_id(),
             HirTy::List(ty) => HirTyId::compute_list_ty_id(&HirTyId::from(ty.inner)),
             HirTy::Named(ty) => HirTyId::compute_name_ty_id(ty.name),
+            HirTy::Enum(ty) => HirTyId::compute_enum_ty_id(ty.name),
+            HirTy::Union(ty) => {
+                let args = ty.args.iter().map(|a| HirTyId::from(*a)).collect::<Vec<_>>();
+                HirTyId::compute_union_ty_id(ty.name, &args)
+            }
+            HirTy::Generic(ty) => {
+                let args = ty.args.iter().map(|a| HirTyId::from(*a)).collect::<Vec<_>>();
+                HirTyId::compute_generic_ty_id(ty.name, &args)
+            }
             HirTy::Uninitialized(_) => Self::compute_uninitialized_ty_id(),
             HirTy::_Function(f) => {
                 let parameters = f.params.iter().map(HirTyId::from).collect::<Vec<_>>();
@@ -111,6 +138,9 @@ pub enum HirTy<'hir> {
     String(HirStringTy),
     List(HirListTy<'hir>),
     Named(HirNamedTy<'hir>),
+    Enum(HirEnumTy<'hir>),
+    Union(HirUnionTy<'hir>),
+    Generic(HirGenericTy<'hir>),
     Uninitialized(HirUninitializedTy),
 
     _Function(HirFunctionTy<'hir>),
@@ -128,6 +158,16 @@ impl fmt::Display for HirTy<'_> {
             HirTy::String(_) => write!(f, "str"),
             HirTy::List(ty) => write!(f, "[{}]", ty),
             HirTy::Named(ty) => write!(f, "{}", ty.name),
+            HirTy::Enum(ty) => write!(f, "{}", ty.name),
+            HirTy::Union(ty) if ty.args.is_empty() => write!(f, "{}", ty.name),
+            HirTy::Union(ty) => {
+                let args = ty.args.iter().map(|a| format!("{}", a)).collect::<Vec<_>>().join(", ");
+                write!(f, "{}<{}>", ty.name, args)
+            }
+            HirTy::Generic(ty) => {
+                let args = ty.args.iter().map(|a| format!("{}", a)).collect::<Vec<_>>().join(", ");
+                write!(f, "{}<{}>", ty.name, args)
+            }
             HirTy::Uninitialized(_) => write!(f, "uninitialized"),
             HirTy::_Function(func) => {
                 let params = func
@@ -142,6 +182,25 @@ impl fmt::Display for HirTy<'_> {
     }
 }
 
+impl<'hir> HirTy<'hir> {
+    /// Whether a value of type `other` can be used where `self` is expected
+    ///
+    /// Type arguments that are still uninitialized (e.g. the `T` of `Option::None`) match anything
+    pub fn accepts(&self, other: &HirTy<'hir>) -> bool {
+        match (self, other) {
+            (HirTy::Union(a), HirTy::Union(b)) => {
+                a.name == b.name
+                    && a.args.len() == b.args.len()
+                    && a.args.iter().zip(b.args.iter()).all(|(a, b)| {
+                        matches!(a, HirTy::Uninitialized(_)) || matches!(b, HirTy::Uninitialized(_)) || a.accepts(b)
+                    })
+            }
+            (HirTy::List(a), HirTy::List(b)) => a.inner.accepts(b.inner),
+            _ => HirTyId::from(self) == HirTyId::from(other),
+        }
+    }
+}
+
 /// The char type is a 32-bit Unicode code point.
 ///
 /// It can be considered as a 4-byte integer.
@@ -192,3 +251,32 @@ pub struct HirNamedTy<'hir> {
     /// Span of the name declaration.
     pub span: Span,
 }
+
+/// Enums are their variant's discriminant at runtime
+#[derive(Debug, Clone, Serialize, Eq, Hash, PartialEq)]
+pub struct HirEnumTy<'hir> {
+    pub name: &'hir str,
+    /// Span of the name declaration.
+    pub span: Span,
+}
+
+/// Unions are objects holding the variant's tag and its fields at runtime
+#[derive(Debug, Clone, Serialize, Eq, Hash, PartialEq)]
+pub struct HirUnionTy<'hir> {
+    pub name: &'hir str,
+    /// Span of the name declaration.
+    pub span: Span,
+    /// Type arguments, `uninitialized` when they're not known yet
+    pub args: Vec<&'hir HirTy<'hir>>,
+}
+
+/// A generic class given type arguments, e.g. `Vec<int64>`
+///
+/// The type checker replaces it by the named type of the class' instance, named after this type
+#[derive(Debug, Clone, Serialize, Eq, Hash, PartialEq)]
+pub struct HirGenericTy<'hir> {
+    pub name: &'hir str,
+    /// Span of the name declaration.
+    pub span: Span,
+    pub args: Vec<&'hir HirTy<'hir>>,
+}
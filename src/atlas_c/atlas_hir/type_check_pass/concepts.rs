@@ -0,0 +1,193 @@
+//! Concepts are checked statically, they're never compiled.
+//!
+//! A class implementing a concept must have all its methods, with the concept's type parameters replaced by the
+//! type arguments of `impl Name<A, B>`. A type argument given to a constrained type parameter must be a class
+//! implementing the required concepts.
+
+use super::TypeChecker;
+use crate::atlas_c::atlas_hir::error::{
+    ConceptMethodMismatchError, HirError, HirResult, MissingConceptMethodError, TypeArgumentCountError,
+    UnknownTypeError, UnsatisfiedConstraintError,
+};
+use crate::atlas_c::atlas_hir::signature::{
+    HirClassMethodModifier, HirClassMethodSignature, HirClassSignature, HirConceptConstraint, HirConceptSignature,
+    HirTypeParameterItemSignature,
+};
+use crate::atlas_c::atlas_hir::ty::{HirTy, HirTyId};
+use logos::Span;
+use miette::{SourceOffset, SourceSpan};
+
+impl<'hir> TypeChecker<'hir> {
+    /// Check the class has the methods of every concept it implements
+    pub(super) fn check_concepts(&mut self, class: &'hir HirClassSignature<'hir>) -> HirResult<()> {
+        for constraint in class.concepts.iter() {
+            let concept = self.concept(constraint)?;
+            let generics = concept.generics.iter().map(|g| g.name).collect::<Vec<_>>();
+            self.check_constraints(concept.name, &concept.generics, &constraint.args, &constraint.span)?;
+            for (name, method) in concept.methods.iter() {
+                let expected = self.substitute_method(method, &generics, &constraint.args)?;
+                let found = match class.methods.get(name) {
+                    Some(found) => found,
+                    None => {
+                        return Err(HirError::MissingConceptMethod(MissingConceptMethodError {
+                            class: class.name.to_string(),
+                            concept: Self::constraint_name(constraint),
+                            expected: Self::method_declaration(name, &expected),
+                            span: Self::source_span(&constraint.span),
+                            src: self.src.clone(),
+                        }))
+                    }
+                };
+                if !Self::same_method(&expected, found) {
+                    return Err(HirError::ConceptMethodMismatch(ConceptMethodMismatchError {
+                        method: format!("{}.{}", class.name, name),
+                        concept: Self::constraint_name(constraint),
+                        expected: Self::method_declaration(name, &expected),
+                        span: Self::source_span(&found.span),
+                        src: self.src.clone(),
+                    }));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the type arguments given to `item` implement the concepts its type parameters require
+    pub(super) fn check_constraints(
+        &mut self,
+        item: &str,
+        params: &[&'hir HirTypeParameterItemSignature<'hir>],
+        args: &[&'hir HirTy<'hir>],
+        span: &Span,
+    ) -> HirResult<()> {
+        let generics = params.iter().map(|p| p.name).collect::<Vec<_>>();
+        for (param, arg) in params.iter().zip(args.iter()) {
+            for constraint in param.constraints.iter() {
+                self.concept(constraint)?;
+                let concept_args = constraint
+                    .args
+                    .iter()
+                    .map(|a| self.substitute(a, &generics, args))
+                    .collect::<HirResult<Vec<_>>>()?;
+                if !self.implements(arg, constraint.name, &concept_args) {
+                    let required = HirConceptConstraint {
+                        args: concept_args,
+                        ..(*constraint).clone()
+                    };
+                    return Err(HirError::UnsatisfiedConstraint(UnsatisfiedConstraintError {
+                        ty: arg.to_string(),
+                        concept: Self::constraint_name(&required),
+                        item: item.to_string(),
+                        span: Self::source_span(span),
+                        src: self.src.clone(),
+                    }));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Only classes can implement concepts, by declaring it with `impl Name<A, B>`
+    fn implements(&self, ty: &HirTy<'hir>, concept: &str, args: &[&'hir HirTy<'hir>]) -> bool {
+        let class = match ty {
+            HirTy::Named(n) => match self.signature.classes.get(n.name) {
+                Some(class) => class,
+                None => return false,
+            },
+            _ => return false,
+        };
+        class.concepts.iter().any(|c| {
+            c.name == concept
+                && c.args.len() == args.len()
+                && c.args.iter().zip(args.iter()).all(|(a, b)| HirTyId::from(*a) == HirTyId::from(*b))
+        })
+    }
+
+    fn concept(&self, constraint: &HirConceptConstraint<'hir>) -> HirResult<&'hir HirConceptSignature<'hir>> {
+        let concept = match self.signature.concepts.get(constraint.name) {
+            Some(concept) => *concept,
+            None => {
+                return Err(HirError::UnknownType(UnknownTypeError {
+                    name: constraint.name.to_string(),
+                    span: Self::source_span(&constraint.span),
+                    src: self.src.clone(),
+                }))
+            }
+        };
+        if concept.generics.len() != constraint.args.len() {
+            return Err(HirError::TypeArgumentCount(TypeArgumentCountError {
+                name: constraint.name.to_string(),
+                expected: concept.generics.len(),
+                found: constraint.args.len(),
+                span: Self::source_span(&constraint.span),
+                src: self.src.clone(),
+            }));
+        }
+        Ok(concept)
+    }
+
+    /// `substitute_class` does the same for the constraints of a class
+    pub(super) fn substitute_constraint(
+        &mut self,
+        constraint: &'hir HirConceptConstraint<'hir>,
+        generics: &[&'hir str],
+        args: &[&'hir HirTy<'hir>],
+    ) -> HirResult<&'hir HirConceptConstraint<'hir>> {
+        let concept_args = constraint
+            .args
+            .iter()
+            .map(|a| self.substitute(a, generics, args))
+            .collect::<HirResult<Vec<_>>>()?;
+        Ok(self.arena.intern(HirConceptConstraint {
+            args: concept_args,
+            ..constraint.clone()
+        }))
+    }
+
+    fn substitute_method(
+        &mut self,
+        method: &HirClassMethodSignature<'hir>,
+        generics: &[&'hir str],
+        args: &[&'hir HirTy<'hir>],
+    ) -> HirResult<HirClassMethodSignature<'hir>> {
+        let mut method = method.clone();
+        method.params = self.substitute_params(&method.params, generics, args)?;
+        method.return_ty = self.substitute(method.return_ty, generics, args)?;
+        Ok(method)
+    }
+
+    fn same_method(expected: &HirClassMethodSignature<'hir>, found: &HirClassMethodSignature<'hir>) -> bool {
+        let is_static = |m: &HirClassMethodSignature| m.modifier == HirClassMethodModifier::Static;
+        is_static(expected) == is_static(found)
+            && expected.params.len() == found.params.len()
+            && expected
+                .params
+                .iter()
+                .zip(found.params.iter())
+                .all(|(a, b)| HirTyId::from(a.ty) == HirTyId::from(b.ty))
+            && HirTyId::from(expected.return_ty) == HirTyId::from(found.return_ty)
+    }
+
+    /// `Name` or `Name<A, B>`
+    fn constraint_name(constraint: &HirConceptConstraint) -> String {
+        if constraint.args.is_empty() {
+            return constraint.name.to_string();
+        }
+        let args = constraint.args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        format!("{}<{}>", constraint.name, args.join(", "))
+    }
+
+    /// e.g. `func get(self, i: int64) -> T`
+    fn method_declaration(name: &str, method: &HirClassMethodSignature) -> String {
+        let mut params = Vec::new();
+        if method.modifier != HirClassMethodModifier::Static {
+            params.push(String::from("self"));
+        }
+        params.extend(method.params.iter().map(|p| format!("{}: {}", p.name, p.ty)));
+        format!("func {}({}) -> {}", name, params.join(", "), method.return_ty)
+    }
+
+    fn source_span(span: &Span) -> SourceSpan {
+        SourceSpan::new(SourceOffset::from(span.start), span.end - span.start)
+    }
+}
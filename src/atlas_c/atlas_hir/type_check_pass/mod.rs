@@ -2,25 +2,28 @@
 //As there will only be primitive types to check.
 //A rework of the type checker will be done when structs, classes, enums and unions are added.
 
+mod concepts;
+mod monomorphize;
+
 use super::{
     arena::HirArena,
     error::{
-        FunctionTypeMismatchError, HirError, HirResult,
-        TryingToNegateUnsignedError, TypeMismatchError, UnknownTypeError,
+        FunctionTypeMismatchError, HirError, HirResult, InvalidPatternError, NonExhaustiveMatchError,
+        TryingToNegateUnsignedError, TypeMismatchError, UnknownTypeError, UnsupportedStatement,
     },
     expr,
-    expr::{HirBinaryOp, HirExpr},
+    expr::{HirBinaryOp, HirExpr, HirPattern},
     stmt::HirStatement,
     ty::{HirTy, HirTyId},
     HirFunction, HirModule, HirModuleSignature,
 };
 use crate::atlas_c::atlas_hir::error::{AccessingClassFieldOutsideClassError, AccessingPrivateFieldError, EmptyListLiteralError, FieldKind, UnsupportedExpr};
-use crate::atlas_c::atlas_hir::expr::{HirFunctionCallExpr, HirIdentExpr};
+use crate::atlas_c::atlas_hir::expr::{HirFunctionCallExpr, HirIdentExpr, HirStaticAccessExpr};
 use crate::atlas_c::atlas_hir::item::{HirClass, HirClassConstructor, HirClassMethod};
 use crate::atlas_c::atlas_hir::signature::{HirClassMethodModifier, HirFunctionParameterSignature, HirFunctionSignature, HirVisibility};
 use logos::Span;
 use miette::{SourceOffset, SourceSpan};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 pub struct TypeChecker<'hir> {
     arena: &'hir HirArena<'hir>,
@@ -35,6 +38,16 @@ pub struct TypeChecker<'hir> {
     // Source code
     src: String,
     extern_monomorphized: HashMap<(&'hir str, Vec<&'hir HirTy<'hir>>), &'hir HirFunctionSignature<'hir>>,
+    /// Every declaration & use of a variable checked so far
+    resolved: Vec<ResolvedVariable<'hir>>,
+    /// Generic functions & classes, see [`monomorphize`]
+    generic_functions: BTreeMap<&'hir str, HirFunction<'hir>>,
+    generic_classes: BTreeMap<&'hir str, HirClass<'hir>>,
+    /// The template & type arguments of each class instance (e.g. `Vec<int64>`)
+    instances: HashMap<&'hir str, (&'hir str, Vec<&'hir HirTy<'hir>>)>,
+    /// Instances waiting to be checked
+    pending_functions: Vec<HirFunction<'hir>>,
+    pending_classes: Vec<HirClass<'hir>>,
 }
 
 pub struct ContextFunction<'hir> {
@@ -118,6 +131,15 @@ pub struct ContextVariable<'hir> {
     pub is_mut: bool,
 }
 
+/// Identifier the type checker resolved to a [`ContextVariable`]
+#[derive(Debug, Clone)]
+pub struct ResolvedVariable<'hir> {
+    pub span: Span,
+    /// Span of the name in the declaration of the variable
+    pub name_span: Span,
+    pub ty: &'hir HirTy<'hir>,
+}
+
 impl<'hir> TypeChecker<'hir> {
     pub fn new(arena: &'hir HirArena<'hir>, src: String) -> Self {
         Self {
@@ -128,11 +150,30 @@ impl<'hir> TypeChecker<'hir> {
             current_func_name: None,
             current_class_name: None,
             extern_monomorphized: HashMap::new(),
+            resolved: Vec::new(),
+            generic_functions: BTreeMap::new(),
+            generic_classes: BTreeMap::new(),
+            instances: HashMap::new(),
+            pending_functions: Vec::new(),
+            pending_classes: Vec::new(),
         }
     }
 
+    /// Variables declared (`let` & `const`) or used by the code checked so far, in order
+    pub fn resolved(&self) -> &[ResolvedVariable<'hir>] {
+        &self.resolved
+    }
+
     pub fn check(&mut self, hir: &mut HirModule<'hir>) -> HirResult<()> {
         self.signature = hir.signature.clone();
+        self.take_templates(hir);
+        //Every signature uses instances of the generic classes before any body is checked
+        for func in hir.body.functions.values_mut() {
+            self.substitute_function(func, &[], &[])?;
+        }
+        for class in hir.body.classes.values_mut() {
+            self.substitute_class(class, &[], &[])?;
+        }
         for func in &mut hir.body.functions {
             self.current_func_name = Some(func.0);
             self.check_func(func.1)?;
@@ -141,10 +182,13 @@ impl<'hir> TypeChecker<'hir> {
             self.current_class_name = Some(class.0);
             self.check_class(class.1)?;
         }
+        self.check_instances(hir)?;
+        hir.signature = self.signature.clone();
         Ok(())
     }
 
     pub fn check_class(&mut self, class: &mut HirClass<'hir>) -> HirResult<()> {
+        self.check_concepts(class.signature)?;
         for method in &mut class.methods {
             self.current_class_name = Some(class.name);
             self.current_func_name = Some(method.name);
@@ -289,9 +333,9 @@ impl<'hir> TypeChecker<'hir> {
                 let actual_ret_ty = self.check_expr(&mut r.value)?;
                 let mut expected_ret_ty = self.arena.types().get_uninitialized_ty();
                 let mut span = SourceSpan::new(SourceOffset::from(r.span.start), r.span.end - r.span.start);
-                if self.current_class_name.is_some() {
+                if let Some(class_name) = self.current_class_name {
                     //This means we're in a class method
-                    let class = self.signature.classes.get(self.current_class_name.unwrap()).unwrap();
+                    let class = self.signature.classes.get(class_name).unwrap();
                     let method = class.methods.get(self.current_func_name.unwrap()).unwrap();
                     expected_ret_ty = method.return_ty;
                     span = SourceSpan::new(
@@ -301,12 +345,12 @@ impl<'hir> TypeChecker<'hir> {
                         method.return_ty_span.clone().unwrap_or(r.span.clone()).end
                             - method.return_ty_span.clone().unwrap_or(r.span.clone()).start,
                     )
-                } else if self.current_func_name.is_some() {
+                } else if let Some(func_name) = self.current_func_name {
                     //This means we're in a standalone function
                     let func_ret_from = self
                         .signature
                         .functions
-                        .get(self.current_func_name.unwrap())
+                        .get(func_name)
                         .unwrap();
                     expected_ret_ty = func_ret_from.return_ty;
                     span = SourceSpan::new(
@@ -317,7 +361,7 @@ impl<'hir> TypeChecker<'hir> {
                             - func_ret_from.return_ty_span.clone().unwrap_or(r.span.clone()).start,
                     )
                 }
-                if HirTyId::from(actual_ret_ty) != HirTyId::from(expected_ret_ty) {
+                if !expected_ret_ty.accepts(actual_ret_ty) {
                     return Err(HirError::TypeMismatch(TypeMismatchError {
                         actual_type: format!("{}", actual_ret_ty),
                         actual_loc: SourceSpan::new(
@@ -423,7 +467,6 @@ impl<'hir> TypeChecker<'hir> {
                 let expr_ty = self.check_expr(&mut c.value)?;
                 let const_ty = c.ty.unwrap_or(expr_ty);
                 c.ty = Some(const_ty);
-                let ty = HirTyId::from(const_ty);
                 self.context_functions
                     .last_mut()
                     .unwrap()
@@ -439,8 +482,13 @@ impl<'hir> TypeChecker<'hir> {
                             is_mut: false,
                         },
                     );
+                self.resolved.push(ResolvedVariable {
+                    span: c.name_span.clone(),
+                    name_span: c.name_span.clone(),
+                    ty: const_ty,
+                });
 
-                if HirTyId::from(expr_ty) != ty {
+                if !const_ty.accepts(expr_ty) {
                     return Err(HirError::TypeMismatch(TypeMismatchError {
                         actual_type: format!("{}", expr_ty),
                         actual_loc: SourceSpan::new(
@@ -461,7 +509,6 @@ impl<'hir> TypeChecker<'hir> {
                 let expr_ty = self.check_expr(&mut l.value)?;
                 let var_ty = l.ty.unwrap_or(expr_ty);
                 l.ty = Some(var_ty);
-                let ty = HirTyId::from(var_ty);
                 self.context_functions
                     .last_mut()
                     .unwrap()
@@ -477,7 +524,12 @@ impl<'hir> TypeChecker<'hir> {
                             is_mut: true,
                         },
                     );
-                if HirTyId::from(expr_ty) != ty {
+                self.resolved.push(ResolvedVariable {
+                    span: l.name_span.clone(),
+                    name_span: l.name_span.clone(),
+                    ty: var_ty,
+                });
+                if !var_ty.accepts(expr_ty) {
                     return Err(HirError::TypeMismatch(TypeMismatchError {
                         actual_type: format!("{}", expr_ty),
                         actual_loc: SourceSpan::new(
@@ -494,8 +546,18 @@ impl<'hir> TypeChecker<'hir> {
                 }
                 Ok(())
             }
-            _ => {
-                todo!("TypeChecker::check_stmt: {:?}", stmt)
+            HirStatement::Break(_) | HirStatement::Continue(_) | HirStatement::_Block(_) => {
+                let span = stmt.span();
+                let stmt = match stmt {
+                    HirStatement::Break(_) => "break",
+                    HirStatement::Continue(_) => "continue",
+                    _ => "block",
+                };
+                Err(HirError::UnsupportedStatement(UnsupportedStatement {
+                    span: SourceSpan::new(SourceOffset::from(span.start), span.end - span.start),
+                    stmt: stmt.to_string(),
+                    src: self.src.clone(),
+                }))
             }
         }
     }
@@ -580,7 +642,8 @@ impl<'hir> TypeChecker<'hir> {
                 let ty = self.check_expr(&mut u.expr)?;
                 match u.op {
                     Some(expr::HirUnaryOp::Neg) => {
-                        if HirTyId::from(ty) != HirTyId::compute_integer64_ty_id() {
+                        let id = HirTyId::from(ty);
+                        if id != HirTyId::compute_integer64_ty_id() && id != HirTyId::compute_float64_ty_id() {
                             return Err(HirError::TryingToNegateUnsigned(
                                 TryingToNegateUnsignedError {
                                     span: SourceSpan::new(
@@ -681,6 +744,19 @@ impl<'hir> TypeChecker<'hir> {
                     }));
                 }
 
+                //Unions have no operators & enums can only be compared for equality
+                if matches!(lhs, HirTy::Union(_))
+                    || matches!(lhs, HirTy::Enum(_)) && !matches!(b.op, HirBinaryOp::Eq | HirBinaryOp::Neq) {
+                    return Err(HirError::UnsupportedExpr(UnsupportedExpr {
+                        span: SourceSpan::new(
+                            SourceOffset::from(b.span.start),
+                            b.span.end - b.span.start,
+                        ),
+                        expr: format!("`{:?}` on `{}` values", b.op, lhs),
+                        src: self.src.clone(),
+                    }));
+                }
+
                 match b.op {
                     HirBinaryOp::And
                     | HirBinaryOp::Eq
@@ -727,11 +803,14 @@ impl<'hir> TypeChecker<'hir> {
                         if func.is_external && func.generics.is_some() {
                             return self.check_extern_fn(name, func_expr, func);
                         }
+                        if func.generics.is_some() {
+                            return self.check_generic_call(name, func_expr, func);
+                        }
 
                         for (param, arg) in func.params.iter().zip(func_expr.args.iter_mut()) {
                             let arg_ty = self.check_expr(arg)?;
 
-                            if HirTyId::from(arg_ty) != HirTyId::from(param.ty) {
+                            if !param.ty.accepts(arg_ty) {
                                 return Err(HirError::TypeMismatch(TypeMismatchError {
                                     actual_type: format!("{}", arg_ty),
                                     actual_loc: SourceSpan::new(
@@ -803,7 +882,7 @@ impl<'hir> TypeChecker<'hir> {
                                 }
                                 for (param, arg) in method_signature.params.iter().zip(func_expr.args.iter_mut()) {
                                     let arg_ty = self.check_expr(arg)?;
-                                    if HirTyId::from(arg_ty) != HirTyId::from(param.ty) {
+                                    if !param.ty.accepts(arg_ty) {
                                         return Err(HirError::TypeMismatch(TypeMismatchError {
                                             actual_type: format!("{}", arg_ty),
                                             actual_loc: SourceSpan::new(
@@ -852,6 +931,23 @@ impl<'hir> TypeChecker<'hir> {
                         }
                     }
                     HirExpr::StaticAccess(static_access) => {
+                        if self.signature.unions.contains_key(static_access.target.name) {
+                            let ty = self.check_union_variant(static_access, &mut func_expr.args)?;
+                            func_expr.ty = ty;
+                            return Ok(ty);
+                        }
+                        //`Vec::method(...)` calls the method of the instance its arguments give
+                        if let Some(template) = self.generic_classes.get(static_access.target.name) {
+                            let params = match template.signature.methods.get(static_access.field.name) {
+                                Some(method) => method.params.clone(),
+                                None => Vec::new(),
+                            };
+                            let args_ty = func_expr.args.iter_mut().map(|a| self.check_expr(a)).collect::<HirResult<Vec<_>>>()?;
+                            let instance = self.infer_class_instance(static_access.target.name, &params, &args_ty, &static_access.span)?;
+                            if let HirTy::Named(n) = instance {
+                                static_access.target.name = n.name;
+                            }
+                        }
                         let class = match self.signature.classes.get(static_access.target.name) {
                             Some(c) => *c,
                             None => {
@@ -889,7 +985,7 @@ impl<'hir> TypeChecker<'hir> {
                             }
                             for (param, arg) in method_signature.params.iter().zip(func_expr.args.iter_mut()) {
                                 let arg_ty = self.check_expr(arg)?;
-                                if HirTyId::from(arg_ty) != HirTyId::from(param.ty) {
+                                if !param.ty.accepts(arg_ty) {
                                     return Err(HirError::TypeMismatch(TypeMismatchError {
                                         actual_type: format!("{}", arg_ty),
                                         actual_loc: SourceSpan::new(
@@ -932,7 +1028,7 @@ impl<'hir> TypeChecker<'hir> {
             HirExpr::Assign(a) => {
                 let rhs = self.check_expr(&mut a.rhs)?;
                 let lhs = self.check_expr(&mut a.lhs)?;
-                if HirTyId::from(lhs) != HirTyId::from(rhs) {
+                if !lhs.accepts(rhs) {
                     return Err(HirError::TypeMismatch(TypeMismatchError {
                         actual_type: format!("{}", rhs),
                         actual_loc: SourceSpan::new(
@@ -977,6 +1073,14 @@ impl<'hir> TypeChecker<'hir> {
                 }
             }
             HirExpr::NewObj(obj) => {
+                //`new Vec(...)` creates the instance its arguments give
+                if let HirTy::Named(n) = obj.ty {
+                    if let Some(template) = self.generic_classes.get(n.name) {
+                        let params = template.signature.constructor.params.clone();
+                        let args_ty = obj.args.iter_mut().map(|a| self.check_expr(a)).collect::<HirResult<Vec<_>>>()?;
+                        obj.ty = self.infer_class_instance(n.name, &params, &args_ty, &obj.span)?;
+                    }
+                }
                 let class_ty;
                 let class_signature = if let HirTy::Named(n) = obj.ty {
                     class_ty = n;
@@ -1011,7 +1115,7 @@ impl<'hir> TypeChecker<'hir> {
                 };
                 for (param, arg) in class_signature.constructor.params.iter().zip(obj.args.iter_mut()) {
                     let arg_ty = self.check_expr(arg)?;
-                    if HirTyId::from(arg_ty) != HirTyId::from(param.ty) {
+                    if !param.ty.accepts(arg_ty) {
                         return Err(HirError::TypeMismatch(TypeMismatchError {
                             actual_type: format!("{}", arg_ty),
                             actual_loc: SourceSpan::new(
@@ -1087,7 +1191,28 @@ impl<'hir> TypeChecker<'hir> {
                     }))
                 }
             }
+            HirExpr::Match(m) => self.check_match(m),
             HirExpr::StaticAccess(static_access) => {
+                if self.signature.unions.contains_key(static_access.target.name) {
+                    return self.check_union_variant(static_access, &mut []);
+                }
+                if let Some(enum_signature) = self.signature.enums.get(static_access.target.name) {
+                    let ty = self.arena.types().get_enum_ty(enum_signature.name, enum_signature.name_span.clone());
+                    if enum_signature.variant(static_access.field.name).is_none() {
+                        return Err(HirError::UnknownType(UnknownTypeError {
+                            name: format!("{}::{}", static_access.target.name, static_access.field.name),
+                            span: SourceSpan::new(
+                                SourceOffset::from(static_access.span.start),
+                                static_access.span.end - static_access.span.start,
+                            ),
+                            src: self.src.clone(),
+                        }));
+                    }
+                    static_access.target.ty = ty;
+                    static_access.field.ty = ty;
+                    static_access.ty = ty;
+                    return Ok(ty);
+                }
                 let class = match self.signature.classes.get(static_access.target.name) {
                     Some(c) => *c,
                     None => {
@@ -1121,6 +1246,316 @@ impl<'hir> TypeChecker<'hir> {
         }
     }
 
+    fn check_match(&mut self, m: &mut expr::HirMatchExpr<'hir>) -> HirResult<&'hir HirTy<'hir>> {
+        let value_ty = self.check_expr(&mut m.value)?;
+        let value_span = m.value.span();
+        if !matches!(
+            value_ty,
+            HirTy::Int64(_)
+                | HirTy::UInt64(_)
+                | HirTy::Char(_)
+                | HirTy::Boolean(_)
+                | HirTy::String(_)
+                | HirTy::Enum(_)
+                | HirTy::Union(_)
+        ) {
+            return Err(HirError::UnsupportedExpr(UnsupportedExpr {
+                span: SourceSpan::new(SourceOffset::from(value_span.start), value_span.end - value_span.start),
+                expr: format!("`match` on `{}` values", value_ty),
+                src: self.src.clone(),
+            }));
+        }
+
+        let mut covered = vec![];
+        let mut has_wildcard = false;
+        let mut arms_ty: Option<(&'hir HirTy<'hir>, Span)> = None;
+        for arm in m.arms.iter_mut() {
+            //Bindings live in the arm's scope
+            self.context_functions
+                .last_mut()
+                .unwrap()
+                .get_mut(self.current_func_name.unwrap())
+                .unwrap()
+                .new_scope();
+            let alternatives = arm.patterns.len();
+            for pattern in arm.patterns.iter_mut() {
+                let pattern_ty = match pattern {
+                    HirPattern::Wildcard(_) => {
+                        has_wildcard = true;
+                        continue;
+                    }
+                    HirPattern::Literal(literal) => {
+                        if let HirExpr::BooleanLiteral(b) = literal {
+                            covered.push(b.value.to_string());
+                        }
+                        self.check_expr(literal)?
+                    }
+                    HirPattern::Variant(v) => {
+                        let ty = self.check_variant_pattern(v, value_ty, alternatives)?;
+                        covered.push(v.access.field.name.to_string());
+                        ty
+                    }
+                };
+                if !value_ty.accepts(pattern_ty) {
+                    let span = pattern.span();
+                    return Err(HirError::TypeMismatch(TypeMismatchError {
+                        actual_type: format!("{}", pattern_ty),
+                        actual_loc: SourceSpan::new(SourceOffset::from(span.start), span.end - span.start),
+                        expected_type: format!("{}", value_ty),
+                        expected_loc: SourceSpan::new(SourceOffset::from(value_span.start), value_span.end - value_span.start),
+                        src: self.src.clone(),
+                    }));
+                }
+            }
+
+            for stmt in &mut arm.body.statements {
+                self.check_stmt(stmt)?;
+            }
+            let (arm_ty, arm_span) = match &mut arm.value {
+                Some(value) => (self.check_expr(value)?, value.span()),
+                None => (self.arena.types().get_unit_ty(), arm.body.span.clone()),
+            };
+            self.context_functions
+                .last_mut()
+                .unwrap()
+                .get_mut(self.current_func_name.unwrap())
+                .unwrap()
+                .end_scope();
+
+            match &arms_ty {
+                Some((ty, span)) if !ty.accepts(arm_ty) => {
+                    return Err(HirError::TypeMismatch(TypeMismatchError {
+                        actual_type: format!("{}", arm_ty),
+                        actual_loc: SourceSpan::new(SourceOffset::from(arm_span.start), arm_span.end - arm_span.start),
+                        expected_type: format!("{}", ty),
+                        expected_loc: SourceSpan::new(SourceOffset::from(span.start), span.end - span.start),
+                        src: self.src.clone(),
+                    }));
+                }
+                Some(_) => {}
+                None => arms_ty = Some((arm_ty, arm_span)),
+            }
+        }
+
+        if m.arms.is_empty() {
+            return Err(HirError::NonExhaustiveMatch(NonExhaustiveMatchError {
+                missing: String::from("`_`"),
+                span: SourceSpan::new(SourceOffset::from(m.span.start), m.span.end - m.span.start),
+                src: self.src.clone(),
+            }));
+        }
+        if !has_wildcard {
+            //Only booleans, enums & unions have a finite set of values to cover without `_`
+            let missing = match value_ty {
+                HirTy::Boolean(_) => ["true", "false"]
+                    .iter()
+                    .filter(|b| !covered.iter().any(|c| c == *b))
+                    .map(|b| format!("`{}`", b))
+                    .collect::<Vec<_>>(),
+                HirTy::Enum(e) => self.signature.enums[e.name]
+                    .variants
+                    .iter()
+                    .filter(|v| !covered.iter().any(|c| c == v.name))
+                    .map(|v| format!("`{}::{}`", e.name, v.name))
+                    .collect::<Vec<_>>(),
+                HirTy::Union(u) => self.signature.unions[u.name]
+                    .variants
+                    .iter()
+                    .filter(|v| !covered.iter().any(|c| c == v.name))
+                    .map(|v| format!("`{}::{}`", u.name, v.name))
+                    .collect::<Vec<_>>(),
+                _ => vec![String::from("`_`")],
+            };
+            if !missing.is_empty() {
+                return Err(HirError::NonExhaustiveMatch(NonExhaustiveMatchError {
+                    missing: missing.join(", "),
+                    span: SourceSpan::new(SourceOffset::from(m.span.start), m.span.end - m.span.start),
+                    src: self.src.clone(),
+                }));
+            }
+        }
+
+        let ty = arms_ty.map_or(self.arena.types().get_unit_ty(), |(ty, _)| ty);
+        m.ty = ty;
+        Ok(ty)
+    }
+
+    /// `Enum::Variant` & `Union::Variant(a, b)` patterns can only name a variant, unlike the expression
+    ///
+    /// The bindings of a union's variant are declared in the current scope
+    fn check_variant_pattern(
+        &mut self,
+        v: &mut expr::HirVariantPattern<'hir>,
+        value_ty: &'hir HirTy<'hir>,
+        alternatives: usize,
+    ) -> HirResult<&'hir HirTy<'hir>> {
+        let access = &mut v.access;
+        if let Some(union_signature) = self.signature.unions.get(access.target.name).copied() {
+            let variant = match union_signature.variant(access.field.name) {
+                Some((_, variant)) => variant,
+                None => {
+                    return Err(HirError::UnknownType(UnknownTypeError {
+                        name: format!("{}::{}", access.target.name, access.field.name),
+                        span: SourceSpan::new(SourceOffset::from(v.span.start), v.span.end - v.span.start),
+                        src: self.src.clone(),
+                    }));
+                }
+            };
+            //The scrutinee's type arguments give the type of the bindings
+            let ty = match value_ty {
+                HirTy::Union(u) if u.name == union_signature.name => value_ty,
+                _ => self.arena.types().get_union_ty(
+                    union_signature.name,
+                    union_signature.name_span.clone(),
+                    vec![self.arena.types().get_uninitialized_ty(); union_signature.generics.len()],
+                ),
+            };
+            let type_args = match ty {
+                HirTy::Union(u) => u.args.clone(),
+                _ => unreachable!(),
+            };
+            if let Some(bindings) = &mut v.bindings {
+                let reason = if alternatives > 1 {
+                    Some(String::from("bindings can't be used in a pattern with `|`"))
+                } else if bindings.len() != variant.fields.len() {
+                    Some(format!(
+                        "`{}::{}` has {} fields, found {} bindings",
+                        access.target.name,
+                        access.field.name,
+                        variant.fields.len(),
+                        bindings.len()
+                    ))
+                } else {
+                    None
+                };
+                if let Some(reason) = reason {
+                    return Err(HirError::InvalidPattern(InvalidPatternError {
+                        reason,
+                        span: SourceSpan::new(SourceOffset::from(v.span.start), v.span.end - v.span.start),
+                        src: self.src.clone(),
+                    }));
+                }
+                for (binding, field) in bindings.iter_mut().zip(variant.fields.iter()) {
+                    let field_ty = self.substitute(field.ty, &union_signature.generics, &type_args)?;
+                    binding.ty = field_ty;
+                    if binding.name == "_" {
+                        continue;
+                    }
+                    self.context_functions
+                        .last_mut()
+                        .unwrap()
+                        .get_mut(self.current_func_name.unwrap())
+                        .unwrap()
+                        .insert(
+                            binding.name,
+                            ContextVariable {
+                                _name: binding.name,
+                                name_span: binding.span.clone(),
+                                ty: field_ty,
+                                ty_span: binding.span.clone(),
+                                is_mut: false,
+                            },
+                        );
+                    self.resolved.push(ResolvedVariable {
+                        span: binding.span.clone(),
+                        name_span: binding.span.clone(),
+                        ty: field_ty,
+                    });
+                }
+            }
+            access.target.ty = ty;
+            access.field.ty = ty;
+            access.ty = ty;
+            return Ok(ty);
+        }
+        let enum_signature = match self.signature.enums.get(access.target.name) {
+            Some(e) if e.variant(access.field.name).is_some() => *e,
+            enum_signature => {
+                let name = match enum_signature {
+                    Some(_) => format!("{}::{}", access.target.name, access.field.name),
+                    None => access.target.name.to_string(),
+                };
+                return Err(HirError::UnknownType(UnknownTypeError {
+                    name,
+                    span: SourceSpan::new(SourceOffset::from(v.span.start), v.span.end - v.span.start),
+                    src: self.src.clone(),
+                }));
+            }
+        };
+        if v.bindings.is_some() {
+            return Err(HirError::InvalidPattern(InvalidPatternError {
+                reason: format!("`{}::{}` has no payload to bind", access.target.name, access.field.name),
+                span: SourceSpan::new(SourceOffset::from(v.span.start), v.span.end - v.span.start),
+                src: self.src.clone(),
+            }));
+        }
+        let ty = self.arena.types().get_enum_ty(enum_signature.name, enum_signature.name_span.clone());
+        access.target.ty = ty;
+        access.field.ty = ty;
+        access.ty = ty;
+        Ok(ty)
+    }
+
+    /// `Union::Variant` or `Union::Variant(args)`, the type arguments are inferred from the fields
+    fn check_union_variant(
+        &mut self,
+        access: &mut HirStaticAccessExpr<'hir>,
+        args: &mut [HirExpr<'hir>],
+    ) -> HirResult<&'hir HirTy<'hir>> {
+        let union_signature = self.signature.unions[access.target.name];
+        let variant = match union_signature.variant(access.field.name) {
+            Some((_, variant)) => variant,
+            None => {
+                return Err(HirError::UnknownType(UnknownTypeError {
+                    name: format!("{}::{}", access.target.name, access.field.name),
+                    span: SourceSpan::new(SourceOffset::from(access.span.start), access.span.end - access.span.start),
+                    src: self.src.clone(),
+                }));
+            }
+        };
+        if variant.fields.len() != args.len() {
+            let fields = variant.fields.iter().map(|f| format!("{}", f.ty)).collect::<Vec<_>>();
+            return Err(HirError::FunctionTypeMismatch(FunctionTypeMismatchError {
+                expected_ty: format!("{}::{}({})", union_signature.name, variant.name, fields.join(", ")),
+                span: SourceSpan::new(SourceOffset::from(access.span.start), access.span.end - access.span.start),
+                src: self.src.clone(),
+            }));
+        }
+
+        let mut bound = vec![None; union_signature.generics.len()];
+        let mut args_ty = Vec::new();
+        for (field, arg) in variant.fields.iter().zip(args.iter_mut()) {
+            let arg_ty = self.check_expr(arg)?;
+            self.infer_type_args(field.ty, arg_ty, &union_signature.generics, &mut bound);
+            args_ty.push(arg_ty);
+        }
+        let type_args = bound
+            .into_iter()
+            .map(|b| b.unwrap_or(self.arena.types().get_uninitialized_ty()))
+            .collect::<Vec<_>>();
+        for ((field, arg), arg_ty) in variant.fields.iter().zip(args.iter()).zip(args_ty) {
+            let field_ty = self.substitute(field.ty, &union_signature.generics, &type_args)?;
+            if !field_ty.accepts(arg_ty) {
+                return Err(HirError::TypeMismatch(TypeMismatchError {
+                    actual_type: format!("{}", arg_ty),
+                    actual_loc: SourceSpan::new(SourceOffset::from(arg.span().start), arg.span().end - arg.span().start),
+                    expected_type: format!("{}", field_ty),
+                    expected_loc: SourceSpan::new(
+                        SourceOffset::from(access.span.start),
+                        access.span.end - access.span.start,
+                    ),
+                    src: self.src.clone(),
+                }));
+            }
+        }
+
+        let ty = self.arena.types().get_union_ty(union_signature.name, union_signature.name_span.clone(), type_args);
+        access.target.ty = ty;
+        access.field.ty = ty;
+        access.ty = ty;
+        Ok(ty)
+    }
+
     fn check_extern_fn(&mut self, name: &'hir str, expr: &mut HirFunctionCallExpr<'hir>, signature: &'hir HirFunctionSignature<'hir>) -> HirResult<&'hir HirTy<'hir>> {
         let args_ty = expr
             .args
@@ -1223,6 +1658,11 @@ impl<'hir> TypeChecker<'hir> {
             .get(i.name)
         {
             i.ty = ctx_var.ty;
+            self.resolved.push(ResolvedVariable {
+                span: i.span.clone(),
+                name_span: ctx_var.name_span.clone(),
+                ty: ctx_var.ty,
+            });
             Ok(ctx_var)
         } else {
             Err(HirError::UnknownType(UnknownTypeError {
@@ -1236,3 +1676,121 @@ impl<'hir> TypeChecker<'hir> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::atlas_c::atlas_hir::error::{HirError, HirResult};
+    use crate::atlas_c::atlas_hir::HirModule;
+    use crate::engine::{AtlasError, Engine};
+
+    /// Parse, lower & type-check `source`
+    #[allow(clippy::result_large_err)]
+    fn check(source: &str) -> HirResult<()> {
+        checked(source, |_| ())
+    }
+
+    /// Parse, lower & type-check `source`, then hand the checked module to `then`
+    #[allow(clippy::result_large_err)]
+    fn checked<T>(source: &str, then: impl for<'hir> FnOnce(HirModule<'hir>) -> T) -> HirResult<T> {
+        match Engine::new().analyse("test.atlas", source, |hir| Ok(then(hir))) {
+            Ok(value) => Ok(value),
+            Err(AtlasError::Hir(e)) => Err(e),
+            Err(e) => panic!("expected a type error, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn enums_are_only_compared_for_equality() {
+        let equal = "enum A { X, Y }\nfunc f(a: A) -> bool { return a == A::X; }\nfunc g(a: A) -> bool { return a != A::Y; }";
+        assert!(check(equal).is_ok());
+        let ordered = "enum A { X, Y }\nfunc f() -> bool { return A::X < A::Y; }";
+        assert!(matches!(check(ordered), Err(HirError::UnsupportedExpr(_))));
+        let unknown = "enum A { X }\nfunc f() -> bool { return A::X == A::Z; }";
+        assert!(matches!(check(unknown), Err(HirError::UnknownType(_))));
+    }
+
+    #[test]
+    fn match_must_be_exhaustive() {
+        let every_variant = "enum A { X, Y }\nfunc f(a: A) -> int64 { return match a { A::X => 1, A::Y => 2 }; }";
+        assert!(check(every_variant).is_ok());
+        let missing = "enum A { X, Y }\nfunc f(a: A) -> int64 { return match a { A::X => 1 }; }";
+        assert!(matches!(check(missing), Err(HirError::NonExhaustiveMatch(_))));
+        let no_wildcard = "func f(n: int64) -> int64 { return match n { 1 => 1, 2 => 2 }; }";
+        assert!(matches!(check(no_wildcard), Err(HirError::NonExhaustiveMatch(_))));
+        let both_bools = "func f(b: bool) -> int64 { return match b { true => 1, false => 2 }; }";
+        assert!(check(both_bools).is_ok());
+    }
+
+    #[test]
+    fn match_arms_have_the_same_type() {
+        let mixed = "func f(n: int64) -> int64 { return match n { 1 => 1, _ => true }; }";
+        assert!(matches!(check(mixed), Err(HirError::TypeMismatch(_))));
+        let pattern = "func f(n: int64) -> int64 { return match n { \"one\" => 1, _ => 2 }; }";
+        assert!(matches!(check(pattern), Err(HirError::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn union_patterns_match_the_variants() {
+        let bindings = "func f(o: Option<int64>) -> int64 { return match o { Option::Some(a, b) => a, Option::None => 0 }; }";
+        assert!(matches!(check(bindings), Err(HirError::InvalidPattern(_))));
+        let missing = "func f(o: Option<int64>) -> int64 { return match o { Option::None => 0 }; }";
+        assert!(matches!(check(missing), Err(HirError::NonExhaustiveMatch(_))));
+        let field = "func f() -> Option<int64> { return Option::Some(\"one\"); }";
+        assert!(matches!(check(field), Err(HirError::TypeMismatch(_))));
+        let binding_ty = "func f(o: Option<str>) -> int64 { return match o { Option::Some(s) => s, Option::None => 0 }; }";
+        assert!(matches!(check(binding_ty), Err(HirError::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn generic_type_arguments_are_checked() {
+        let mismatch = "func id<T>(a: T, b: T) -> T { return a; } func f() -> int64 { return id(1, \"a\"); }";
+        assert!(matches!(check(mismatch), Err(HirError::TypeMismatch(_))));
+        let uninferred = "class Box<T> { public: items: [T]; Box(n: int64) { self.items = new [T; n]; } } func f() { let b = new Box(4); }";
+        assert!(matches!(check(uninferred), Err(HirError::CannotInferTypeArgument(_))));
+        let arity = "class Box<T> { public: item: T; } func f(b: Box<int64, str>) {}";
+        assert!(matches!(check(arity), Err(HirError::TypeArgumentCount(_))));
+    }
+
+    #[test]
+    fn concepts_are_checked() {
+        let shape = "concept Shape { func area(self) -> int64; }";
+        let square = format!(
+            "{} class Sq : impl Shape {{ public: side: int64; func area(self) -> int64 {{ return self.side; }} }} func f() -> int64 {{ let s = new Sq(1); return s.area(); }}",
+            shape
+        );
+        assert!(check(&square).is_ok());
+        let missing = format!("{} class Sq : impl Shape {{ public: side: int64; }} func f() {{ let s = new Sq(1); }}", shape);
+        assert!(matches!(check(&missing), Err(HirError::MissingConceptMethod(_))));
+        let mismatch = format!(
+            "{} class Sq : impl Shape {{ public: side: int64; func area(self) -> str {{ return \"a\"; }} }} func f() {{ let s = new Sq(1); }}",
+            shape
+        );
+        assert!(matches!(check(&mismatch), Err(HirError::ConceptMethodMismatch(_))));
+        let unsatisfied = format!("{} func f<S: impl Shape>(s: S) {{}} func g() {{ f(3); }}", shape);
+        assert!(matches!(check(&unsatisfied), Err(HirError::UnsatisfiedConstraint(_))));
+    }
+
+    #[test]
+    fn each_instance_is_its_own_function() {
+        let source = r#"func id<T>(a: T) -> T {
+    return a;
+}
+func f() -> int64 {
+    let s = id("a");
+    return id(1) + id(2);
+}"#;
+        let functions = checked(source, |hir| {
+            let mut functions = hir
+                .body
+                .functions
+                .iter()
+                .filter(|(name, _)| name.starts_with("id<"))
+                .map(|(name, f)| format!("{}: {} -> {}", name, f.signature.params[0].ty, f.signature.return_ty))
+                .collect::<Vec<_>>();
+            functions.sort();
+            functions
+        })
+        .unwrap();
+        assert_eq!(functions, ["id<int64>: int64 -> int64", "id<str>: str -> str"]);
+    }
+}
@@ -0,0 +1,475 @@
+//! Generic functions & classes are templates, each set of type arguments they're used with gets its own instance.
+//!
+//! An instance is named after the type arguments (e.g. `Vec<int64>` or `max<float64>`), so it's checked & compiled
+//! like any other function or class.
+
+use super::TypeChecker;
+use crate::atlas_c::atlas_hir::error::{
+    CannotInferTypeArgumentError, HirError, HirResult, TypeArgumentCountError, TypeMismatchError, UnknownTypeError,
+};
+use crate::atlas_c::atlas_hir::expr::{HirExpr, HirFunctionCallExpr};
+use crate::atlas_c::atlas_hir::item::{HirClass, HirFunction};
+use crate::atlas_c::atlas_hir::signature::{HirFunctionParameterSignature, HirFunctionSignature};
+use crate::atlas_c::atlas_hir::stmt::{HirBlock, HirStatement};
+use crate::atlas_c::atlas_hir::ty::HirTy;
+use crate::atlas_c::atlas_hir::HirModule;
+use logos::Span;
+use miette::{SourceOffset, SourceSpan};
+
+impl<'hir> TypeChecker<'hir> {
+    /// Take the generic functions & classes out of the module, only their instances are checked & compiled
+    pub(super) fn take_templates(&mut self, hir: &mut HirModule<'hir>) {
+        let (templates, functions) = std::mem::take(&mut hir.body.functions)
+            .into_iter()
+            .partition(|(_, f)| f.signature.generics.is_some());
+        hir.body.functions = functions;
+        self.generic_functions = templates;
+        let (templates, classes) = std::mem::take(&mut hir.body.classes)
+            .into_iter()
+            .partition(|(_, c)| !c.signature.generics.is_empty());
+        hir.body.classes = classes;
+        self.generic_classes = templates;
+    }
+
+    /// Check the instances used so far, they can use other instances in turn
+    pub(super) fn check_instances(&mut self, hir: &mut HirModule<'hir>) -> HirResult<()> {
+        loop {
+            if let Some(mut func) = self.pending_functions.pop() {
+                self.current_class_name = None;
+                self.current_func_name = Some(func.name);
+                self.check_func(&mut func)?;
+                hir.body.functions.insert(func.name, func);
+            } else if let Some(mut class) = self.pending_classes.pop() {
+                self.current_class_name = Some(class.name);
+                self.check_class(&mut class)?;
+                hir.body.classes.insert(class.name, class);
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// `foo(a, b)` where `foo` is generic, the type arguments are inferred from the arguments
+    pub(super) fn check_generic_call(
+        &mut self,
+        name: &'hir str,
+        expr: &mut HirFunctionCallExpr<'hir>,
+        signature: &'hir HirFunctionSignature<'hir>,
+    ) -> HirResult<&'hir HirTy<'hir>> {
+        let generics = signature.generics.iter().flatten().map(|g| g.name).collect::<Vec<_>>();
+        let args_ty = expr.args.iter_mut().map(|a| self.check_expr(a)).collect::<HirResult<Vec<_>>>()?;
+        let mut bound = vec![None; generics.len()];
+        for (param, arg_ty) in signature.params.iter().zip(args_ty.iter()) {
+            self.infer_type_args(param.ty, arg_ty, &generics, &mut bound);
+        }
+        let type_args = self.bound_type_args(name, &generics, bound, &expr.span)?;
+        let (instance_name, instance) = self.instantiate_function(name, type_args, &expr.span)?;
+
+        for ((param, arg), arg_ty) in instance.params.iter().zip(expr.args.iter()).zip(args_ty) {
+            if !param.ty.accepts(arg_ty) {
+                return Err(HirError::TypeMismatch(TypeMismatchError {
+                    actual_type: format!("{}", arg_ty),
+                    actual_loc: SourceSpan::new(SourceOffset::from(arg.span().start), arg.span().end - arg.span().start),
+                    expected_type: format!("{}", param.ty),
+                    expected_loc: SourceSpan::new(SourceOffset::from(param.span.start), param.span.end - param.span.start),
+                    src: self.src.clone(),
+                }));
+            }
+        }
+        if let HirExpr::Ident(i) = expr.callee.as_mut() {
+            i.name = instance_name;
+        }
+        expr.ty = instance.return_ty;
+        Ok(instance.return_ty)
+    }
+
+    /// The instance of the generic class `name` whose `params` accept `args_ty`
+    ///
+    /// Used by `new Vec(...)` & `Vec::method(...)` when no type arguments are given
+    pub(super) fn infer_class_instance(
+        &mut self,
+        name: &'hir str,
+        params: &[&'hir HirFunctionParameterSignature<'hir>],
+        args_ty: &[&'hir HirTy<'hir>],
+        span: &Span,
+    ) -> HirResult<&'hir HirTy<'hir>> {
+        let generics = self.generic_classes[name].signature.generics.iter().map(|g| g.name).collect::<Vec<_>>();
+        let mut bound = vec![None; generics.len()];
+        for (param, arg_ty) in params.iter().zip(args_ty.iter()) {
+            self.infer_type_args(param.ty, arg_ty, &generics, &mut bound);
+        }
+        let type_args = self.bound_type_args(name, &generics, bound, span)?;
+        self.instantiate_class(name, type_args, span)
+    }
+
+    fn bound_type_args(
+        &self,
+        item: &str,
+        generics: &[&'hir str],
+        bound: Vec<Option<&'hir HirTy<'hir>>>,
+        span: &Span,
+    ) -> HirResult<Vec<&'hir HirTy<'hir>>> {
+        bound
+            .into_iter()
+            .zip(generics.iter())
+            .map(|(ty, name)| {
+                ty.ok_or_else(|| {
+                    HirError::CannotInferTypeArgument(CannotInferTypeArgumentError {
+                        name: name.to_string(),
+                        item: item.to_string(),
+                        span: SourceSpan::new(SourceOffset::from(span.start), span.end - span.start),
+                        src: self.src.clone(),
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Bind the type parameters in `ty` to what they are in `given_ty`, the first binding wins
+    pub(super) fn infer_type_args(
+        &self,
+        ty: &'hir HirTy<'hir>,
+        given_ty: &'hir HirTy<'hir>,
+        generics: &[&'hir str],
+        bound: &mut [Option<&'hir HirTy<'hir>>],
+    ) {
+        match (ty, given_ty) {
+            (_, HirTy::Uninitialized(_)) => {}
+            (HirTy::Named(n), _) => {
+                if let Some(i) = generics.iter().position(|g| *g == n.name) {
+                    bound[i].get_or_insert(given_ty);
+                }
+            }
+            (HirTy::List(l1), HirTy::List(l2)) => self.infer_type_args(l1.inner, l2.inner, generics, bound),
+            (HirTy::Union(u1), HirTy::Union(u2)) if u1.name == u2.name => {
+                for (a1, a2) in u1.args.iter().zip(u2.args.iter()) {
+                    self.infer_type_args(a1, a2, generics, bound);
+                }
+            }
+            //The given type is an instance, e.g. `Vec<int64>` given for a `Vec<T>`
+            (HirTy::Generic(g), HirTy::Named(n)) => {
+                if let Some((template, args)) = self.instances.get(n.name) {
+                    if *template == g.name {
+                        for (a1, a2) in g.args.iter().zip(args.iter()) {
+                            self.infer_type_args(a1, a2, generics, bound);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Replace the type parameters in `ty` by their type arguments, generic classes become their instance
+    pub(super) fn substitute(
+        &mut self,
+        ty: &'hir HirTy<'hir>,
+        generics: &[&'hir str],
+        args: &[&'hir HirTy<'hir>],
+    ) -> HirResult<&'hir HirTy<'hir>> {
+        match ty {
+            HirTy::Named(n) => match generics.iter().position(|g| *g == n.name) {
+                Some(i) => Ok(args[i]),
+                None => match self.generic_classes.get(n.name) {
+                    Some(template) => Err(HirError::TypeArgumentCount(TypeArgumentCountError {
+                        name: n.name.to_string(),
+                        expected: template.signature.generics.len(),
+                        found: 0,
+                        span: SourceSpan::new(SourceOffset::from(n.span.start), n.span.end - n.span.start),
+                        src: self.src.clone(),
+                    })),
+                    None => Ok(ty),
+                },
+            },
+            HirTy::List(l) => {
+                let inner = self.substitute(l.inner, generics, args)?;
+                Ok(self.arena.types().get_list_ty(inner))
+            }
+            HirTy::Union(u) => {
+                let union_args = u.args.iter().map(|a| self.substitute(a, generics, args)).collect::<HirResult<_>>()?;
+                Ok(self.arena.types().get_union_ty(u.name, u.span.clone(), union_args))
+            }
+            HirTy::Generic(g) => {
+                let type_args = g.args.iter().map(|a| self.substitute(a, generics, args)).collect::<HirResult<Vec<_>>>()?;
+                //e.g. a union's field when the union's type arguments aren't known yet
+                if !type_args.iter().all(|a| Self::is_known(a)) {
+                    return Ok(self.arena.types().get_generic_ty(g.name, g.span.clone(), type_args));
+                }
+                self.instantiate_class(g.name, type_args, &g.span)
+            }
+            _ => Ok(ty),
+        }
+    }
+
+    fn is_known(ty: &HirTy) -> bool {
+        match ty {
+            HirTy::Uninitialized(_) => false,
+            HirTy::List(l) => Self::is_known(l.inner),
+            HirTy::Union(u) => u.args.iter().all(|a| Self::is_known(a)),
+            HirTy::Generic(g) => g.args.iter().all(|a| Self::is_known(a)),
+            _ => true,
+        }
+    }
+
+    /// The named type of `name<args>`, the instance is created the first time
+    fn instantiate_class(
+        &mut self,
+        name: &'hir str,
+        args: Vec<&'hir HirTy<'hir>>,
+        span: &Span,
+    ) -> HirResult<&'hir HirTy<'hir>> {
+        let source_span = SourceSpan::new(SourceOffset::from(span.start), span.end - span.start);
+        let (expected, name_span) = match self.generic_classes.get(name) {
+            Some(template) => (template.signature.generics.len(), template.name_span.clone()),
+            None if self.signature.classes.contains_key(name) => (0, Span::default()),
+            None => {
+                return Err(HirError::UnknownType(UnknownTypeError {
+                    name: name.to_string(),
+                    span: source_span,
+                    src: self.src.clone(),
+                }))
+            }
+        };
+        if expected != args.len() {
+            return Err(HirError::TypeArgumentCount(TypeArgumentCountError {
+                name: name.to_string(),
+                expected,
+                found: args.len(),
+                span: source_span,
+                src: self.src.clone(),
+            }));
+        }
+        if let Some(template) = self.generic_classes.get(name) {
+            let params = template.signature.generics.clone();
+            self.check_constraints(name, &params, &args, span)?;
+        }
+
+        let generic_ty = self.arena.types().get_generic_ty(name, span.clone(), args.clone());
+        let instance_name = self.arena.names().get(&generic_ty.to_string());
+        let ty = self.arena.types().get_named_ty(instance_name, name_span);
+        //Recorded before its members are substituted, as they can refer to the instance itself
+        if self.instances.insert(instance_name, (name, args.clone())).is_some() {
+            return Ok(ty);
+        }
+        let mut class = self.generic_classes[name].clone();
+        let generics = class.signature.generics.iter().map(|g| g.name).collect::<Vec<_>>();
+        class.name = instance_name;
+        self.substitute_class(&mut class, &generics, &args)?;
+        self.pending_classes.push(class);
+        Ok(ty)
+    }
+
+    /// The name & signature of `name<args>`, the instance is created the first time
+    fn instantiate_function(
+        &mut self,
+        name: &'hir str,
+        args: Vec<&'hir HirTy<'hir>>,
+        span: &Span,
+    ) -> HirResult<(&'hir str, &'hir HirFunctionSignature<'hir>)> {
+        let type_args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ");
+        let instance_name = self.arena.names().get(&format!("{}<{}>", name, type_args));
+        if let Some(signature) = self.signature.functions.get(instance_name) {
+            return Ok((instance_name, *signature));
+        }
+        let mut function = match self.generic_functions.get(name) {
+            Some(template) => template.clone(),
+            //Only the signature of an imported function is known
+            None => {
+                return Err(HirError::UnknownType(UnknownTypeError {
+                    name: name.to_string(),
+                    span: SourceSpan::new(SourceOffset::from(span.start), span.end - span.start),
+                    src: self.src.clone(),
+                }))
+            }
+        };
+        let params = function.signature.generics.clone().unwrap_or_default();
+        self.check_constraints(name, &params, &args, span)?;
+        let generics = params.iter().map(|g| g.name).collect::<Vec<_>>();
+        function.name = instance_name;
+        self.substitute_function(&mut function, &generics, &args)?;
+        let signature = function.signature;
+        self.pending_functions.push(function);
+        Ok((instance_name, signature))
+    }
+
+    /// Substitute the types in the function's signature & body, the new signature replaces the old one
+    ///
+    /// Non-generic functions go through it as well, for the generic classes they use
+    pub(super) fn substitute_function(
+        &mut self,
+        func: &mut HirFunction<'hir>,
+        generics: &[&'hir str],
+        args: &[&'hir HirTy<'hir>],
+    ) -> HirResult<()> {
+        let mut signature = func.signature.clone();
+        signature.params = self.substitute_params(&signature.params, generics, args)?;
+        signature.return_ty = self.substitute(signature.return_ty, generics, args)?;
+        signature.generics = None;
+        let signature = self.arena.intern(signature);
+        //Known before the body is substituted, the function can call itself
+        self.signature.functions.insert(func.name, signature);
+        func.signature = signature;
+        self.substitute_block(&mut func.body, generics, args)
+    }
+
+    /// Substitute the types in the class' members, the new signature replaces the old one
+    pub(super) fn substitute_class(
+        &mut self,
+        class: &mut HirClass<'hir>,
+        generics: &[&'hir str],
+        args: &[&'hir HirTy<'hir>],
+    ) -> HirResult<()> {
+        for field in class.fields.iter_mut() {
+            field.ty = self.substitute(field.ty, generics, args)?;
+        }
+        for method in class.methods.iter_mut() {
+            let mut signature = method.signature.clone();
+            signature.params = self.substitute_params(&signature.params, generics, args)?;
+            signature.return_ty = self.substitute(signature.return_ty, generics, args)?;
+            method.signature = self.arena.intern(signature);
+            self.substitute_block(&mut method.body, generics, args)?;
+        }
+        for constructor in [&mut class.constructor, &mut class.destructor] {
+            constructor.params = self.substitute_params(&constructor.params, generics, args)?;
+            self.substitute_block(&mut constructor.body, generics, args)?;
+        }
+
+        let mut signature = class.signature.clone();
+        signature.name = class.name;
+        signature.generics = Vec::new();
+        signature.concepts = class
+            .signature
+            .concepts
+            .iter()
+            .map(|c| self.substitute_constraint(c, generics, args))
+            .collect::<HirResult<_>>()?;
+        signature.methods = class.methods.iter().map(|m| (m.name, m.signature)).collect();
+        signature.fields = class.fields.iter().map(|f| (f.name, f.clone())).collect();
+        signature.constructor.params = class.constructor.params.clone();
+        signature.destructor.params = class.destructor.params.clone();
+        let signature = self.arena.intern(signature);
+        self.signature.classes.insert(class.name, signature);
+        class.signature = signature;
+        Ok(())
+    }
+
+    pub(super) fn substitute_params(
+        &mut self,
+        params: &[&'hir HirFunctionParameterSignature<'hir>],
+        generics: &[&'hir str],
+        args: &[&'hir HirTy<'hir>],
+    ) -> HirResult<Vec<&'hir HirFunctionParameterSignature<'hir>>> {
+        params
+            .iter()
+            .map(|p| {
+                let ty = self.substitute(p.ty, generics, args)?;
+                let param: &'hir HirFunctionParameterSignature<'hir> =
+                    self.arena.intern(HirFunctionParameterSignature { ty, ..(*p).clone() });
+                Ok(param)
+            })
+            .collect()
+    }
+
+    /// Only the types written in the code are substituted, the others are given by the type checker afterward
+    fn substitute_block(
+        &mut self,
+        block: &mut HirBlock<'hir>,
+        generics: &[&'hir str],
+        args: &[&'hir HirTy<'hir>],
+    ) -> HirResult<()> {
+        for stmt in block.statements.iter_mut() {
+            match stmt {
+                HirStatement::Expr(e) => self.substitute_expr(&mut e.expr, generics, args)?,
+                HirStatement::Return(r) => self.substitute_expr(&mut r.value, generics, args)?,
+                HirStatement::Let(l) | HirStatement::Const(l) => {
+                    if let Some(ty) = l.ty {
+                        l.ty = Some(self.substitute(ty, generics, args)?);
+                    }
+                    self.substitute_expr(&mut l.value, generics, args)?;
+                }
+                HirStatement::IfElse(i) => {
+                    self.substitute_expr(&mut i.condition, generics, args)?;
+                    self.substitute_block(&mut i.then_branch, generics, args)?;
+                    if let Some(else_branch) = &mut i.else_branch {
+                        self.substitute_block(else_branch, generics, args)?;
+                    }
+                }
+                HirStatement::While(w) => {
+                    self.substitute_expr(&mut w.condition, generics, args)?;
+                    self.substitute_block(&mut w.body, generics, args)?;
+                }
+                HirStatement::_Block(b) => self.substitute_block(b, generics, args)?,
+                HirStatement::Break(_) | HirStatement::Continue(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn substitute_expr(
+        &mut self,
+        expr: &mut HirExpr<'hir>,
+        generics: &[&'hir str],
+        args: &[&'hir HirTy<'hir>],
+    ) -> HirResult<()> {
+        match expr {
+            HirExpr::Casting(c) => {
+                c.ty = self.substitute(c.ty, generics, args)?;
+                self.substitute_expr(&mut c.expr, generics, args)
+            }
+            HirExpr::NewArray(a) => {
+                a.ty = self.substitute(a.ty, generics, args)?;
+                self.substitute_expr(&mut a.size, generics, args)
+            }
+            HirExpr::NewObj(obj) => {
+                //`new Vec(...)`, the type arguments are inferred when it's checked
+                let inferred = matches!(obj.ty, HirTy::Named(n) if self.generic_classes.contains_key(n.name));
+                if !inferred {
+                    obj.ty = self.substitute(obj.ty, generics, args)?;
+                }
+                for arg in obj.args.iter_mut() {
+                    self.substitute_expr(arg, generics, args)?;
+                }
+                Ok(())
+            }
+            HirExpr::Call(c) => {
+                self.substitute_expr(&mut c.callee, generics, args)?;
+                for arg in c.args.iter_mut() {
+                    self.substitute_expr(arg, generics, args)?;
+                }
+                Ok(())
+            }
+            HirExpr::Assign(a) => {
+                self.substitute_expr(&mut a.lhs, generics, args)?;
+                self.substitute_expr(&mut a.rhs, generics, args)
+            }
+            HirExpr::HirBinaryOp(b) => {
+                self.substitute_expr(&mut b.lhs, generics, args)?;
+                self.substitute_expr(&mut b.rhs, generics, args)
+            }
+            HirExpr::Indexing(i) => {
+                self.substitute_expr(&mut i.target, generics, args)?;
+                self.substitute_expr(&mut i.index, generics, args)
+            }
+            HirExpr::ListLiteral(l) => {
+                for item in l.items.iter_mut() {
+                    self.substitute_expr(item, generics, args)?;
+                }
+                Ok(())
+            }
+            HirExpr::Unary(u) => self.substitute_expr(&mut u.expr, generics, args),
+            HirExpr::Delete(d) => self.substitute_expr(&mut d.expr, generics, args),
+            HirExpr::FieldAccess(f) => self.substitute_expr(&mut f.target, generics, args),
+            HirExpr::Match(m) => {
+                self.substitute_expr(&mut m.value, generics, args)?;
+                for arm in m.arms.iter_mut() {
+                    self.substitute_block(&mut arm.body, generics, args)?;
+                    if let Some(value) = &mut arm.value {
+                        self.substitute_expr(value, generics, args)?;
+                    }
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
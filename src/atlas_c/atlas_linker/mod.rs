@@ -0,0 +1,230 @@
+//! Link step run after [`crate::atlas_c::atlas_codegen::CodeGenUnit::compile`].
+//!
+//! The codegen refers to functions, methods, classes & native functions by name.
+//! The linker resolves every one of them once, so the VM never has to search by name at runtime:
+//! - `FunctionCall`, `MethodCall` & `StaticCall` become `LinkedCall` (index in `Program::labels`)
+//! - `NewObj` becomes `LinkedNewObj` (index in `ConstantPool::class_pool`)
+//! - `ExternCall` becomes `LinkedNativeCall` (index in `ConstantPool::native_pool`)
+//!
+//! Native functions come from the standard libraries & the [`NativeModule`]s registered by the host.
+
+use std::collections::{HashMap, HashSet};
+
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::atlas_c::atlas_codegen::arena::CodeGenArena;
+use crate::atlas_vm::libraries;
+use crate::atlas_vm::native::NativeModule;
+use crate::atlas_vm::runtime::instruction::{Instruction, Label, Program};
+
+#[derive(Error, Diagnostic, Debug)]
+pub enum LinkError {
+    #[error("unresolved {kind} `{name}` (referenced in `{label}`)")]
+    #[diagnostic(
+        code(link::unresolved_symbol),
+        help("Make sure `{name}` is defined, or that the library declaring it is imported")
+    )]
+    UnresolvedSymbol {
+        kind: SymbolKind,
+        name: String,
+        label: String,
+    },
+    #[error("unknown library `{0}`")]
+    #[diagnostic(
+        code(link::unknown_library),
+        help("Only the standard libraries & the native modules registered by the host can be imported")
+    )]
+    UnknownLibrary(String),
+}
+
+pub type LinkResult<T> = Result<T, LinkError>;
+
+#[derive(Debug)]
+pub enum SymbolKind {
+    Function,
+    Method,
+    Class,
+    Native,
+}
+
+impl std::fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SymbolKind::Function => write!(f, "function"),
+            SymbolKind::Method => write!(f, "method"),
+            SymbolKind::Class => write!(f, "class"),
+            SymbolKind::Native => write!(f, "native function"),
+        }
+    }
+}
+
+/// Resolve every symbolic reference of `program`.
+///
+/// The linked label bodies & the native pool are allocated in `arena`.
+/// `modules` provides the natives of the imported libraries that aren't standard ones.
+pub fn link<'run>(
+    program: &Program<'run>,
+    arena: &CodeGenArena<'run>,
+    modules: &[NativeModule],
+) -> LinkResult<Program<'run>> {
+    let labels = program
+        .labels
+        .iter()
+        .enumerate()
+        .map(|(i, label)| (label.name, i))
+        .collect::<HashMap<_, _>>();
+    let classes = program
+        .global
+        .class_pool
+        .iter()
+        .enumerate()
+        .map(|(i, class)| (class.name, i))
+        .collect::<HashMap<_, _>>();
+
+    let mut available_natives = HashSet::new();
+    for lib in program.libraries.iter() {
+        let unknown = || LinkError::UnknownLibrary(lib.name.clone());
+        if lib.is_std {
+            for (name, _) in libraries::std_library(&lib.name).ok_or_else(unknown)? {
+                available_natives.insert(name.to_string());
+            }
+        } else {
+            let module = modules
+                .iter()
+                .find(|module| module.name() == lib.name)
+                .ok_or_else(unknown)?;
+            for function in module.functions() {
+                available_natives.insert(function.name.clone());
+            }
+        }
+    }
+    let mut native_pool: Vec<&'run str> = program.global.native_pool.to_vec();
+    let mut natives: HashMap<&'run str, usize> = HashMap::new();
+    for (i, name) in native_pool.iter().enumerate() {
+        natives.entry(*name).or_insert(i);
+    }
+
+    let mut linked_labels = Vec::with_capacity(program.labels.len());
+    for label in program.labels.iter() {
+        let unresolved = |kind, name: &str| LinkError::UnresolvedSymbol {
+            kind,
+            name: name.to_string(),
+            label: label.name.to_string(),
+        };
+        let mut body = Vec::with_capacity(label.body.len());
+        for instr in label.body.iter() {
+            let linked = match *instr {
+                Instruction::FunctionCall {
+                    function_name,
+                    nb_args,
+                } => Instruction::LinkedCall {
+                    label: *labels
+                        .get(function_name)
+                        .ok_or_else(|| unresolved(SymbolKind::Function, function_name))?,
+                    nb_args,
+                },
+                Instruction::MethodCall {
+                    method_name,
+                    nb_args,
+                }
+                | Instruction::StaticCall {
+                    method_name,
+                    nb_args,
+                } => Instruction::LinkedCall {
+                    label: *labels
+                        .get(method_name)
+                        .ok_or_else(|| unresolved(SymbolKind::Method, method_name))?,
+                    nb_args,
+                },
+                Instruction::NewObj { class_name } => Instruction::LinkedNewObj {
+                    class: *classes
+                        .get(class_name)
+                        .ok_or_else(|| unresolved(SymbolKind::Class, class_name))?,
+                },
+                Instruction::ExternCall {
+                    function_name,
+                    nb_args,
+                } => {
+                    if !available_natives.contains(function_name) {
+                        return Err(unresolved(SymbolKind::Native, function_name));
+                    }
+                    let native = *natives.entry(function_name).or_insert_with(|| {
+                        native_pool.push(function_name);
+                        native_pool.len() - 1
+                    });
+                    Instruction::LinkedNativeCall { native, nb_args }
+                }
+                ref instr => instr.clone(),
+            };
+            body.push(linked);
+        }
+        linked_labels.push(Label {
+            name: label.name,
+            position: label.position,
+            body: arena.alloc(body),
+            spans: label.spans,
+            locals: label.locals,
+        });
+    }
+
+    let mut linked = program.clone();
+    linked.labels = linked_labels;
+    linked.global.native_pool = arena.alloc(native_pool);
+    Ok(linked)
+}
+
+#[cfg(test)]
+mod tests {
+    use bumpalo::Bump;
+
+    use super::{link, LinkError, SymbolKind};
+    use crate::atlas_c::atlas_codegen::arena::CodeGenArena;
+    use crate::atlas_vm::runtime::instruction::{ImportedLibrary, Instruction, Label, Program};
+
+    /// Program made of a `main` label running `body`
+    fn program<'run>(arena: &CodeGenArena<'run>, body: Vec<Instruction<'run>>) -> Program<'run> {
+        let mut program = Program::new();
+        program.labels.push(Label {
+            name: "main",
+            position: 0,
+            body: arena.alloc(body),
+            spans: &[],
+            locals: &[],
+        });
+        program
+    }
+
+    #[test]
+    fn unresolved_function() {
+        let bump = Bump::new();
+        let arena = CodeGenArena::new(&bump);
+        let call = Instruction::FunctionCall { function_name: "missing", nb_args: 0 };
+        let res = link(&program(&arena, vec![call]), &arena, &[]);
+        assert!(matches!(
+            res,
+            Err(LinkError::UnresolvedSymbol { kind: SymbolKind::Function, name, label }) if name == "missing" && label == "main"
+        ));
+    }
+
+    #[test]
+    fn unknown_class() {
+        let bump = Bump::new();
+        let arena = CodeGenArena::new(&bump);
+        let new = Instruction::NewObj { class_name: "Missing" };
+        let res = link(&program(&arena, vec![new]), &arena, &[]);
+        assert!(matches!(
+            res,
+            Err(LinkError::UnresolvedSymbol { kind: SymbolKind::Class, name, .. }) if name == "Missing"
+        ));
+    }
+
+    #[test]
+    fn unknown_library() {
+        let bump = Bump::new();
+        let arena = CodeGenArena::new(&bump);
+        let mut program = program(&arena, vec![Instruction::Halt]);
+        program.libraries.push(ImportedLibrary { name: String::from("missing"), is_std: false });
+        assert!(matches!(link(&program, &arena, &[]), Err(LinkError::UnknownLibrary(name)) if name == "missing"));
+    }
+}
@@ -2,4 +2,5 @@ pub mod atlas_asm;
 pub mod atlas_codegen;
 pub mod atlas_frontend;
 pub mod atlas_hir;
+pub mod atlas_linker;
 pub mod atlas_macro;
@@ -0,0 +1,31 @@
+//! Hooks letting a debugger or a tracer follow a VM as it runs.
+//!
+//! They are only called by [`Atlas77VM::run`] & [`Atlas77VM::invoke`],
+//! [`Atlas77VM::run_assembly`] calls [`DebugHook::on_call`] & [`DebugHook::on_return`] only.
+
+use crate::atlas_vm::errors::RuntimeError;
+use crate::atlas_vm::memory::vm_data::VMData;
+use crate::atlas_vm::{Atlas77VM, RuntimeResult};
+
+/// Every method does nothing by default
+pub trait DebugHook<'run> {
+    /// Called before the instruction at `vm.pc` is executed
+    ///
+    /// Returning an error stops the run with it
+    fn before_instruction(&mut self, _vm: &Atlas77VM<'run>) -> RuntimeResult<()> {
+        Ok(())
+    }
+    /// Called once the frame of the callee is the last of `vm.frames()`
+    fn on_call(&mut self, _vm: &Atlas77VM<'run>) {}
+    /// Called while the frame of the returning function is still the last of `vm.frames()`
+    fn on_return(&mut self, _vm: &Atlas77VM<'run>, _value: VMData) {}
+    /// Called where `error` is raised, before the frames are unwound
+    fn on_error(&mut self, _vm: &Atlas77VM<'run>, _error: &RuntimeError) {}
+}
+
+/// Local of a frame, see [`Atlas77VM::locals`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Local<'run> {
+    pub name: &'run str,
+    pub value: VMData,
+}
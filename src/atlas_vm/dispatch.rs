@@ -0,0 +1,271 @@
+//! Dispatch loop running the byte encoding produced by [`crate::atlas_c::atlas_asm::assemble`]
+
+use std::time::Instant;
+
+use crate::atlas_c::atlas_asm::{read_imm, Assembly, OpCode};
+use crate::atlas_vm::errors::RuntimeError;
+use crate::atlas_vm::memory::vm_data::VMData;
+use crate::atlas_vm::runtime::instruction::Type;
+use crate::atlas_vm::{Atlas77VM, RuntimeResult};
+
+impl<'run> Atlas77VM<'run> {
+    /// Run an assembled version of [`Atlas77VM::program`].
+    ///
+    /// Contrary to [`Atlas77VM::run`], `pc` is a byte position in `asm.code`.
+    pub fn run_assembly(&mut self, asm: &Assembly<'run>) -> RuntimeResult<VMData> {
+        let entry_point = asm.entry_point.ok_or_else(|| {
+            RuntimeError::EntryPointNotFound(self.program.entry_point.to_string())
+        })?;
+        self.stack.extends(
+            &asm.function_pool
+                .iter()
+                .map(|t| VMData::new_fn_ptr(*t))
+                .collect::<Vec<_>>(),
+        )?;
+        let code = asm.code.as_slice();
+        let entry = asm.label_index_at(entry_point).ok_or(RuntimeError::InvalidOperation)?;
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        self.check_interrupts()?;
+        self.call(entry, entry_point, 0, code.len());
+
+        let res = self.dispatch(asm);
+        if res.is_err() {
+            self.restore_instruction_positions(asm);
+        }
+        self.exit_value(res)
+    }
+
+    /// Turn the byte positions of `pc` & of the call frames back into instruction indices,
+    /// so [`Atlas77VM::backtrace`] finds the spans of the failing run
+    fn restore_instruction_positions(&mut self, asm: &Assembly<'run>) {
+        let code = asm.code.as_slice();
+        let mut positions = Vec::new();
+        let mut pos = 0;
+        while pos < code.len() {
+            positions.push(pos);
+            pos += 1 + OpCode::from_byte(code[pos]).map_or(0, |op| op.immediate_size());
+        }
+        let index = |pos: usize| positions.partition_point(|start| *start <= pos).saturating_sub(1);
+        self.pc = index(self.pc);
+        for frame in self.frames.iter_mut() {
+            //Past the end for the entry point
+            frame.return_pc = if frame.return_pc < code.len() {
+                index(frame.return_pc)
+            } else {
+                positions.len()
+            };
+        }
+    }
+
+    fn dispatch(&mut self, asm: &Assembly<'run>) -> RuntimeResult<()> {
+        let code = asm.code.as_slice();
+        while self.pc < code.len() {
+            self.tick()?;
+            let op = OpCode::from_byte(code[self.pc]).ok_or(RuntimeError::InvalidOperation)?;
+            let imm = self.pc + 1;
+            let next = imm + op.immediate_size();
+            let u32_at = |pos: usize| u32::from_le_bytes(read_imm(code, pos));
+            match op {
+                OpCode::Nop => {}
+                OpCode::PushInteger => {
+                    self.stack.push(VMData::new_i64(i64::from_le_bytes(read_imm(code, imm))))?
+                }
+                OpCode::PushFloat => {
+                    self.stack.push(VMData::new_f64(f64::from_le_bytes(read_imm(code, imm))))?
+                }
+                OpCode::PushUnsignedInteger => {
+                    self.stack.push(VMData::new_u64(u64::from_le_bytes(read_imm(code, imm))))?
+                }
+                OpCode::PushBoolean => self.stack.push(VMData::new_bool(code[imm] != 0))?,
+                OpCode::PushChar => {
+                    let c = char::from_u32(u32_at(imm)).ok_or(RuntimeError::InvalidOperation)?;
+                    self.stack.push(VMData::new_char(c))?
+                }
+                OpCode::PushStr => self.push_str(u32_at(imm) as usize)?,
+                OpCode::PushFnPtr => {
+                    let ptr = asm.function_pool[u32_at(imm) as usize];
+                    self.stack.push(VMData::new_fn_ptr(ptr))?
+                }
+                OpCode::PushUnit => self.stack.push(VMData::new_unit())?,
+                OpCode::Pop => {
+                    self.stack.pop_with_rc(&mut self.object_map)?;
+                }
+                OpCode::Swap => {
+                    let val1 = self.stack.pop()?;
+                    let val2 = self.stack.pop()?;
+                    self.stack.push(val1)?;
+                    self.stack.push(val2)?;
+                }
+                OpCode::Dup => {
+                    let val = *self.stack.last()?;
+                    self.stack.push_with_rc(val, &mut self.object_map)?;
+                }
+                OpCode::Get => self.get_local(u32_at(imm) as usize)?,
+                OpCode::Store => self.store_local(u32_at(imm) as usize)?,
+                OpCode::Reserve => self.reserve(u32_at(imm) as usize)?,
+                OpCode::CastTo => {
+                    let t = Type::from_byte(code[imm]).ok_or(RuntimeError::InvalidOperation)?;
+                    self.cast_to(t)?
+                }
+                OpCode::IMul => self.binary_op(|a, b| VMData::new_i64(a.as_i64() * b.as_i64()))?,
+                OpCode::FMul => self.binary_op(|a, b| VMData::new_f64(a.as_f64() * b.as_f64()))?,
+                OpCode::UIMul => self.binary_op(|a, b| VMData::new_u64(a.as_u64() * b.as_u64()))?,
+                OpCode::IDiv => self.division(VMData::new_i64(0), |a, b| VMData::new_i64(a.as_i64() / b.as_i64()))?,
+                OpCode::FDiv => self.division(VMData::new_f64(0.0), |a, b| VMData::new_f64(a.as_f64() / b.as_f64()))?,
+                OpCode::UIDiv => self.division(VMData::new_u64(0), |a, b| VMData::new_u64(a.as_u64() / b.as_u64()))?,
+                OpCode::ISub => self.binary_op(|a, b| VMData::new_i64(a.as_i64() - b.as_i64()))?,
+                OpCode::FSub => self.binary_op(|a, b| VMData::new_f64(a.as_f64() - b.as_f64()))?,
+                OpCode::UISub => self.binary_op(|a, b| VMData::new_u64(a.as_u64() - b.as_u64()))?,
+                OpCode::IMod => self.binary_op(|a, b| VMData::new_i64(a.as_i64() % b.as_i64()))?,
+                OpCode::FMod => self.binary_op(|a, b| VMData::new_f64(a.as_f64() % b.as_f64()))?,
+                OpCode::UIMod => self.binary_op(|a, b| VMData::new_u64(a.as_u64() % b.as_u64()))?,
+                OpCode::IAdd => self.binary_op(|a, b| VMData::new_i64(a.as_i64() + b.as_i64()))?,
+                OpCode::FAdd => self.binary_op(|a, b| VMData::new_f64(a.as_f64() + b.as_f64()))?,
+                OpCode::UIAdd => self.binary_op(|a, b| VMData::new_u64(a.as_u64() + b.as_u64()))?,
+                OpCode::Eq => self.equality(true)?,
+                OpCode::Neq => self.equality(false)?,
+                OpCode::Gt => self.binary_op(|a, b| VMData::new_bool(a.as_i64() > b.as_i64()))?,
+                OpCode::Gte => self.binary_op(|a, b| VMData::new_bool(a.as_i64() >= b.as_i64()))?,
+                OpCode::Lt => self.binary_op(|a, b| VMData::new_bool(a.as_i64() < b.as_i64()))?,
+                OpCode::Lte => self.binary_op(|a, b| VMData::new_bool(a.as_i64() <= b.as_i64()))?,
+                OpCode::DJmp => {
+                    self.pc = u32_at(imm) as usize;
+                    continue;
+                }
+                OpCode::RJmp => {
+                    let offset = i32::from_le_bytes(read_imm(code, imm));
+                    self.pc = (self.pc as isize + offset as isize) as usize;
+                    continue;
+                }
+                OpCode::JmpTable => {
+                    let min = i64::from_le_bytes(read_imm(code, imm));
+                    let index = self.jump_table_index(min, u32_at(imm + 8) as usize)?;
+                    //Every entry is a `RJmp`
+                    self.pc = next + index * (1 + OpCode::RJmp.immediate_size());
+                    continue;
+                }
+                OpCode::JmpZ => {
+                    if !self.stack.pop()?.as_bool() {
+                        let offset = i32::from_le_bytes(read_imm(code, imm));
+                        self.pc = (self.pc as isize + offset as isize) as usize;
+                        continue;
+                    }
+                }
+                OpCode::DirectCall => {
+                    let position = self.stack[u32_at(imm) as usize].as_fn_ptr();
+                    let label = asm.label_index_at(position).ok_or(RuntimeError::InvalidOperation)?;
+                    self.call(label, position, code[imm + 4], next);
+                    continue;
+                }
+                OpCode::FunctionCall => {
+                    let position = u32_at(imm) as usize;
+                    let label = asm.label_index_at(position).ok_or(RuntimeError::InvalidOperation)?;
+                    self.call(label, position, code[imm + 4], next);
+                    continue;
+                }
+                OpCode::ExternCall => self.native_call(u32_at(imm) as usize)?,
+                OpCode::Return => {
+                    self.pc = self.ret()?;
+                    continue;
+                }
+                OpCode::NewObj => {
+                    let class = &self.program.global.class_pool[u32_at(imm) as usize];
+                    self.new_obj(class)?
+                }
+                OpCode::DeleteObj => self.delete_obj()?,
+                OpCode::GetField => self.get_field(asm.symbols[u32_at(imm) as usize])?,
+                OpCode::NewUnion => self.new_union(u32_at(imm) as usize, code[imm + 4] as usize)?,
+                OpCode::UnionTag => self.union_tag()?,
+                OpCode::UnionField => self.union_field(u32_at(imm) as usize)?,
+                OpCode::SetField => self.set_field(asm.symbols[u32_at(imm) as usize])?,
+                OpCode::NewList => self.new_list()?,
+                OpCode::ListLoad => self.list_load()?,
+                OpCode::ListStore => self.list_store()?,
+                OpCode::StringLoad => self.string_load()?,
+                OpCode::StringStore => self.string_store()?,
+                OpCode::Halt => {
+                    self.pc = code.len();
+                    continue;
+                }
+                OpCode::Call => {
+                    let position = self.stack.pop()?.as_fn_ptr();
+                    let label = asm.label_index_at(position).ok_or(RuntimeError::InvalidOperation)?;
+                    self.call(label, position, code[imm], next);
+                    continue;
+                }
+                OpCode::PushList | OpCode::ListCopy | OpCode::ListIndex => {
+                    return Err(RuntimeError::UnsupportedInstruction(format!("{:?}", op)));
+                }
+            }
+            self.pc = next;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atlas_c::atlas_asm::assemble;
+    use crate::atlas_vm::runtime::{arena::RuntimeArena, binary};
+    use crate::atlas_vm::Atlas77VM;
+    use crate::engine::Engine;
+    use bumpalo::Bump;
+
+    /// Outcome & backtrace of running `source`, through the byte encoding if `assembled`
+    fn run(name: &str, source: &str, assembled: bool) -> (String, String) {
+        let script = Engine::new().compile(name, source).unwrap();
+        let bump = Bump::new();
+        let program = binary::deserialize(script.bytecode(), &RuntimeArena::new(&bump)).unwrap();
+        let mut vm = Atlas77VM::new(program, RuntimeArena::new(&bump)).unwrap();
+        let res = if assembled {
+            let asm = assemble(&vm.program).unwrap();
+            vm.run_assembly(&asm)
+        } else {
+            vm.run()
+        };
+        let backtrace = match res {
+            Ok(_) => Vec::new(),
+            Err(_) => vm.backtrace(),
+        };
+        (format!("{:?}", res), format!("{:?}", backtrace))
+    }
+
+    #[test]
+    fn examples_run_the_same_assembled() {
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut paths = std::fs::read_dir(root.join("examples"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|e| e == "atlas"))
+            .collect::<Vec<_>>();
+        paths.sort();
+        let engine = Engine::new();
+        let mut ran = 0;
+        for path in paths {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            let source = std::fs::read_to_string(&path).unwrap();
+            //Reads stdin, the ones not compiling are listed by the engine's tests
+            if name == "guessing_game.atlas" || engine.compile(name, &source).is_err() {
+                continue;
+            }
+            assert_eq!(run(name, &source, true), run(name, &source, false), "{}", name);
+            ran += 1;
+        }
+        assert!(ran >= 10, "only {} examples ran", ran);
+    }
+
+    #[test]
+    fn assembled_errors_have_the_same_backtrace() {
+        let source = r#"func div(a: int64, b: int64) -> int64 {
+    return a / b;
+}
+func main() -> int64 {
+    let zero = 0;
+    return div(1, zero);
+}"#;
+        let (res, backtrace) = run("div.atlas", source, true);
+        assert!(res.starts_with("Err(DivisionByZero"), "{}", res);
+        assert!(backtrace.contains("span: Some"), "{}", backtrace);
+        assert_eq!((res, backtrace), run("div.atlas", source, false));
+    }
+}
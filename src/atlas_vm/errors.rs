@@ -1,37 +1,123 @@
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use thiserror::Error;
+
 use crate::atlas_vm::runtime::instruction::Type;
 
 pub type RuntimeResult<T> = Result<T, RuntimeError>;
 
-#[derive(Debug, Clone)]
+#[derive(Error, Debug, Clone)]
 pub enum RuntimeError {
+    #[error("out of memory, the object heap reached its maximum size")]
     OutOfMemory,
+    #[error("stack overflow, the VM stack reached its maximum size")]
     StackOverflow,
+    #[error("stack underflow, tried to pop from an empty stack")]
     StackUnderflow,
+    #[error("null reference, the object was already freed")]
     NullReference,
+    #[error("division by zero")]
     DivisionByZero,
+    #[error("invalid cast from a value tagged {0} to {1:?}")]
     InvalidCast(u8, Type),
-    IndexOutOfBounds,
+    #[error("index out of bounds, the index is {index} but the length is {len}")]
+    IndexOutOfBounds { index: i64, len: usize },
+    #[error("a list can't have a negative size, got {0}")]
+    NegativeListSize(i64),
+    #[error("invalid operation")]
     InvalidOperation,
+    #[error("the object has no field `{0}`")]
+    UnknownField(String),
+    /// The codegen never emits it, but a `.atlasc` file may contain it
+    #[error("the VM can't execute `{0}`")]
+    UnsupportedInstruction(String),
+    #[error("type mismatch")]
     TypeMismatchError,
+    #[error("entry point `{0}` not found")]
     EntryPointNotFound(String),
+    #[error("`{0}` was not resolved, the program needs to be linked before running it")]
+    UnlinkedSymbol(String),
+    #[error("native function `{0}` is not available")]
+    NativeNotFound(String),
+    #[error("`{0}` doesn't match the fields of the Atlas class it is converted from or to")]
+    ClassMismatch(String),
+    #[error("unknown library `{0}`, it is neither a standard library nor a registered native module")]
+    UnknownLibrary(String),
+    #[error("out of fuel, the program executed more instructions than allowed")]
+    OutOfFuel,
+    #[error("the program ran longer than allowed")]
+    Timeout,
+    #[error("the program was cancelled")]
+    Cancelled,
+    /// Raised by the debugger when it's quit, it isn't reported as a failure
+    #[error("the debugger was quit")]
+    DebuggerQuit,
+    #[error("the library `{0}` is not allowed in this VM")]
+    LibraryNotAllowed(String),
+    /// Raised by `std/process`'s `exit`, the VM stops and returns the code
+    #[error("the program exited with code {0}")]
+    Exit(i64),
+    /// Raised by `std/test`'s `assert` & `assert_eq`
+    #[error("assertion failed: {0}")]
+    AssertionFailed(String),
+}
+
+/// Atlas function that was running when a runtime error happened
+#[derive(Debug, Clone)]
+pub struct TraceFrame {
+    pub function: String,
+    /// Source span of the statement being executed, if the program has a line table
+    pub span: Option<SourceSpan>,
 }
 
-impl std::fmt::Display for RuntimeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        use RuntimeError::*;
-        match self {
-            OutOfMemory => writeln!(f, "No more memory bozo"),
-            StackOverflow => writeln!(f, "No more stack bozo"),
-            StackUnderflow => writeln!(f, "Too little stack bozo"),
-            NullReference => writeln!(f, "Null Reference error"),
-            DivisionByZero => writeln!(f, "There are no infinity, you can't divide by zero"),
-            InvalidCast(from, to) => writeln!(f, "Invalid cast from {} to {:?}", from, to),
-            IndexOutOfBounds => writeln!(f, "Index out of bounds"),
-            InvalidOperation => writeln!(f, "Invalid Operation (default error)"),
-            TypeMismatchError => writeln!(f, "Incorrect types bozo"),
-            EntryPointNotFound(entry_point) => {
-                writeln!(f, "Entry point {} not found", entry_point)
+/// Frames shown in a backtrace, deep recursions would flood the terminal otherwise
+const MAX_TRACE_FRAMES: usize = 32;
+
+/// A [`RuntimeError`] rendered against the source of the program, with the Atlas call stack
+#[derive(Error, Diagnostic, Debug)]
+#[error("runtime error: {error}")]
+#[diagnostic(code(runtime::error))]
+pub struct RuntimeErrorReport {
+    pub error: RuntimeError,
+    #[label("raised here")]
+    pub span: Option<SourceSpan>,
+    #[source_code]
+    pub src: Option<NamedSource<String>>,
+    #[help]
+    pub backtrace: Option<String>,
+}
+
+impl RuntimeErrorReport {
+    /// `trace` goes from the innermost frame to the entry point, `src` is the source file name & content
+    pub fn new(error: RuntimeError, trace: &[TraceFrame], src: Option<(String, String)>) -> Self {
+        let backtrace = if trace.is_empty() {
+            None
+        } else {
+            let mut backtrace = String::from("Atlas backtrace:");
+            for (i, frame) in trace.iter().enumerate().take(MAX_TRACE_FRAMES) {
+                backtrace.push_str(&format!("\n  {}: {}", i, frame.function));
+                if let (Some(span), Some((name, text))) = (frame.span, &src) {
+                    let (line, col) = line_col(text, span.offset());
+                    backtrace.push_str(&format!(" at {}:{}:{}", name, line, col));
+                }
+            }
+            if trace.len() > MAX_TRACE_FRAMES {
+                backtrace.push_str(&format!("\n  ... {} more frames", trace.len() - MAX_TRACE_FRAMES));
             }
+            Some(backtrace)
+        };
+        Self {
+            error,
+            span: trace.first().and_then(|frame| frame.span),
+            src: src.map(|(name, text)| NamedSource::new(name, text)),
+            backtrace,
         }
     }
 }
+
+/// 1-based line & column of `offset` in `text`
+fn line_col(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let col = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, col)
+}
@@ -1,6 +1,6 @@
 use crate::atlas_vm::errors::RuntimeError;
-use crate::atlas_vm::memory::object_map::ObjectKind;
 use crate::atlas_vm::memory::vm_data::VMData;
+use crate::atlas_vm::native::IntoAtlas;
 use crate::atlas_vm::runtime::vm_state::VMState;
 use crate::atlas_vm::CallBack;
 
@@ -12,58 +12,44 @@ pub const FILE_FUNCTIONS: [(&str, CallBack); 5] = [
     ("remove_file", remove_file),
 ];
 
-pub fn read_dir(state: VMState) -> Result<VMData, RuntimeError> {
-    let path_ptr = state.stack.pop_with_rc(state.object_map)?.as_object();
+pub fn read_dir(mut state: VMState) -> Result<VMData, RuntimeError> {
+    let path_ptr = state.stack.pop()?.as_object();
     let raw_path = state.object_map.get(path_ptr)?;
     let path = raw_path.string();
 
-    let entries = std::fs::read_dir(path).unwrap();
-    let mut list = Vec::new();
-    for entry in entries {
-        let entry = entry.unwrap();
-        let path = entry.path();
-        let path_str = path.to_str().unwrap().to_string();
-        let obj_idx = state.object_map.put(ObjectKind::String(path_str));
-        match obj_idx {
-            Ok(index) => list.push(VMData::new_string(index)),
-            Err(_) => return Err(RuntimeError::OutOfMemory),
-        }
-    }
-
-    let list_idx = state.object_map.put(ObjectKind::List(list));
-    match list_idx {
-        Ok(index) => Ok(VMData::new_list(index)),
-        Err(_) => Err(RuntimeError::OutOfMemory),
-    }
+    let entries = std::fs::read_dir(path).and_then(|entries| {
+        entries
+            .map(|entry| Ok(entry?.path().to_string_lossy().into_owned()))
+            .collect::<std::io::Result<Vec<String>>>()
+    });
+    entries.map_err(|e| e.to_string()).into_atlas(&mut state)
 }
 
-pub fn read_file(state: VMState) -> Result<VMData, RuntimeError> {
-    let path_ptr = state.stack.pop_with_rc(state.object_map)?.as_object();
+pub fn read_file(mut state: VMState) -> Result<VMData, RuntimeError> {
+    let path_ptr = state.stack.pop()?.as_object();
     let raw_path = state.object_map.get(path_ptr)?;
     let path = raw_path.string();
 
-    let content = std::fs::read_to_string(path).unwrap();
-    let obj_idx = state.object_map.put(ObjectKind::String(content));
-    match obj_idx {
-        Ok(index) => Ok(VMData::new_string(index)),
-        Err(_) => Err(RuntimeError::OutOfMemory),
-    }
+    std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .into_atlas(&mut state)
 }
 
-pub fn write_file(state: VMState) -> Result<VMData, RuntimeError> {
-    let content_ptr = state.stack.pop_with_rc(state.object_map)?.as_object();
-    let path_ptr = state.stack.pop_with_rc(state.object_map)?.as_object();
+pub fn write_file(mut state: VMState) -> Result<VMData, RuntimeError> {
+    let content_ptr = state.stack.pop()?.as_object();
+    let path_ptr = state.stack.pop()?.as_object();
 
     let path = state.object_map.get(path_ptr)?.string().clone();
     let raw_content = state.object_map.get(content_ptr)?;
     let content = raw_content.string();
 
-    std::fs::write(path, content).unwrap();
-    Ok(VMData::new_unit())
+    std::fs::write(path, content)
+        .map_err(|e| e.to_string())
+        .into_atlas(&mut state)
 }
 
 pub fn file_exists(state: VMState) -> Result<VMData, RuntimeError> {
-    let path_ptr = state.stack.pop_with_rc(state.object_map)?.as_object();
+    let path_ptr = state.stack.pop()?.as_object();
     let raw_path = state.object_map.get(path_ptr)?;
     let path = raw_path.string();
 
@@ -71,11 +57,12 @@ pub fn file_exists(state: VMState) -> Result<VMData, RuntimeError> {
     Ok(VMData::new_bool(exists))
 }
 
-pub fn remove_file(state: VMState) -> Result<VMData, RuntimeError> {
-    let path_ptr = state.stack.pop_with_rc(state.object_map)?.as_object();
+pub fn remove_file(mut state: VMState) -> Result<VMData, RuntimeError> {
+    let path_ptr = state.stack.pop()?.as_object();
     let raw_path = state.object_map.get(path_ptr)?;
     let path = raw_path.string();
 
-    std::fs::remove_file(path).unwrap();
-    Ok(VMData::new_unit())
+    std::fs::remove_file(path)
+        .map_err(|e| e.to_string())
+        .into_atlas(&mut state)
 }
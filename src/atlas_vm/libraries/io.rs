@@ -49,12 +49,10 @@ pub fn print(state: VMState) -> Result<VMData, RuntimeError> {
     Ok(VMData::new_unit())
 }
 
-pub fn input(state: VMState) -> Result<VMData, RuntimeError> {
+pub fn input(mut state: VMState) -> Result<VMData, RuntimeError> {
     let mut input = String::new();
     std::io::stdin().read_line(&mut input).unwrap();
-    let obj_index = state
-        .object_map
-        .put(ObjectKind::String(input.trim().to_string()));
+    let obj_index = state.alloc(ObjectKind::String(input.trim().to_string()));
     match obj_index {
         Ok(index) => {
             println!("Input: {}", input.trim());
@@ -2,5 +2,27 @@ pub mod fs;
 pub mod io;
 pub mod list;
 pub mod math;
+pub mod process;
 pub mod string;
+pub mod test;
 pub mod time;
+
+use crate::atlas_vm::CallBack;
+
+/// Name of every standard library, the last segment of its import path
+pub const STD_LIBRARIES: [&str; 8] = ["file", "io", "list", "math", "process", "string", "test", "time"];
+
+/// Native functions of a standard library, found by the last segment of its import path (e.g. `std/io`)
+pub fn std_library(path: &str) -> Option<&'static [(&'static str, CallBack)]> {
+    match path.split('/').next_back()? {
+        "file" => Some(&fs::FILE_FUNCTIONS),
+        "io" => Some(&io::IO_FUNCTIONS),
+        "list" => Some(&list::LIST_FUNCTIONS),
+        "math" => Some(&math::MATH_FUNCTIONS),
+        "process" => Some(&process::PROCESS_FUNCTIONS),
+        "string" => Some(&string::STRING_FUNCTIONS),
+        "test" => Some(&test::TEST_FUNCTIONS),
+        "time" => Some(&time::TIME_FUNCTIONS),
+        _ => None,
+    }
+}
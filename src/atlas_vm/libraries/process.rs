@@ -0,0 +1,12 @@
+use crate::atlas_vm::errors::RuntimeError;
+use crate::atlas_vm::memory::vm_data::VMData;
+use crate::atlas_vm::runtime::vm_state::VMState;
+use crate::atlas_vm::CallBack;
+
+pub const PROCESS_FUNCTIONS: [(&str, CallBack); 1] = [("exit", exit)];
+
+/// Unwinds the whole VM, see [`RuntimeError::Exit`]
+pub fn exit(state: VMState) -> Result<VMData, RuntimeError> {
+    let code = state.stack.pop()?.as_i64();
+    Err(RuntimeError::Exit(code))
+}
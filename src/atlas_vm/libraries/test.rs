@@ -0,0 +1,36 @@
+use crate::atlas_vm::errors::RuntimeError;
+use crate::atlas_vm::memory::vm_data::VMData;
+use crate::atlas_vm::runtime::vm_state::VMState;
+use crate::atlas_vm::CallBack;
+
+pub const TEST_FUNCTIONS: [(&str, CallBack); 2] = [("assert", assert), ("assert_eq", assert_eq)];
+
+/// Fails with [`RuntimeError::AssertionFailed`] if the condition is false
+pub fn assert(state: VMState) -> Result<VMData, RuntimeError> {
+    if !state.stack.pop()?.as_bool() {
+        return Err(RuntimeError::AssertionFailed(String::from("the condition is false")));
+    }
+    Ok(VMData::new_unit())
+}
+
+/// Fails with [`RuntimeError::AssertionFailed`] if both values differ, objects are compared by content
+pub fn assert_eq(state: VMState) -> Result<VMData, RuntimeError> {
+    let right = state.stack.pop()?;
+    let left = state.stack.pop()?;
+    let (left_text, right_text) = (state.object_map.describe(left), state.object_map.describe(right));
+    let equal = match left.is_object() {
+        true => left.tag == right.tag && left_text == right_text,
+        false => left == right,
+    };
+    //The arguments are moved to the native
+    for value in [left, right].into_iter().filter(|value| value.is_object()) {
+        state.object_map.get(value.as_object())?;
+    }
+    if !equal {
+        return Err(RuntimeError::AssertionFailed(format!(
+            "`{}` isn't equal to `{}`",
+            left_text, right_text
+        )));
+    }
+    Ok(VMData::new_unit())
+}
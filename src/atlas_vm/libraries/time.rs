@@ -1,12 +1,11 @@
-// All of this is assuming that Time is of type: Time(sec: i64, nsec: i64)
-// Time will have a tag of 256 as it will be the first type defined by the compiler (0-255 are reserved for the compiler)
+// Time is the class declared by `std/time`: Time(sec: int64, nsec: int64)
 
+use crate::atlas_class;
 use crate::atlas_vm::errors::RuntimeError;
-use crate::atlas_vm::memory::object_map::{Class, ObjectKind};
 use crate::atlas_vm::memory::vm_data::VMData;
+use crate::atlas_vm::native::{pop_arg, IntoAtlas};
 use crate::atlas_vm::runtime::vm_state::VMState;
 use crate::atlas_vm::CallBack;
-use std::collections::HashMap;
 use time::{format_description, OffsetDateTime};
 
 pub const TIME_FUNCTIONS: [(&str, CallBack); 4] = [
@@ -16,107 +15,57 @@ pub const TIME_FUNCTIONS: [(&str, CallBack); 4] = [
     ("elapsed", elapsed),
 ];
 
+struct Time {
+    sec: i64,
+    nsec: i64,
+}
+atlas_class!(Time { sec, nsec });
+
+impl Time {
+    fn to_offset_date_time(&self) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(self.sec).unwrap() + time::Duration::nanoseconds(self.nsec)
+    }
+}
+
 //now() -> &Time
-pub fn now(state: VMState) -> Result<VMData, RuntimeError> {
+pub fn now(mut state: VMState) -> Result<VMData, RuntimeError> {
     let time = std::time::SystemTime::now();
     let duration = time.duration_since(std::time::UNIX_EPOCH).unwrap();
 
-    let sec = duration.as_secs();
-    let nsec = duration.subsec_nanos();
-
-    let mut fields = HashMap::new();
-    fields.insert("sec", VMData::new_i64(sec as i64));
-    fields.insert("nsec", VMData::new_i64(nsec as i64));
-
-    let obj_idx = state.object_map.put(ObjectKind::Class(Class {
-        fields,
-    }));
-    match obj_idx {
-        Ok(index) => Ok(VMData::new_object(index)),
-        Err(_) => Err(RuntimeError::OutOfMemory),
+    Time {
+        sec: duration.as_secs() as i64,
+        nsec: duration.subsec_nanos() as i64,
     }
+    .into_atlas(&mut state)
 }
 
 //format_time_iso(time: &Time) -> &string
-pub fn format_time_iso(state: VMState) -> Result<VMData, RuntimeError> {
-    let time_ptr = state.stack.pop_with_rc(state.object_map)?.as_object();
-    let raw_time_obj = state.object_map.get(time_ptr)?;
-    let time_obj = raw_time_obj.class();
-
-    let sec = time_obj.fields.get("sec").unwrap().as_i64();
-    let nsec = time_obj.fields.get("nsec").unwrap().as_i64();
-
-    let time =
-        OffsetDateTime::from_unix_timestamp(sec).unwrap() + time::Duration::nanoseconds(nsec);
+pub fn format_time_iso(mut state: VMState) -> Result<VMData, RuntimeError> {
+    let time = pop_arg::<Time>(&mut state)?.to_offset_date_time();
 
     let fmt =
-        format_description::parse("[year]-[month]-[day]T[hour]:[minute]:[second].[frac][offset]")
+        format_description::parse_borrowed::<1>("[year]-[month]-[day]T[hour]:[minute]:[second].[frac][offset]")
             .unwrap();
-    let formatted = time.format(&fmt).unwrap();
-
-    let obj_idx = state.object_map.put(ObjectKind::String(formatted));
-    match obj_idx {
-        Ok(index) => Ok(VMData::new_string(index)),
-        Err(_) => Err(RuntimeError::OutOfMemory),
-    }
+    time.format(&fmt).unwrap().into_atlas(&mut state)
 }
 
 //format_time(time: &Time, format: &string) -> &string
-pub fn format_time(state: VMState) -> Result<VMData, RuntimeError> {
-    let format_ptr = state.stack.pop_with_rc(state.object_map)?.as_object(); // a string is an object
-    let time_ptr = state.stack.pop_with_rc(state.object_map)?.as_object();
-
-    let fmt_str = &state.object_map.get(format_ptr)?.string().clone();
-    let raw_time_obj = state.object_map.get(time_ptr)?;
-    let time_obj = raw_time_obj.class();
+pub fn format_time(mut state: VMState) -> Result<VMData, RuntimeError> {
+    let fmt_str = pop_arg::<String>(&mut state)?;
+    let time = pop_arg::<Time>(&mut state)?.to_offset_date_time();
 
-    let sec = time_obj.fields.get("sec").unwrap().as_i64();
-    let nsec = time_obj.fields.get("nsec").unwrap().as_i64();
-
-    let time =
-        OffsetDateTime::from_unix_timestamp(sec).unwrap() + time::Duration::nanoseconds(nsec);
-
-    let fmt = format_description::parse(fmt_str).unwrap();
-    let formatted = time.format(&fmt).unwrap();
-
-    let obj_idx = state.object_map.put(ObjectKind::String(formatted));
-    match obj_idx {
-        Ok(index) => Ok(VMData::new_string(index)),
-        Err(_) => Err(RuntimeError::OutOfMemory),
-    }
+    let fmt = format_description::parse_borrowed::<1>(&fmt_str).unwrap();
+    time.format(&fmt).unwrap().into_atlas(&mut state)
 }
 
 // elapsed(start: &Time, end: &Time) -> &Time
-pub fn elapsed(state: VMState) -> Result<VMData, RuntimeError> {
-    let end_ptr = state.stack.pop_with_rc(state.object_map)?.as_object();
-    let start_ptr = state.stack.pop_with_rc(state.object_map)?.as_object();
-
-    let raw_start_obj = state.object_map.get(start_ptr)?;
-    let start_obj = raw_start_obj.class();
-
-    let start_sec = start_obj.fields.get("sec").unwrap().as_i64();
-    let start_nsec = start_obj.fields.get("nsec").unwrap().as_i64();
-
-
-    let raw_end_obj = state.object_map.get(end_ptr)?;
-    let end_obj = raw_end_obj.class();
-
-    let end_sec = end_obj.fields.get("sec").unwrap().as_i64();
-    let end_nsec = end_obj.fields.get("nsec").unwrap().as_i64();
-
-    let elapsed_sec = end_sec - start_sec;
-    let elapsed_nsec = end_nsec - start_nsec;
-
-    let mut fields = HashMap::new();
-    fields.insert("sec", VMData::new_i64(elapsed_sec));
-    fields.insert("nsec", VMData::new_i64(elapsed_nsec));
-
-    let obj_idx = state.object_map.put(ObjectKind::Class(Class {
-        fields,
-    }));
+pub fn elapsed(mut state: VMState) -> Result<VMData, RuntimeError> {
+    let end = pop_arg::<Time>(&mut state)?;
+    let start = pop_arg::<Time>(&mut state)?;
 
-    match obj_idx {
-        Ok(index) => Ok(VMData::new_object(index)),
-        Err(_) => Err(RuntimeError::OutOfMemory),
+    Time {
+        sec: end.sec - start.sec,
+        nsec: end.nsec - start.nsec,
     }
+    .into_atlas(&mut state)
 }
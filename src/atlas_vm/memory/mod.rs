@@ -1,4 +1,3 @@
 pub mod object_map;
 pub mod stack;
-pub mod varmap;
 pub mod vm_data;
@@ -5,11 +5,31 @@ use std::collections::HashMap;
 
 /// Probably should be renamed lmao
 ///
-/// Need to find a way to make the memory shrink, grows, and garbage collect unused memory (by scanning the stack & VarMap)
+/// Objects are freed as soon as their reference count drops to 0.
+/// Cycles are reclaimed by [`Memory::collect`], which traces everything reachable from a set of roots.
+///
+/// The memory doubles when it's full, up to [`Memory::max_space`] objects.
+/// The live objects are also bounded by their size, see [`Memory::max_size`].
 pub struct Memory<'mem> {
     mem: Vec<Object<'mem>>,
     pub free: ObjectIndex,
     pub used_space: usize,
+    /// Maximum number of live objects, `None` means no limit
+    pub max_space: Option<usize>,
+    /// Bytes taken by the live objects, see [`ObjectKind::size`]
+    pub used_size: usize,
+    /// Maximum of `used_size`, `None` means no limit
+    pub max_size: Option<usize>,
+    initial_space: usize,
+    peak_space: usize,
+    allocations: usize,
+    collections: usize,
+    /// Number of allocations since the last collection
+    allocations_since_gc: usize,
+    /// Collect after this many allocations.
+    ///
+    /// With `None`, a collection only happens when the memory is full or on demand
+    pub gc_threshold: Option<usize>,
 }
 
 #[repr(C)]
@@ -48,9 +68,79 @@ impl<'mem> Memory<'mem> {
                 })
                 .collect(),
             used_space: 0,
+            max_space: None,
+            used_size: 0,
+            max_size: None,
+            initial_space: space,
+            peak_space: 0,
+            allocations: 0,
+            collections: 0,
+            allocations_since_gc: 0,
+            gc_threshold: None,
+        }
+    }
+
+    /// Number of objects the memory can hold before growing
+    pub fn capacity(&self) -> usize {
+        self.mem.len()
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        HeapStats {
+            used_space: self.used_space,
+            capacity: self.mem.len(),
+            peak_space: self.peak_space,
+            allocations: self.allocations,
+            collections: self.collections,
+        }
+    }
+
+    /// Double the capacity (at least 1 slot), without going over `max_space`
+    fn grow(&mut self) -> RuntimeResult<()> {
+        let old = self.mem.len();
+        let mut new = (old * 2).max(1);
+        if let Some(max) = self.max_space {
+            new = new.min(max);
+        }
+        if new <= old {
+            return Err(RuntimeError::OutOfMemory);
+        }
+        let free = self.free;
+        self.mem.extend((old..new).map(|x| Object {
+            kind: ObjectKind::Free {
+                next: if x + 1 < new { ObjectIndex::new(x as u64 + 1) } else { free },
+            },
+            rc: 0,
+        }));
+        self.free = ObjectIndex::new(old as u64);
+        Ok(())
+    }
+
+    /// Release the free slots at the end of the memory, never going under the initial space
+    pub fn shrink(&mut self) {
+        let last_used = self
+            .mem
+            .iter()
+            .rposition(|obj| !matches!(obj.kind, ObjectKind::Free { .. }))
+            .map_or(0, |idx| idx + 1);
+        let len = last_used.max(self.initial_space);
+        if len >= self.mem.len() {
+            return;
+        }
+        self.mem.truncate(len);
+        //The free list may point to a released slot, so it's rebuilt
+        self.free = ObjectIndex::new(len as u64);
+        for idx in (0..len).rev() {
+            if let ObjectKind::Free { next } = &mut self.mem[idx].kind {
+                *next = self.free;
+                self.free = ObjectIndex::new(idx as u64);
+            }
         }
     }
     pub fn clear(&mut self) {
+        self.used_space = 0;
+        self.used_size = 0;
+        self.allocations_since_gc = 0;
         for (idx, obj) in self.mem.iter_mut().enumerate() {
             obj.kind = ObjectKind::Free {
                 next: self.free,
@@ -61,6 +151,14 @@ impl<'mem> Memory<'mem> {
     }
 
     pub fn put(&mut self, object: ObjectKind<'mem>) -> Result<ObjectIndex, RuntimeError> {
+        let size = object.size();
+        //The initial space can be bigger than `max_space`
+        if self.is_at_max_space() || !self.fits(size) {
+            return Err(RuntimeError::OutOfMemory);
+        }
+        if self.used_space == self.mem.len() {
+            self.grow()?;
+        }
         let idx = self.free;
         let v = self.mem.get_mut(usize::from(self.free)).unwrap();
         let repl = std::mem::replace(v, Object { kind: object, rc: 1 });
@@ -68,6 +166,11 @@ impl<'mem> Memory<'mem> {
         match repl {
             Object { kind: ObjectKind::Free { next }, .. } => {
                 self.free = next;
+                self.used_space += 1;
+                self.used_size += size;
+                self.peak_space = self.peak_space.max(self.used_space);
+                self.allocations += 1;
+                self.allocations_since_gc += 1;
                 Ok(idx)
             }
             _ => {
@@ -76,50 +179,132 @@ impl<'mem> Memory<'mem> {
         }
     }
 
-    pub fn free(&mut self, index: ObjectIndex) -> RuntimeResult<()> {
-        println!("Freeing {}", index);
-        let next = self.free;
-        let v = self.mem.get_mut(usize::from(index)).unwrap().kind.clone();
-        if let ObjectKind::List(list) = v {
-            for item in list {
-                match item.tag {
-                    VMData::TAG_STR | VMData::TAG_LIST | VMData::TAG_OBJECT => {
-                        let obj_to_dec = item.as_object();
-                        self.rc_dec(obj_to_dec)?;
-                    }
-                    _ => {}
+    /// Whether allocating an object of `size` bytes should be preceded by a [`Memory::collect`]
+    pub fn needs_collection(&self, size: usize) -> bool {
+        self.used_space == self.mem.len()
+            || self.is_at_max_space()
+            || !self.fits(size)
+            || self
+                .gc_threshold
+                .is_some_and(|threshold| self.allocations_since_gc >= threshold)
+    }
+
+    fn is_at_max_space(&self) -> bool {
+        self.max_space.is_some_and(|max| self.used_space >= max)
+    }
+
+    /// Whether an object of `size` bytes can be allocated without going over `max_size`
+    pub fn fits(&self, size: usize) -> bool {
+        self.max_size.is_none_or(|max| self.used_size.saturating_add(size) <= max)
+    }
+
+    /// Free every object unreachable from `roots`, reference cycles included.
+    ///
+    /// Returns the number of freed objects
+    pub fn collect(&mut self, roots: impl IntoIterator<Item = VMData>) -> usize {
+        self.allocations_since_gc = 0;
+        self.collections += 1;
+        let mut marked = vec![false; self.mem.len()];
+        let mut pending = roots
+            .into_iter()
+            .filter_map(Self::object_of)
+            .collect::<Vec<_>>();
+        while let Some(index) = pending.pop() {
+            let idx = usize::from(index);
+            if marked[idx] {
+                continue;
+            }
+            marked[idx] = true;
+            pending.extend(self.mem[idx].kind.children().filter_map(Self::object_of));
+        }
+
+        let mut freed = 0;
+        for idx in 0..self.mem.len() {
+            if marked[idx] || matches!(self.mem[idx].kind, ObjectKind::Free { .. }) {
+                continue;
+            }
+            let garbage = std::mem::replace(
+                &mut self.mem[idx],
+                Object {
+                    kind: ObjectKind::Free { next: self.free },
+                    rc: 0,
+                },
+            );
+            //Keep the reference count of the survivors in sync
+            for child in garbage.kind.children().filter_map(Self::object_of) {
+                if marked[usize::from(child)] {
+                    let rc = &mut self.mem[usize::from(child)].rc;
+                    *rc = rc.saturating_sub(1);
                 }
             }
+            self.free = ObjectIndex::new(idx as u64);
+            self.used_space -= 1;
+            self.used_size -= garbage.kind.size();
+            freed += 1;
+        }
+        freed
+    }
+
+    fn object_of(data: VMData) -> Option<ObjectIndex> {
+        match data.tag {
+            VMData::TAG_STR | VMData::TAG_LIST | VMData::TAG_OBJECT => Some(data.as_object()),
+            _ => None,
         }
-        let v = self.mem.get_mut(usize::from(index)).unwrap();
-        let repl = std::mem::replace(
+    }
+
+    /// Free the object & release the references it holds
+    pub fn free(&mut self, index: ObjectIndex) -> RuntimeResult<()> {
+        let v = &mut self.mem[usize::from(index)];
+        if let ObjectKind::Free { .. } = v.kind {
+            return Err(RuntimeError::NullReference);
+        }
+        let freed = std::mem::replace(
             v,
             Object {
-                kind: ObjectKind::Free { next },
+                kind: ObjectKind::Free { next: self.free },
                 rc: 0,
             },
         );
-        let res = match repl {
-            Object { kind: ObjectKind::Free { .. }, .. } => {
-                Err(RuntimeError::NullReference)
-            }
-            _ => {
-                Ok(())
-            }
-        };
         self.free = index;
-        res
+        self.used_space -= 1;
+        self.used_size -= freed.kind.size();
+        //The object is freed before its children, so a cycle going back to it stops there
+        for child in freed.kind.children().filter_map(Self::object_of) {
+            self.rc_dec(child)?;
+        }
+        Ok(())
     }
 
     #[inline(always)]
-    pub fn get(&mut self, index: ObjectIndex) -> RuntimeResult<ObjectKind> {
-        let kind = self.mem[usize::from(index)].kind.clone();
+    pub fn get(&mut self, index: ObjectIndex) -> RuntimeResult<ObjectKind<'mem>> {
+        let kind = self.peek(index)?.clone();
         self.rc_dec(index)?;
         Ok(kind)
     }
 
+    /// Like [`Memory::get`], without consuming a reference to the object
+    #[inline(always)]
+    pub fn peek(&self, index: ObjectIndex) -> RuntimeResult<&ObjectKind<'mem>> {
+        match &self.mem[usize::from(index)].kind {
+            ObjectKind::Free { .. } => Err(RuntimeError::NullReference),
+            kind => Ok(kind),
+        }
+    }
+
+    /// `value`, or the object it points to
+    pub fn describe(&self, value: VMData) -> String {
+        match value.is_object() {
+            true => match self.peek(value.as_object()) {
+                Ok(kind) => kind.to_string(),
+                Err(e) => format!("{} ({})", value, e),
+            },
+            false => value.to_string(),
+        }
+    }
+
     #[inline(always)]
     pub fn get_mut(&mut self, index: ObjectIndex) -> RuntimeResult<&mut ObjectKind<'mem>> {
+        self.peek(index)?;
         //You can decrement the rc here, because if it reaches 0 and still need to return a mutable reference, it's a bug
         self.rc_dec(index)?;
         let kind = &mut self.mem[usize::from(index)].kind;
@@ -133,7 +318,12 @@ impl<'mem> Memory<'mem> {
 
     #[inline(always)]
     pub fn rc_dec(&mut self, index: ObjectIndex) -> RuntimeResult<()> {
-        let rc = &mut self.mem[usize::from(index)].rc;
+        let obj = &mut self.mem[usize::from(index)];
+        //`delete` frees an object even if it's still referenced
+        if let ObjectKind::Free { .. } = obj.kind {
+            return Ok(());
+        }
+        let rc = &mut obj.rc;
         *rc -= 1;
         if *rc == 0 {
             self.free(index)?;
@@ -142,7 +332,7 @@ impl<'mem> Memory<'mem> {
     }
 
     #[inline(always)]
-    pub fn raw(&self) -> &[Object] {
+    pub fn raw(&self) -> &[Object<'mem>] {
         &self.mem
     }
 
@@ -164,11 +354,27 @@ impl std::fmt::Display for Memory<'_> {
     }
 }
 
+/// Snapshot of the [`Memory`] usage
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    /// Number of live objects
+    pub used_space: usize,
+    /// Number of objects the memory can hold before growing
+    pub capacity: usize,
+    /// Highest `used_space` reached
+    pub peak_space: usize,
+    /// Total number of allocations
+    pub allocations: usize,
+    /// Number of garbage collections
+    pub collections: usize,
+}
+
 #[derive(Debug, Clone)]
 pub enum ObjectKind<'mem> {
     String(String),
     Class(Class<'mem>),
     List(Vec<VMData>),
+    Union(Union),
     Free { next: ObjectIndex },
 }
 impl Default for ObjectKind<'_> {
@@ -197,6 +403,7 @@ impl std::fmt::Display for ObjectKind<'_> {
             ObjectKind::String(s) => write!(f, "`String`: \"{}\"", s),
             ObjectKind::Class(s) => write!(f, "{:?}", s),
             ObjectKind::List(l) => write!(f, "{:?}", l),
+            ObjectKind::Union(u) => write!(f, "{:?}", u),
             ObjectKind::Free { next } => write!(f, "Free: next -> {}", next),
         }
     }
@@ -207,6 +414,32 @@ impl<'mem> ObjectKind<'mem> {
         data.into()
     }
 
+    /// Every value directly held by this object
+    pub fn children(&self) -> Box<dyn Iterator<Item = VMData> + '_> {
+        match self {
+            ObjectKind::List(l) => Box::new(l.iter().copied()),
+            ObjectKind::Class(c) => Box::new(c.fields.values().copied()),
+            ObjectKind::Union(u) => Box::new(u.payload.iter().copied()),
+            ObjectKind::String(_) | ObjectKind::Free { .. } => Box::new(std::iter::empty()),
+        }
+    }
+
+    /// Bytes counted against [`Memory::max_size`]: the slot & what the object owns
+    pub fn size(&self) -> usize {
+        match self {
+            ObjectKind::String(s) => size_of::<Object>() + s.len(),
+            ObjectKind::List(l) => Self::list_size(l.len()),
+            ObjectKind::Class(c) => size_of::<Object>() + c.fields.len() * size_of::<(&str, VMData)>(),
+            ObjectKind::Union(u) => size_of::<Object>() + u.payload.len() * size_of::<VMData>(),
+            ObjectKind::Free { .. } => 0,
+        }
+    }
+
+    /// [`ObjectKind::size`] of a list of `len` values, so it's known before the list is allocated
+    pub fn list_size(len: usize) -> usize {
+        len.saturating_mul(size_of::<VMData>()).saturating_add(size_of::<Object>())
+    }
+
     pub fn string(&self) -> &String {
         match &self {
             ObjectKind::String(s) => s,
@@ -248,6 +481,13 @@ impl<'mem> ObjectKind<'mem> {
             _ => unreachable!("Expected a list, got a {:?}", self),
         }
     }
+
+    pub fn union(&self) -> &Union {
+        match &self {
+            ObjectKind::Union(u) => u,
+            _ => unreachable!("Expected a union, got a {:?}", self),
+        }
+    }
 }
 
 
@@ -273,3 +513,135 @@ impl From<Vec<VMData>> for ObjectKind<'_> {
 pub struct Class<'mem> {
     pub fields: HashMap<&'mem str, VMData>,
 }
+
+impl From<Union> for ObjectKind<'_> {
+    fn from(value: Union) -> Self {
+        ObjectKind::Union(value)
+    }
+}
+
+/// A union's value: the variant's tag (its index in the declaration) & its fields
+#[derive(Clone, Debug)]
+pub struct Union {
+    pub tag: usize,
+    pub payload: Vec<VMData>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node<'mem>(mem: &mut Memory<'mem>) -> ObjectIndex {
+        let mut fields = HashMap::new();
+        fields.insert("next", VMData::new_unit());
+        mem.put(ObjectKind::Class(Class { fields })).unwrap()
+    }
+
+    fn link(mem: &mut Memory, from: ObjectIndex, to: ObjectIndex) {
+        mem.rc_inc(to);
+        mem.raw_mut()[usize::from(from)]
+            .kind
+            .class_mut()
+            .fields
+            .insert("next", VMData::new_object(to));
+    }
+
+    #[test]
+    fn collect_frees_unreachable_cycles() {
+        let mut mem = Memory::new(8);
+        let a = node(&mut mem);
+        let b = node(&mut mem);
+        link(&mut mem, a, b);
+        link(&mut mem, b, a);
+        let rooted = node(&mut mem);
+        let kept = node(&mut mem);
+        link(&mut mem, rooted, kept);
+        assert_eq!(mem.used_space, 4);
+
+        let freed = mem.collect([VMData::new_object(rooted), VMData::new_i64(3)]);
+        assert_eq!(freed, 2);
+        assert_eq!(mem.used_space, 2);
+        assert!(matches!(mem.raw()[usize::from(a)].kind, ObjectKind::Free { .. }));
+        assert!(matches!(mem.raw()[usize::from(b)].kind, ObjectKind::Free { .. }));
+        assert_eq!(mem.raw()[usize::from(kept)].rc, 2);
+
+        //The freed slots are reused
+        node(&mut mem);
+        node(&mut mem);
+        assert_eq!(mem.used_space, 4);
+    }
+
+    #[test]
+    fn freeing_a_class_releases_its_fields() {
+        let mut mem = Memory::new(8);
+        let item = mem.put(ObjectKind::String(String::from("item"))).unwrap();
+        let list = mem.put(ObjectKind::List(vec![VMData::new_string(item)])).unwrap();
+        let holder = node(&mut mem);
+        link(&mut mem, holder, list);
+        mem.rc_dec(list).unwrap();
+        assert_eq!(mem.used_space, 3);
+
+        mem.rc_dec(holder).unwrap();
+        assert_eq!(mem.used_space, 0);
+        //Every slot is back in the free list
+        (0..8).for_each(|_| {
+            node(&mut mem);
+        });
+        assert_eq!(mem.capacity(), 8);
+    }
+
+    #[test]
+    fn memory_grows_up_to_max_space() {
+        let mut mem = Memory::new(2);
+        mem.max_space = Some(5);
+        let nodes = (0..5).map(|_| node(&mut mem)).collect::<Vec<_>>();
+        assert_eq!(mem.capacity(), 5);
+        assert!(matches!(mem.put(ObjectKind::String(String::new())), Err(RuntimeError::OutOfMemory)));
+
+        for n in nodes.iter().skip(1) {
+            mem.rc_dec(*n).unwrap();
+        }
+        mem.shrink();
+        assert_eq!(mem.capacity(), 2);
+        node(&mut mem);
+        node(&mut mem);
+        let stats = mem.stats();
+        assert_eq!(stats.used_space, 3);
+        assert_eq!(stats.peak_space, 5);
+        assert_eq!(stats.allocations, 7);
+    }
+
+    #[test]
+    fn max_size_counts_the_contents() {
+        let mut mem = Memory::new(8);
+        let string = mem.put(ObjectKind::String(String::from("abc"))).unwrap();
+        let list = mem.put(ObjectKind::List(vec![VMData::new_unit(); 4])).unwrap();
+        let slot = size_of::<Object>();
+        assert_eq!(mem.used_size, 2 * slot + 3 + 4 * size_of::<VMData>());
+
+        mem.max_size = Some(mem.used_size + ObjectKind::list_size(8) - 1);
+        assert!(mem.fits(ObjectKind::list_size(7)));
+        assert!(mem.needs_collection(ObjectKind::list_size(8)));
+        let big = ObjectKind::List(vec![VMData::new_unit(); 8]);
+        assert!(matches!(mem.put(big), Err(RuntimeError::OutOfMemory)));
+        assert!(!mem.fits(ObjectKind::list_size(usize::MAX)));
+
+        mem.rc_dec(list).unwrap();
+        mem.rc_dec(string).unwrap();
+        assert_eq!(mem.used_size, 0);
+        mem.put(ObjectKind::List(vec![VMData::new_unit(); 8])).unwrap();
+    }
+
+    #[test]
+    fn gc_threshold_triggers_collection() {
+        let mut mem = Memory::new(8);
+        mem.gc_threshold = Some(2);
+        node(&mut mem);
+        assert!(!mem.needs_collection(0));
+        node(&mut mem);
+        assert!(mem.needs_collection(0));
+        mem.collect([]);
+        assert!(!mem.needs_collection(0));
+        assert_eq!(mem.used_space, 0);
+    }
+}
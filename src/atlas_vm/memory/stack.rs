@@ -3,10 +3,8 @@ use crate::atlas_vm::{errors::RuntimeError, memory::vm_data::VMData, RuntimeResu
 use std::fmt::Display;
 use std::ops::Index;
 
-/// The size of the stack in bytes, 16384 is the maximum before it overflows "thread main"
-///
-/// I'll try allocating the stack into the heap later on so
-const STACK_SIZE: usize = 16 * 16384 / size_of::<VMData>();
+/// Default maximum number of values on the stack, 1 MiB worth of [`VMData`]
+pub const DEFAULT_STACK_SIZE: usize = 1024 * 1024 / size_of::<VMData>();
 /// The stack should be more used overall.
 ///
 /// And allow features such as holding objects themselves e.g.
@@ -15,12 +13,13 @@ const STACK_SIZE: usize = 16 * 16384 / size_of::<VMData>();
 /// fn access(&mut self, offset: usize) -> VMData {}
 /// ```
 ///
-/// The stack should also be able to create new stack frames with special rules
-/// to access data in other stack frames
+/// The values live on the heap and grow on demand, up to `max_size`.
+/// The frames are kept by the VM as [`CallFrame`] records.
 #[derive(Debug)]
 pub struct Stack {
-    values: [VMData; STACK_SIZE],
+    values: Vec<VMData>,
     pub top: usize,
+    max_size: usize,
 }
 impl Default for Stack {
     fn default() -> Self {
@@ -28,49 +27,72 @@ impl Default for Stack {
     }
 }
 
+/// Record of a function call currently running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallFrame {
+    /// Index of the called function in `Program::labels`
+    pub function: usize,
+    /// Position to jump back to once the function returns
+    pub return_pc: usize,
+    /// Base pointer, the local slots of the function are relative to it
+    pub bp: usize,
+}
+
 /// TODO: this implementation should be overhauled a bit cuz it's kinda clunky
 impl Stack {
     pub fn new() -> Self {
+        Self::with_max_size(DEFAULT_STACK_SIZE)
+    }
+    pub fn with_max_size(max_size: usize) -> Self {
         Self {
-            values: [VMData::new_unit(); STACK_SIZE],
+            values: Vec::new(),
             top: 0,
+            max_size,
         }
     }
+    /// Maximum number of values the stack can hold
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+    }
     pub fn clear(&mut self) {
         self.top = 0;
     }
 
-    pub fn push(&mut self, val: VMData) -> Result<(), RuntimeError> {
-        if self.top < STACK_SIZE {
+    #[inline(always)]
+    fn write_top(&mut self, val: VMData) -> Result<(), RuntimeError> {
+        if self.top < self.values.len() {
             self.values[self.top] = val;
-            self.top += 1;
-            Ok(())
+        } else if self.top < self.max_size {
+            self.values.push(val);
         } else {
-            Self::push_stack_overflow()
+            return Self::push_stack_overflow();
         }
+        self.top += 1;
+        Ok(())
+    }
+
+    pub fn push(&mut self, val: VMData) -> Result<(), RuntimeError> {
+        self.write_top(val)
     }
     pub fn push_with_rc(&mut self, val: VMData, mem: &mut Memory) -> Result<(), RuntimeError> {
-        if self.top < STACK_SIZE {
-            self.values[self.top] = val;
-            match val.tag {
-                VMData::TAG_OBJECT | VMData::TAG_LIST | VMData::TAG_STR => {
-                    mem.rc_inc(val.as_object());
-                }
-                _ => {}
+        self.write_top(val)?;
+        match val.tag {
+            VMData::TAG_OBJECT | VMData::TAG_LIST | VMData::TAG_STR => {
+                mem.rc_inc(val.as_object());
             }
-            self.top += 1;
-            Ok(())
-        } else {
-            Self::push_stack_overflow()
+            _ => {}
         }
+        Ok(())
     }
 
     #[inline(always)]
     pub fn truncate(&mut self, new_top: usize, mem: &mut Memory) -> RuntimeResult<()> {
-        for i in new_top..=self.top {
+        for i in new_top..self.top {
             match self.values[i].tag {
                 VMData::TAG_OBJECT | VMData::TAG_LIST | VMData::TAG_STR => {
-                    println!("Decrementing reference count of object: {}", self.values[i]);
                     mem.rc_dec(self.values[i].as_object())?;
                 }
                 _ => {}
@@ -102,7 +124,6 @@ impl Stack {
         let r = self.values[self.top];
         match r.tag {
             VMData::TAG_OBJECT | VMData::TAG_LIST | VMData::TAG_STR => {
-                println!("Decrementing reference count of object: {}", r);
                 mem.rc_dec(r.as_object())?;
             }
             _ => {}
@@ -132,36 +153,44 @@ impl Stack {
     }
 
     pub fn extends(&mut self, values: &[VMData]) -> Result<(), RuntimeError> {
-        if self.top + values.len() < STACK_SIZE {
-            for val in values {
-                self.values[self.top] = *val;
-                self.top += 1;
-            }
-            Ok(())
-        } else {
-            Err(RuntimeError::StackOverflow)
+        if self.top + values.len() > self.max_size {
+            return Err(RuntimeError::StackOverflow);
         }
+        for val in values {
+            self.write_top(*val)?;
+        }
+        Ok(())
     }
 
     pub fn push_object(&mut self, _obj: &[VMData]) -> Result<(), RuntimeError> {
         unimplemented!("push_object(&mut self, obj: &[VMData])")
     }
 
-    pub fn new_stack_frame(&mut self) {}
-
-    pub fn set(&mut self, _offset: usize) {}
+    /// Replace the value at `offset`, returning the previous one
+    ///
+    /// The caller is in charge of the reference count of both values
+    pub fn set(&mut self, offset: usize, val: VMData) -> RuntimeResult<VMData> {
+        if offset < self.top {
+            Ok(std::mem::replace(&mut self.values[offset], val))
+        } else {
+            Err(RuntimeError::IndexOutOfBounds {
+                index: offset as i64,
+                len: self.top,
+            })
+        }
+    }
 
-    pub fn iter(&self) -> std::slice::Iter<VMData> {
+    pub fn iter(&self) -> std::slice::Iter<'_, VMData> {
         self.values[..self.top].iter()
     }
 }
 
 impl IntoIterator for Stack {
     type Item = VMData;
-    type IntoIter = std::vec::IntoIter<Self::Item>;
+    type IntoIter = std::iter::Take<std::vec::IntoIter<Self::Item>>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.values[..self.top].to_vec().into_iter()
+        self.values.into_iter().take(self.top)
     }
 }
 
@@ -188,3 +217,21 @@ impl Display for Stack {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_fails_past_max_size() {
+        let mut stack = Stack::with_max_size(2);
+        stack.push(VMData::new_i64(1)).unwrap();
+        stack.push(VMData::new_i64(2)).unwrap();
+        assert!(matches!(stack.push(VMData::new_i64(3)), Err(RuntimeError::StackOverflow)));
+
+        stack.set_max_size(3);
+        stack.push(VMData::new_i64(3)).unwrap();
+        assert_eq!(stack.pop().unwrap().as_i64(), 3);
+        assert_eq!(stack.iter().count(), 2);
+    }
+}
@@ -223,7 +223,7 @@ impl std::fmt::Debug for VMData {
                 _ if self.is_object() => "obj",
                 _ => "res",
             },
-            format!("{}", self)
+            self
         )
     }
 }
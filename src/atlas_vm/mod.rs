@@ -1,99 +1,226 @@
+pub mod debug;
 pub mod errors;
 pub mod memory;
 pub mod runtime;
 pub mod libraries;
+pub mod native;
+pub mod profiler;
+pub mod sandbox;
+mod dispatch;
 
-use errors::RuntimeError;
-use libraries::{
-    fs::FILE_FUNCTIONS, io::IO_FUNCTIONS, list::LIST_FUNCTIONS, math::MATH_FUNCTIONS,
-    string::STRING_FUNCTIONS, time::TIME_FUNCTIONS,
-};
-use runtime::{arena::RuntimeArena, instruction::{Instruction, Label, Program, Type}};
+use debug::{DebugHook, Local};
+use errors::{RuntimeError, TraceFrame};
+use miette::SourceSpan;
+use native::{NativeFn, NativeModule};
+use sandbox::{CancelHandle, Capabilities, Limits};
+use runtime::{arena::RuntimeArena, instruction::{ConstantClass, Instruction, Program, Type}};
 use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use crate::atlas_vm::memory::object_map::{Class, ObjectKind};
-use crate::atlas_vm::memory::varmap::{Key, VarMap};
-use crate::atlas_vm::memory::{object_map::Memory, stack::Stack, vm_data::VMData};
+use crate::atlas_vm::memory::object_map::{Class, HeapStats, ObjectIndex, ObjectKind, Union};
+use crate::atlas_vm::memory::{
+    object_map::Memory,
+    stack::{CallFrame, Stack},
+    vm_data::VMData,
+};
 
 pub type RuntimeResult<T> = Result<T, RuntimeError>;
 pub type CallBack = fn(runtime::vm_state::VMState) -> RuntimeResult<VMData>;
 
 pub struct Atlas77VM<'run> {
     pub program: Program<'run>,
+    /// Every label body of `program` laid out contiguously, `pc` indexes into it
+    pub code: &'run [Instruction<'run>],
     pub stack: Stack,
-    stack_frame: Vec<(usize, usize)>, //previous pc and previous stack top
+    /// Every function call currently running, the entry point included
+    frames: Vec<CallFrame>,
+    /// Base pointer of the last frame, local slots are relative to it
+    bp: usize,
     pub object_map: Memory<'run>,
     pub runtime_arena: RuntimeArena<'run>,
-    pub var_map: VarMap<'run>,
-    pub extern_fn: HashMap<&'run str, CallBack>,
+    pub extern_fn: HashMap<String, NativeFn>,
+    /// `extern_fn` resolved for each entry of the program's `native_pool`
+    natives: Vec<Option<NativeFn>>,
+    /// Instructions left before the run fails, see [`Limits::fuel`]
+    fuel: Option<u64>,
+    timeout: Option<Duration>,
+    /// End of the wall time given to the current run
+    deadline: Option<Instant>,
+    cancel: Option<CancelHandle>,
+    /// Instructions executed since the last check of `deadline` & `cancel`
+    steps: u32,
+    hook: Option<Box<dyn DebugHook<'run> + 'run>>,
     pub pc: usize,
 }
 
+/// Reading the clock & the cancel flag at every instruction would slow everything down
+const INTERRUPT_CHECK_INTERVAL: u32 = 1024;
+
 impl<'run> Atlas77VM<'run> {
-    pub fn new(program: Program<'run>, runtime_arena: RuntimeArena<'run>) -> Self {
-        let mut extern_fn: HashMap<&str, CallBack> = HashMap::new();
-        program.libraries.iter().for_each(|lib| {
+    pub fn new(program: Program<'run>, runtime_arena: RuntimeArena<'run>) -> RuntimeResult<Self> {
+        Self::with_natives(program, runtime_arena, &[], &Capabilities::all())
+    }
+    /// Libraries imported by `program` that aren't standard ones are looked up in `modules`
+    ///
+    /// Fails with `LibraryNotAllowed` if `program` imports a standard library `capabilities` doesn't allow
+    pub fn with_natives(
+        program: Program<'run>,
+        runtime_arena: RuntimeArena<'run>,
+        modules: &[NativeModule],
+        capabilities: &Capabilities,
+    ) -> RuntimeResult<Self> {
+        let mut extern_fn: HashMap<String, NativeFn> = HashMap::new();
+        for lib in program.libraries.iter() {
             if lib.is_std {
-                let lib_name = lib.name.split('/').last().unwrap();
-                match lib_name {
-                    "file" => {
-                        FILE_FUNCTIONS.iter().for_each(|(name, func)| {
-                            extern_fn.insert(name, *func);
-                        });
-                    }
-                    "io" => {
-                        IO_FUNCTIONS.iter().for_each(|(name, func)| {
-                            extern_fn.insert(name, *func);
-                        });
-                    }
-                    "list" => {
-                        LIST_FUNCTIONS.iter().for_each(|(name, func)| {
-                            extern_fn.insert(name, *func);
-                        });
-                    }
-                    "math" => {
-                        MATH_FUNCTIONS.iter().for_each(|(name, func)| {
-                            extern_fn.insert(name, *func);
-                        });
-                    }
-                    "string" => {
-                        STRING_FUNCTIONS.iter().for_each(|(name, func)| {
-                            extern_fn.insert(name, *func);
-                        });
-                    }
-                    "time" => {
-                        TIME_FUNCTIONS.iter().for_each(|(name, func)| {
-                            extern_fn.insert(name, *func);
-                        });
-                    }
-                    _ => panic!("Unknown standard libraries"),
+                if !capabilities.allows(&lib.name) {
+                    return Err(RuntimeError::LibraryNotAllowed(lib.name.clone()));
+                }
+                let functions = libraries::std_library(&lib.name)
+                    .ok_or_else(|| RuntimeError::UnknownLibrary(lib.name.clone()))?;
+                for (name, func) in functions {
+                    extern_fn.insert(name.to_string(), Rc::new(*func));
+                }
+            } else {
+                let module = modules
+                    .iter()
+                    .find(|module| module.name() == lib.name)
+                    .ok_or_else(|| RuntimeError::UnknownLibrary(lib.name.clone()))?;
+                for function in module.functions() {
+                    extern_fn.insert(function.name.clone(), function.callback.clone());
                 }
             }
-        });
-        Self {
+        }
+        let natives = program
+            .global
+            .native_pool
+            .iter()
+            .map(|name| extern_fn.get(*name).cloned())
+            .collect();
+        Ok(Self {
+            code: runtime_arena.alloc_slice(program.flatten()),
             program,
             stack: Stack::new(),
-            stack_frame: Vec::new(),
+            frames: Vec::new(),
             object_map: Memory::new(256),
-            var_map: VarMap::new(),
+            bp: 0,
             runtime_arena,
             extern_fn,
+            natives,
+            fuel: None,
+            timeout: None,
+            deadline: None,
+            cancel: None,
+            steps: 0,
+            hook: None,
             pc: 0,
+        })
+    }
+    /// Bound the next runs, the heap & stack caps apply right away
+    pub fn set_limits(&mut self, limits: &Limits) {
+        self.fuel = limits.fuel;
+        self.timeout = limits.timeout;
+        self.object_map.max_size = limits.max_heap;
+        if let Some(max_stack) = limits.max_stack {
+            self.stack.set_max_size(max_stack);
         }
     }
+    /// Fuel left, `None` if the VM has no instruction budget
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+    /// The running program stops soon after `handle` is cancelled
+    pub fn set_cancel_handle(&mut self, handle: CancelHandle) {
+        self.cancel = Some(handle);
+    }
+    /// Let `hook` follow the next runs, see [`DebugHook`]
+    pub fn set_debug_hook(&mut self, hook: impl DebugHook<'run> + 'run) {
+        self.hook = Some(Box::new(hook));
+    }
+    pub fn take_debug_hook(&mut self) -> Option<Box<dyn DebugHook<'run> + 'run>> {
+        self.hook.take()
+    }
+    /// Arguments & locals of `frame`, the ones not reserved yet are left out
+    pub fn locals(&self, frame: &CallFrame) -> Vec<Local<'run>> {
+        self.program.labels[frame.function]
+            .locals
+            .iter()
+            .enumerate()
+            .filter(|(slot, _)| frame.bp + slot < self.stack.top)
+            .map(|(slot, name)| Local {
+                name,
+                value: self.stack[frame.bp + slot],
+            })
+            .collect()
+    }
+    /// Free every object unreachable from the stack, reference cycles included
+    ///
+    /// Between two instructions, the stack holds every live reference.
+    /// Returns the number of freed objects
+    pub fn collect_garbage(&mut self) -> usize {
+        self.object_map.collect(self.stack.iter().copied())
+    }
+    pub fn heap_stats(&self) -> HeapStats {
+        self.object_map.stats()
+    }
     pub fn reset(&mut self) {
         self.stack.clear();
-        self.stack_frame.clear();
+        self.frames.clear();
         self.object_map.clear();
-        self.var_map.var_map.clear();
+        self.bp = 0;
         self.pc = 0;
     }
+    /// Maximum number of values on the stack, deep recursions fail with a `StackOverflow` past it
+    pub fn set_stack_size(&mut self, size: usize) {
+        self.stack.set_max_size(size);
+    }
+    /// Active calls, from the entry point to the running function
+    pub fn frames(&self) -> &[CallFrame] {
+        &self.frames
+    }
+    /// Name of the function running in `frame`
+    pub fn frame_name(&self, frame: &CallFrame) -> &'run str {
+        self.program.labels[frame.function].name
+    }
+    /// Function & statement being executed by every active call, innermost first
+    ///
+    /// Only meaningful for [`Atlas77VM::run`], as `pc` is an instruction index there
+    pub fn backtrace(&self) -> Vec<TraceFrame> {
+        let mut pc = self.pc;
+        let mut trace = Vec::with_capacity(self.frames.len());
+        for frame in self.frames.iter().rev() {
+            let label = &self.program.labels[frame.function];
+            let span = pc
+                .checked_sub(label.position)
+                .and_then(|offset| label.span_at(offset))
+                .map(|entry| SourceSpan::from(entry.start..entry.end));
+            trace.push(TraceFrame {
+                function: label.name.to_string(),
+                span,
+            });
+            //The caller is on its call instruction
+            pc = frame.return_pc.wrapping_sub(1);
+        }
+        trace
+    }
+    /// Run the entry point until it returns, halts or calls `exit`
+    ///
+    /// Returns the code given to `exit`, the value returned by the entry point, or unit if it halted
     pub fn run(&mut self) -> RuntimeResult<VMData> {
+        let entry_point = self.program.entry_point.clone();
+        self.invoke(&entry_point, &[])
+    }
+    /// Call `function` with `args` and run it until it returns, like [`Atlas77VM::run`] does for the entry point
+    ///
+    /// The arguments are moved to the callee, objects among them must already be in `object_map`.
+    /// The VM has to be fresh (or [`Atlas77VM::reset`]), as the function pool is pushed first.
+    pub fn invoke(&mut self, function: &str, args: &[VMData]) -> RuntimeResult<VMData> {
         let label = self
             .program
             .labels
             .iter()
-            .find(|label| label.name == self.program.entry_point);
+            .position(|label| label.name == function)
+            .ok_or_else(|| RuntimeError::EntryPointNotFound(function.to_string()))?;
 
         self.stack.extends(
             &self
@@ -104,157 +231,115 @@ impl<'run> Atlas77VM<'run> {
                 .map(|t| VMData::new_fn_ptr(*t))
                 .collect::<Vec<_>>(),
         )?;
-        if let Some(label) = label {
-            self.pc = label.position;
-        } else {
-            return Err(RuntimeError::EntryPointNotFound(
-                self.program.entry_point.to_string(),
-            ));
+        self.stack.extends(args)?;
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        self.check_interrupts()?;
+        let code = self.code;
+        self.call(label, self.program.labels[label].position, args.len() as u8, code.len());
+        let res = self.interpret(code);
+        self.exit_value(res)
+    }
+
+    fn interpret(&mut self, code: &'run [Instruction<'run>]) -> RuntimeResult<()> {
+        while self.pc < code.len() {
+            self.tick()?;
+            if self.hook.is_some() {
+                self.notify(|hook, vm| hook.before_instruction(vm))?;
+            }
+            if let Err(e) = self.execute_instruction(&code[self.pc]) {
+                if self.hook.is_some() && !matches!(e, RuntimeError::Exit(_)) {
+                    self.notify(|hook, vm| {
+                        hook.on_error(vm, &e);
+                        Ok(())
+                    })?;
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand the VM to the debug hook, it's put back afterward
+    fn notify(
+        &mut self,
+        f: impl FnOnce(&mut dyn DebugHook<'run>, &Self) -> RuntimeResult<()>,
+    ) -> RuntimeResult<()> {
+        match self.hook.take() {
+            Some(mut hook) => {
+                let res = f(hook.as_mut(), self);
+                self.hook = Some(hook);
+                res
+            }
+            None => Ok(()),
         }
-        while self.pc < self.program.len() {
-            //println!("Instruction: {:?}", self.program[self.pc]);
-            let instr = self.program[self.pc].clone();
-            self.execute_instruction(instr.clone())?;
-            //println!("Stack: {}", self.stack);
-            //println!("VarMap: {{ {} }}", self.var_map.var_map.iter().map(|(k, v)| format!("{}: {}", k.key, v)).collect::<Vec<_>>().join(", "));
-            //println!("ObjectMap: {{\n{}}}", self.object_map);
+    }
+
+    /// Consume one unit of fuel before executing an instruction
+    #[inline(always)]
+    fn tick(&mut self) -> RuntimeResult<()> {
+        if let Some(fuel) = &mut self.fuel {
+            if *fuel == 0 {
+                return Err(RuntimeError::OutOfFuel);
+            }
+            *fuel -= 1;
+        }
+        self.steps += 1;
+        if self.steps == INTERRUPT_CHECK_INTERVAL {
+            self.steps = 0;
+            self.check_interrupts()?;
+        }
+        Ok(())
+    }
+
+    fn check_interrupts(&self) -> RuntimeResult<()> {
+        if self.cancel.as_ref().is_some_and(|handle| handle.is_cancelled()) {
+            return Err(RuntimeError::Cancelled);
+        }
+        if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(RuntimeError::Timeout);
+        }
+        Ok(())
+    }
+
+    /// Value the program exited with once the execution loop is done.
+    ///
+    /// The entry point returned if its frame is gone, otherwise it halted
+    fn exit_value(&self, res: RuntimeResult<()>) -> RuntimeResult<VMData> {
+        match res {
+            Err(RuntimeError::Exit(code)) => Ok(VMData::new_i64(code)),
+            Err(e) => Err(e),
+            Ok(()) if self.frames.is_empty() => self.stack.last().copied(),
+            Ok(()) => Ok(VMData::new_unit()),
         }
-        self.stack.top += 1;
-        self.stack.last().cloned()
     }
 }
 impl<'run> Atlas77VM<'run> {
     /// TODO: Add check for unsigned int
-    pub fn execute_instruction(&mut self, instr: Instruction<'run>) -> RuntimeResult<()> {
-        match instr {
-            Instruction::DeleteObj => {
-                let obj = self.stack.pop()?;
-                self.object_map.free(obj.as_object())?;
-                self.pc += 1;
-            }
-            Instruction::NewObj { class_name } => {
-                let class = self.
-                    program
-                    .global
-                    .class_pool
-                    .iter()
-                    .find(|c| c.name == class_name).unwrap();
-                let mut fields = HashMap::new();
-                for field in class.fields.iter() {
-                    fields.insert(*field, VMData::new_unit());
-                }
-                let class_ptr = self.object_map.put(ObjectKind::Class(Class {
-                    fields
-                }))?;
-                self.stack.push(VMData::new_object(class_ptr))?;
-                self.pc += 1;
-            }
-            Instruction::GetField { field_name } => {
-                let obj = self.stack.pop()?;
-                let obj_ptr = obj.as_object();
-                let raw_obj = self.object_map.get(obj_ptr)?;
-                let class = raw_obj.class();
-                let field = class.fields.get(field_name).unwrap();
-                self.stack.push_with_rc(*field, &mut self.object_map)?;
-                self.pc += 1;
-            }
-            Instruction::SetField { field_name } => {
-                let val = self.stack.pop()?;
-                match val.tag {
-                    VMData::TAG_OBJECT | VMData::TAG_LIST | VMData::TAG_STR => {
-                        self.object_map.rc_inc(val.as_object());
-                    }
-                    _ => {}
-                }
-                let obj = self.stack.pop()?;
-                let obj_ptr = obj.as_object();
-                let raw_obj = self.object_map.get_mut(obj_ptr)?;
-                let class = raw_obj.class_mut();
-                class.fields.insert(field_name, val);
-                //println!("ObjectMap: {}", self.object_map);
-                self.pc += 1;
-            }
-            Instruction::PushInt(i) => {
-                let val = VMData::new_i64(i);
-                self.stack.push(val)?;
-                self.pc += 1;
-            }
-            Instruction::PushFloat(f) => {
-                let val = VMData::new_f64(f);
-                self.stack.push(val)?;
-                self.pc += 1;
-            }
-            Instruction::PushUnsignedInt(u) => {
-                let val = VMData::new_u64(u);
-                self.stack.push(val)?;
-                self.pc += 1;
-            }
-            Instruction::PushChar(c) => {
-                let val = VMData::new_char(c);
-                self.stack.push(val)?;
-                self.pc += 1;
-            }
-            Instruction::PushUnit => {
-                let val = VMData::new_unit();
-                self.stack.push(val)?;
-                self.pc += 1;
-            }
-            Instruction::PushBool(b) => {
-                let val = VMData::new_bool(b);
-                self.stack.push(val)?;
-                self.pc += 1;
-            }
-            Instruction::PushStr(u) => {
-                let string = self.program.global.string_pool[u];
-                let ptr = match self.object_map.put(ObjectKind::String(String::from(string))) {
-                    Ok(ptr) => ptr,
-                    Err(_) => return Err(RuntimeError::OutOfMemory),
-                };
-                self.stack.push(VMData::new_string(ptr))?;
-                self.pc += 1;
-            }
-            Instruction::Lt => {
-                let a = self.stack.pop()?;
-                let b = self.stack.pop()?;
-                let res = VMData::new_bool(b.as_i64() < a.as_i64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
-            Instruction::Lte => {
-                let a = self.stack.pop()?;
-                let b = self.stack.pop()?;
-                let res = VMData::new_bool(b.as_i64() <= a.as_i64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
-            Instruction::Gt => {
-                let a = self.stack.pop()?;
-                let b = self.stack.pop()?;
-                let res = VMData::new_bool(b.as_i64() > a.as_i64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
-            Instruction::Gte => {
-                let a = self.stack.pop()?;
-                let b = self.stack.pop()?;
-                let res = VMData::new_bool(b.as_i64() >= a.as_i64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
-            Instruction::Eq => {
-                let a = self.stack.pop()?;
-                let b = self.stack.pop()?;
-                let res = VMData::new_bool(b.as_i64() == a.as_i64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
-            Instruction::Neq => {
-                let a = self.stack.pop()?;
-                let b = self.stack.pop()?;
-                let res = VMData::new_bool(b.as_i64() != a.as_i64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
+    pub fn execute_instruction(&mut self, instr: &Instruction<'run>) -> RuntimeResult<()> {
+        match *instr {
+            Instruction::DeleteObj => self.delete_obj()?,
+            Instruction::LinkedNewObj { class } => {
+                let class = &self.program.global.class_pool[class];
+                self.new_obj(class)?;
+            }
+            Instruction::GetField { field_name } => self.get_field(field_name)?,
+            Instruction::NewUnion { tag, nb_fields } => self.new_union(tag, nb_fields)?,
+            Instruction::UnionTag => self.union_tag()?,
+            Instruction::UnionField(i) => self.union_field(i)?,
+            Instruction::SetField { field_name } => self.set_field(field_name)?,
+            Instruction::PushInt(i) => self.stack.push(VMData::new_i64(i))?,
+            Instruction::PushFloat(f) => self.stack.push(VMData::new_f64(f))?,
+            Instruction::PushUnsignedInt(u) => self.stack.push(VMData::new_u64(u))?,
+            Instruction::PushChar(c) => self.stack.push(VMData::new_char(c))?,
+            Instruction::PushUnit => self.stack.push(VMData::new_unit())?,
+            Instruction::PushBool(b) => self.stack.push(VMData::new_bool(b))?,
+            Instruction::PushStr(u) => self.push_str(u)?,
+            Instruction::Lt => self.binary_op(|a, b| VMData::new_bool(a.as_i64() < b.as_i64()))?,
+            Instruction::Lte => self.binary_op(|a, b| VMData::new_bool(a.as_i64() <= b.as_i64()))?,
+            Instruction::Gt => self.binary_op(|a, b| VMData::new_bool(a.as_i64() > b.as_i64()))?,
+            Instruction::Gte => self.binary_op(|a, b| VMData::new_bool(a.as_i64() >= b.as_i64()))?,
+            Instruction::Eq => self.equality(true)?,
+            Instruction::Neq => self.equality(false)?,
             Instruction::JmpZ { pos } => {
                 let cond = self.stack.pop()?;
                 if !cond.as_bool() {
@@ -262,359 +347,486 @@ impl<'run> Atlas77VM<'run> {
                 } else {
                     self.pc += 1;
                 }
+                return Ok(());
             }
-            Instruction::CastTo(t) => {
-                let val = self.stack.pop_with_rc(&mut self.object_map)?;
-                let res = match t {
-                    Type::String => {
-                        let string = val.to_string();
-                        let ptr = match self.object_map.put(ObjectKind::String(string)) {
-                            Ok(ptr) => {
-                                ptr
-                            }
-                            Err(_) => return Err(RuntimeError::OutOfMemory),
-                        };
-                        VMData::new_string(ptr)
-                    }
-                    Type::Char => {
-                        match val.tag {
-                            VMData::TAG_STR => {
-                                let raw_string = self.object_map.get(val.as_object())?;
-                                let string = raw_string.string();
-                                if string.len() != 1 {
-                                    return Err(RuntimeError::InvalidCast(
-                                        val.tag,
-                                        Type::Char,
-                                    ));
-                                }
-                                VMData::new_char(string.chars().next().unwrap())
-                            }
-                            _ => {
-                                VMData::new_char(val.as_char())
-                            }
-                        }
-                    }
-                    Type::Boolean => {
-                        match val.tag {
-                            VMData::TAG_STR => {
-                                let raw_string = self.object_map.get(val.as_object())?;
-                                let string = raw_string.string();
-                                VMData::new_bool(string.parse::<bool>().unwrap())
-                            }
-                            _ => VMData::new_bool(val.as_bool()),
-                        }
-                    }
-                    Type::Float => {
-                        match val.tag {
-                            VMData::TAG_STR => {
-                                let raw_string = self.object_map.get(val.as_object())?;
-                                let string = raw_string.string();
-                                VMData::new_f64(string.parse::<f64>().unwrap())
-                            }
-                            VMData::TAG_U64 => VMData::new_f64(val.as_u64() as f64),
-                            VMData::TAG_I64 => VMData::new_f64(val.as_i64() as f64),
-                            VMData::TAG_BOOL => VMData::new_f64(val.as_bool() as i64 as f64),
-                            VMData::TAG_CHAR => VMData::new_f64(val.as_char() as i64 as f64),
-                            _ => unreachable!("Invalid cast to float"),
-                        }
-                    }
-                    Type::Integer => {
-                        match val.tag {
-                            VMData::TAG_STR => {
-                                let raw_string = self.object_map.get(val.as_object())?;
-                                let string = raw_string.string();
-                                VMData::new_i64(string.parse::<i64>().unwrap())
-                            }
-                            VMData::TAG_U64 => VMData::new_i64(val.as_u64() as i64),
-                            VMData::TAG_FLOAT => VMData::new_i64(val.as_f64() as i64),
-                            VMData::TAG_BOOL => VMData::new_i64(val.as_bool() as i64),
-                            VMData::TAG_CHAR => VMData::new_i64(val.as_char() as i64),
-                            _ => unreachable!("Invalid cast to integer"),
-                        }
-                    }
-                    Type::UnsignedInteger => {
-                        match val.tag {
-                            VMData::TAG_STR => {
-                                let raw_string = self.object_map.get(val.as_object())?;
-                                let string = raw_string.string();
-                                VMData::new_u64(string.parse::<u64>().unwrap())
-                            }
-                            VMData::TAG_I64 => VMData::new_u64(val.as_i64() as u64),
-                            VMData::TAG_FLOAT => VMData::new_u64(val.as_f64() as u64),
-                            VMData::TAG_BOOL => VMData::new_u64(val.as_bool() as u64),
-                            VMData::TAG_CHAR => VMData::new_u64(val.as_char() as u64),
-                            _ => unreachable!("Invalid cast to unsigned integer"),
-                        }
-                    }
-                };
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
+            Instruction::CastTo(t) => self.cast_to(t)?,
             Instruction::Jmp { pos } => {
                 self.pc = (self.pc as isize + pos) as usize;
+                return Ok(());
             }
-            Instruction::Store { var_name } => {
-                let val = self.stack.pop()?;
-
-                self.var_map.insert(Key {
-                    scope: self.stack_frame.len(),
-                    name: var_name,
-                }, val, &mut self.object_map)?;
-                self.pc += 1;
-            }
-            Instruction::Load { var_name } => {
-                let key = Key {
-                    scope: self.stack_frame.len(),
-                    name: var_name,
-                };
-                let val = self.var_map.get(&key).unwrap();
-                self.stack.push_with_rc(*val, &mut self.object_map)?;
-                self.pc += 1;
+            Instruction::JmpTable { min, len } => {
+                self.pc += 1 + self.jump_table_index(min, len)?;
+                return Ok(());
             }
+            Instruction::Store(slot) => self.store_local(slot)?,
+            Instruction::Get(slot) => self.get_local(slot)?,
+            Instruction::Reserve(n) => self.reserve(n)?,
             Instruction::Pop => {
                 self.stack.pop_with_rc(&mut self.object_map)?;
-                self.pc += 1;
             }
             Instruction::Swap => {
                 let val1 = self.stack.pop()?;
                 let val2 = self.stack.pop()?;
                 self.stack.push(val1)?;
                 self.stack.push(val2)?;
-                self.pc += 1;
             }
             Instruction::Dup => {
                 let val = *self.stack.last()?;
                 self.stack.push_with_rc(val, &mut self.object_map)?;
-                self.pc += 1;
-            }
-            Instruction::IMul => {
-                let a = self.stack.pop()?;
-                let b = self.stack.pop()?;
-                let res = VMData::new_i64(b.as_i64() * a.as_i64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
-            Instruction::FMul => {
-                let a = self.stack.pop()?;
-                let b = self.stack.pop()?;
-                let res = VMData::new_f64(b.as_f64() * a.as_f64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
-            Instruction::UIMul => {
-                let a = self.stack.pop()?;
-                let b = self.stack.pop()?;
-                let res = VMData::new_u64(b.as_u64() * a.as_u64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
-            Instruction::IDiv => {
-                let a = self.stack.pop()?;
-                if a == VMData::new_i64(0) {
-                    return Err(RuntimeError::DivisionByZero);
-                }
-                let b = self.stack.pop()?;
-                let res = VMData::new_i64(b.as_i64() / a.as_i64());
-                self.stack.push(res)?;
-                self.pc += 1;
             }
-            Instruction::FDiv => {
-                let a = self.stack.pop()?;
-                if a == VMData::new_f64(0.0) {
-                    return Err(RuntimeError::DivisionByZero);
-                }
-                let b = self.stack.pop()?;
-                let res = VMData::new_f64(b.as_f64() / a.as_f64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
-            Instruction::UIDiv => {
-                let a = self.stack.pop()?;
-                if a == VMData::new_u64(0) {
-                    return Err(RuntimeError::DivisionByZero);
-                }
-                let b = self.stack.pop()?;
-                let res = VMData::new_u64(b.as_u64() / a.as_u64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
-            Instruction::IAdd => {
-                let a = self.stack.pop()?;
-                let b = self.stack.pop()?;
-                let res = VMData::new_i64(b.as_i64() + a.as_i64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
-            Instruction::FAdd => {
-                let a = self.stack.pop()?;
-                let b = self.stack.pop()?;
-                let res = VMData::new_f64(b.as_f64() + a.as_f64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
-            Instruction::UIAdd => {
-                let a = self.stack.pop()?;
-                let b = self.stack.pop()?;
-                let res = VMData::new_u64(b.as_u64() + a.as_u64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
-            Instruction::ISub => {
-                let a = self.stack.pop()?;
-                let b = self.stack.pop()?;
-                let res = VMData::new_i64(b.as_i64() - a.as_i64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
-            Instruction::FSub => {
-                let a = self.stack.pop()?;
-                let b = self.stack.pop()?;
-                let res = VMData::new_f64(b.as_f64() - a.as_f64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
-            Instruction::UISub => {
-                let a = self.stack.pop()?;
-                let b = self.stack.pop()?;
-                let res = VMData::new_u64(b.as_u64() - a.as_u64());
-                self.stack.push(res)?;
-                self.pc += 1;
-            }
-            Instruction::IMod => {
-                let a = self.stack.pop()?;
-                let b = self.stack.pop()?;
-                let res = VMData::new_i64(b.as_i64() % a.as_i64());
-                self.stack.push(res)?;
-                self.pc += 1;
+            Instruction::IMul => self.binary_op(|a, b| VMData::new_i64(a.as_i64() * b.as_i64()))?,
+            Instruction::FMul => self.binary_op(|a, b| VMData::new_f64(a.as_f64() * b.as_f64()))?,
+            Instruction::UIMul => self.binary_op(|a, b| VMData::new_u64(a.as_u64() * b.as_u64()))?,
+            Instruction::IDiv => self.division(VMData::new_i64(0), |a, b| VMData::new_i64(a.as_i64() / b.as_i64()))?,
+            Instruction::FDiv => self.division(VMData::new_f64(0.0), |a, b| VMData::new_f64(a.as_f64() / b.as_f64()))?,
+            Instruction::UIDiv => self.division(VMData::new_u64(0), |a, b| VMData::new_u64(a.as_u64() / b.as_u64()))?,
+            Instruction::IAdd => self.binary_op(|a, b| VMData::new_i64(a.as_i64() + b.as_i64()))?,
+            Instruction::FAdd => self.binary_op(|a, b| VMData::new_f64(a.as_f64() + b.as_f64()))?,
+            Instruction::UIAdd => self.binary_op(|a, b| VMData::new_u64(a.as_u64() + b.as_u64()))?,
+            Instruction::ISub => self.binary_op(|a, b| VMData::new_i64(a.as_i64() - b.as_i64()))?,
+            Instruction::FSub => self.binary_op(|a, b| VMData::new_f64(a.as_f64() - b.as_f64()))?,
+            Instruction::UISub => self.binary_op(|a, b| VMData::new_u64(a.as_u64() - b.as_u64()))?,
+            Instruction::IMod => self.binary_op(|a, b| VMData::new_i64(a.as_i64() % b.as_i64()))?,
+            Instruction::FMod => self.binary_op(|a, b| VMData::new_f64(a.as_f64() % b.as_f64()))?,
+            Instruction::UIMod => self.binary_op(|a, b| VMData::new_u64(a.as_u64() % b.as_u64()))?,
+            Instruction::StringLoad => self.string_load()?,
+            Instruction::ListLoad => self.list_load()?,
+            Instruction::StringStore => self.string_store()?,
+            Instruction::ListStore => self.list_store()?,
+            Instruction::NewList => self.new_list()?,
+            Instruction::LinkedNativeCall { native, .. } => self.native_call(native)?,
+            Instruction::DirectCall { pos, args } => {
+                let position = self.stack[pos].as_fn_ptr();
+                let label = self
+                    .program
+                    .label_index_at(position)
+                    .ok_or(RuntimeError::InvalidOperation)?;
+                self.call(label, position, args, self.pc + 1);
+                return Ok(());
+            }
+            Instruction::LinkedCall { label, nb_args } => {
+                let position = self.program.labels[label].position;
+                self.call(label, position, nb_args, self.pc + 1);
+                return Ok(());
+            }
+            Instruction::FunctionCall { function_name: name, .. }
+            | Instruction::StaticCall { method_name: name, .. }
+            | Instruction::MethodCall { method_name: name, .. }
+            | Instruction::ExternCall { function_name: name, .. }
+            | Instruction::NewObj { class_name: name } => {
+                return Err(RuntimeError::UnlinkedSymbol(name.to_string()));
             }
-            Instruction::StringLoad => {
-                let index = self.stack.pop()?;
-                let str_ptr = self.stack.pop()?;
-                let raw_string = self.object_map.get(str_ptr.as_object())?;
-                let string = raw_string.string();
-                let val = string.chars().nth(index.as_u64() as usize).unwrap();
-                self.stack.push(VMData::new_char(val))?;
-                self.pc += 1;
+            Instruction::Return => {
+                self.pc = self.ret()?;
+                return Ok(());
             }
-            Instruction::ListLoad => {
-                let index = self.stack.pop()?;
-                let list_ptr = self.stack.pop()?;
-                let raw_list = self.object_map.get(list_ptr.as_object())?;
-                let list = raw_list.list();
-                let val = list[index.as_u64() as usize];
-                self.stack.push_with_rc(val, &mut self.object_map)?;
-                self.pc += 1;
+            Instruction::Halt => {
+                self.pc = self.code.len();
+                return Ok(());
             }
-            Instruction::StringStore => {
-                let val = self.stack.pop()?.as_char();
-                let str_ptr = self.stack.pop()?;
-                let index = self.stack.pop()?;
-                let string = self.object_map.get_mut(str_ptr.as_object())?.string_mut();
-                string.remove(index.as_u64() as usize);
-                string.insert(index.as_u64() as usize, val);
-                self.pc += 1;
+            Instruction::Call { nb_args } => {
+                let position = self.stack.pop()?.as_fn_ptr();
+                let label = self
+                    .program
+                    .label_index_at(position)
+                    .ok_or(RuntimeError::InvalidOperation)?;
+                self.call(label, position, nb_args, self.pc + 1);
+                return Ok(());
             }
-            Instruction::ListStore => {
-                let val = self.stack.pop()?;
-                let list_ptr = self.stack.pop()?;
-                let index = self.stack.pop()?;
-                let list = self.object_map.get_mut(list_ptr.as_object())?.list_mut();
-                list[index.as_u64() as usize] = val;
-                self.pc += 1;
+            Instruction::PushList(_) | Instruction::ListIndex(_) => {
+                return Err(RuntimeError::UnsupportedInstruction(format!("{:?}", instr)));
             }
-            Instruction::NewList => {
-                let size = self.stack.pop()?;
-                let list = vec![VMData::new_unit(); size.as_u64() as usize];
-                let ptr = self.object_map.put(ObjectKind::List(list))?;
+        }
+        self.pc += 1;
+        Ok(())
+    }
+}
 
-                self.stack.push(VMData::new_list(ptr))?;
-                self.pc += 1;
-            }
-            Instruction::ExternCall { function_name, .. } => {
-                let consts = HashMap::new();
-                let vm_state = runtime::vm_state::VMState::new(
-                    &mut self.stack,
-                    &mut self.object_map,
-                    &consts,
-                    &mut self.var_map,
-                );
-                let extern_fn: &CallBack = self.extern_fn.get::<&str>(&function_name).unwrap();
-                let res = extern_fn(vm_state)?;
-                self.stack.push_with_rc(res, &mut self.object_map)?;
-                self.pc += 1;
-            }
-            Instruction::DirectCall { pos, args } => {
-                let fn_ptr: VMData = self.stack[pos];
-                let (pc, sp) = (self.pc, self.stack.top - args as usize);
-                self.stack_frame.push((pc, sp));
-                self.pc = fn_ptr.as_fn_ptr();
-            }
-            Instruction::FunctionCall { function_name, nb_args } => {
-                let label: &Label<'_> = self
-                    .program
-                    .labels
-                    .iter()
-                    .find(|label| label.name == function_name)
-                    .unwrap();
-                let (pc, sp) = (self.pc, self.stack.top - nb_args as usize);
-                self.stack_frame.push((pc, sp));
-                self.pc = label.position;
+/// Semantics of each operation, shared by [`Atlas77VM::execute_instruction`] and the bytecode dispatch loop.
+///
+/// None of these touch the program counter, except [`Atlas77VM::call`] & [`Atlas77VM::ret`].
+impl<'run> Atlas77VM<'run> {
+    /// Pop `b` then `a` and push `f(a, b)`
+    #[inline(always)]
+    fn binary_op(&mut self, f: impl FnOnce(VMData, VMData) -> VMData) -> RuntimeResult<()> {
+        let b = self.stack.pop()?;
+        let a = self.stack.pop()?;
+        self.stack.push(f(a, b))
+    }
+
+    /// `==` (or `!=` if `eq` is false), strings are compared by content
+    fn equality(&mut self, eq: bool) -> RuntimeResult<()> {
+        let b = self.stack.pop()?;
+        let a = self.stack.pop()?;
+        let equal = if a.tag == VMData::TAG_STR && b.tag == VMData::TAG_STR {
+            let equal = self.object_map.peek(a.as_object())?.string() == self.object_map.peek(b.as_object())?.string();
+            self.object_map.rc_dec(a.as_object())?;
+            self.object_map.rc_dec(b.as_object())?;
+            equal
+        } else {
+            a.as_i64() == b.as_i64()
+        };
+        self.stack.push(VMData::new_bool(equal == eq))
+    }
+
+    /// Pop the value switched on by a jump table, the index of the jump to take
+    fn jump_table_index(&mut self, min: i64, len: usize) -> RuntimeResult<usize> {
+        let value = self.stack.pop()?.as_i64();
+        Ok(match value.checked_sub(min) {
+            Some(index) if (0..len as i64).contains(&index) => index as usize,
+            _ => len,
+        })
+    }
+
+    #[inline(always)]
+    fn division(&mut self, zero: VMData, f: impl FnOnce(VMData, VMData) -> VMData) -> RuntimeResult<()> {
+        if *self.stack.last()? == zero {
+            return Err(RuntimeError::DivisionByZero);
+        }
+        self.binary_op(f)
+    }
+
+    /// Put `object` in memory, collecting the garbage first if needed
+    ///
+    /// Every live value has to be on the stack at this point, as it's the only root of the collection
+    /// The values `object` holds are roots of the collection, they may not be on the stack anymore
+    fn alloc(&mut self, object: ObjectKind<'run>) -> RuntimeResult<ObjectIndex> {
+        if self.object_map.needs_collection(object.size()) {
+            self.object_map.collect(self.stack.iter().copied().chain(object.children()));
+        }
+        self.object_map.put(object)
+    }
+
+    fn push_str(&mut self, idx: usize) -> RuntimeResult<()> {
+        let string = self.program.global.string_pool[idx];
+        let ptr = self.alloc(ObjectKind::String(String::from(string)))?;
+        self.stack.push(VMData::new_string(ptr))
+    }
+
+    fn new_obj(&mut self, class: &ConstantClass<'run>) -> RuntimeResult<()> {
+        let mut fields = HashMap::new();
+        for field in class.fields.iter() {
+            fields.insert(*field, VMData::new_unit());
+        }
+        let class_ptr = self.alloc(ObjectKind::Class(Class {
+            fields
+        }))?;
+        self.stack.push(VMData::new_object(class_ptr))
+    }
+
+    fn delete_obj(&mut self) -> RuntimeResult<()> {
+        let obj = self.stack.pop()?;
+        self.object_map.free(obj.as_object())
+    }
+
+    fn get_field(&mut self, field_name: &'run str) -> RuntimeResult<()> {
+        let obj = self.stack.pop()?;
+        let obj_ptr = obj.as_object();
+        let raw_obj = self.object_map.get(obj_ptr)?;
+        let class = raw_obj.class();
+        let field = *class
+            .fields
+            .get(field_name)
+            .ok_or_else(|| RuntimeError::UnknownField(field_name.to_string()))?;
+        self.stack.push_with_rc(field, &mut self.object_map)
+    }
+
+    fn set_field(&mut self, field_name: &'run str) -> RuntimeResult<()> {
+        let val = self.stack.pop()?;
+        let obj = self.stack.pop()?;
+        let obj_ptr = obj.as_object();
+        let raw_obj = self.object_map.get_mut(obj_ptr)?;
+        let class = raw_obj.class_mut();
+        //The fields are the ones of the class, so its size doesn't change
+        let field = class
+            .fields
+            .get_mut(field_name)
+            .ok_or_else(|| RuntimeError::UnknownField(field_name.to_string()))?;
+        *field = val;
+        match val.tag {
+            VMData::TAG_OBJECT | VMData::TAG_LIST | VMData::TAG_STR => {
+                self.object_map.rc_inc(val.as_object());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn new_union(&mut self, tag: usize, nb_fields: usize) -> RuntimeResult<()> {
+        //The payload stays on the stack until the union is allocated, so a collection can't free it
+        let payload = vec![VMData::new_unit(); nb_fields];
+        let ptr = self.alloc(ObjectKind::Union(Union { tag, payload }))?;
+        let mut payload = (0..nb_fields).map(|_| self.stack.pop()).collect::<RuntimeResult<Vec<_>>>()?;
+        payload.reverse();
+        if let ObjectKind::Union(u) = &mut self.object_map.raw_mut()[usize::from(ptr)].kind {
+            u.payload = payload;
+        }
+        self.stack.push(VMData::new_object(ptr))
+    }
+
+    fn union_tag(&mut self) -> RuntimeResult<()> {
+        let obj = self.stack.pop()?;
+        let raw_obj = self.object_map.get(obj.as_object())?;
+        self.stack.push(VMData::new_i64(raw_obj.union().tag as i64))
+    }
+
+    fn union_field(&mut self, i: usize) -> RuntimeResult<()> {
+        let obj = self.stack.pop()?;
+        let raw_obj = self.object_map.get(obj.as_object())?;
+        let field = raw_obj.union().payload[i];
+        self.stack.push_with_rc(field, &mut self.object_map)
+    }
+
+    fn cast_to(&mut self, t: Type) -> RuntimeResult<()> {
+        //`Memory::get` consumes the reference to a string
+        let val = self.stack.pop()?;
+        let res = match t {
+            Type::String => {
+                if val.tag == VMData::TAG_STR {
+                    self.object_map.rc_dec(val.as_object())?;
+                }
+                let string = val.to_string();
+                let ptr = self.alloc(ObjectKind::String(string))?;
+                VMData::new_string(ptr)
             }
-            Instruction::StaticCall { method_name, nb_args } => {
-                let label: &Label<'_> = self
-                    .program
-                    .labels
-                    .iter()
-                    .find(|label: &&Label<'_>| label.name == method_name)
-                    .unwrap();
-                let (pc, sp) = (self.pc, self.stack.top - nb_args as usize);
-                self.stack_frame.push((pc, sp));
-                self.pc = label.position;
+            Type::Char => {
+                match val.tag {
+                    VMData::TAG_STR => {
+                        let raw_string = self.object_map.get(val.as_object())?;
+                        let string = raw_string.string();
+                        if string.len() != 1 {
+                            return Err(RuntimeError::InvalidCast(
+                                val.tag,
+                                Type::Char,
+                            ));
+                        }
+                        VMData::new_char(string.chars().next().unwrap())
+                    }
+                    _ => {
+                        VMData::new_char(val.as_char())
+                    }
+                }
             }
-            Instruction::MethodCall { method_name, nb_args } => {
-                println!("Calling: {} with {} args", method_name, nb_args);
-                let label: &Label<'_> = self
-                    .program
-                    .labels
-                    .iter()
-                    .find(|label| label.name == method_name)
-                    .unwrap();
-                let (pc, sp) = (self.pc, self.stack.top - (nb_args) as usize);
-                self.stack_frame.push((pc, sp));
-                self.pc = label.position;
+            Type::Boolean => {
+                match val.tag {
+                    VMData::TAG_STR => {
+                        let raw_string = self.object_map.get(val.as_object())?;
+                        let string = raw_string.string();
+                        VMData::new_bool(string.parse::<bool>().map_err(|_| RuntimeError::InvalidCast(val.tag, t))?)
+                    }
+                    _ => VMData::new_bool(val.as_bool()),
+                }
             }
-            Instruction::Return => {
-                let (pc, sp) = self.stack_frame.pop().unwrap_or_else(|| {
-                    eprintln!(
-                        "No stack frame to return from {:?} @ {}",
-                        self.stack.last(),
-                        self.pc
-                    );
-                    std::process::exit(1);
-                });
-                let ret = *self.stack.last()?;
-                //This is weird asf but it works
-                match ret.tag {
-                    VMData::TAG_LIST | VMData::TAG_STR => {
-                        self.object_map.rc_inc(ret.as_object());
-                        self.object_map.rc_inc(ret.as_object());
+            Type::Float => {
+                match val.tag {
+                    VMData::TAG_STR => {
+                        let raw_string = self.object_map.get(val.as_object())?;
+                        let string = raw_string.string();
+                        VMData::new_f64(string.parse::<f64>().map_err(|_| RuntimeError::InvalidCast(val.tag, t))?)
                     }
-                    VMData::TAG_OBJECT => {
-                        self.object_map.rc_inc(ret.as_object());
+                    VMData::TAG_U64 => VMData::new_f64(val.as_u64() as f64),
+                    VMData::TAG_I64 => VMData::new_f64(val.as_i64() as f64),
+                    VMData::TAG_BOOL => VMData::new_f64(val.as_bool() as i64 as f64),
+                    VMData::TAG_CHAR => VMData::new_f64(val.as_char() as i64 as f64),
+                    _ => return Err(RuntimeError::InvalidCast(val.tag, t)),
+                }
+            }
+            Type::Integer => {
+                match val.tag {
+                    VMData::TAG_STR => {
+                        let raw_string = self.object_map.get(val.as_object())?;
+                        let string = raw_string.string();
+                        VMData::new_i64(string.parse::<i64>().map_err(|_| RuntimeError::InvalidCast(val.tag, t))?)
                     }
-                    _ => {}
+                    VMData::TAG_U64 => VMData::new_i64(val.as_u64() as i64),
+                    VMData::TAG_FLOAT => VMData::new_i64(val.as_f64() as i64),
+                    VMData::TAG_BOOL => VMData::new_i64(val.as_bool() as i64),
+                    VMData::TAG_CHAR => VMData::new_i64(val.as_char() as i64),
+                    _ => return Err(RuntimeError::InvalidCast(val.tag, t)),
                 }
-                self.stack.truncate(sp, &mut self.object_map)?;
-                self.var_map.clean_scope(self.stack_frame.len() + 1, &mut self.object_map)?;
-                self.pc = pc + 1;
-                self.stack.push(ret)?;
-                println!("Stack: {}", self.stack);
             }
-            Instruction::Halt => {
-                self.pc = self.program.len();
+            Type::UnsignedInteger => {
+                match val.tag {
+                    VMData::TAG_STR => {
+                        let raw_string = self.object_map.get(val.as_object())?;
+                        let string = raw_string.string();
+                        VMData::new_u64(string.parse::<u64>().map_err(|_| RuntimeError::InvalidCast(val.tag, t))?)
+                    }
+                    VMData::TAG_I64 => VMData::new_u64(val.as_i64() as u64),
+                    VMData::TAG_FLOAT => VMData::new_u64(val.as_f64() as u64),
+                    VMData::TAG_BOOL => VMData::new_u64(val.as_bool() as u64),
+                    VMData::TAG_CHAR => VMData::new_u64(val.as_char() as u64),
+                    _ => return Err(RuntimeError::InvalidCast(val.tag, t)),
+                }
             }
-            _ => unimplemented!("{:?}", instr),
+        };
+        self.stack.push(res)
+    }
+
+    /// The popped value moves into the slot, the previous one loses its reference
+    fn store_local(&mut self, slot: usize) -> RuntimeResult<()> {
+        let val = self.stack.pop()?;
+        let old = self.stack.set(self.bp + slot, val)?;
+        match old.tag {
+            VMData::TAG_STR | VMData::TAG_LIST | VMData::TAG_OBJECT => {
+                self.object_map.rc_dec(old.as_object())?;
+            }
+            _ => {}
         }
         Ok(())
     }
+
+    fn get_local(&mut self, slot: usize) -> RuntimeResult<()> {
+        let val = self.stack[self.bp + slot];
+        self.stack.push_with_rc(val, &mut self.object_map)
+    }
+
+    fn reserve(&mut self, n: usize) -> RuntimeResult<()> {
+        for _ in 0..n {
+            self.stack.push(VMData::new_unit())?;
+        }
+        Ok(())
+    }
+
+    fn string_load(&mut self) -> RuntimeResult<()> {
+        let index = self.stack.pop()?;
+        let str_ptr = self.stack.pop()?;
+        let raw_string = self.object_map.get(str_ptr.as_object())?;
+        let string = raw_string.string();
+        let i = Self::bounds_check(index, string.chars().count())?;
+        let val = string.chars().nth(i).unwrap();
+        self.stack.push(VMData::new_char(val))
+    }
+
+    fn list_load(&mut self) -> RuntimeResult<()> {
+        let index = self.stack.pop()?;
+        let list_ptr = self.stack.pop()?;
+        let raw_list = self.object_map.get(list_ptr.as_object())?;
+        let list = raw_list.list();
+        let val = list[Self::bounds_check(index, list.len())?];
+        self.stack.push_with_rc(val, &mut self.object_map)
+    }
+
+    fn string_store(&mut self) -> RuntimeResult<()> {
+        let val = self.stack.pop()?.as_char();
+        let str_ptr = self.stack.pop()?;
+        let index = self.stack.pop()?;
+        let string = self.object_map.get_mut(str_ptr.as_object())?.string_mut();
+        let i = Self::bounds_check(index, string.chars().count())?;
+        let (start, old) = string.char_indices().nth(i).unwrap();
+        string.replace_range(start..start + old.len_utf8(), val.encode_utf8(&mut [0; 4]));
+        //The characters may not take the same number of bytes
+        self.object_map.used_size = self.object_map.used_size + val.len_utf8() - old.len_utf8();
+        Ok(())
+    }
+
+    fn list_store(&mut self) -> RuntimeResult<()> {
+        let val = self.stack.pop()?;
+        let list_ptr = self.stack.pop()?;
+        let index = self.stack.pop()?;
+        let list = self.object_map.get_mut(list_ptr.as_object())?.list_mut();
+        let i = Self::bounds_check(index, list.len())?;
+        list[i] = val;
+        Ok(())
+    }
+
+    /// `index` as a position in a list or a string of `len` elements
+    fn bounds_check(index: VMData, len: usize) -> RuntimeResult<usize> {
+        let i = match index.tag {
+            VMData::TAG_U64 => i64::try_from(index.as_u64()).unwrap_or(i64::MAX),
+            _ => index.as_i64(),
+        };
+        match usize::try_from(i) {
+            Ok(i) if i < len => Ok(i),
+            _ => Err(RuntimeError::IndexOutOfBounds { index: i, len }),
+        }
+    }
+
+    fn new_list(&mut self) -> RuntimeResult<()> {
+        let size = self.stack.pop()?;
+        let len = match size.tag {
+            VMData::TAG_U64 => i64::try_from(size.as_u64()).unwrap_or(i64::MAX),
+            _ => size.as_i64(),
+        };
+        let len = usize::try_from(len).map_err(|_| RuntimeError::NegativeListSize(len))?;
+        //Checked before the list exists, allocating it could already take down the host
+        if self.object_map.needs_collection(ObjectKind::list_size(len)) {
+            self.object_map.collect(self.stack.iter().copied());
+        }
+        if !self.object_map.fits(ObjectKind::list_size(len)) {
+            return Err(RuntimeError::OutOfMemory);
+        }
+        let mut list = Vec::new();
+        list.try_reserve_exact(len).map_err(|_| RuntimeError::OutOfMemory)?;
+        list.resize(len, VMData::new_unit());
+        let ptr = self.alloc(ObjectKind::List(list))?;
+        self.stack.push(VMData::new_list(ptr))
+    }
+
+    fn native_call(&mut self, native: usize) -> RuntimeResult<()> {
+        let extern_fn = self.natives[native].clone().ok_or_else(|| {
+            RuntimeError::NativeNotFound(self.program.global.native_pool[native].to_string())
+        })?;
+        let consts = HashMap::new();
+        let vm_state = runtime::vm_state::VMState::new(
+            &mut self.stack,
+            &mut self.object_map,
+            &consts,
+            self.program.global.class_pool,
+        );
+        let res = extern_fn(vm_state)?;
+        self.stack.push_with_rc(res, &mut self.object_map)
+    }
+
+    /// Push a new frame for the function `label` and jump to `position`
+    ///
+    /// The arguments already on the stack become the first slots of the new frame
+    #[inline(always)]
+    fn call(&mut self, label: usize, position: usize, nb_args: u8, return_pc: usize) {
+        self.bp = self.stack.top - nb_args as usize;
+        self.frames.push(CallFrame {
+            function: label,
+            return_pc,
+            bp: self.bp,
+        });
+        self.pc = position;
+        if self.hook.is_some() {
+            let _ = self.notify(|hook, vm| {
+                hook.on_call(vm);
+                Ok(())
+            });
+        }
+    }
+
+    /// Pop the current frame, keeping the returned value on the stack
+    ///
+    /// Every argument & local of the frame loses its reference
+    ///
+    /// Returns the position to jump back to
+    fn ret(&mut self) -> RuntimeResult<usize> {
+        if self.hook.is_some() {
+            let value = *self.stack.last()?;
+            self.notify(|hook, vm| {
+                hook.on_return(vm, value);
+                Ok(())
+            })?;
+        }
+        let frame = self.frames.pop().ok_or(RuntimeError::StackUnderflow)?;
+        let ret = *self.stack.last()?;
+        //This is weird asf but it works
+        match ret.tag {
+            VMData::TAG_LIST | VMData::TAG_STR => {
+                self.object_map.rc_inc(ret.as_object());
+                self.object_map.rc_inc(ret.as_object());
+            }
+            VMData::TAG_OBJECT => {
+                self.object_map.rc_inc(ret.as_object());
+            }
+            _ => {}
+        }
+        self.stack.truncate(frame.bp, &mut self.object_map)?;
+        self.bp = self.frames.last().map_or(0, |frame| frame.bp);
+        self.stack.push(ret)?;
+        Ok(frame.return_pc)
+    }
 }
@@ -0,0 +1,525 @@
+//! Native functions registered by the host application.
+//!
+//! A [`NativeModule`] groups closures under a module name. Scripts bring them in scope with
+//! `import "<name>"`, the compiler then sees one `public extern` declaration per function
+//! (see [`NativeModule::declarations`]) and the linker & the VM resolve them like the standard libraries.
+//!
+//! ```
+//! use atlas_77::atlas_vm::native::NativeModule;
+//!
+//! let rate = 1.5;
+//! let module = NativeModule::new("pricing")
+//!     .function("price", move |id: i64| id as f64 * rate)
+//!     .function("label", |id: i64| format!("item #{}", id));
+//! assert_eq!(
+//!     module.declarations(),
+//!     "public extern price(arg0: int64) -> float64\npublic extern label(arg0: int64) -> str\n"
+//! );
+//! ```
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::atlas_vm::errors::RuntimeError;
+use crate::atlas_vm::memory::object_map::{Class, ObjectKind, Union};
+use crate::atlas_vm::memory::vm_data::VMData;
+use crate::atlas_vm::runtime::vm_state::VMState;
+use crate::atlas_vm::RuntimeResult;
+
+/// A native function, the arguments are on the stack (the last one on top) and the VM pushes the returned value
+pub type NativeFn = Rc<dyn Fn(VMState) -> RuntimeResult<VMData>>;
+
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: String,
+    /// Parameters & return type, written like after the name of an Atlas `extern` (e.g. `(s: str) -> int64`)
+    pub signature: String,
+    pub callback: NativeFn,
+}
+
+/// Native functions exposed to scripts under a module name
+#[derive(Clone)]
+pub struct NativeModule {
+    name: String,
+    functions: Vec<NativeFunction>,
+}
+
+impl NativeModule {
+    /// `name` is the path scripts import, it can't be the one of a standard library (e.g. `io`)
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            functions: Vec::new(),
+        }
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn functions(&self) -> &[NativeFunction] {
+        &self.functions
+    }
+    /// Register a closure taking & returning Rust values, see [`FromAtlas`] & [`IntoAtlas`]
+    ///
+    /// The Atlas signature is derived from the closure's types
+    pub fn function<Args, F: NativeClosure<Args>>(self, name: &str, f: F) -> Self {
+        self.raw(name, &F::signature(), move |state| f.call(state))
+    }
+    /// Register a closure working on the VM state directly, like the standard libraries do
+    ///
+    /// It has to pop its arguments itself, `signature` is what scripts are type-checked against
+    pub fn raw(
+        mut self,
+        name: &str,
+        signature: &str,
+        f: impl Fn(VMState) -> RuntimeResult<VMData> + 'static,
+    ) -> Self {
+        self.functions.retain(|function| function.name != name);
+        self.functions.push(NativeFunction {
+            name: name.to_string(),
+            signature: signature.to_string(),
+            callback: Rc::new(f),
+        });
+        self
+    }
+    /// Atlas source declaring every function of the module as `public extern`
+    pub fn declarations(&self) -> String {
+        self.functions
+            .iter()
+            .map(|function| format!("public extern {}{}\n", function.name, function.signature))
+            .collect()
+    }
+}
+
+/// Rust types with an Atlas counterpart
+pub trait AtlasType {
+    /// Name of the type in Atlas source
+    fn atlas_type() -> String;
+}
+
+/// Read a Rust value from an Atlas one, without taking ownership of it
+pub trait FromAtlas: AtlasType + Sized {
+    fn from_atlas(value: VMData, state: &VMState) -> RuntimeResult<Self>;
+}
+
+/// Turn a Rust value into an Atlas one, allocating it in the VM memory if needed
+pub trait IntoAtlas: AtlasType {
+    fn into_atlas(self, state: &mut VMState) -> RuntimeResult<VMData>;
+}
+
+macro_rules! impl_scalar {
+    ($ty:ty, $name:literal, $tag:ident, $new:ident, $as:ident) => {
+        impl AtlasType for $ty {
+            fn atlas_type() -> String {
+                String::from($name)
+            }
+        }
+        impl FromAtlas for $ty {
+            fn from_atlas(value: VMData, _: &VMState) -> RuntimeResult<Self> {
+                if value.tag != VMData::$tag {
+                    return Err(RuntimeError::TypeMismatchError);
+                }
+                Ok(value.$as())
+            }
+        }
+        impl IntoAtlas for $ty {
+            fn into_atlas(self, _: &mut VMState) -> RuntimeResult<VMData> {
+                Ok(VMData::$new(self))
+            }
+        }
+    };
+}
+
+impl_scalar!(i64, "int64", TAG_I64, new_i64, as_i64);
+impl_scalar!(u64, "uint64", TAG_U64, new_u64, as_u64);
+impl_scalar!(f64, "float64", TAG_FLOAT, new_f64, as_f64);
+impl_scalar!(bool, "bool", TAG_BOOL, new_bool, as_bool);
+impl_scalar!(char, "char", TAG_CHAR, new_char, as_char);
+
+impl AtlasType for () {
+    fn atlas_type() -> String {
+        String::from("unit")
+    }
+}
+impl FromAtlas for () {
+    fn from_atlas(value: VMData, _: &VMState) -> RuntimeResult<Self> {
+        if value.tag != VMData::TAG_UNIT {
+            return Err(RuntimeError::TypeMismatchError);
+        }
+        Ok(())
+    }
+}
+impl IntoAtlas for () {
+    fn into_atlas(self, _: &mut VMState) -> RuntimeResult<VMData> {
+        Ok(VMData::new_unit())
+    }
+}
+
+impl AtlasType for String {
+    fn atlas_type() -> String {
+        String::from("str")
+    }
+}
+impl FromAtlas for String {
+    fn from_atlas(value: VMData, state: &VMState) -> RuntimeResult<Self> {
+        if value.tag != VMData::TAG_STR {
+            return Err(RuntimeError::TypeMismatchError);
+        }
+        Ok(state.object_map.peek(value.as_object())?.string().clone())
+    }
+}
+impl IntoAtlas for String {
+    fn into_atlas(self, state: &mut VMState) -> RuntimeResult<VMData> {
+        let ptr = state.alloc(ObjectKind::String(self))?;
+        Ok(VMData::new_string(ptr))
+    }
+}
+impl AtlasType for &str {
+    fn atlas_type() -> String {
+        String::from("str")
+    }
+}
+impl IntoAtlas for &str {
+    fn into_atlas(self, state: &mut VMState) -> RuntimeResult<VMData> {
+        self.to_string().into_atlas(state)
+    }
+}
+
+/// Natives can fail, the error stops the script like any runtime error
+impl<T: AtlasType> AtlasType for RuntimeResult<T> {
+    fn atlas_type() -> String {
+        T::atlas_type()
+    }
+}
+impl<T: IntoAtlas> IntoAtlas for RuntimeResult<T> {
+    fn into_atlas(self, state: &mut VMState) -> RuntimeResult<VMData> {
+        self?.into_atlas(state)
+    }
+}
+
+impl<T: AtlasType> AtlasType for Vec<T> {
+    fn atlas_type() -> String {
+        format!("[{}]", T::atlas_type())
+    }
+}
+impl<T: FromAtlas> FromAtlas for Vec<T> {
+    fn from_atlas(value: VMData, state: &VMState) -> RuntimeResult<Self> {
+        if value.tag != VMData::TAG_LIST {
+            return Err(RuntimeError::TypeMismatchError);
+        }
+        state
+            .object_map
+            .peek(value.as_object())?
+            .list()
+            .iter()
+            .map(|item| T::from_atlas(*item, state))
+            .collect()
+    }
+}
+impl<T: IntoAtlas> IntoAtlas for Vec<T> {
+    fn into_atlas(self, state: &mut VMState) -> RuntimeResult<VMData> {
+        let list = self
+            .into_iter()
+            .map(|item| item.into_atlas(state))
+            .collect::<RuntimeResult<Vec<_>>>()?;
+        let ptr = state.alloc(ObjectKind::List(list))?;
+        Ok(VMData::new_list(ptr))
+    }
+}
+
+/// Tag & payload of a union value
+fn read_union(value: VMData, state: &VMState) -> RuntimeResult<(usize, Vec<VMData>)> {
+    if value.tag != VMData::TAG_OBJECT {
+        return Err(RuntimeError::TypeMismatchError);
+    }
+    match state.object_map.peek(value.as_object())? {
+        ObjectKind::Union(u) => Ok((u.tag, u.payload.clone())),
+        _ => Err(RuntimeError::TypeMismatchError),
+    }
+}
+
+fn new_union(state: &mut VMState, tag: usize, payload: Vec<VMData>) -> RuntimeResult<VMData> {
+    let ptr = state.alloc(ObjectKind::Union(Union { tag, payload }))?;
+    Ok(VMData::new_object(ptr))
+}
+
+/// The prelude's `Option<T>`, `Some` is the first variant
+impl<T: AtlasType> AtlasType for Option<T> {
+    fn atlas_type() -> String {
+        format!("Option<{}>", T::atlas_type())
+    }
+}
+impl<T: FromAtlas> FromAtlas for Option<T> {
+    fn from_atlas(value: VMData, state: &VMState) -> RuntimeResult<Self> {
+        match read_union(value, state)? {
+            (0, payload) if payload.len() == 1 => Ok(Some(T::from_atlas(payload[0], state)?)),
+            (1, payload) if payload.is_empty() => Ok(None),
+            _ => Err(RuntimeError::TypeMismatchError),
+        }
+    }
+}
+impl<T: IntoAtlas> IntoAtlas for Option<T> {
+    fn into_atlas(self, state: &mut VMState) -> RuntimeResult<VMData> {
+        match self {
+            Some(v) => {
+                let v = v.into_atlas(state)?;
+                new_union(state, 0, vec![v])
+            }
+            None => new_union(state, 1, vec![]),
+        }
+    }
+}
+
+/// The prelude's `Result<T, E>`, `Ok` is the first variant
+///
+/// Unlike a [`RuntimeResult`], an error is given to the script instead of stopping it
+impl<T: AtlasType, E: AtlasType> AtlasType for Result<T, E> {
+    fn atlas_type() -> String {
+        format!("Result<{}, {}>", T::atlas_type(), E::atlas_type())
+    }
+}
+impl<T: FromAtlas, E: FromAtlas> FromAtlas for Result<T, E> {
+    fn from_atlas(value: VMData, state: &VMState) -> RuntimeResult<Self> {
+        match read_union(value, state)? {
+            (0, payload) if payload.len() == 1 => Ok(Ok(T::from_atlas(payload[0], state)?)),
+            (1, payload) if payload.len() == 1 => Ok(Err(E::from_atlas(payload[0], state)?)),
+            _ => Err(RuntimeError::TypeMismatchError),
+        }
+    }
+}
+impl<T: IntoAtlas, E: IntoAtlas> IntoAtlas for Result<T, E> {
+    fn into_atlas(self, state: &mut VMState) -> RuntimeResult<VMData> {
+        let (tag, value) = match self {
+            Ok(v) => (0, v.into_atlas(state)?),
+            Err(e) => (1, e.into_atlas(state)?),
+        };
+        new_union(state, tag, vec![value])
+    }
+}
+
+/// Pop the last argument of a native, the popped reference is released once converted
+pub fn pop_arg<T: FromAtlas>(state: &mut VMState) -> RuntimeResult<T> {
+    let value = state.stack.pop()?;
+    let arg = T::from_atlas(value, state)?;
+    match value.tag {
+        VMData::TAG_STR | VMData::TAG_LIST | VMData::TAG_OBJECT => {
+            state.object_map.rc_dec(value.as_object())?;
+        }
+        _ => {}
+    }
+    Ok(arg)
+}
+
+/// Values of `fields` in an instance of `class`, in the same order
+///
+/// The instance must have exactly these fields, used by [`atlas_class!`]
+pub fn read_class(value: VMData, state: &VMState, class: &str, fields: &[&str]) -> RuntimeResult<Vec<VMData>> {
+    let mismatch = || RuntimeError::ClassMismatch(class.to_string());
+    if value.tag != VMData::TAG_OBJECT {
+        return Err(RuntimeError::TypeMismatchError);
+    }
+    let instance = match state.object_map.peek(value.as_object())? {
+        ObjectKind::Class(instance) => instance,
+        _ => return Err(RuntimeError::TypeMismatchError),
+    };
+    if instance.fields.len() != fields.len() {
+        return Err(mismatch());
+    }
+    fields
+        .iter()
+        .map(|field| instance.fields.get(field).copied().ok_or_else(mismatch))
+        .collect()
+}
+
+/// Allocate an instance of `class`, `fields` must be exactly the ones of its
+/// [`ConstantClass`](crate::atlas_vm::runtime::instruction::ConstantClass)
+///
+/// Used by [`atlas_class!`]
+pub fn new_class(state: &mut VMState, class: &str, fields: Vec<(&str, VMData)>) -> RuntimeResult<VMData> {
+    let mismatch = || RuntimeError::ClassMismatch(class.to_string());
+    let constant = state
+        .classes
+        .iter()
+        .find(|c| c.name == class)
+        .ok_or_else(mismatch)?;
+    if constant.fields.len() != fields.len() {
+        return Err(mismatch());
+    }
+    let mut instance = HashMap::new();
+    for (name, value) in fields {
+        let field = constant.fields.iter().find(|f| **f == name).ok_or_else(mismatch)?;
+        instance.insert(*field, value);
+    }
+    let ptr = state.alloc(ObjectKind::Class(Class { fields: instance }))?;
+    Ok(VMData::new_object(ptr))
+}
+
+/// Implement [`FromAtlas`] & [`IntoAtlas`] for a struct mirroring an Atlas class of the same name
+///
+/// Every listed field converts on its own, so they can be classes or lists too:
+/// ```
+/// use atlas_77::atlas_class;
+///
+/// struct Point {
+///     x: i64,
+///     y: i64,
+/// }
+/// atlas_class!(Point { x, y });
+/// ```
+#[macro_export]
+macro_rules! atlas_class {
+    ($name:ident { $($field:ident),* $(,)? }) => {
+        impl $crate::atlas_vm::native::AtlasType for $name {
+            fn atlas_type() -> String {
+                String::from(stringify!($name))
+            }
+        }
+        impl $crate::atlas_vm::native::FromAtlas for $name {
+            fn from_atlas(
+                value: $crate::atlas_vm::memory::vm_data::VMData,
+                state: &$crate::atlas_vm::runtime::vm_state::VMState,
+            ) -> $crate::atlas_vm::RuntimeResult<Self> {
+                let fields = [$(stringify!($field)),*];
+                let mut values = $crate::atlas_vm::native::read_class(value, state, stringify!($name), &fields)?
+                    .into_iter();
+                Ok(Self {
+                    $($field: $crate::atlas_vm::native::FromAtlas::from_atlas(values.next().unwrap(), state)?,)*
+                })
+            }
+        }
+        impl $crate::atlas_vm::native::IntoAtlas for $name {
+            fn into_atlas(
+                self,
+                state: &mut $crate::atlas_vm::runtime::vm_state::VMState,
+            ) -> $crate::atlas_vm::RuntimeResult<$crate::atlas_vm::memory::vm_data::VMData> {
+                let fields = vec![
+                    $((stringify!($field), $crate::atlas_vm::native::IntoAtlas::into_atlas(self.$field, state)?),)*
+                ];
+                $crate::atlas_vm::native::new_class(state, stringify!($name), fields)
+            }
+        }
+    };
+}
+
+/// Closures usable with [`NativeModule::function`], `Args` is the tuple of their parameter types
+pub trait NativeClosure<Args>: 'static {
+    fn signature() -> String;
+    fn call(&self, state: VMState) -> RuntimeResult<VMData>;
+}
+
+macro_rules! impl_native_closure {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> NativeClosure<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: IntoAtlas,
+            $($arg: FromAtlas,)*
+        {
+            fn signature() -> String {
+                let params: Vec<String> = vec![$($arg::atlas_type()),*];
+                let params = params
+                    .iter()
+                    .enumerate()
+                    .map(|(i, ty)| format!("arg{}: {}", i, ty))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("({}) -> {}", params, R::atlas_type())
+            }
+            #[allow(non_snake_case, unused_mut, unused_variables, unused_assignments)]
+            fn call(&self, mut state: VMState) -> RuntimeResult<VMData> {
+                let nb_args = <[&str]>::len(&[$(stringify!($arg)),*]);
+                let base = state.stack.top.checked_sub(nb_args).ok_or(RuntimeError::StackUnderflow)?;
+                let mut i = base;
+                $(
+                    let $arg = $arg::from_atlas(state.stack[i], &state)?;
+                    i += 1;
+                )*
+                //The arguments were moved to the native, drop them before running it
+                state.stack.truncate(base, state.object_map)?;
+                (self)($($arg),*).into_atlas(&mut state)
+            }
+        }
+    };
+}
+
+impl_native_closure!();
+impl_native_closure!(A);
+impl_native_closure!(A, B);
+impl_native_closure!(A, B, C);
+impl_native_closure!(A, B, C, D);
+impl_native_closure!(A, B, C, D, E);
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use bumpalo::Bump;
+
+    use std::collections::HashMap;
+
+    use super::{FromAtlas, IntoAtlas, NativeModule};
+    use crate::atlas_c::atlas_codegen::{arena::CodeGenArena, CodeGenUnit};
+    use crate::atlas_c::atlas_frontend::{parse, parser::arena::AstArena};
+    use crate::atlas_c::atlas_hir::{
+        arena::HirArena, syntax_lowering_pass::AstSyntaxLoweringPass, type_check_pass::TypeChecker,
+    };
+    use crate::atlas_c::atlas_linker::link;
+    use crate::atlas_vm::errors::RuntimeError;
+    use crate::atlas_vm::memory::{object_map::Memory, stack::Stack};
+    use crate::atlas_vm::runtime::arena::RuntimeArena;
+    use crate::atlas_vm::runtime::vm_state::VMState;
+    use crate::atlas_vm::sandbox::Capabilities;
+    use crate::atlas_vm::Atlas77VM;
+
+    #[test]
+    fn scripts_call_host_closures() {
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        let module = NativeModule::new("host")
+            .function("add", |a: i64, b: i64| a + b)
+            .function("greet", |name: String| format!("hello {}", name))
+            .function("size", move |s: String| {
+                counter.set(counter.get() + 1);
+                s.len() as i64
+            });
+        let source = r#"import "host"
+func main() -> int64 {
+    let total: int64 = add(2, 3);
+    let s: str = greet("bob");
+    return total + size(s);
+}"#;
+
+        let bump = Bump::new();
+        let ast_arena = AstArena::new(&bump);
+        let ast = parse("test.atlas", &ast_arena, source.to_string()).unwrap();
+        let hir_arena = HirArena::new();
+        let lower = AstSyntaxLoweringPass::new(&hir_arena, &ast, &ast_arena, source.to_string())
+            .with_natives(std::slice::from_ref(&module));
+        let mut hir = lower.lower().unwrap();
+        TypeChecker::new(&hir_arena, source.to_string()).check(&mut hir).unwrap();
+        let arena = CodeGenArena::new(&bump);
+        let program = CodeGenUnit::new(hir, arena, source.to_string()).compile().unwrap();
+        let program = link(&program, &CodeGenArena::new(&bump), std::slice::from_ref(&module)).unwrap();
+
+        assert!(matches!(
+            Atlas77VM::new(program.clone(), RuntimeArena::new(&bump)),
+            Err(RuntimeError::UnknownLibrary(_))
+        ));
+        let mut vm = Atlas77VM::with_natives(program, RuntimeArena::new(&bump), &[module], &Capabilities::all()).unwrap();
+        assert_eq!(vm.run().unwrap().as_i64(), 5 + "hello bob".len() as i64);
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn native_allocations_survive_collections() {
+        let mut stack = Stack::new();
+        let mut memory = Memory::new(1);
+        memory.gc_threshold = Some(1);
+        let consts = HashMap::new();
+        let mut state = VMState::new(&mut stack, &mut memory, &consts, &[]);
+        let words = vec![String::from("a"), String::from("b"), String::from("c")];
+        let list = words.clone().into_atlas(&mut state).unwrap();
+        assert_eq!(Vec::<String>::from_atlas(list, &state).unwrap(), words);
+        assert_eq!(state.object_map.stats().collections, 3);
+    }
+}
@@ -0,0 +1,244 @@
+//! Instrumenting profiler, a [`DebugHook`] counting what every function of a program does.
+//!
+//! ```ignore
+//! let profiler = Profiler::new();
+//! vm.set_debug_hook(profiler.clone());
+//! vm.run()?;
+//! std::fs::write("profile.folded", profiler.folded())?;
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::atlas_vm::debug::DebugHook;
+use crate::atlas_vm::memory::vm_data::VMData;
+use crate::atlas_vm::{Atlas77VM, RuntimeResult};
+
+/// What a function did during a run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FunctionProfile {
+    pub name: String,
+    pub calls: u64,
+    /// Instructions executed by the function itself
+    pub instructions: u64,
+    /// Objects allocated by the function itself, natives it called included
+    pub allocations: u64,
+    /// Time spent in the function itself
+    pub self_time: Duration,
+    /// Time spent in the function & its callees, recursive calls are counted once
+    pub total_time: Duration,
+}
+
+/// Collects a profile of the runs it's the debug hook of
+///
+/// Clones share the same profile, keep one to read it once the run is over
+#[derive(Debug, Clone, Default)]
+pub struct Profiler(Rc<RefCell<ProfileState>>);
+
+#[derive(Debug, Default)]
+struct ProfileState {
+    /// Indexed by label
+    functions: Vec<FunctionProfile>,
+    calls: Vec<OpenCall>,
+    /// Parent path & label of every call path
+    paths: Vec<(Option<usize>, usize)>,
+    path_ids: HashMap<(Option<usize>, usize), usize>,
+    /// Instructions executed with each call path as the stack
+    path_instructions: Vec<u64>,
+    /// Label that executed the last instruction
+    current: Option<usize>,
+    allocations: usize,
+}
+
+#[derive(Debug)]
+struct OpenCall {
+    label: usize,
+    path: usize,
+    start: Instant,
+    /// Time spent in the callees so far
+    children: Duration,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Every function called so far, the ones executing the most instructions first
+    ///
+    /// Calls still running (e.g. the entry point of a program that halted) are counted up to now
+    pub fn functions(&self) -> Vec<FunctionProfile> {
+        let state = self.0.borrow();
+        let mut functions = state.functions.clone();
+        let now = Instant::now();
+        let mut callee_time = Duration::ZERO;
+        for (i, call) in state.calls.iter().enumerate().rev() {
+            let elapsed = now - call.start;
+            let function = &mut functions[call.label];
+            function.self_time += elapsed.saturating_sub(call.children + callee_time);
+            if !state.calls[..i].iter().any(|outer| outer.label == call.label) {
+                function.total_time += elapsed;
+            }
+            callee_time = elapsed;
+        }
+        functions.retain(|function| function.calls > 0);
+        functions.sort_by(|a, b| b.instructions.cmp(&a.instructions).then(a.name.cmp(&b.name)));
+        functions
+    }
+    /// Instructions executed per call stack, in the folded format of flamegraph tools:
+    /// one `outer;inner count` line per stack
+    pub fn folded(&self) -> String {
+        let state = self.0.borrow();
+        let mut lines = Vec::new();
+        for (path, instructions) in state.path_instructions.iter().enumerate() {
+            if *instructions == 0 {
+                continue;
+            }
+            let mut names = Vec::new();
+            let mut node = Some(path);
+            while let Some(path) = node {
+                let (parent, label) = state.paths[path];
+                names.push(state.functions[label].name.as_str());
+                node = parent;
+            }
+            names.reverse();
+            lines.push(format!("{} {}", names.join(";"), instructions));
+        }
+        lines.sort();
+        lines.join("\n") + "\n"
+    }
+}
+
+impl ProfileState {
+    /// Give the objects allocated since the last instruction to the label that executed it
+    fn count_allocations(&mut self, vm: &Atlas77VM) {
+        let allocations = vm.heap_stats().allocations;
+        if let Some(current) = self.current {
+            self.functions[current].allocations += allocations.saturating_sub(self.allocations) as u64;
+        }
+        self.allocations = allocations;
+    }
+}
+
+impl<'run> DebugHook<'run> for Profiler {
+    fn before_instruction(&mut self, vm: &Atlas77VM<'run>) -> RuntimeResult<()> {
+        let state = &mut *self.0.borrow_mut();
+        state.count_allocations(vm);
+        if let Some(call) = state.calls.last() {
+            state.functions[call.label].instructions += 1;
+            state.path_instructions[call.path] += 1;
+            state.current = Some(call.label);
+        }
+        Ok(())
+    }
+
+    fn on_call(&mut self, vm: &Atlas77VM<'run>) {
+        let state = &mut *self.0.borrow_mut();
+        let Some(frame) = vm.frames().last() else {
+            return;
+        };
+        let label = frame.function;
+        if state.functions.len() <= label {
+            state.functions.resize(label + 1, FunctionProfile::default());
+        }
+        let function = &mut state.functions[label];
+        if function.calls == 0 {
+            function.name = vm.frame_name(frame).to_string();
+        }
+        function.calls += 1;
+
+        let key = (state.calls.last().map(|call| call.path), label);
+        let path = match state.path_ids.get(&key) {
+            Some(path) => *path,
+            None => {
+                state.paths.push(key);
+                state.path_instructions.push(0);
+                state.path_ids.insert(key, state.paths.len() - 1);
+                state.paths.len() - 1
+            }
+        };
+        state.calls.push(OpenCall {
+            label,
+            path,
+            start: Instant::now(),
+            children: Duration::ZERO,
+        });
+    }
+
+    fn on_return(&mut self, vm: &Atlas77VM<'run>, _value: VMData) {
+        let state = &mut *self.0.borrow_mut();
+        state.count_allocations(vm);
+        let Some(call) = state.calls.pop() else {
+            return;
+        };
+        let elapsed = call.start.elapsed();
+        let recursive = state.calls.iter().any(|outer| outer.label == call.label);
+        let function = &mut state.functions[call.label];
+        function.self_time += elapsed.saturating_sub(call.children);
+        if !recursive {
+            function.total_time += elapsed;
+        }
+        if let Some(caller) = state.calls.last_mut() {
+            caller.children += elapsed;
+        }
+        state.current = state.calls.last().map(|call| call.label);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bumpalo::Bump;
+
+    use super::Profiler;
+    use crate::atlas_vm::runtime::{arena::RuntimeArena, binary};
+    use crate::atlas_vm::Atlas77VM;
+    use crate::engine::Engine;
+
+    #[test]
+    fn functions_and_call_stacks_are_counted() {
+        let source = "func fib(n: int64) -> int64 {
+    if n <= 1 {
+        return n;
+    }
+    return fib(n - 1) + fib(n - 2);
+}
+func name() -> str {
+    return \"fib\";
+}
+func main() -> int64 {
+    let s = name();
+    return fib(5);
+}";
+        let script = Engine::new().compile("fib.atlas", source).unwrap();
+        let bump = Bump::new();
+        let program = binary::deserialize(script.bytecode(), &RuntimeArena::new(&bump)).unwrap();
+        let mut vm = Atlas77VM::new(program, RuntimeArena::new(&bump)).unwrap();
+        let profiler = Profiler::new();
+        vm.set_debug_hook(profiler.clone());
+        assert_eq!(vm.run().unwrap().as_i64(), 5);
+
+        let functions = profiler.functions();
+        let calls = functions.iter().map(|f| (f.name.as_str(), f.calls)).collect::<Vec<_>>();
+        assert_eq!(calls, [("fib", 15), ("main", 1), ("name", 1)]);
+        assert_eq!(functions[2].allocations, 1);
+        assert!(functions[0].total_time <= functions[1].total_time);
+
+        let folded = profiler.folded();
+        let stacks = folded.lines().map(|line| line.rsplit_once(' ').unwrap().0).collect::<Vec<_>>();
+        assert_eq!(
+            stacks,
+            [
+                "main",
+                "main;fib",
+                "main;fib;fib",
+                "main;fib;fib;fib",
+                "main;fib;fib;fib;fib",
+                "main;fib;fib;fib;fib;fib",
+                "main;name"
+            ]
+        );
+        let total: u64 = folded.lines().map(|line| line.rsplit_once(' ').unwrap().1.parse::<u64>().unwrap()).sum();
+        assert_eq!(total, functions.iter().map(|f| f.instructions).sum::<u64>());
+    }
+}
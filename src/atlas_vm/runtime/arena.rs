@@ -13,4 +13,10 @@ impl<'arena> RuntimeArena<'arena> {
     pub fn alloc<T>(&self, value: T) -> &'arena mut T {
         self.allocator.alloc(value)
     }
-}
\ No newline at end of file
+    pub fn alloc_str(&self, value: &str) -> &'arena str {
+        self.allocator.alloc_str(value)
+    }
+    pub fn alloc_slice<T>(&self, values: Vec<T>) -> &'arena [T] {
+        self.allocator.alloc_slice_fill_iter(values)
+    }
+}
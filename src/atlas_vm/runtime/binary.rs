@@ -0,0 +1,940 @@
+//! Binary container for a compiled [`Program`] (the `.atlasc` file).
+//!
+//! Layout (every integer is little-endian):
+//! ```text
+//! magic        b"A77C"
+//! version      u16
+//! entry_point  str
+//! source_path  str
+//! libraries    u32 count, then (str name, u8 is_std)
+//! string_pool  u32 count, then str
+//! list_pool    u32 count, then ConstantValue
+//! function_pool u32 count, then u64
+//! class_pool   u32 count, then ConstantClass
+//! native_pool  u32 count, then str
+//! labels       u32 count, then (str name, u64 position, u32 count, Instruction...,
+//!              u32 count, (u64 offset, u64 start, u64 end)..., u32 count, str local...)
+//! ```
+//! A `str` is a `u32` byte length followed by UTF-8 bytes.
+//!
+//! Instructions and constants are encoded as a `u8` tag followed by their operands.
+//! Every index & position they hold is checked against the program when loading it.
+//! Adding, removing or reordering an [`Instruction`] variant requires bumping [`FORMAT_VERSION`].
+
+use std::collections::BTreeMap;
+
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::atlas_c::atlas_hir::signature::ConstantValue;
+use crate::atlas_vm::runtime::arena::RuntimeArena;
+use crate::atlas_vm::runtime::instruction::{
+    ConstantClass, ConstantPool, ImportedLibrary, Instruction, Label, Program, SpanEntry, Type,
+};
+
+/// Every `.atlasc` file starts with these bytes
+pub const MAGIC: [u8; 4] = *b"A77C";
+/// Version of the binary layout, checked when loading a file
+pub const FORMAT_VERSION: u16 = 7;
+
+#[derive(Error, Diagnostic, Debug)]
+pub enum BinaryError {
+    #[error("not an Atlas77 binary (bad magic header)")]
+    #[diagnostic(
+        code(binary::invalid_magic),
+        help("Compile the source file with `atlas_77 build` first")
+    )]
+    InvalidMagic,
+    #[error("unsupported binary format version {found} (expected {expected})")]
+    #[diagnostic(
+        code(binary::unsupported_version),
+        help("Rebuild the program with this version of the compiler")
+    )]
+    UnsupportedVersion { found: u16, expected: u16 },
+    #[error("unexpected end of file at byte {offset}")]
+    #[diagnostic(code(binary::unexpected_eof))]
+    UnexpectedEof { offset: usize },
+    #[error("invalid {what} tag `{tag}` at byte {offset}")]
+    #[diagnostic(code(binary::invalid_tag))]
+    InvalidTag {
+        what: &'static str,
+        tag: u8,
+        offset: usize,
+    },
+    #[error("invalid UTF-8 data at byte {offset}")]
+    #[diagnostic(code(binary::invalid_utf8))]
+    InvalidUtf8 { offset: usize },
+    #[error("{what} {index} in {place} is out of range, there are {len}")]
+    #[diagnostic(
+        code(binary::out_of_range),
+        help("The file is corrupted, rebuild the program")
+    )]
+    OutOfRange {
+        what: &'static str,
+        index: usize,
+        len: usize,
+        place: String,
+    },
+}
+
+pub type BinaryResult<T> = Result<T, BinaryError>;
+
+/// Encode a program into the `.atlasc` binary format
+pub fn serialize(program: &Program) -> Vec<u8> {
+    let mut w = Writer { buf: Vec::new() };
+    w.buf.extend_from_slice(&MAGIC);
+    w.u16(FORMAT_VERSION);
+    w.str(&program.entry_point);
+    w.str(&program.source_path);
+
+    w.len(program.libraries.len());
+    for lib in &program.libraries {
+        w.str(&lib.name);
+        w.bool(lib.is_std);
+    }
+
+    let global = &program.global;
+    w.len(global.string_pool.len());
+    for s in global.string_pool {
+        w.str(s);
+    }
+    w.len(global.list_pool.len());
+    for value in global.list_pool {
+        w.constant(value);
+    }
+    w.len(global.function_pool.len());
+    for pos in global.function_pool {
+        w.u64(*pos as u64);
+    }
+    w.len(global.class_pool.len());
+    for class in global.class_pool {
+        w.str(class.name);
+        w.len(class.fields.len());
+        for field in &class.fields {
+            w.str(field);
+        }
+        w.u64(class.constructor_nb_args as u64);
+        w.len(class.constants.len());
+        for (name, value) in &class.constants {
+            w.str(name);
+            w.constant(value);
+        }
+    }
+    w.len(global.native_pool.len());
+    for name in global.native_pool {
+        w.str(name);
+    }
+
+    w.len(program.labels.len());
+    for label in &program.labels {
+        w.str(label.name);
+        w.u64(label.position as u64);
+        w.len(label.body.len());
+        for instr in label.body {
+            w.instruction(instr);
+        }
+        w.len(label.spans.len());
+        for entry in label.spans {
+            w.u64(entry.offset as u64);
+            w.u64(entry.start as u64);
+            w.u64(entry.end as u64);
+        }
+        w.len(label.locals.len());
+        for local in label.locals {
+            w.str(local);
+        }
+    }
+    w.buf
+}
+
+/// Decode a program from the `.atlasc` binary format.
+///
+/// Every string and slice of the program is allocated in the given arena.
+pub fn deserialize<'run>(
+    bytes: &[u8],
+    arena: &RuntimeArena<'run>,
+) -> BinaryResult<Program<'run>> {
+    let mut r = Reader {
+        bytes,
+        pos: 0,
+        arena,
+    };
+    if r.take(MAGIC.len())? != MAGIC {
+        return Err(BinaryError::InvalidMagic);
+    }
+    let version = r.u16()?;
+    if version != FORMAT_VERSION {
+        return Err(BinaryError::UnsupportedVersion {
+            found: version,
+            expected: FORMAT_VERSION,
+        });
+    }
+    let entry_point = r.string()?;
+    let source_path = r.string()?;
+
+    let mut libraries = Vec::new();
+    for _ in 0..r.len()? {
+        libraries.push(ImportedLibrary {
+            name: r.string()?,
+            is_std: r.bool()?,
+        });
+    }
+
+    let mut string_pool = Vec::new();
+    for _ in 0..r.len()? {
+        string_pool.push(r.str()?);
+    }
+    let mut list_pool = Vec::new();
+    for _ in 0..r.len()? {
+        list_pool.push(r.constant()?);
+    }
+    let mut function_pool = Vec::new();
+    for _ in 0..r.len()? {
+        function_pool.push(r.u64()? as usize);
+    }
+    let mut class_pool = Vec::new();
+    for _ in 0..r.len()? {
+        let name = r.str()?;
+        let mut fields = Vec::new();
+        for _ in 0..r.len()? {
+            fields.push(r.str()?);
+        }
+        let constructor_nb_args = r.u64()? as usize;
+        let mut constants = BTreeMap::new();
+        for _ in 0..r.len()? {
+            let name = r.str()?;
+            constants.insert(name, r.constant()?);
+        }
+        class_pool.push(ConstantClass {
+            name,
+            fields,
+            constructor_nb_args,
+            constants,
+        });
+    }
+
+    let mut native_pool = Vec::new();
+    for _ in 0..r.len()? {
+        native_pool.push(r.str()?);
+    }
+
+    let mut labels = Vec::new();
+    for _ in 0..r.len()? {
+        let name = r.str()?;
+        let position = r.u64()? as usize;
+        let mut body = Vec::new();
+        for _ in 0..r.len()? {
+            body.push(r.instruction()?);
+        }
+        let mut spans = Vec::new();
+        for _ in 0..r.len()? {
+            spans.push(SpanEntry {
+                offset: r.u64()? as usize,
+                start: r.u64()? as usize,
+                end: r.u64()? as usize,
+            });
+        }
+        let mut locals = Vec::new();
+        for _ in 0..r.len()? {
+            locals.push(r.str()?);
+        }
+        labels.push(Label {
+            name,
+            position,
+            body: arena.alloc_slice(body),
+            spans: arena.alloc_slice(spans),
+            locals: arena.alloc_slice(locals),
+        });
+    }
+
+    let program = Program {
+        labels,
+        entry_point,
+        source_path,
+        libraries,
+        global: ConstantPool {
+            string_pool: arena.alloc_slice(string_pool),
+            list_pool: arena.alloc_slice(list_pool),
+            function_pool: arena.alloc_slice(function_pool),
+            class_pool: arena.alloc_slice(class_pool),
+            native_pool: arena.alloc_slice(native_pool),
+        },
+    };
+    check_indices(&program)?;
+    Ok(program)
+}
+
+/// Make sure the VM won't index past the pools, the labels or the code of `program`
+fn check_indices(program: &Program) -> BinaryResult<()> {
+    let code_len = program.len();
+    let global = &program.global;
+    let check = |what, index: usize, len: usize, place: &dyn Fn() -> String| {
+        if index < len {
+            Ok(())
+        } else {
+            Err(BinaryError::OutOfRange {
+                what,
+                index,
+                len,
+                place: place(),
+            })
+        }
+    };
+    for (i, position) in global.function_pool.iter().enumerate() {
+        check("position", *position, code_len, &|| format!("the function pool (entry {})", i))?;
+    }
+    let mut position = 0;
+    for label in &program.labels {
+        let place = |offset: usize| move || format!("`{}` (instruction {})", label.name, offset);
+        if label.position != position {
+            return Err(BinaryError::OutOfRange {
+                what: "position",
+                index: label.position,
+                len: position,
+                place: format!("`{}`, it should be right after the previous label", label.name),
+            });
+        }
+        for (offset, instr) in label.body.iter().enumerate() {
+            let pc = position + offset;
+            let place = place(offset);
+            //A jump may go right past the last instruction, ending the program
+            let target = |delta: isize| pc.checked_add_signed(delta).unwrap_or(usize::MAX);
+            match *instr {
+                Instruction::PushStr(i) => check("string", i, global.string_pool.len(), &place)?,
+                Instruction::PushList(i) => check("list", i, global.list_pool.len(), &place)?,
+                Instruction::LinkedCall { label, .. } => check("label", label, program.labels.len(), &place)?,
+                Instruction::LinkedNativeCall { native, .. } => {
+                    check("native", native, global.native_pool.len(), &place)?
+                }
+                Instruction::LinkedNewObj { class } => check("class", class, global.class_pool.len(), &place)?,
+                Instruction::Jmp { pos } => check("jump target", target(pos), code_len + 1, &place)?,
+                Instruction::JmpZ { pos } => check("jump target", target(pos + 1), code_len + 1, &place)?,
+                //The table is followed by `len` jumps & the one taken when no value matches
+                Instruction::JmpTable { len, .. } => {
+                    check("jump target", pc.saturating_add(len).saturating_add(1), code_len, &place)?
+                }
+                _ => {}
+            }
+        }
+        position += label.body.len();
+    }
+    Ok(())
+}
+
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+    fn u16(&mut self, v: u16) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+    fn u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+    fn u64(&mut self, v: u64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+    fn i64(&mut self, v: i64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+    fn f64(&mut self, v: f64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+    fn bool(&mut self, v: bool) {
+        self.u8(v as u8);
+    }
+    fn char(&mut self, v: char) {
+        self.u32(v as u32);
+    }
+    fn len(&mut self, len: usize) {
+        self.u32(len as u32);
+    }
+    fn str(&mut self, s: &str) {
+        self.len(s.len());
+        self.buf.extend_from_slice(s.as_bytes());
+    }
+
+    fn constant(&mut self, value: &ConstantValue) {
+        match value {
+            ConstantValue::Int(i) => {
+                self.u8(0);
+                self.i64(*i);
+            }
+            ConstantValue::Float(f) => {
+                self.u8(1);
+                self.f64(*f);
+            }
+            ConstantValue::UInt(u) => {
+                self.u8(2);
+                self.u64(*u);
+            }
+            ConstantValue::String(s) => {
+                self.u8(3);
+                self.str(s);
+            }
+            ConstantValue::Bool(b) => {
+                self.u8(4);
+                self.bool(*b);
+            }
+            ConstantValue::Char(c) => {
+                self.u8(5);
+                self.char(*c);
+            }
+            ConstantValue::List(l) => {
+                self.u8(6);
+                self.len(l.len());
+                for value in l {
+                    self.constant(value);
+                }
+            }
+        }
+    }
+
+    fn instruction(&mut self, instr: &Instruction) {
+        use Instruction::*;
+        match instr {
+            PushInt(i) => {
+                self.u8(0);
+                self.i64(*i);
+            }
+            PushFloat(f) => {
+                self.u8(1);
+                self.f64(*f);
+            }
+            PushUnsignedInt(u) => {
+                self.u8(2);
+                self.u64(*u);
+            }
+            PushBool(b) => {
+                self.u8(3);
+                self.bool(*b);
+            }
+            PushChar(c) => {
+                self.u8(4);
+                self.char(*c);
+            }
+            PushStr(i) => {
+                self.u8(5);
+                self.u64(*i as u64);
+            }
+            PushList(i) => {
+                self.u8(6);
+                self.u64(*i as u64);
+            }
+            PushUnit => self.u8(7),
+            Get(i) => {
+                self.u8(8);
+                self.u64(*i as u64);
+            }
+            Pop => self.u8(9),
+            Swap => self.u8(10),
+            Dup => self.u8(11),
+            Store(i) => {
+                self.u8(12);
+                self.u64(*i as u64);
+            }
+            Reserve(n) => {
+                self.u8(13);
+                self.u64(*n as u64);
+            }
+            ListLoad => self.u8(14),
+            ListStore => self.u8(15),
+            NewList => self.u8(16),
+            ListIndex(i) => {
+                self.u8(17);
+                self.u64(*i as u64);
+            }
+            StringLoad => self.u8(18),
+            StringStore => self.u8(19),
+            CastTo(t) => {
+                self.u8(20);
+                self.u8(*t as u8);
+            }
+            IAdd => self.u8(21),
+            FAdd => self.u8(22),
+            UIAdd => self.u8(23),
+            ISub => self.u8(24),
+            FSub => self.u8(25),
+            UISub => self.u8(26),
+            IMul => self.u8(27),
+            FMul => self.u8(28),
+            UIMul => self.u8(29),
+            IDiv => self.u8(30),
+            FDiv => self.u8(31),
+            UIDiv => self.u8(32),
+            IMod => self.u8(33),
+            FMod => self.u8(34),
+            UIMod => self.u8(35),
+            Eq => self.u8(36),
+            Neq => self.u8(37),
+            Gt => self.u8(38),
+            Gte => self.u8(39),
+            Lt => self.u8(40),
+            Lte => self.u8(41),
+            Jmp { pos } => {
+                self.u8(42);
+                self.i64(*pos as i64);
+            }
+            JmpZ { pos } => {
+                self.u8(43);
+                self.i64(*pos as i64);
+            }
+            DirectCall { pos, args } => {
+                self.u8(44);
+                self.u64(*pos as u64);
+                self.u8(*args);
+            }
+            Call { nb_args } => {
+                self.u8(45);
+                self.u8(*nb_args);
+            }
+            FunctionCall {
+                function_name,
+                nb_args,
+            } => {
+                self.u8(46);
+                self.str(function_name);
+                self.u8(*nb_args);
+            }
+            ExternCall {
+                function_name,
+                nb_args,
+            } => {
+                self.u8(47);
+                self.str(function_name);
+                self.u8(*nb_args);
+            }
+            Return => self.u8(48),
+            DeleteObj => self.u8(49),
+            GetField { field_name } => {
+                self.u8(50);
+                self.str(field_name);
+            }
+            SetField { field_name } => {
+                self.u8(51);
+                self.str(field_name);
+            }
+            NewObj { class_name } => {
+                self.u8(52);
+                self.str(class_name);
+            }
+            MethodCall {
+                method_name,
+                nb_args,
+            } => {
+                self.u8(53);
+                self.str(method_name);
+                self.u8(*nb_args);
+            }
+            StaticCall {
+                method_name,
+                nb_args,
+            } => {
+                self.u8(54);
+                self.str(method_name);
+                self.u8(*nb_args);
+            }
+            Halt => self.u8(55),
+            LinkedCall { label, nb_args } => {
+                self.u8(56);
+                self.u64(*label as u64);
+                self.u8(*nb_args);
+            }
+            LinkedNativeCall { native, nb_args } => {
+                self.u8(57);
+                self.u64(*native as u64);
+                self.u8(*nb_args);
+            }
+            LinkedNewObj { class } => {
+                self.u8(58);
+                self.u64(*class as u64);
+            }
+            JmpTable { min, len } => {
+                self.u8(59);
+                self.i64(*min);
+                self.u64(*len as u64);
+            }
+            NewUnion { tag, nb_fields } => {
+                self.u8(60);
+                self.u64(*tag as u64);
+                self.u64(*nb_fields as u64);
+            }
+            UnionTag => self.u8(61),
+            UnionField(i) => {
+                self.u8(62);
+                self.u64(*i as u64);
+            }
+        }
+    }
+}
+
+struct Reader<'a, 'arena, 'run> {
+    bytes: &'a [u8],
+    pos: usize,
+    arena: &'arena RuntimeArena<'run>,
+}
+
+impl<'run> Reader<'_, '_, 'run> {
+    fn take(&mut self, n: usize) -> BinaryResult<&[u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(BinaryError::UnexpectedEof { offset: self.pos })?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+    fn array<const N: usize>(&mut self) -> BinaryResult<[u8; N]> {
+        let mut buf = [0; N];
+        buf.copy_from_slice(self.take(N)?);
+        Ok(buf)
+    }
+    fn u8(&mut self) -> BinaryResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> BinaryResult<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+    fn u32(&mut self) -> BinaryResult<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+    fn u64(&mut self) -> BinaryResult<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+    fn i64(&mut self) -> BinaryResult<i64> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+    fn f64(&mut self) -> BinaryResult<f64> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+    fn bool(&mut self) -> BinaryResult<bool> {
+        let offset = self.pos;
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            tag => Err(BinaryError::InvalidTag {
+                what: "boolean",
+                tag,
+                offset,
+            }),
+        }
+    }
+    fn char(&mut self) -> BinaryResult<char> {
+        let offset = self.pos;
+        char::from_u32(self.u32()?).ok_or(BinaryError::InvalidUtf8 { offset })
+    }
+    fn len(&mut self) -> BinaryResult<usize> {
+        Ok(self.u32()? as usize)
+    }
+    fn string(&mut self) -> BinaryResult<String> {
+        let len = self.len()?;
+        let offset = self.pos;
+        let bytes = self.take(len)?;
+        std::str::from_utf8(bytes)
+            .map(String::from)
+            .map_err(|_| BinaryError::InvalidUtf8 { offset })
+    }
+    fn str(&mut self) -> BinaryResult<&'run str> {
+        let s = self.string()?;
+        Ok(self.arena.alloc_str(&s))
+    }
+
+    fn constant(&mut self) -> BinaryResult<ConstantValue> {
+        let offset = self.pos;
+        Ok(match self.u8()? {
+            0 => ConstantValue::Int(self.i64()?),
+            1 => ConstantValue::Float(self.f64()?),
+            2 => ConstantValue::UInt(self.u64()?),
+            3 => ConstantValue::String(self.string()?),
+            4 => ConstantValue::Bool(self.bool()?),
+            5 => ConstantValue::Char(self.char()?),
+            6 => {
+                let mut list = Vec::new();
+                for _ in 0..self.len()? {
+                    list.push(self.constant()?);
+                }
+                ConstantValue::List(list)
+            }
+            tag => {
+                return Err(BinaryError::InvalidTag {
+                    what: "constant",
+                    tag,
+                    offset,
+                })
+            }
+        })
+    }
+
+    fn ty(&mut self) -> BinaryResult<Type> {
+        let offset = self.pos;
+        Ok(match self.u8()? {
+            0 => Type::Integer,
+            1 => Type::Float,
+            2 => Type::UnsignedInteger,
+            3 => Type::Boolean,
+            4 => Type::String,
+            5 => Type::Char,
+            tag => {
+                return Err(BinaryError::InvalidTag {
+                    what: "type",
+                    tag,
+                    offset,
+                })
+            }
+        })
+    }
+
+    fn instruction(&mut self) -> BinaryResult<Instruction<'run>> {
+        use Instruction::*;
+        let offset = self.pos;
+        Ok(match self.u8()? {
+            0 => PushInt(self.i64()?),
+            1 => PushFloat(self.f64()?),
+            2 => PushUnsignedInt(self.u64()?),
+            3 => PushBool(self.bool()?),
+            4 => PushChar(self.char()?),
+            5 => PushStr(self.u64()? as usize),
+            6 => PushList(self.u64()? as usize),
+            7 => PushUnit,
+            8 => Get(self.u64()? as usize),
+            9 => Pop,
+            10 => Swap,
+            11 => Dup,
+            12 => Store(self.u64()? as usize),
+            13 => Reserve(self.u64()? as usize),
+            14 => ListLoad,
+            15 => ListStore,
+            16 => NewList,
+            17 => ListIndex(self.u64()? as usize),
+            18 => StringLoad,
+            19 => StringStore,
+            20 => CastTo(self.ty()?),
+            21 => IAdd,
+            22 => FAdd,
+            23 => UIAdd,
+            24 => ISub,
+            25 => FSub,
+            26 => UISub,
+            27 => IMul,
+            28 => FMul,
+            29 => UIMul,
+            30 => IDiv,
+            31 => FDiv,
+            32 => UIDiv,
+            33 => IMod,
+            34 => FMod,
+            35 => UIMod,
+            36 => Eq,
+            37 => Neq,
+            38 => Gt,
+            39 => Gte,
+            40 => Lt,
+            41 => Lte,
+            42 => Jmp {
+                pos: self.i64()? as isize,
+            },
+            43 => JmpZ {
+                pos: self.i64()? as isize,
+            },
+            44 => DirectCall {
+                pos: self.u64()? as usize,
+                args: self.u8()?,
+            },
+            45 => Call {
+                nb_args: self.u8()?,
+            },
+            46 => FunctionCall {
+                function_name: self.str()?,
+                nb_args: self.u8()?,
+            },
+            47 => ExternCall {
+                function_name: self.str()?,
+                nb_args: self.u8()?,
+            },
+            48 => Return,
+            49 => DeleteObj,
+            50 => GetField {
+                field_name: self.str()?,
+            },
+            51 => SetField {
+                field_name: self.str()?,
+            },
+            52 => NewObj {
+                class_name: self.str()?,
+            },
+            53 => MethodCall {
+                method_name: self.str()?,
+                nb_args: self.u8()?,
+            },
+            54 => StaticCall {
+                method_name: self.str()?,
+                nb_args: self.u8()?,
+            },
+            55 => Halt,
+            56 => LinkedCall {
+                label: self.u64()? as usize,
+                nb_args: self.u8()?,
+            },
+            57 => LinkedNativeCall {
+                native: self.u64()? as usize,
+                nb_args: self.u8()?,
+            },
+            58 => LinkedNewObj {
+                class: self.u64()? as usize,
+            },
+            59 => JmpTable {
+                min: self.i64()?,
+                len: self.u64()? as usize,
+            },
+            60 => NewUnion {
+                tag: self.u64()? as usize,
+                nb_fields: self.u64()? as usize,
+            },
+            61 => UnionTag,
+            62 => UnionField(self.u64()? as usize),
+            tag => {
+                return Err(BinaryError::InvalidTag {
+                    what: "instruction",
+                    tag,
+                    offset,
+                })
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atlas_vm::errors::RuntimeError;
+    use crate::atlas_vm::Atlas77VM;
+    use bumpalo::Bump;
+
+    #[test]
+    fn round_trip() {
+        let bump = Bump::new();
+        let arena = RuntimeArena::new(&bump);
+        let body = [
+            Instruction::PushStr(0),
+            Instruction::LinkedNativeCall {
+                native: 0,
+                nb_args: 1,
+            },
+            Instruction::JmpZ { pos: -3 },
+            Instruction::CastTo(Type::Char),
+            Instruction::Halt,
+        ];
+        let program = Program {
+            labels: vec![Label {
+                name: "main",
+                position: 0,
+                body: &body,
+                spans: &[SpanEntry {
+                    offset: 0,
+                    start: 4,
+                    end: 12,
+                }],
+                locals: &["x"],
+            }],
+            entry_point: String::from("main"),
+            source_path: String::from("main.atlas"),
+            libraries: vec![ImportedLibrary {
+                name: String::from("std/io"),
+                is_std: true,
+            }],
+            global: ConstantPool {
+                string_pool: &["Hello"],
+                list_pool: &[ConstantValue::List(vec![ConstantValue::Float(1.5)])],
+                function_pool: &[0],
+                class_pool: &[ConstantClass {
+                    name: "Point",
+                    fields: vec!["x", "y"],
+                    constructor_nb_args: 2,
+                    constants: BTreeMap::from([("ORIGIN", ConstantValue::Int(0))]),
+                }],
+                native_pool: &["println"],
+            },
+        };
+        let bytes = serialize(&program);
+        assert_eq!(deserialize(&bytes, &arena).unwrap(), program);
+        assert!(matches!(
+            deserialize(&bytes[..bytes.len() - 1], &arena),
+            Err(BinaryError::UnexpectedEof { .. })
+        ));
+    }
+
+    #[test]
+    fn indices_are_checked() {
+        let bump = Bump::new();
+        let arena = RuntimeArena::new(&bump);
+        let load = |body: Vec<Instruction<'static>>| {
+            let body = body.leak();
+            let mut program = Program::new();
+            program.entry_point = String::from("main");
+            program.labels = vec![Label {
+                name: "main",
+                position: 0,
+                body,
+                spans: &[],
+                locals: &[],
+            }];
+            program.global.string_pool = &["a"];
+            deserialize(&serialize(&program), &arena).map(|_| ())
+        };
+        let out_of_range = |res: BinaryResult<()>, expected: &str| {
+            assert!(
+                matches!(&res, Err(BinaryError::OutOfRange { what, .. }) if *what == expected),
+                "{:?}",
+                res
+            );
+        };
+        assert!(load(vec![Instruction::PushStr(0), Instruction::JmpZ { pos: 0 }, Instruction::Jmp { pos: -2 }]).is_ok());
+        out_of_range(load(vec![Instruction::PushStr(1)]), "string");
+        out_of_range(load(vec![Instruction::PushList(0)]), "list");
+        out_of_range(load(vec![Instruction::LinkedCall { label: 1, nb_args: 0 }]), "label");
+        out_of_range(load(vec![Instruction::LinkedNativeCall { native: 0, nb_args: 0 }]), "native");
+        out_of_range(load(vec![Instruction::LinkedNewObj { class: 0 }]), "class");
+        out_of_range(load(vec![Instruction::Jmp { pos: -1 }]), "jump target");
+        out_of_range(load(vec![Instruction::JmpZ { pos: 1 }]), "jump target");
+        out_of_range(load(vec![Instruction::JmpTable { min: 0, len: 1 }, Instruction::Jmp { pos: 1 }]), "jump target");
+
+        let mut program = Program::new();
+        program.global.function_pool = &[3];
+        out_of_range(deserialize(&serialize(&program), &arena).map(|_| ()), "position");
+        program.global.function_pool = &[];
+        program.labels = vec![Label {
+            name: "main",
+            position: 2,
+            body: &[Instruction::Halt],
+            spans: &[],
+            locals: &[],
+        }];
+        out_of_range(deserialize(&serialize(&program), &arena).map(|_| ()), "position");
+    }
+
+    #[test]
+    fn unsupported_instructions_are_runtime_errors() {
+        let bump = Bump::new();
+        let body = [Instruction::PushList(0), Instruction::Halt];
+        let mut program = Program::new();
+        program.entry_point = String::from("main");
+        program.labels = vec![Label {
+            name: "main",
+            position: 0,
+            body: &body,
+            spans: &[],
+            locals: &[],
+        }];
+        let list_pool = [ConstantValue::List(Vec::new())];
+        program.global.list_pool = &list_pool;
+        let arena = RuntimeArena::new(&bump);
+        let program = deserialize(&serialize(&program), &arena).unwrap();
+        let mut vm = Atlas77VM::new(program, RuntimeArena::new(&bump)).unwrap();
+        assert!(matches!(vm.run(), Err(RuntimeError::UnsupportedInstruction(_))));
+    }
+}
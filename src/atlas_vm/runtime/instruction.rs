@@ -2,13 +2,12 @@
 //A more powerful version will be done for the v0.5.2 & v0.5.3
 
 use std::collections::BTreeMap;
-use std::ops::Index;
 
 use crate::atlas_c::atlas_hir::signature::ConstantValue;
 use serde::{Deserialize, Serialize};
 
 #[repr(u8)]
-#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub enum Type {
     Integer,
     Float,
@@ -33,23 +32,19 @@ pub enum Instruction<'run> {
     PushList(usize),
     PushUnit,
 
-    ///Store will replace StoreInteger, StoreFloat, StoreUnsignedInteger, StoreBool and will stop using the var_map and favor the stack instead
-    //Store(usize),
-    ///Load will replace LoadInteger, LoadFloat, LoadUnsignedInteger, LoadBool and will stop using the var_map and favor the stack instead
+    /// Pop the top of the stack into the local at the given slot of the current frame
+    ///
+    /// Slots are relative to the frame base pointer, the arguments come first
+    Store(usize),
+    /// Push a copy of the local at the given slot of the current frame
     Get(usize),
+    /// Push `n` unit values to make room for the locals of the current function
+    Reserve(usize),
 
     Pop,
     Swap,
     Dup,
 
-    Store {
-        var_name: &'run str,
-    },
-
-    Load {
-        var_name: &'run str,
-    },
-
     /// Stack state:
     ///
     /// - **Bottom** `[ListPointer, Index,]` **Top**
@@ -115,6 +110,13 @@ pub enum Instruction<'run> {
     JmpZ {
         pos: isize,
     },
+    /// Pop an integer `v` & run the `v - min`th of the `len + 1` [`Instruction::Jmp`] following it
+    ///
+    /// The last one is taken when `v` is out of `min..min + len`
+    JmpTable {
+        min: i64,
+        len: usize,
+    },
 
     /// Call a function by taking the value at `pos` in the stack as the fn_ptr
     DirectCall {
@@ -134,6 +136,20 @@ pub enum Instruction<'run> {
         function_name: &'run str,
         nb_args: u8,
     },
+    /// `FunctionCall`, `MethodCall` & `StaticCall` after linking
+    ///
+    /// `label` is an index in `Program::labels`
+    LinkedCall {
+        label: usize,
+        nb_args: u8,
+    },
+    /// `ExternCall` after linking
+    ///
+    /// `native` is an index in `ConstantPool::native_pool`
+    LinkedNativeCall {
+        native: usize,
+        nb_args: u8,
+    },
     Return,
 
     /// Delete the object from memory (the object pointer is at the top of the stack)
@@ -148,14 +164,34 @@ pub enum Instruction<'run> {
     SetField {
         field_name: &'run str,
     },
+    /// Stack:
+    /// - **[Field1, ..., FieldN] -> [UnionPtr]**
+    ///
+    /// `tag` is the variant's index in the union
+    NewUnion {
+        tag: usize,
+        nb_fields: usize,
+    },
+    /// Stack:
+    /// - **[UnionPtr] -> [Tag]**
+    UnionTag,
+    /// Stack:
+    /// - **[UnionPtr] -> [FieldValue]**
+    UnionField(usize),
     /// Create a new object
     /// The information about the object is in the constant pool
     NewObj {
         class_name: &'run str,
     },
+    /// `NewObj` after linking
+    ///
+    /// `class` is an index in `ConstantPool::class_pool`
+    LinkedNewObj {
+        class: usize,
+    },
     /// This jumps to the correct position in the program to execute the method
     ///
-    /// And creates a `self` variable in the var_map
+    /// `self` is the first argument, so it ends up in the slot 0 of the method frame
     MethodCall {
         method_name: &'run str,
         nb_args: u8,
@@ -174,36 +210,41 @@ pub struct ImportedLibrary {
     pub is_std: bool,
 }
 
-///todo: Make the program serializable and deserializable
-/// This will allow the program to be saved and loaded from a file
+/// A compiled program, see [`super::binary`] to save it to and load it from a `.atlasc` file
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize)]
 pub struct Program<'run> {
     pub labels: Vec<Label<'run>>,
     pub entry_point: String,
+    /// Path of the source file, used to show runtime errors, empty if unknown
+    pub source_path: String,
     pub libraries: Vec<ImportedLibrary>,
     pub global: ConstantPool<'run>,
 }
 
-impl<'run> Index<usize> for Program<'run> {
-    type Output = Instruction<'run>;
-
-    fn index(&self, index: usize) -> &Self::Output {
-        let mut current_index = 0;
-        for label in &self.labels {
-            if current_index + label.body.len() > index {
-                return &label.body[index - current_index];
-            }
-            current_index += label.body.len();
-        }
-        panic!("Index out of bounds");
-    }
-}
 impl Default for Program<'_> {
     fn default() -> Self {
         Self::new()
     }
 }
-impl Program<'_> {
+impl<'run> Program<'run> {
+    /// Concatenate the body of every label, in order, so `label.position` indexes the result
+    pub fn flatten(&self) -> Vec<Instruction<'run>> {
+        let mut code = Vec::with_capacity(self.len());
+        for label in &self.labels {
+            code.extend_from_slice(label.body);
+        }
+        code
+    }
+    /// Label whose body contains the instruction at `pc` in the flattened program
+    pub fn label_at(&self, pc: usize) -> Option<&Label<'run>> {
+        self.label_index_at(pc).map(|idx| &self.labels[idx])
+    }
+    /// Index in `labels` of [`Program::label_at`]
+    pub fn label_index_at(&self, pc: usize) -> Option<usize> {
+        let idx = self.labels.partition_point(|label| label.position <= pc);
+        idx.checked_sub(1)
+            .filter(|idx| pc < self.labels[*idx].position + self.labels[*idx].body.len())
+    }
     pub fn len(&self) -> usize {
         self.labels.iter().map(|label| label.body.len()).sum()
     }
@@ -214,11 +255,13 @@ impl Program<'_> {
         Self {
             labels: vec![],
             entry_point: String::new(),
+            source_path: String::new(),
             global: ConstantPool {
                 string_pool: &[],
                 list_pool: &[],
                 function_pool: &[],
                 class_pool: &[],
+                native_pool: &[],
             },
             libraries: vec![],
         }
@@ -232,6 +275,8 @@ pub struct ConstantPool<'run> {
     pub list_pool: &'run [ConstantValue],
     pub function_pool: &'run [usize],
     pub class_pool: &'run [ConstantClass<'run>],
+    /// Name of every native function called by the program, filled by the linker
+    pub native_pool: &'run [&'run str],
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize)]
@@ -248,4 +293,24 @@ pub struct Label<'run> {
     pub name: &'run str,
     pub position: usize,
     pub body: &'run [Instruction<'run>],
+    /// Line table of `body`, sorted by offset
+    pub spans: &'run [SpanEntry],
+    /// Name of every local slot of `body`, arguments first
+    pub locals: &'run [&'run str],
+}
+
+impl Label<'_> {
+    /// Source span of the instruction at `offset` in `body`
+    pub fn span_at(&self, offset: usize) -> Option<&SpanEntry> {
+        let idx = self.spans.partition_point(|entry| entry.offset <= offset);
+        idx.checked_sub(1).map(|idx| &self.spans[idx])
+    }
+}
+
+/// The instructions from `offset` (in a label body) up to the next entry come from `start..end` in the source
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+pub struct SpanEntry {
+    pub offset: usize,
+    pub start: usize,
+    pub end: usize,
 }
@@ -1,3 +1,4 @@
 pub mod instruction;
 pub mod vm_state;
 pub mod arena;
+pub mod binary;
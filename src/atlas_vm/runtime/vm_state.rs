@@ -1,13 +1,18 @@
 use std::collections::HashMap;
 
-use crate::atlas_vm::memory::varmap::VarMap;
+use crate::atlas_vm::memory::object_map::{ObjectIndex, ObjectKind};
 use crate::atlas_vm::memory::{object_map::Memory, stack::Stack, vm_data::VMData};
+use crate::atlas_vm::runtime::instruction::ConstantClass;
+use crate::atlas_vm::RuntimeResult;
 
 pub struct VMState<'state, 'run> {
     pub stack: &'state mut Stack,
     pub object_map: &'state mut Memory<'run>,
     pub consts: &'state HashMap<&'run str, VMData>,
-    pub var_map: &'state mut VarMap<'run>,
+    /// Classes of the running program, natives build their instances from them
+    pub classes: &'state [ConstantClass<'run>],
+    /// Objects allocated through [`VMState::alloc`], they stay roots until the native returns
+    allocated: Vec<VMData>,
 }
 
 impl<'state, 'run> VMState<'state, 'run> {
@@ -15,13 +20,33 @@ impl<'state, 'run> VMState<'state, 'run> {
         stack: &'state mut Stack,
         object_map: &'state mut Memory<'run>,
         consts: &'state HashMap<&'run str, VMData>,
-        var_map: &'state mut VarMap<'run>,
+        classes: &'state [ConstantClass<'run>],
     ) -> Self {
         Self {
             stack,
             object_map,
             consts,
-            var_map,
+            classes,
+            allocated: Vec::new(),
         }
     }
+
+    /// Put `object` in memory, collecting the garbage first if needed
+    ///
+    /// The roots are the stack, `consts`, the objects this state allocated & the values `object` holds
+    pub fn alloc(&mut self, object: ObjectKind<'run>) -> RuntimeResult<ObjectIndex> {
+        if self.object_map.needs_collection(object.size()) {
+            let roots = self
+                .stack
+                .iter()
+                .chain(self.consts.values())
+                .chain(self.allocated.iter())
+                .copied()
+                .chain(object.children());
+            self.object_map.collect(roots);
+        }
+        let ptr = self.object_map.put(object)?;
+        self.allocated.push(VMData::new_object(ptr));
+        Ok(ptr)
+    }
 }
@@ -0,0 +1,218 @@
+//! Bounds put on a VM running untrusted code.
+//!
+//! - [`Limits`] caps the instructions, heap, stack & wall time of a run
+//! - [`CancelHandle`] stops a run from another thread
+//! - [`Capabilities`] decides which standard libraries a program may load
+
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::atlas_vm::libraries;
+
+/// Every limit is off by default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    /// Number of instructions a run may execute, it fails with `OutOfFuel` past it
+    pub fuel: Option<u64>,
+    /// Maximum number of bytes taken by the live objects, the contents of strings & lists included
+    pub max_heap: Option<usize>,
+    /// Maximum number of values on the stack
+    pub max_stack: Option<usize>,
+    /// Wall time a run may take, it fails with `Timeout` past it
+    pub timeout: Option<Duration>,
+}
+
+/// Stop a running VM from any thread, the run fails with `Cancelled`
+///
+/// Clones share the same flag, it stays set until [`CancelHandle::reset`]
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Standard libraries a program may load, by their name (e.g. `io` for `std/io`)
+///
+/// Native modules registered by the host are always allowed, the host chose to expose them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    allowed: BTreeSet<String>,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl Capabilities {
+    /// Every standard library
+    pub fn all() -> Self {
+        Self {
+            allowed: libraries::STD_LIBRARIES.iter().map(|lib| lib.to_string()).collect(),
+        }
+    }
+    /// No standard library, only pure computations & host natives
+    pub fn none() -> Self {
+        Self {
+            allowed: BTreeSet::new(),
+        }
+    }
+    pub fn allow(mut self, library: &str) -> Self {
+        self.allowed.insert(library.to_string());
+        self
+    }
+    pub fn deny(mut self, library: &str) -> Self {
+        self.allowed.remove(library);
+        self
+    }
+    /// Whether the standard library imported as `path` (e.g. `std/file`) may be loaded
+    pub fn allows(&self, path: &str) -> bool {
+        path.split('/')
+            .next_back()
+            .is_some_and(|name| self.allowed.contains(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Capabilities, Limits};
+    use crate::atlas_vm::errors::RuntimeError;
+    use crate::engine::{AtlasError, Engine, IntoArgs};
+
+    const SOURCE: &str = r#"import "std/io"
+func spin() -> int64 {
+    while true {
+    }
+    return 0;
+}
+func fill(n: int64) -> int64 {
+    let xs = new [str; n];
+    let i = 0;
+    while i < n {
+        xs[i] = "x";
+        i = i + 1;
+    }
+    return n;
+}"#;
+
+    fn run_error(engine: &Engine, function: &str, args: impl IntoArgs) -> RuntimeError {
+        let script = engine.compile("sandbox.atlas", SOURCE).unwrap();
+        match script.call::<_, i64>(function, args) {
+            Err(AtlasError::Runtime(report)) => report.error,
+            _ => panic!("`{}` should have failed", function),
+        }
+    }
+
+    #[test]
+    fn limits_stop_runaway_scripts() {
+        let mut engine = Engine::new();
+        engine.set_limits(Limits {
+            fuel: Some(10_000),
+            ..Limits::default()
+        });
+        assert!(matches!(run_error(&engine, "spin", ()), RuntimeError::OutOfFuel));
+
+        engine.set_limits(Limits {
+            timeout: Some(Duration::from_millis(20)),
+            ..Limits::default()
+        });
+        assert!(matches!(run_error(&engine, "spin", ()), RuntimeError::Timeout));
+
+        engine.set_limits(Limits::default());
+        let handle = engine.cancel_handle();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            handle.cancel();
+        });
+        assert!(matches!(run_error(&engine, "spin", ()), RuntimeError::Cancelled));
+        canceller.join().unwrap();
+        engine.cancel_handle().reset();
+
+        engine.set_limits(Limits {
+            max_heap: Some(1024),
+            ..Limits::default()
+        });
+        let script = engine.compile("sandbox.atlas", SOURCE).unwrap();
+        assert_eq!(script.call::<_, i64>("fill", (4_i64,)).unwrap(), 4);
+        assert!(matches!(run_error(&engine, "fill", (32_i64,)), RuntimeError::OutOfMemory));
+    }
+
+    const ALLOCATIONS: &str = r#"func list(n: int64) -> int64 {
+    let xs = new [int64; n];
+    return n;
+}
+func grow() -> int64 {
+    let n = 1;
+    while true {
+        let xs = new [int64; n];
+        n = n * 2;
+    }
+    return n;
+}"#;
+
+    fn allocation_error(engine: &Engine, function: &str, args: impl IntoArgs) -> RuntimeError {
+        let script = engine.compile("allocations.atlas", ALLOCATIONS).unwrap();
+        match script.call::<_, i64>(function, args) {
+            Err(AtlasError::Runtime(report)) => report.error,
+            _ => panic!("`{}` should have failed", function),
+        }
+    }
+
+    #[test]
+    fn the_heap_limit_counts_bytes() {
+        let mut engine = Engine::new();
+        engine.set_capabilities(Capabilities::none());
+        //Way too big for the host, the list is never allocated
+        let huge = 1_000_000_000_000_000_000_i64;
+        assert!(matches!(allocation_error(&engine, "list", (huge,)), RuntimeError::OutOfMemory));
+
+        engine.set_limits(Limits {
+            max_heap: Some(16),
+            ..Limits::default()
+        });
+        assert!(matches!(allocation_error(&engine, "list", (-1_i64,)), RuntimeError::NegativeListSize(-1)));
+        let big = 100_000_000_000_i64;
+        assert!(matches!(allocation_error(&engine, "list", (big,)), RuntimeError::OutOfMemory));
+
+        engine.set_limits(Limits {
+            max_heap: Some(4096),
+            ..Limits::default()
+        });
+        let script = engine.compile("allocations.atlas", ALLOCATIONS).unwrap();
+        assert_eq!(script.call::<_, i64>("list", (100_i64,)).unwrap(), 100);
+        assert!(matches!(allocation_error(&engine, "list", (1000_i64,)), RuntimeError::OutOfMemory));
+        assert!(matches!(allocation_error(&engine, "grow", ()), RuntimeError::OutOfMemory));
+    }
+
+    #[test]
+    fn capabilities_gate_standard_libraries() {
+        let caps = Capabilities::all().deny("file");
+        assert!(caps.allows("std/io"));
+        assert!(!caps.allows("std/file"));
+        assert!(Capabilities::none().allow("math").allows("std/math"));
+
+        let mut engine = Engine::new();
+        engine.set_capabilities(Capabilities::none());
+        assert!(matches!(
+            run_error(&engine, "fill", (1_i64,)),
+            RuntimeError::LibraryNotAllowed(lib) if lib == "std/io"
+        ));
+    }
+}
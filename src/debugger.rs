@@ -0,0 +1,284 @@
+//! Line debugger behind `atlas_77 debug`, built on [`DebugHook`].
+//!
+//! The program stops before the first instruction of a source line, where commands are read
+//! until one of them resumes it.
+
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+
+use crate::atlas_vm::debug::DebugHook;
+use crate::atlas_vm::errors::RuntimeError;
+use crate::atlas_vm::{Atlas77VM, RuntimeResult};
+
+const HELP: &str = "\
+break <line>    (b)   stop at <line>
+delete <line>   (d)   remove the breakpoint at <line>
+continue        (c)   run until the next breakpoint
+step            (s)   run until the next line, entering calls
+next            (n)   run until the next line of this function
+finish          (f)   run until this function returns
+locals          (l)   arguments & locals of this function
+print <name>    (p)   value of a local
+backtrace       (bt)  active calls
+stack                 every value on the stack
+heap                  every live object
+quit            (q)   stop the program";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Continue,
+    Step,
+    /// Stop on a line of a frame at this depth or an outer one
+    Next(usize),
+    /// Stop once the frame at this depth returned
+    Finish(usize),
+}
+
+pub struct Debugger<R, W> {
+    file: String,
+    source: String,
+    /// Offset of the start of every line of `source`
+    line_starts: Vec<usize>,
+    breakpoints: BTreeSet<usize>,
+    mode: Mode,
+    /// Frame depth & line of the last line reached
+    position: Option<(usize, usize)>,
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+    /// Commands are read from `input`, the program stops on its first line
+    pub fn new(file: &str, source: &str, input: R, output: W) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            file: file.to_string(),
+            source: source.to_string(),
+            line_starts,
+            breakpoints: BTreeSet::new(),
+            mode: Mode::Step,
+            position: None,
+            input,
+            output,
+        }
+    }
+    /// The program runs until one of `lines` instead of stopping on its first line
+    pub fn with_breakpoints(mut self, lines: impl IntoIterator<Item = usize>) -> Self {
+        self.breakpoints.extend(lines);
+        if !self.breakpoints.is_empty() {
+            self.mode = Mode::Continue;
+        }
+        self
+    }
+
+    /// Line (from 1) of the source offset `offset`
+    fn line_of(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|start| *start <= offset)
+    }
+
+    fn line_text(&self, line: usize) -> &str {
+        let start = self.line_starts[line - 1];
+        let end = self.line_starts.get(line).copied().unwrap_or(self.source.len());
+        self.source[start..end].trim_end()
+    }
+
+    /// Line about to be executed by the innermost frame, if it starts at `vm.pc`
+    fn line_start(&self, vm: &Atlas77VM) -> Option<usize> {
+        let frame = vm.frames().last()?;
+        let label = &vm.program.labels[frame.function];
+        let offset = vm.pc.checked_sub(label.position)?;
+        let entry = label.span_at(offset)?;
+        (entry.offset == offset).then(|| self.line_of(entry.start))
+    }
+
+    /// Read & execute commands until one resumes the program
+    fn pause(&mut self, vm: &Atlas77VM, line: usize) -> RuntimeResult<()> {
+        let function = vm.frames().last().map_or("?", |frame| vm.frame_name(frame));
+        self.say(format!("{} at {}:{}", function, self.file, line));
+        self.say(format!("{:>4} | {}", line, self.line_text(line)));
+        loop {
+            let _ = write!(self.output, "(adb) ");
+            let _ = self.output.flush();
+            let mut command = String::new();
+            if self.input.read_line(&mut command).unwrap_or(0) == 0 {
+                return Err(RuntimeError::DebuggerQuit);
+            }
+            let mut words = command.split_whitespace();
+            let depth = vm.frames().len();
+            match (words.next(), words.next()) {
+                (Some("c" | "continue"), _) => return self.resume(Mode::Continue),
+                (Some("s" | "step"), _) => return self.resume(Mode::Step),
+                (Some("n" | "next"), _) => return self.resume(Mode::Next(depth)),
+                (Some("f" | "finish"), _) => return self.resume(Mode::Finish(depth)),
+                (Some("q" | "quit"), _) => return Err(RuntimeError::DebuggerQuit),
+                (Some("b" | "break"), Some(line)) => match line.parse::<usize>() {
+                    Ok(line) if line >= 1 && line <= self.line_starts.len() => {
+                        self.breakpoints.insert(line);
+                        self.say(format!("breakpoint at {}:{}", self.file, line));
+                    }
+                    _ => self.say(format!("`{}` isn't a line of {}", line, self.file)),
+                },
+                (Some("d" | "delete"), Some(line)) => {
+                    if !line.parse().is_ok_and(|line| self.breakpoints.remove(&line)) {
+                        self.say(format!("no breakpoint at line {}", line));
+                    }
+                }
+                (Some("l" | "locals"), _) => {
+                    if let Some(frame) = vm.frames().last() {
+                        for local in vm.locals(frame) {
+                            self.say(format!("{} = {}", local.name, vm.object_map.describe(local.value)));
+                        }
+                    }
+                }
+                (Some("p" | "print"), Some(name)) => {
+                    let local = vm
+                        .frames()
+                        .last()
+                        .and_then(|frame| vm.locals(frame).into_iter().rev().find(|l| l.name == name));
+                    match local {
+                        Some(local) => self.say(format!("{} = {}", name, vm.object_map.describe(local.value))),
+                        None => self.say(format!("no local named `{}`", name)),
+                    }
+                }
+                (Some("bt" | "backtrace"), _) => {
+                    for (i, frame) in vm.backtrace().iter().enumerate() {
+                        match frame.span {
+                            Some(span) => {
+                                let line = self.line_of(span.offset());
+                                self.say(format!("{:>4}: {} at {}:{}", i, frame.function, self.file, line))
+                            }
+                            None => self.say(format!("{:>4}: {}", i, frame.function)),
+                        }
+                    }
+                }
+                (Some("stack"), _) => {
+                    for (i, value) in vm.stack.iter().enumerate() {
+                        self.say(format!("{:>4}: {}", i, vm.object_map.describe(*value)));
+                    }
+                }
+                (Some("heap"), _) => self.say(vm.object_map.to_string()),
+                (Some("h" | "help"), _) => self.say(HELP.to_string()),
+                (None, _) => {}
+                (Some(command), _) => self.say(format!("unknown command `{}`, try `help`", command)),
+            }
+        }
+    }
+
+    fn resume(&mut self, mode: Mode) -> RuntimeResult<()> {
+        self.mode = mode;
+        Ok(())
+    }
+
+    fn say(&mut self, message: String) {
+        let _ = writeln!(self.output, "{}", message);
+    }
+}
+
+impl<'run, R: BufRead, W: Write> DebugHook<'run> for Debugger<R, W> {
+    fn before_instruction(&mut self, vm: &Atlas77VM<'run>) -> RuntimeResult<()> {
+        let Some(line) = self.line_start(vm) else {
+            return Ok(());
+        };
+        let depth = vm.frames().len();
+        if self.position.replace((depth, line)) == Some((depth, line)) {
+            return Ok(());
+        }
+        let stop = match self.mode {
+            Mode::Continue => false,
+            Mode::Step => true,
+            Mode::Next(d) => depth <= d,
+            Mode::Finish(d) => depth < d,
+        };
+        if stop || self.breakpoints.contains(&line) {
+            self.pause(vm, line)?;
+        }
+        Ok(())
+    }
+
+    fn on_error(&mut self, vm: &Atlas77VM<'run>, error: &RuntimeError) {
+        self.say(format!("error: {}", error));
+        let line = vm.backtrace().first().and_then(|frame| frame.span).map(|span| self.line_of(span.offset()));
+        if let Some(line) = line {
+            //The program can't be resumed, only inspected
+            let _ = self.pause(vm, line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::rc::Rc;
+
+    use bumpalo::Bump;
+
+    use super::Debugger;
+    use crate::atlas_vm::errors::RuntimeError;
+    use crate::atlas_vm::runtime::{arena::RuntimeArena, binary};
+    use crate::atlas_vm::Atlas77VM;
+    use crate::engine::Engine;
+
+    const SOURCE: &str = "func add(a: int64, b: int64) -> int64 {
+    let c = a + b;
+    return c;
+}
+func main() -> int64 {
+    let x = 1;
+    let y = add(x, 2);
+    return y;
+}";
+
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn breakpoints_and_stepping() {
+        let script = Engine::new().compile("add.atlas", SOURCE).unwrap();
+        let bump = Bump::new();
+        let program = binary::deserialize(script.bytecode(), &RuntimeArena::new(&bump)).unwrap();
+        let mut vm = Atlas77VM::new(program, RuntimeArena::new(&bump)).unwrap();
+        let output = Output::default();
+        let commands = "break 3\nnext\nnext\nlocals\nfinish\nprint y\ncontinue\n";
+        let debugger = Debugger::new("add.atlas", SOURCE, commands.as_bytes(), output.clone());
+        vm.set_debug_hook(debugger);
+        assert_eq!(vm.run().unwrap().as_i64(), 3);
+
+        let output = String::from_utf8(output.0.borrow().clone()).unwrap();
+        let stops = output
+            .lines()
+            .map(|line| line.trim_start_matches("(adb) "))
+            .filter(|line| line.contains(" at add.atlas:") && !line.starts_with("breakpoint"))
+            .collect::<Vec<_>>();
+        //`next` doesn't step over breakpoints, `finish` stops on the line after the call
+        assert_eq!(
+            stops,
+            ["main at add.atlas:6", "main at add.atlas:7", "add at add.atlas:3", "main at add.atlas:8"]
+        );
+        assert!(output.contains("a = 1\nb = 2\nc = 3\n"));
+        assert!(output.contains("y = 3\n"));
+    }
+
+    #[test]
+    fn quitting_stops_the_program() {
+        let script = Engine::new().compile("add.atlas", SOURCE).unwrap();
+        let bump = Bump::new();
+        for commands in ["step\nquit\n", "step\n"] {
+            let program = binary::deserialize(script.bytecode(), &RuntimeArena::new(&bump)).unwrap();
+            let mut vm = Atlas77VM::new(program, RuntimeArena::new(&bump)).unwrap();
+            vm.set_debug_hook(Debugger::new("add.atlas", SOURCE, commands.as_bytes(), Output::default()));
+            assert!(matches!(vm.run(), Err(RuntimeError::DebuggerQuit)));
+        }
+    }
+}
@@ -0,0 +1,567 @@
+//! Compile & run Atlas source from a Rust application.
+//!
+//! Contrary to [`crate::build`] & [`crate::run`], nothing is read from or written to the disk,
+//! and nothing is printed: every problem comes back as an [`AtlasError`], warnings are kept on the [`Script`].
+//!
+//! ```
+//! use atlas_77::engine::Engine;
+//! use atlas_77::atlas_vm::native::NativeModule;
+//!
+//! let mut engine = Engine::new();
+//! engine.register(NativeModule::new("host").function("double", |x: i64| x * 2));
+//! let script = engine
+//!     .compile("script.atlas", "import \"host\"\nfunc add(a: int64, b: int64) -> int64 { return double(a) + b; }")
+//!     .unwrap();
+//! let sum: i64 = script.call("add", (1_i64, 2_i64)).unwrap();
+//! assert_eq!(sum, 4);
+//! ```
+
+use std::collections::HashMap;
+
+use bumpalo::Bump;
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::atlas_c::atlas_codegen::{arena::CodeGenArena, CodeGenUnit};
+use crate::atlas_c::atlas_frontend::{parse, parser::arena::AstArena, parser::error::ParseError};
+use crate::atlas_c::atlas_hir::{
+    arena::HirArena,
+    error::{HirError, NamingConventionWarning},
+    syntax_lowering_pass::AstSyntaxLoweringPass,
+    type_check_pass::TypeChecker, HirModule,
+};
+use crate::atlas_c::atlas_linker::{link, LinkError};
+use crate::atlas_vm::errors::RuntimeErrorReport;
+use crate::atlas_vm::memory::vm_data::VMData;
+use crate::atlas_vm::native::{AtlasType, FromAtlas, IntoAtlas, NativeModule};
+use crate::atlas_vm::runtime::binary::{self, BinaryError};
+use crate::atlas_vm::sandbox::{CancelHandle, Capabilities, Limits};
+use crate::atlas_vm::runtime::{arena::RuntimeArena, vm_state::VMState};
+use crate::atlas_vm::{Atlas77VM, RuntimeResult};
+use crate::declare_error_type;
+
+declare_error_type! {
+    #[error("Atlas error: {0}")]
+    pub enum AtlasError {
+        Parse(ParseError),
+        Hir(HirError),
+        Link(LinkError),
+        Binary(BinaryError),
+        Runtime(RuntimeErrorReport),
+        UnknownFunction(UnknownFunctionError),
+        SignatureMismatch(SignatureMismatchError),
+    }
+}
+
+pub type AtlasResult<T> = Result<T, AtlasError>;
+
+#[derive(Error, Diagnostic, Debug)]
+#[diagnostic(
+    code(engine::unknown_function),
+    help("Only the functions defined by the script can be called")
+)]
+#[error("function `{0}` not found")]
+pub struct UnknownFunctionError(pub String);
+
+#[derive(Error, Diagnostic, Debug)]
+#[diagnostic(code(engine::signature_mismatch))]
+#[error("`{function}` is `{expected}`, it can't be called as `{found}`")]
+pub struct SignatureMismatchError {
+    pub function: String,
+    pub expected: String,
+    pub found: String,
+}
+
+/// Compiles scripts against the native modules registered by the host
+#[derive(Clone)]
+pub struct Engine {
+    natives: Vec<NativeModule>,
+    limits: Limits,
+    capabilities: Capabilities,
+    cancel: CancelHandle,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//Diagnostics carry their source code, they are only built once per failed compilation or run
+#[allow(clippy::result_large_err)]
+impl Engine {
+    pub fn new() -> Self {
+        Self {
+            natives: Vec::new(),
+            limits: Limits::default(),
+            capabilities: Capabilities::all(),
+            cancel: CancelHandle::new(),
+        }
+    }
+    /// Let the scripts compiled afterward `import` `module`
+    pub fn register(&mut self, module: NativeModule) -> &mut Self {
+        self.natives.retain(|m| m.name() != module.name());
+        self.natives.push(module);
+        self
+    }
+    /// Bound every run of the scripts compiled afterward
+    pub fn set_limits(&mut self, limits: Limits) -> &mut Self {
+        self.limits = limits;
+        self
+    }
+    /// Standard libraries the scripts compiled afterward may load, they fail to run otherwise
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) -> &mut Self {
+        self.capabilities = capabilities;
+        self
+    }
+    /// Cancels the runs of every script compiled by this engine
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+    /// `name` is only used to refer to the source in diagnostics
+    pub fn compile(&self, name: &str, source: &str) -> AtlasResult<Script> {
+        self.analyse_with_warnings(name, source, |hir, warnings| {
+            let functions = hir
+                .signature
+                .functions
+                .iter()
+                //Generic functions are only compiled through their instances
+                .filter(|(_, signature)| !signature.is_external && signature.generics.is_none())
+                .map(|(name, signature)| {
+                    let params = signature.params.iter().map(|p| p.ty.to_string()).collect();
+                    let signature = FunctionSignature {
+                        params,
+                        return_ty: signature.return_ty.to_string(),
+                    };
+                    (name.to_string(), signature)
+                })
+                .collect();
+
+            //codegen
+            let bump = Bump::new();
+            let arena = CodeGenArena::new(&bump);
+            let mut codegen = CodeGenUnit::new(hir, arena, source.to_string());
+            let program = codegen.compile()?;
+            let mut program = link(&program, &CodeGenArena::new(&bump), &self.natives)?;
+            program.source_path = name.to_string();
+
+            Ok(Script {
+                name: name.to_string(),
+                source: source.to_string(),
+                bytecode: binary::serialize(&program),
+                functions,
+                warnings,
+                natives: self.natives.clone(),
+                limits: self.limits,
+                capabilities: self.capabilities.clone(),
+                cancel: self.cancel.clone(),
+            })
+        })
+    }
+    /// Parse, lower & type-check `source`, then hand the checked module to `then`
+    pub(crate) fn analyse<T>(
+        &self,
+        name: &str,
+        source: &str,
+        then: impl for<'hir> FnOnce(HirModule<'hir>) -> AtlasResult<T>,
+    ) -> AtlasResult<T> {
+        self.analyse_with_warnings(name, source, |hir, _| then(hir))
+    }
+    /// [`Engine::analyse`], `then` also gets the warnings about `source`
+    fn analyse_with_warnings<T>(
+        &self,
+        name: &str,
+        source: &str,
+        then: impl for<'hir> FnOnce(HirModule<'hir>, Vec<NamingConventionWarning>) -> AtlasResult<T>,
+    ) -> AtlasResult<T> {
+        //parse
+        let bump = Bump::new();
+        let ast_arena = AstArena::new(&bump);
+        let program = parse(name, &ast_arena, source.to_string())?;
+
+        //hir
+        let hir_arena = HirArena::new();
+        let lower = AstSyntaxLoweringPass::new(&hir_arena, &program, &ast_arena, source.to_string())
+            .with_natives(&self.natives);
+        let mut hir = lower.lower()?;
+        let warnings = lower.take_warnings();
+
+        //type-check
+        let mut type_checker = TypeChecker::new(&hir_arena, source.to_string());
+        type_checker.check(&mut hir)?;
+        then(hir, warnings)
+    }
+    /// Native modules the scripts may `import`
+    pub(crate) fn natives(&self) -> &[NativeModule] {
+        &self.natives
+    }
+}
+
+/// Parameter & return types of a function, as written in Atlas
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionSignature {
+    pub params: Vec<String>,
+    pub return_ty: String,
+}
+
+impl std::fmt::Display for FunctionSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}) -> {}", self.params.join(", "), self.return_ty)
+    }
+}
+
+/// A compiled script, every run starts from a fresh VM
+pub struct Script {
+    name: String,
+    source: String,
+    bytecode: Vec<u8>,
+    functions: HashMap<String, FunctionSignature>,
+    warnings: Vec<NamingConventionWarning>,
+    natives: Vec<NativeModule>,
+    limits: Limits,
+    capabilities: Capabilities,
+    cancel: CancelHandle,
+}
+
+#[allow(clippy::result_large_err)]
+impl Script {
+    /// The program in the `.atlasc` format, see [`crate::atlas_vm::runtime::binary`]
+    pub fn bytecode(&self) -> &[u8] {
+        &self.bytecode
+    }
+    /// Signature of a function defined by the script
+    pub fn signature(&self, function: &str) -> Option<&FunctionSignature> {
+        self.functions.get(function)
+    }
+    /// Names of the script not following the naming conventions
+    pub fn warnings(&self) -> &[NamingConventionWarning] {
+        &self.warnings
+    }
+    /// Run `main`, returns the `int64` it returned or gave to `exit`, 0 otherwise
+    pub fn run(&self) -> AtlasResult<i64> {
+        self.with_vm(|vm| {
+            let value = vm.run()?;
+            Ok(match value.tag {
+                VMData::TAG_I64 => value.as_i64(),
+                _ => 0,
+            })
+        })
+    }
+    /// Call `function` with `args` (a tuple) and convert the returned value
+    ///
+    /// The argument & return types are checked against the signature of `function` first
+    pub fn call<A: IntoArgs, R: FromAtlas>(&self, function: &str, args: A) -> AtlasResult<R> {
+        let signature = self
+            .signature(function)
+            .ok_or_else(|| UnknownFunctionError(function.to_string()))?;
+        let found = FunctionSignature {
+            params: A::atlas_types(),
+            return_ty: R::atlas_type(),
+        };
+        if *signature != found {
+            return Err(SignatureMismatchError {
+                function: function.to_string(),
+                expected: signature.to_string(),
+                found: found.to_string(),
+            }
+            .into());
+        }
+        self.with_vm(|vm| {
+            let consts = HashMap::new();
+            let classes = vm.program.global.class_pool;
+            let mut state = VMState::new(&mut vm.stack, &mut vm.object_map, &consts, classes);
+            let args = args.into_args(&mut state)?;
+            let value = vm.invoke(function, &args)?;
+            R::from_atlas(value, &VMState::new(&mut vm.stack, &mut vm.object_map, &consts, classes))
+        })
+    }
+
+    /// Call every function of `functions` (taking no argument) in turn, the VM is reset before each call
+    ///
+    /// Returns how each call ended, an error is only returned if the VM couldn't be created
+    pub(crate) fn call_each(&self, functions: &[&str]) -> AtlasResult<Vec<Result<(), RuntimeErrorReport>>> {
+        self.with_vm(|vm| {
+            let src = (self.name.clone(), self.source.clone());
+            let results = functions
+                .iter()
+                .map(|function| {
+                    vm.reset();
+                    vm.invoke(function, &[])
+                        .map(|_| ())
+                        .map_err(|e| RuntimeErrorReport::new(e, &vm.backtrace(), Some(src.clone())))
+                })
+                .collect();
+            Ok(results)
+        })
+    }
+
+    fn with_vm<T>(&self, f: impl FnOnce(&mut Atlas77VM) -> RuntimeResult<T>) -> AtlasResult<T> {
+        let bump = Bump::new();
+        let program = binary::deserialize(&self.bytecode, &RuntimeArena::new(&bump))?;
+        let mut vm = Atlas77VM::with_natives(
+            program,
+            RuntimeArena::new(&bump),
+            &self.natives,
+            &self.capabilities,
+        )
+        .map_err(|e| RuntimeErrorReport::new(e, &[], None))?;
+        vm.set_limits(&self.limits);
+        vm.set_cancel_handle(self.cancel.clone());
+        f(&mut vm).map_err(|e| {
+            let src = Some((self.name.clone(), self.source.clone()));
+            RuntimeErrorReport::new(e, &vm.backtrace(), src).into()
+        })
+    }
+}
+
+/// Arguments of [`Script::call`], implemented for tuples of [`IntoAtlas`] values
+pub trait IntoArgs {
+    fn atlas_types() -> Vec<String>;
+    fn into_args(self, state: &mut VMState) -> RuntimeResult<Vec<VMData>>;
+}
+
+macro_rules! impl_into_args {
+    ($($arg:ident),*) => {
+        impl<$($arg: IntoAtlas),*> IntoArgs for ($($arg,)*) {
+            fn atlas_types() -> Vec<String> {
+                vec![$(<$arg as AtlasType>::atlas_type()),*]
+            }
+            #[allow(non_snake_case, unused_variables)]
+            fn into_args(self, state: &mut VMState) -> RuntimeResult<Vec<VMData>> {
+                let ($($arg,)*) = self;
+                Ok(vec![$($arg.into_atlas(state)?),*])
+            }
+        }
+    };
+}
+
+impl_into_args!();
+impl_into_args!(A);
+impl_into_args!(A, B);
+impl_into_args!(A, B, C);
+impl_into_args!(A, B, C, D);
+impl_into_args!(A, B, C, D, E);
+
+#[cfg(test)]
+mod tests {
+    use super::{AtlasError, AtlasResult, Engine};
+    use crate::atlas_vm::errors::RuntimeError;
+    use crate::atlas_vm::native::NativeModule;
+
+    #[test]
+    fn call_functions_of_a_compiled_string() {
+        let mut engine = Engine::new();
+        engine.register(NativeModule::new("host").function("exclaim", |s: String| s + "!"));
+        let script = engine
+            .compile(
+                "greet.atlas",
+                r#"import "host"
+func greet(name: str) -> str {
+    return exclaim(name);
+}
+func add(a: int64, b: int64) -> int64 {
+    return a + b;
+}
+func main() -> int64 {
+    return add(1, 2);
+}"#,
+            )
+            .unwrap();
+        assert_eq!(script.call::<_, String>("greet", ("bob",)).unwrap(), "bob!");
+        assert_eq!(script.call::<_, i64>("add", (40_i64, 2_i64)).unwrap(), 42);
+        assert_eq!(script.run().unwrap(), 3);
+        assert!(matches!(
+            script.call::<_, i64>("greet", ("bob",)),
+            Err(AtlasError::SignatureMismatch(_))
+        ));
+        assert!(matches!(
+            script.call::<_, i64>("missing", ()),
+            Err(AtlasError::UnknownFunction(_))
+        ));
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Point {
+        x: i64,
+        y: i64,
+    }
+    crate::atlas_class!(Point { x, y });
+
+    #[test]
+    fn classes_and_lists_are_marshalled() {
+        let script = Engine::new()
+            .compile(
+                "point.atlas",
+                r#"public class Point {
+    public:
+        x: int64;
+        y: int64;
+    public:
+        Point(x: int64, y: int64) {
+            self.x = x;
+            self.y = y;
+        }
+        ~Point() {}
+}
+func swap(p: Point) -> Point {
+    return new Point(p.y, p.x);
+}
+func last(names: [str], len: int64) -> str {
+    return names[len - 1];
+}"#,
+            )
+            .unwrap();
+        let swapped: Point = script.call("swap", (Point { x: 1, y: 2 },)).unwrap();
+        assert_eq!(swapped, Point { x: 2, y: 1 });
+        let names = vec![String::from("a"), String::from("b")];
+        assert_eq!(script.call::<_, String>("last", (names, 2_i64)).unwrap(), "b");
+    }
+
+    /// Examples written for syntax the compiler doesn't support yet, or failing on purpose
+    const FAILING_EXAMPLES: [&str; 4] = [
+        //Lambdas aren't supported
+        "function.atlas",
+        //Struct literals aren't supported
+        "struct.atlas",
+        //Reads a private field
+        "test.atlas",
+        //Shows the type checker's errors
+        "type_check.atlas",
+    ];
+
+    #[test]
+    fn std_and_examples_compile() {
+        let engine = Engine::new();
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let sources = |dir: &str| {
+            let mut paths = std::fs::read_dir(root.join(dir))
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.extension().is_some_and(|e| e == "atlas"))
+                .collect::<Vec<_>>();
+            paths.sort();
+            paths
+        };
+        for path in sources("src/atlas_lib/std") {
+            let source = match path.file_stem().unwrap().to_str().unwrap() {
+                //Not importable
+                "prelude" | "number" => std::fs::read_to_string(&path).unwrap(),
+                "fs" => String::from("import \"std/file\""),
+                //Externs are only linked when their module is imported
+                module => format!("import \"std/{}\"", module),
+            };
+            let result = engine.compile("std.atlas", &source);
+            assert!(result.is_ok(), "{}: {:?}", path.display(), result.err());
+        }
+        for path in sources("examples") {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            let result = engine.compile(name, &std::fs::read_to_string(&path).unwrap());
+            if FAILING_EXAMPLES.contains(&name) {
+                assert!(result.is_err(), "{} compiles, remove it from FAILING_EXAMPLES", name);
+            } else {
+                assert!(result.is_ok(), "{}: {:?}", name, result.err());
+            }
+        }
+    }
+
+    #[test]
+    fn diagnostics_are_returned() {
+        let engine = Engine::new();
+        assert!(matches!(
+            engine.compile("bad.atlas", "func main() -> int64 { return 1 }"),
+            Err(AtlasError::Parse(_))
+        ));
+        assert!(matches!(
+            engine.compile("bad.atlas", "func main() -> int64 { return §; }"),
+            Err(AtlasError::Parse(_))
+        ));
+        let script = engine
+            .compile("div.atlas", "func div(a: int64) -> int64 { return a / 0; }")
+            .unwrap();
+        assert!(matches!(script.call::<_, i64>("div", (1_i64,)), Err(AtlasError::Runtime(_))));
+        assert!(script.warnings().is_empty());
+
+        let script = engine.compile("warn.atlas", "func Double(x: int64) -> int64 { return x * 2; }").unwrap();
+        let warnings = script.warnings().iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        assert_eq!(warnings, ["function `Double` isn't in snake_case"]);
+        assert_eq!(script.call::<_, i64>("Double", (2_i64,)).unwrap(), 4);
+    }
+
+    #[test]
+    fn indexing_past_the_end_is_an_error() {
+        let script = Engine::new()
+            .compile(
+                "bounds.atlas",
+                r#"func load(i: int64) -> int64 {
+    let xs = [1, 2, 3];
+    return xs[i];
+}
+func store(i: int64) -> int64 {
+    let xs = new [int64; 2];
+    xs[i] = 1;
+    return xs[0];
+}
+func char_at(s: str, i: int64) -> char {
+    return s[i];
+}"#,
+            )
+            .unwrap();
+        fn error<T>(result: AtlasResult<T>) -> RuntimeError {
+            match result {
+                Err(AtlasError::Runtime(report)) => report.error,
+                _ => panic!("indexing should have failed"),
+            }
+        }
+        assert_eq!(script.call::<_, i64>("load", (2_i64,)).unwrap(), 3);
+        assert!(matches!(
+            error(script.call::<_, i64>("load", (3_i64,))),
+            RuntimeError::IndexOutOfBounds { index: 3, len: 3 }
+        ));
+        assert!(matches!(
+            error(script.call::<_, i64>("load", (-1_i64,))),
+            RuntimeError::IndexOutOfBounds { index: -1, len: 3 }
+        ));
+        assert!(matches!(
+            error(script.call::<_, i64>("store", (2_i64,))),
+            RuntimeError::IndexOutOfBounds { index: 2, len: 2 }
+        ));
+        assert_eq!(script.call::<_, char>("char_at", ("abc", 1_i64)).unwrap(), 'b');
+        assert!(matches!(
+            error(script.call::<_, char>("char_at", ("abc", 3_i64))),
+            RuntimeError::IndexOutOfBounds { index: 3, len: 3 }
+        ));
+    }
+
+    #[test]
+    fn delete_leaves_the_locals_alone() {
+        let script = Engine::new()
+            .compile(
+                "delete.atlas",
+                r#"class Holder {
+public:
+    items: [int64];
+}
+func deleted() -> int64 {
+    let kept = 7;
+    let holder = new Holder([1, 2, 3]);
+    delete holder;
+    let after = 1;
+    return kept + after;
+}"#,
+            )
+            .unwrap();
+        assert_eq!(script.call::<_, i64>("deleted", ()).unwrap(), 8);
+    }
+
+    #[test]
+    fn unparsable_casts_are_errors() {
+        let script = Engine::new()
+            .compile("cast.atlas", "func parse(s: str) -> int64 { return s as int64; }")
+            .unwrap();
+        assert_eq!(script.call::<_, i64>("parse", ("42",)).unwrap(), 42);
+        assert!(matches!(
+            script.call::<_, i64>("parse", ("forty-two",)),
+            Err(AtlasError::Runtime(report)) if matches!(report.error, RuntimeError::InvalidCast(..))
+        ));
+    }
+}
@@ -1,25 +1,19 @@
 pub mod atlas_vm;
 pub mod atlas_c;
 pub mod atlas_lib;
+pub mod engine;
+pub mod debugger;
+pub mod repl;
+pub mod lsp;
+pub mod test_runner;
 
-use atlas_c::{
-    atlas_codegen::{
-        arena::CodeGenArena,
-        CodeGenUnit,
-    },
-    atlas_frontend::{
-        parse,
-        parser::arena::AstArena,
-    },
-    atlas_hir::{
-        arena::HirArena,
-        syntax_lowering_pass::AstSyntaxLoweringPass,
-        type_check_pass::TypeChecker,
-    },
-};
 use bumpalo::Bump;
 
-use crate::atlas_vm::runtime::arena::RuntimeArena;
+use crate::atlas_vm::errors::{RuntimeError, RuntimeErrorReport};
+use crate::atlas_vm::memory::vm_data::VMData;
+use crate::atlas_vm::profiler::Profiler;
+use crate::atlas_vm::runtime::{arena::RuntimeArena, binary, instruction::Program};
+use miette::IntoDiagnostic;
 use std::{
     io::Write,
     path::PathBuf,
@@ -33,6 +27,30 @@ pub enum CompilationFlag {
     Debug,
 }
 
+/// Settings of the VM running a program
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Maximum number of values on the VM stack
+    pub stack_size: usize,
+    /// Print how long the program took on stderr
+    pub show_time: bool,
+    /// Profile the program, print a summary on stderr & write the folded stacks to this file
+    pub profile: Option<PathBuf>,
+    /// Assemble the program & run its byte encoding, see [`atlas_c::atlas_asm`]
+    pub assembled: bool,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            stack_size: atlas_vm::memory::stack::DEFAULT_STACK_SIZE,
+            show_time: false,
+            profile: None,
+            assembled: false,
+        }
+    }
+}
+
 fn get_path(path: &str) -> PathBuf {
     let mut path_buf = PathBuf::from(path.to_owned());
     if let Ok(current_dir) = std::env::current_dir() {
@@ -46,81 +64,307 @@ fn get_path(path: &str) -> PathBuf {
 }
 
 pub fn build(path: String, _flag: CompilationFlag) -> miette::Result<()> {
-    let path_buf = get_path(&path);
+    let script = compile_file(&path)?;
+    write_program(script.bytecode())
+}
 
-    let source = std::fs::read_to_string(path).unwrap();
-    //parse
+//The "run" function needs a bit of refactoring
+/// Returns the exit code of the program
+pub fn run(path: String, _flag: CompilationFlag, options: RunOptions) -> miette::Result<i32> {
+    let script = compile_file(&path)?;
+    write_program(script.bytecode())?;
+
+    //run
     let bump = Bump::new();
-    let ast_arena = AstArena::new(&bump);
-    let program = parse(path_buf.to_str().unwrap(), &ast_arena, source.clone())?;
+    let program = binary::deserialize(script.bytecode(), &RuntimeArena::new(&bump))?;
+    run_program(program, RuntimeArena::new(&bump), options)
+}
 
-    //hir
-    let hir_arena = HirArena::new();
-    let lower = AstSyntaxLoweringPass::new(&hir_arena, &program, &ast_arena, source.clone());
-    let mut hir = lower.lower()?;
+fn compile_file(path: &str) -> miette::Result<engine::Script> {
+    let path_buf = get_path(path);
+    let source = std::fs::read_to_string(&path_buf).into_diagnostic()?;
+    let script = engine::Engine::new().compile(&path_buf.to_string_lossy(), &source)?;
+    for warning in script.warnings() {
+        eprintln!("{:?}", miette::Report::new(warning.clone()));
+    }
+    Ok(script)
+}
+
+/// Run an already compiled `.atlasc` file without going through the compiler again
+///
+/// Returns the exit code of the program
+pub fn exec(path: String, options: RunOptions) -> miette::Result<i32> {
+    let bytes = std::fs::read(get_path(&path)).into_diagnostic()?;
+    let bump = Bump::new();
+    let program = binary::deserialize(&bytes, &RuntimeArena::new(&bump))?;
+    run_program(program, RuntimeArena::new(&bump), options)
+}
 
-    //type-check
-    let mut type_checker = TypeChecker::new(&hir_arena, source.clone());
-    type_checker.check(&mut hir)?;
+/// Run a source file under the line debugger, commands are read from stdin
+///
+/// It stops on the first line of `main`, or on the first of `breakpoints` reached if there are some
+///
+/// Returns the exit code of the program
+pub fn debug(path: String, breakpoints: Vec<usize>, options: RunOptions) -> miette::Result<i32> {
+    let script = compile_file(&path)?;
+    let source = std::fs::read_to_string(get_path(&path)).into_diagnostic()?;
 
-    //codegen
     let bump = Bump::new();
-    let arena = CodeGenArena::new(&bump);
-    let mut codegen = CodeGenUnit::new(hir, arena, source);
-    let program = codegen.compile()?;
-    let output = ron::ser::to_string_pretty(&program, Default::default()).unwrap();
-    let mut file = std::fs::File::create("output.atlasc").unwrap();
-    file.write_all(output.as_bytes()).unwrap();
+    let program = binary::deserialize(script.bytecode(), &RuntimeArena::new(&bump))?;
+    let mut vm = atlas_vm::Atlas77VM::new(program, RuntimeArena::new(&bump))
+        .map_err(|e| RuntimeErrorReport::new(e, &[], None))?;
+    //Not locked, the program may read stdin as well
+    let input = std::io::BufReader::new(std::io::stdin());
+    let debugger = debugger::Debugger::new(&path, &source, input, std::io::stdout())
+        .with_breakpoints(breakpoints);
+    vm.set_debug_hook(debugger);
+    println!("Debugging {}, type `help` for the commands", path);
+    match run_vm(vm, options) {
+        //Quitting the debugger isn't a failure of the program
+        Err(e) if e
+            .downcast_ref::<RuntimeErrorReport>()
+            .is_some_and(|report| matches!(report.error, RuntimeError::DebuggerQuit)) =>
+        {
+            Ok(0)
+        }
+        res => res,
+    }
+}
 
-    Ok(())
+/// Read declarations & statements from stdin until `:quit` or the end of the input
+///
+/// Diagnostics are printed and the session goes on
+pub fn repl() -> miette::Result<()> {
+    let bump = Bump::new();
+    let mut session = repl::Repl::new(&bump);
+    let stdin = std::io::stdin();
+    let mut input = String::new();
+    loop {
+        print!("{}", if input.is_empty() { ">>> " } else { "... " });
+        std::io::stdout().flush().into_diagnostic()?;
+        let mut line = String::new();
+        if stdin.read_line(&mut line).into_diagnostic()? == 0 {
+            return Ok(());
+        }
+        match line.trim() {
+            ":quit" | ":q" if input.is_empty() => return Ok(()),
+            ":vars" if input.is_empty() => {
+                for (name, ty) in session.variables() {
+                    println!("{}: {}", name, ty);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        input.push_str(&line);
+        if repl::Repl::is_incomplete(&input) {
+            continue;
+        }
+        match session.eval(&std::mem::take(&mut input)) {
+            Ok(Some(value)) => println!("{}", value),
+            Ok(None) => {}
+            Err(e) => eprintln!("{:?}", miette::Report::new(e)),
+        }
+    }
 }
 
-//The "run" function needs a bit of refactoring
-pub fn run(path: String, _flag: CompilationFlag) -> miette::Result<()> {
-    let path_buf = get_path(&path);
+/// Serve an editor over stdio until it shuts the language server down
+pub fn lsp() -> miette::Result<()> {
+    let (connection, io_threads) = lsp_server::Connection::stdio();
+    lsp::serve(&connection).map_err(|e| miette::miette!("language server: {}", e))?;
+    //Closing the connection lets the writer thread end
+    drop(connection);
+    io_threads.join().into_diagnostic()
+}
 
-    let source = std::fs::read_to_string(path).unwrap();
-    //parse
-    let bump = Bump::new();
-    let ast_arena = AstArena::new(&bump);
-    let program = parse(path_buf.to_str().unwrap(), &ast_arena, source.clone())?;
+/// Format the `.atlas` files in `paths`, directories are searched recursively
+///
+/// With `check`, files are only compared to their formatted version. Returns 1 if a file isn't
+/// formatted (with `check`) or doesn't parse, 0 otherwise
+pub fn fmt(paths: Vec<PathBuf>, check: bool) -> miette::Result<i32> {
+    let mut files = Vec::new();
+    for path in paths {
+        atlas_files(path, &mut files).into_diagnostic()?;
+    }
+    let mut code = 0;
+    for file in files {
+        let name = file.display().to_string();
+        let source = std::fs::read_to_string(&file).into_diagnostic()?;
+        let formatted = match atlas_c::atlas_frontend::formatter::format(&name, &source) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                eprintln!("{:?}", miette::Report::new(e));
+                code = 1;
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        if check {
+            let line = source.lines().zip(formatted.lines()).take_while(|(a, b)| a == b).count() + 1;
+            println!("{} isn't formatted (first difference on line {})", name, line);
+            code = 1;
+        } else {
+            std::fs::write(&file, formatted).into_diagnostic()?;
+            println!("formatted {}", name);
+        }
+    }
+    Ok(code)
+}
 
-    //hir
-    let hir_arena = HirArena::new();
-    let lower = AstSyntaxLoweringPass::new(&hir_arena, &program, &ast_arena, source.clone());
-    let mut hir = lower.lower()?;
+/// Run the `#[test]` functions of the `.atlas` files in `paths`, directories are searched recursively
+///
+/// Returns 1 if a test failed or a file with tests didn't compile, 0 otherwise
+pub fn test(paths: Vec<PathBuf>) -> miette::Result<i32> {
+    let mut files = Vec::new();
+    for path in paths {
+        atlas_files(path, &mut files).into_diagnostic()?;
+    }
+    let engine = engine::Engine::new();
+    let (mut passed, mut failures, mut broken) = (0, Vec::new(), 0);
+    for file in files {
+        let name = file.display().to_string();
+        let source = std::fs::read_to_string(&file).into_diagnostic()?;
+        //Files that don't parse are only reported if they have tests
+        if !source.contains("#[test]") {
+            continue;
+        }
+        match test_runner::run_tests(&engine, &name, &source) {
+            Ok(results) => {
+                for result in results {
+                    match result.failure {
+                        None => {
+                            println!("test {}::{} ... ok", name, result.name);
+                            passed += 1;
+                        }
+                        Some(failure) => {
+                            println!("test {}::{} ... FAILED", name, result.name);
+                            failures.push((format!("{}::{}", name, result.name), failure));
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("{:?}", e);
+                broken += 1;
+            }
+        }
+    }
+    let failed = failures.len();
+    for (test, failure) in failures {
+        println!("\n---- {} ----", test);
+        println!("{:?}", miette::Report::new(failure));
+    }
+    let status = if failed == 0 && broken == 0 { "ok" } else { "FAILED" };
+    print!("\ntest result: {}. {} passed; {} failed", status, passed, failed);
+    if broken > 0 {
+        print!("; {} file(s) didn't compile", broken);
+    }
+    println!();
+    Ok(if status == "ok" { 0 } else { 1 })
+}
 
-    //type-check
-    let mut type_checker = TypeChecker::new(&hir_arena, source.clone());
-    type_checker.check(&mut hir)?;
+/// `path` if it's a file, the `.atlas` files under it if it's a directory
+fn atlas_files(path: PathBuf, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        files.push(path);
+        return Ok(());
+    }
+    let mut entries = std::fs::read_dir(&path)?.map(|e| e.map(|e| e.path())).collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for entry in entries {
+        let hidden = entry.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.'));
+        if entry.is_dir() && !hidden && !entry.ends_with("target") {
+            atlas_files(entry, files)?;
+        } else if entry.extension().is_some_and(|e| e == "atlas") {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
 
-    //codegen
-    let bump = Bump::new();
-    let arena = CodeGenArena::new(&bump);
-    let mut codegen = CodeGenUnit::new(hir, arena, source);
-    let program = codegen.compile()?;
-    let output = ron::ser::to_string_pretty(&program, Default::default()).unwrap();
-    let mut file = std::fs::File::create("output.atlasc").unwrap();
-    file.write_all(output.as_bytes()).unwrap();
+/// Summary of the hottest functions on stderr, the folded stacks in `path`
+fn write_profile(profiler: &Profiler, path: &std::path::Path) -> miette::Result<()> {
+    eprintln!(
+        "{:<32} {:>8} {:>14} {:>12} {:>12} {:>12}",
+        "function", "calls", "instructions", "allocations", "self (µs)", "total (µs)"
+    );
+    for function in profiler.functions() {
+        eprintln!(
+            "{:<32} {:>8} {:>14} {:>12} {:>12} {:>12}",
+            function.name,
+            function.calls,
+            function.instructions,
+            function.allocations,
+            function.self_time.as_micros(),
+            function.total_time.as_micros()
+        );
+    }
+    std::fs::write(path, profiler.folded()).into_diagnostic()?;
+    eprintln!("Folded stacks written to {}", path.display());
+    Ok(())
+}
 
-    //run
-    let bump = Bump::new();
-    let runtime_arena = RuntimeArena::new(&bump);
-    let mut vm = atlas_vm::Atlas77VM::new(program, runtime_arena);
+fn write_program(bytecode: &[u8]) -> miette::Result<()> {
+    let mut file = std::fs::File::create("output.atlasc").into_diagnostic()?;
+    file.write_all(bytecode).into_diagnostic()?;
+    Ok(())
+}
+
+/// The exit code is the `int64` returned by `main` or given to `exit`, 0 otherwise
+fn run_program<'run>(
+    program: Program<'run>,
+    runtime_arena: RuntimeArena<'run>,
+    options: RunOptions,
+) -> miette::Result<i32> {
+    let vm = atlas_vm::Atlas77VM::new(program, runtime_arena)
+        .map_err(|e| RuntimeErrorReport::new(e, &[], None))?;
+    run_vm(vm, options)
+}
+
+fn run_vm(mut vm: atlas_vm::Atlas77VM, options: RunOptions) -> miette::Result<i32> {
+    let source_path = vm.program.source_path.clone();
+    vm.set_stack_size(options.stack_size);
+    let profiler = options.profile.as_ref().map(|_| Profiler::new());
+    if let Some(profiler) = &profiler {
+        vm.set_debug_hook(profiler.clone());
+    }
+    let assembly = if options.assembled {
+        Some(atlas_c::atlas_asm::assemble(&vm.program)?)
+    } else {
+        None
+    };
     let start = Instant::now();
-    let res = vm.run();
+    let res = match &assembly {
+        Some(assembly) => vm.run_assembly(assembly),
+        None => vm.run(),
+    };
     let end = Instant::now();
+    if let (Some(profiler), Some(path)) = (&profiler, &options.profile) {
+        write_profile(profiler, path)?;
+    }
     match res {
-        Ok(_) => {
-            println!(
-                "Program ran successfully (time: {}µs)",
-                (end - start).as_micros()
-            );
+        Ok(value) => {
+            let code = match value.tag {
+                VMData::TAG_I64 => value.as_i64() as i32,
+                _ => 0,
+            };
+            if options.show_time {
+                eprintln!(
+                    "Program exited with code {} (time: {}µs)",
+                    code,
+                    (end - start).as_micros()
+                );
+            }
+            Ok(code)
         }
         Err(e) => {
-            eprintln!("{}", e);
+            //The source may have moved since it was compiled, the backtrace is still shown without it
+            let src = std::fs::read_to_string(&source_path)
+                .ok()
+                .map(|text| (source_path, text));
+            Err(RuntimeErrorReport::new(e, &vm.backtrace(), src).into())
         }
     }
-
-    Ok(())
 }
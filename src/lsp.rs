@@ -0,0 +1,946 @@
+//! Language server behind `atlas_77 lsp`, speaking LSP over stdio.
+//!
+//! Every change runs the parser, the lowering pass & the type checker again on the document:
+//! their first error is published as a diagnostic, the typed HIR gives hovers, definitions & completions.
+//! While a document doesn't type check, the last analysis that did answers instead.
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use bumpalo::Bump;
+use logos::Span;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _, PublishDiagnostics};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as _};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse, Diagnostic,
+    DiagnosticSeverity, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, Location, MarkupContent, MarkupKind, NumberOrString, OneOf, Position,
+    PublishDiagnosticsParams, Range, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit,
+    Uri,
+};
+
+use crate::atlas_c::atlas_frontend::{parse, parser::arena::AstArena};
+use crate::atlas_c::atlas_hir::{
+    arena::HirArena,
+    expr::{HirExpr, HirPattern},
+    item::HirClass,
+    signature::{HirClassMethodModifier, HirFunctionParameterSignature, HirVisibility},
+    stmt::{HirBlock, HirStatement},
+    syntax_lowering_pass::AstSyntaxLoweringPass,
+    ty::HirTy,
+    type_check_pass::TypeChecker,
+    HirModule,
+};
+use crate::atlas_vm::libraries::STD_LIBRARIES;
+
+/// Name documents are parsed under, it only shows up in the errors' source code
+const DOCUMENT: &str = "document.atlas";
+
+/// Serve the client on the other end of `connection` until it shuts the server down
+pub fn serve(connection: &Connection) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![".".to_string()]),
+            ..CompletionOptions::default()
+        }),
+        ..ServerCapabilities::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let mut server = Server::new();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                connection.sender.send(server.respond(request).into())?;
+            }
+            Message::Notification(notification) => {
+                if let Some(params) = server.notify(notification) {
+                    let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+                    connection.sender.send(notification.into())?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Open documents & the functions of the standard libraries
+pub struct Server {
+    documents: HashMap<Uri, Document>,
+    std_functions: Vec<StdFunction>,
+}
+
+struct Document {
+    text: Text,
+    /// Last analysis that got through the type checker
+    analysis: Analysis,
+    /// Text `analysis` was made from, positions are mapped with it while the document doesn't type check
+    analysed: Text,
+}
+
+#[derive(Clone)]
+struct Text {
+    text: String,
+    /// Offset of the start of every line of `text`
+    line_starts: Vec<usize>,
+}
+
+struct StdFunction {
+    library: &'static str,
+    name: String,
+    signature: String,
+}
+
+/// What the passes found in a document, without the arenas it was found with
+#[derive(Debug, Default)]
+struct Analysis {
+    error: Option<PassError>,
+    functions: Vec<Item>,
+    classes: Vec<Class>,
+    /// Functions, methods, constructors & destructors
+    scopes: Vec<Span>,
+    /// Variables & parameters, with the scope they're declared in
+    variables: Vec<(Span, Variable)>,
+    references: Vec<Reference>,
+}
+
+/// First error of a pass
+#[derive(Debug)]
+struct PassError {
+    message: String,
+    code: Option<String>,
+    span: Option<Span>,
+}
+
+/// Function, class or member declared in the document
+#[derive(Debug, Clone)]
+struct Item {
+    name: String,
+    /// The whole item
+    span: Span,
+    name_span: Span,
+    /// How it's declared, e.g. `func add(a: int64, b: int64) -> int64`
+    detail: String,
+    is_public: bool,
+    is_static: bool,
+}
+
+#[derive(Debug)]
+struct Class {
+    item: Item,
+    fields: Vec<Item>,
+    methods: Vec<Item>,
+}
+
+#[derive(Debug, Clone)]
+struct Variable {
+    name: String,
+    name_span: Span,
+    ty: String,
+}
+
+/// Part of the source with a type or a declaration
+#[derive(Debug)]
+struct Reference {
+    span: Span,
+    hover: String,
+    definition: Option<Span>,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Server {
+    pub fn new() -> Self {
+        //A library the passes can't handle yet is left out, without a panic message
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(|_| {}));
+        let std_functions = STD_LIBRARIES
+            .iter()
+            .flat_map(|library| std::panic::catch_unwind(|| std_library(library)).unwrap_or_default())
+            .collect();
+        std::panic::set_hook(hook);
+        Self {
+            documents: HashMap::new(),
+            std_functions,
+        }
+    }
+
+    /// Handle a notification, returns the diagnostics to publish if a document changed
+    fn notify(&mut self, notification: Notification) -> Option<PublishDiagnosticsParams> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = notification.extract::<lsp_types::DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD).ok()?;
+                let uri = params.text_document.uri;
+                Some(PublishDiagnosticsParams::new(uri.clone(), self.update(uri, params.text_document.text), None))
+            }
+            DidChangeTextDocument::METHOD => {
+                let params = notification.extract::<lsp_types::DidChangeTextDocumentParams>(DidChangeTextDocument::METHOD).ok()?;
+                let uri = params.text_document.uri;
+                //The server asked for full syncs, the last change is the whole document
+                let text = params.content_changes.into_iter().next_back()?.text;
+                Some(PublishDiagnosticsParams::new(uri.clone(), self.update(uri, text), None))
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = notification.extract::<lsp_types::DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD).ok()?;
+                self.documents.remove(&params.text_document.uri);
+                Some(PublishDiagnosticsParams::new(params.text_document.uri, Vec::new(), None))
+            }
+            _ => None,
+        }
+    }
+
+    fn respond(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        let method = request.method.clone();
+        let result = match method.as_str() {
+            HoverRequest::METHOD => request
+                .extract::<HoverParams>(HoverRequest::METHOD)
+                .map(|(_, params)| serde_json::to_value(self.hover(params))),
+            GotoDefinition::METHOD => request
+                .extract::<GotoDefinitionParams>(GotoDefinition::METHOD)
+                .map(|(_, params)| serde_json::to_value(self.definition(params))),
+            Completion::METHOD => request
+                .extract::<CompletionParams>(Completion::METHOD)
+                .map(|(_, params)| serde_json::to_value(self.completion(params))),
+            method => {
+                return Response::new_err(id, ErrorCode::MethodNotFound as i32, format!("unknown request `{}`", method))
+            }
+        };
+        match result {
+            Ok(Ok(value)) => Response { id, result: Some(value), error: None },
+            Ok(Err(e)) => Response::new_err(id, ErrorCode::InternalError as i32, e.to_string()),
+            Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+        }
+    }
+
+    /// Analyse the new text of a document, returns its diagnostics
+    pub fn update(&mut self, uri: Uri, text: String) -> Vec<Diagnostic> {
+        let analysis = analyse(&text);
+        let text = Text::new(text);
+        let mut document = match self.documents.remove(&uri) {
+            Some(document) => Document { text, ..document },
+            None => Document {
+                text: text.clone(),
+                analysis: Analysis::default(),
+                analysed: text,
+            },
+        };
+        let diagnostics = analysis
+            .error
+            .iter()
+            .map(|error| Diagnostic {
+                range: document.text.range(error.span.clone().unwrap_or(0..0)),
+                severity: Some(DiagnosticSeverity::ERROR),
+                code: error.code.clone().map(NumberOrString::String),
+                source: Some("atlas_77".to_string()),
+                message: error.message.clone(),
+                ..Diagnostic::default()
+            })
+            .collect();
+        if analysis.error.is_none() {
+            document.analysis = analysis;
+            document.analysed = document.text.clone();
+        }
+        self.documents.insert(uri, document);
+        diagnostics
+    }
+
+    pub fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let position = params.text_document_position_params;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let reference = document.analysis.reference_at(document.analysed.offset(position.position))?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```atlas\n{}\n```", reference.hover),
+            }),
+            range: Some(document.analysed.range(reference.span.clone())),
+        })
+    }
+
+    pub fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = params.text_document_position_params;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let offset = document.analysed.offset(position.position);
+        let analysis = &document.analysis;
+        //Declarations lead to themselves
+        let declarations = analysis.functions.iter().chain(analysis.classes.iter().flat_map(|class| {
+            std::iter::once(&class.item).chain(&class.fields).chain(&class.methods)
+        }));
+        let span = declarations
+            .map(|item| &item.name_span)
+            .find(|span| span.contains(&offset))
+            .cloned()
+            .or_else(|| analysis.reference_at(offset)?.definition.clone())?;
+        Some(GotoDefinitionResponse::Scalar(Location::new(position.text_document.uri, document.analysed.range(span))))
+    }
+
+    pub fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let position = params.text_document_position;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let analysis = &document.analysis;
+        //Offset of the cursor in the analysed text
+        let offset = document.analysed.offset(position.position);
+        //Start of the word being completed
+        let before = &document.text.text[..document.text.offset(position.position)];
+        let start = before.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_').len();
+
+        if let Some(target) = before[..start].strip_suffix('.') {
+            let name_start = target.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_').len();
+            let name = &target[name_start..];
+            let (class, inside) = if name == "self" {
+                (analysis.classes.iter().find(|class| class.item.span.contains(&offset))?, true)
+            } else {
+                let ty = analysis.variable(name, offset)?.ty;
+                let class = analysis.classes.iter().find(|class| class.item.name == ty)?;
+                (class, class.item.span.contains(&offset))
+            };
+            let members = class
+                .fields
+                .iter()
+                .map(|field| (field, CompletionItemKind::FIELD))
+                .chain(class.methods.iter().map(|method| (method, CompletionItemKind::METHOD)))
+                .filter(|(item, _)| (inside || item.is_public) && !item.is_static)
+                .map(|(item, kind)| completion_item(&item.name, kind, &item.detail))
+                .collect();
+            return Some(CompletionResponse::Array(members));
+        }
+
+        let mut items = Vec::new();
+        let mut variables = analysis.variables_at(offset);
+        variables.sort_by(|a, b| a.name.cmp(&b.name));
+        for variable in variables {
+            items.push(completion_item(&variable.name, CompletionItemKind::VARIABLE, &variable.ty));
+        }
+        for function in analysis.functions.iter() {
+            items.push(completion_item(&function.name, CompletionItemKind::FUNCTION, &function.detail));
+        }
+        for class in analysis.classes.iter() {
+            items.push(completion_item(&class.item.name, CompletionItemKind::CLASS, &class.item.detail));
+        }
+        for function in self.std_functions.iter() {
+            if analysis.functions.iter().any(|f| f.name == function.name) {
+                continue;
+            }
+            let import = format!("import \"std/{}\"", function.library);
+            let mut item = completion_item(&function.name, CompletionItemKind::FUNCTION, &function.signature);
+            item.detail = Some(format!("{} ({})", function.signature, import));
+            //Using a function of a library imports it
+            if !document.text.text.contains(&import) {
+                item.additional_text_edits = Some(vec![TextEdit::new(Range::default(), format!("{}\n", import))]);
+            }
+            items.push(item);
+        }
+        Some(CompletionResponse::Array(items))
+    }
+}
+
+impl Text {
+    fn new(text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, line_starts }
+    }
+
+    fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        let character = self.text[self.line_starts[line]..offset].encode_utf16().count();
+        Position::new(line as u32, character as u32)
+    }
+
+    /// Offset of `position`, columns are counted in UTF-16 code units
+    fn offset(&self, position: Position) -> usize {
+        let Some(start) = self.line_starts.get(position.line as usize) else {
+            return self.text.len();
+        };
+        let mut units = 0;
+        for (i, c) in self.text[*start..].char_indices() {
+            if units >= position.character as usize || c == '\n' {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        self.text.len()
+    }
+
+    fn range(&self, span: Span) -> Range {
+        Range::new(self.position(span.start), self.position(span.end))
+    }
+
+}
+
+impl Analysis {
+    /// Innermost reference at `offset`
+    fn reference_at(&self, offset: usize) -> Option<&Reference> {
+        self.references
+            .iter()
+            .filter(|reference| reference.span.start <= offset && offset < reference.span.end)
+            .min_by_key(|reference| (reference.span.len(), reference.definition.is_none()))
+    }
+
+    /// Latest declaration of the variable `name` before `offset`, in the function around it
+    fn variable(&self, name: &str, offset: usize) -> Option<Variable> {
+        self.variables_at(offset).into_iter().find(|variable| variable.name == name)
+    }
+
+    /// Variables declared before `offset` in the function around it, one per name
+    fn variables_at(&self, offset: usize) -> Vec<Variable> {
+        let mut variables: Vec<Variable> = Vec::new();
+        for (scope, variable) in self.variables.iter() {
+            if scope.contains(&offset) && variable.name_span.start < offset {
+                variables.retain(|v| v.name != variable.name);
+                variables.push(variable.clone());
+            }
+        }
+        variables
+    }
+}
+
+/// Functions a program importing `std/<library>` can call
+fn std_library(library: &'static str) -> Vec<StdFunction> {
+    let source = format!("import \"std/{}\"\n", library);
+    let bump = Bump::new();
+    let ast_arena = AstArena::new(&bump);
+    let Ok(program) = parse(DOCUMENT, &ast_arena, source.clone()) else {
+        return Vec::new();
+    };
+    let hir_arena = HirArena::new();
+    let Ok(hir) = AstSyntaxLoweringPass::new(&hir_arena, &program, &ast_arena, source).lower() else {
+        return Vec::new();
+    };
+    hir.signature
+        .functions
+        .iter()
+        .map(|(name, signature)| StdFunction {
+            library,
+            name: name.to_string(),
+            signature: function_detail(name, &signature.params, signature.return_ty),
+        })
+        .collect()
+}
+
+fn completion_item(label: &str, kind: CompletionItemKind, detail: &str) -> CompletionItem {
+    CompletionItem {
+        label: label.to_string(),
+        kind: Some(kind),
+        detail: Some(detail.to_string()),
+        ..CompletionItem::default()
+    }
+}
+
+fn function_detail(name: &str, params: &[&HirFunctionParameterSignature], return_ty: &HirTy) -> String {
+    let params = params
+        .iter()
+        .map(|param| format!("{}: {}", param.name, param.ty))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("func {}({}) -> {}", name, params, return_ty)
+}
+
+fn error(error: &dyn miette::Diagnostic) -> PassError {
+    let mut message = error.to_string();
+    for label in error.labels().into_iter().flatten() {
+        if let Some(label) = label.label() {
+            message.push_str(&format!("\n{}", label));
+        }
+    }
+    if let Some(help) = error.help() {
+        message.push_str(&format!("\nhelp: {}", help));
+    }
+    PassError {
+        message,
+        code: error.code().map(|code| code.to_string()),
+        span: error
+            .labels()
+            .and_then(|mut labels| labels.next())
+            .map(|label| label.offset()..label.offset() + label.len()),
+    }
+}
+
+/// Run the passes on `source` & keep what they found
+fn analyse(source: &str) -> Analysis {
+    //Some passes still panic on what they don't support, e.g. the standard libraries using enums
+    std::panic::catch_unwind(|| run_passes(source)).unwrap_or_else(|_| Analysis {
+        error: Some(PassError {
+            message: "the compiler crashed on this document".to_string(),
+            code: None,
+            span: None,
+        }),
+        ..Analysis::default()
+    })
+}
+
+fn run_passes(source: &str) -> Analysis {
+    let mut analysis = Analysis::default();
+    let bump = Bump::new();
+    let ast_arena = AstArena::new(&bump);
+    let program = match parse(DOCUMENT, &ast_arena, source.to_string()) {
+        Ok(program) => program,
+        Err(e) => {
+            analysis.error = Some(error(&e));
+            return analysis;
+        }
+    };
+    let hir_arena = HirArena::new();
+    let lower = AstSyntaxLoweringPass::new(&hir_arena, &program, &ast_arena, source.to_string());
+    let mut hir = match lower.lower() {
+        Ok(hir) => hir,
+        Err(e) => {
+            analysis.error = Some(error(&e));
+            return analysis;
+        }
+    };
+    let mut type_checker = TypeChecker::new(&hir_arena, source.to_string());
+    if let Err(e) = type_checker.check(&mut hir) {
+        analysis.error = Some(error(&e));
+    }
+    analysis.declarations(&hir);
+    for variable in type_checker.resolved() {
+        let name = &source[variable.name_span.clone()];
+        let name = name.split(':').next().unwrap_or(name).trim();
+        analysis.references.push(Reference {
+            span: variable.span.clone(),
+            hover: format!("{}: {}", name, variable.ty),
+            definition: Some(variable.name_span.clone()),
+        });
+        //Declarations resolve to themselves
+        if variable.span == variable.name_span {
+            analysis.declare_variable(name, variable.name_span.clone(), variable.ty);
+        }
+    }
+    Walker { hir: &hir, analysis: &mut analysis }.module();
+    analysis
+}
+
+impl Analysis {
+    fn declarations(&mut self, hir: &HirModule) {
+        for function in hir.body.functions.values() {
+            let signature = function.signature;
+            self.functions.push(Item {
+                name: function.name.to_string(),
+                span: function.span.clone(),
+                name_span: function.name_span.clone(),
+                detail: function_detail(function.name, &signature.params, signature.return_ty),
+                is_public: signature.vis == HirVisibility::Public,
+                is_static: false,
+            });
+            self.scopes.push(function.span.clone());
+            self.declare_params(&signature.params);
+        }
+        for class in hir.body.classes.values() {
+            let fields = class
+                .fields
+                .iter()
+                .map(|field| Item {
+                    name: field.name.to_string(),
+                    span: field.span.clone(),
+                    name_span: field.name_span.clone(),
+                    detail: format!("{}: {}", field.name, field.ty),
+                    is_public: field.vis == HirVisibility::Public,
+                    is_static: false,
+                })
+                .collect();
+            let methods = class
+                .methods
+                .iter()
+                .map(|method| {
+                    self.scopes.push(method.span.clone());
+                    self.declare_params(&method.signature.params);
+                    Item {
+                        name: method.name.to_string(),
+                        span: method.span.clone(),
+                        name_span: method.name_span.clone(),
+                        detail: function_detail(method.name, &method.signature.params, method.signature.return_ty),
+                        is_public: method.signature.vis == HirVisibility::Public,
+                        is_static: method.signature.modifier == HirClassMethodModifier::Static,
+                    }
+                })
+                .collect();
+            self.scopes.push(class.constructor.span.clone());
+            self.declare_params(&class.constructor.params);
+            self.scopes.push(class.destructor.span.clone());
+            self.classes.push(Class {
+                item: Item {
+                    name: class.name.to_string(),
+                    span: class.span.clone(),
+                    name_span: class.name_span.clone(),
+                    detail: format!("class {}", class.name),
+                    is_public: class.signature.vis == HirVisibility::Public,
+                    is_static: false,
+                },
+                fields,
+                methods,
+            });
+        }
+    }
+
+    fn declare_params(&mut self, params: &[&HirFunctionParameterSignature]) {
+        for param in params {
+            self.declare_variable(param.name, param.span.clone(), param.ty);
+            self.references.push(Reference {
+                span: param.name_span.clone(),
+                hover: format!("{}: {}", param.name, param.ty),
+                definition: Some(param.span.clone()),
+            });
+        }
+    }
+
+    /// The variable is visible in the innermost scope around its declaration
+    fn declare_variable(&mut self, name: &str, name_span: Span, ty: &HirTy) {
+        let scope = self
+            .scopes
+            .iter()
+            .filter(|scope| scope.contains(&name_span.start))
+            .min_by_key(|scope| scope.len())
+            .cloned()
+            .unwrap_or(name_span.clone());
+        self.variables.push((
+            scope,
+            Variable {
+                name: name.to_string(),
+                name_span,
+                ty: ty.to_string(),
+            },
+        ));
+    }
+}
+
+/// Collects the references of the typed HIR
+struct Walker<'a, 'hir> {
+    hir: &'a HirModule<'hir>,
+    analysis: &'a mut Analysis,
+}
+
+impl<'a, 'hir> Walker<'a, 'hir> {
+    fn module(&mut self) {
+        let hir = self.hir;
+        for function in hir.body.functions.values() {
+            self.params(&function.signature.params);
+            if let Some(span) = function.signature.return_ty_span.clone() {
+                self.ty(function.signature.return_ty, span);
+            }
+            self.block(&function.body);
+        }
+        for class in hir.body.classes.values() {
+            for field in class.fields.iter() {
+                self.ty(field.ty, field.ty_span.clone());
+            }
+            for method in class.methods.iter() {
+                self.params(&method.signature.params);
+                if let Some(span) = method.signature.return_ty_span.clone() {
+                    self.ty(method.signature.return_ty, span);
+                }
+                self.block(&method.body);
+            }
+            self.params(&class.constructor.params);
+            self.block(&class.constructor.body);
+            self.block(&class.destructor.body);
+        }
+    }
+
+    fn params(&mut self, params: &[&HirFunctionParameterSignature<'hir>]) {
+        for param in params {
+            self.ty(param.ty, param.ty_span.clone());
+        }
+    }
+
+    /// Types naming a class lead to it
+    fn ty(&mut self, ty: &HirTy, span: Span) {
+        let mut ty = ty;
+        while let HirTy::List(list) = ty {
+            ty = list.inner;
+        }
+        if let HirTy::Named(named) = ty {
+            if let Some(class) = self.class(named.name) {
+                self.analysis.references.push(Reference {
+                    span,
+                    hover: format!("class {}", class.name),
+                    definition: Some(class.name_span.clone()),
+                });
+            }
+        }
+    }
+
+    fn class(&self, name: &str) -> Option<&'a HirClass<'hir>> {
+        self.hir.body.classes.get(name)
+    }
+
+    fn block(&mut self, block: &HirBlock<'hir>) {
+        for statement in block.statements.iter() {
+            match statement {
+                HirStatement::Expr(e) => self.expr(&e.expr),
+                HirStatement::Return(r) => self.expr(&r.value),
+                HirStatement::Let(l) | HirStatement::Const(l) => {
+                    if let (Some(ty), Some(span)) = (l.ty, l.ty_span.clone()) {
+                        self.ty(ty, span);
+                    }
+                    self.expr(&l.value);
+                }
+                HirStatement::IfElse(i) => {
+                    self.expr(&i.condition);
+                    self.block(&i.then_branch);
+                    if let Some(else_branch) = &i.else_branch {
+                        self.block(else_branch);
+                    }
+                }
+                HirStatement::While(w) => {
+                    self.expr(&w.condition);
+                    self.block(&w.body);
+                }
+                HirStatement::_Block(b) => self.block(b),
+                HirStatement::Break(_) | HirStatement::Continue(_) => {}
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &HirExpr<'hir>) {
+        let ty = expr.ty();
+        if !matches!(ty, HirTy::Uninitialized(_) | HirTy::Unit(_)) && !matches!(expr, HirExpr::Ident(_)) {
+            self.analysis.references.push(Reference {
+                span: expr.span(),
+                hover: ty.to_string(),
+                definition: None,
+            });
+        }
+        match expr {
+            HirExpr::Assign(e) => {
+                self.expr(&e.lhs);
+                self.expr(&e.rhs);
+            }
+            HirExpr::HirBinaryOp(e) => {
+                self.expr(&e.lhs);
+                self.expr(&e.rhs);
+            }
+            HirExpr::Call(call) => {
+                match call.callee.as_ref() {
+                    HirExpr::Ident(callee) => {
+                        let hir = self.hir;
+                        if let Some(signature) = hir.signature.functions.get(callee.name) {
+                            self.analysis.references.push(Reference {
+                                span: callee.span.clone(),
+                                hover: function_detail(callee.name, &signature.params, signature.return_ty),
+                                definition: hir.body.functions.get(callee.name).map(|f| f.name_span.clone()),
+                            });
+                        }
+                    }
+                    HirExpr::FieldAccess(access) => {
+                        self.expr(&access.target);
+                        self.member(access.target.ty(), access.field.name, access.field.span.clone());
+                    }
+                    HirExpr::StaticAccess(access) => self.static_access(access.target.name, access.target.span.clone(), access.field.name, access.field.span.clone()),
+                    callee => self.expr(callee),
+                }
+                for arg in call.args.iter() {
+                    self.expr(arg);
+                }
+            }
+            HirExpr::Unary(e) => self.expr(&e.expr),
+            HirExpr::Casting(e) => self.expr(&e.expr),
+            HirExpr::Indexing(e) => {
+                self.expr(&e.target);
+                self.expr(&e.index);
+            }
+            HirExpr::ListLiteral(e) => {
+                for item in e.items.iter() {
+                    self.expr(item);
+                }
+            }
+            HirExpr::NewArray(e) => self.expr(&e.size),
+            HirExpr::NewObj(e) => {
+                self.ty(e.ty, e.span.clone());
+                for arg in e.args.iter() {
+                    self.expr(arg);
+                }
+            }
+            HirExpr::Delete(e) => self.expr(&e.expr),
+            HirExpr::FieldAccess(access) => {
+                self.expr(&access.target);
+                self.member(access.target.ty(), access.field.name, access.field.span.clone());
+            }
+            HirExpr::StaticAccess(access) => self.static_access(access.target.name, access.target.span.clone(), access.field.name, access.field.span.clone()),
+            HirExpr::Match(m) => {
+                self.expr(&m.value);
+                for arm in m.arms.iter() {
+                    for pattern in arm.patterns.iter() {
+                        match pattern {
+                            HirPattern::Variant(v) => self.static_access(v.access.target.name, v.access.target.span.clone(), v.access.field.name, v.access.field.span.clone()),
+                            HirPattern::Literal(_) | HirPattern::Wildcard(_) => {}
+                        }
+                    }
+                    self.block(&arm.body);
+                    if let Some(value) = &arm.value {
+                        self.expr(value);
+                    }
+                }
+            }
+            HirExpr::Ident(_)
+            | HirExpr::FloatLiteral(_)
+            | HirExpr::CharLiteral(_)
+            | HirExpr::IntegerLiteral(_)
+            | HirExpr::UnitLiteral(_)
+            | HirExpr::BooleanLiteral(_)
+            | HirExpr::UnsignedIntegerLiteral(_)
+            | HirExpr::SelfLiteral(_)
+            | HirExpr::StringLiteral(_) => {}
+        }
+    }
+
+    /// Field or method `name` of an instance of `ty`
+    fn member(&mut self, ty: &HirTy, name: &str, span: Span) {
+        let HirTy::Named(named) = ty else {
+            return;
+        };
+        let Some(class) = self.class(named.name) else {
+            return;
+        };
+        let reference = match class.fields.iter().find(|field| field.name == name) {
+            Some(field) => Reference {
+                span,
+                hover: format!("{}.{}: {}", class.name, field.name, field.ty),
+                definition: Some(field.name_span.clone()),
+            },
+            None => {
+                let Some(method) = class.methods.iter().find(|method| method.name == name) else {
+                    return;
+                };
+                Reference {
+                    span,
+                    hover: function_detail(&format!("{}.{}", class.name, method.name), &method.signature.params, method.signature.return_ty),
+                    definition: Some(method.name_span.clone()),
+                }
+            }
+        };
+        self.analysis.references.push(reference);
+    }
+
+    /// `class_name::name`, a constant or a static method
+    fn static_access(&mut self, class_name: &str, class_span: Span, name: &str, span: Span) {
+        let Some(class) = self.class(class_name) else {
+            return;
+        };
+        self.analysis.references.push(Reference {
+            span: class_span,
+            hover: format!("class {}", class.name),
+            definition: Some(class.name_span.clone()),
+        });
+        if let Some(method) = class.methods.iter().find(|method| method.name == name) {
+            self.analysis.references.push(Reference {
+                span,
+                hover: function_detail(&format!("{}::{}", class.name, method.name), &method.signature.params, method.signature.return_ty),
+                definition: Some(method.name_span.clone()),
+            });
+        } else if let Some(constant) = class.signature.constants.get(name) {
+            self.analysis.references.push(Reference {
+                span,
+                hover: format!("const {}::{}: {}", class.name, constant.name, constant.ty),
+                definition: Some(constant.name_span.clone()),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{
+        CompletionParams, CompletionResponse, GotoDefinitionParams, GotoDefinitionResponse, HoverContents, HoverParams,
+        Position, Range, TextDocumentIdentifier, TextDocumentPositionParams, Uri,
+    };
+
+    use super::Server;
+
+    const SOURCE: &str = "class Point {
+public:
+    x: int64;
+    y: int64;
+    Point(x: int64, y: int64) {
+        self.x = x;
+        self.y = y;
+    }
+    ~Point() {}
+    func norm(self) -> int64 {
+        return self.x * self.x + self.y * self.y;
+    }
+}
+func main() -> int64 {
+    let p = new Point(3, 4);
+    return p.norm();
+}";
+
+    fn at(uri: &Uri, line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams::new(TextDocumentIdentifier::new(uri.clone()), Position::new(line, character))
+    }
+
+    fn labels(response: Option<CompletionResponse>) -> Vec<String> {
+        match response {
+            Some(CompletionResponse::Array(items)) => items.into_iter().map(|item| item.label).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn diagnostics_hovers_definitions_and_completions() {
+        let mut server = Server::new();
+        let uri: Uri = "file:///point.atlas".parse().unwrap();
+        assert!(server.update(uri.clone(), SOURCE.to_string()).is_empty());
+
+        let hover = |line, character| {
+            let params = HoverParams {
+                text_document_position_params: at(&uri, line, character),
+                work_done_progress_params: Default::default(),
+            };
+            match server.hover(params).map(|hover| hover.contents) {
+                Some(HoverContents::Markup(markup)) => markup.value,
+                _ => String::new(),
+            }
+        };
+        assert_eq!(hover(15, 11), "```atlas\np: Point\n```");
+        assert_eq!(hover(15, 14), "```atlas\nfunc Point.norm() -> int64\n```");
+        assert_eq!(hover(10, 20), "```atlas\nPoint.x: int64\n```");
+
+        let definition = |line, character| {
+            let params = GotoDefinitionParams {
+                text_document_position_params: at(&uri, line, character),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            };
+            match server.definition(params) {
+                Some(GotoDefinitionResponse::Scalar(location)) => Some(location.range),
+                _ => None,
+            }
+        };
+        assert_eq!(definition(15, 14), Some(Range::new(Position::new(9, 9), Position::new(9, 13))));
+        assert_eq!(definition(14, 18), Some(Range::new(Position::new(0, 6), Position::new(0, 11))));
+        assert_eq!(definition(15, 11), Some(Range::new(Position::new(14, 8), Position::new(14, 9))));
+
+        //Members are completed from the last analysis while the document doesn't parse
+        let broken = SOURCE.replace("return p.norm();", "return p.");
+        let diagnostics = server.update(uri.clone(), broken);
+        assert_eq!(diagnostics.len(), 1);
+        let complete = |line, character| {
+            labels(server.completion(CompletionParams {
+                text_document_position: at(&uri, line, character),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+                context: None,
+            }))
+        };
+        assert_eq!(complete(15, 13), ["x", "y", "norm"]);
+        let names = complete(15, 4);
+        assert!(names.contains(&"p".to_string()) && names.contains(&"main".to_string()));
+        assert!(names.contains(&"println".to_string()));
+    }
+}
@@ -1,5 +1,8 @@
-use atlas_77::{build, run, CompilationFlag};
-use clap::{command, Parser};
+use atlas_77::{build, debug, exec, fmt, lsp, repl, run, test, CompilationFlag, RunOptions};
+use clap::Parser;
+use miette::IntoDiagnostic;
+use std::io::Write;
+use std::path::PathBuf;
 
 #[derive(Parser)] // requires `derive` feature
 #[command(name = "Atlas77")]
@@ -22,11 +25,24 @@ enum AtlasRuntimeCLI {
         release: bool,
         #[arg(short = 'd', long)]
         debug: bool,
+        /// Maximum number of values on the VM stack
+        #[arg(long)]
+        stack_size: Option<usize>,
+        /// Print how long the program took
+        #[arg(long)]
+        time: bool,
+        /// Print the calls, instructions, allocations & time of every function,
+        /// and write the folded call stacks (for flamegraph tools) to FILE
+        #[arg(long, value_name = "FILE", num_args = 0..=1, default_missing_value = "profile.folded")]
+        profile: Option<PathBuf>,
+        /// Run the byte encoding of the program instead of its instructions
+        #[arg(long)]
+        assembled: bool,
     },
     #[command(
         arg_required_else_help = true,
         about = "Compile a local package and all of its dependencies",
-        long_about = "Compile a local package and all of its dependencies. The output will be written to the current directory as `output.atlas_c`. NB: That output file is not executable."
+        long_about = "Compile a local package and all of its dependencies. The output will be written to the current directory as `output.atlasc`. It can then be run with `atlas_77 exec`."
     )]
     Build {
         file_path: String,
@@ -35,6 +51,70 @@ enum AtlasRuntimeCLI {
         #[arg(short = 'd', long)]
         debug: bool,
     },
+    #[command(
+        arg_required_else_help = true,
+        about = "Run a compiled `.atlasc` file",
+        long_about = "Run a `.atlasc` file produced by `atlas_77 build` without compiling the source again."
+    )]
+    Exec {
+        file_path: String,
+        /// Maximum number of values on the VM stack
+        #[arg(long)]
+        stack_size: Option<usize>,
+        /// Print how long the program took
+        #[arg(long)]
+        time: bool,
+        /// Print the calls, instructions, allocations & time of every function,
+        /// and write the folded call stacks (for flamegraph tools) to FILE
+        #[arg(long, value_name = "FILE", num_args = 0..=1, default_missing_value = "profile.folded")]
+        profile: Option<PathBuf>,
+        /// Run the byte encoding of the program instead of its instructions
+        #[arg(long)]
+        assembled: bool,
+    },
+    #[command(
+        arg_required_else_help = true,
+        about = "Run a source file under the line debugger",
+        long_about = "Run a source file under the line debugger. It stops on the first line, or on the given breakpoints, then reads commands (`help` lists them) from stdin."
+    )]
+    Debug {
+        file_path: String,
+        /// Line to stop at, can be repeated
+        #[arg(short = 'b', long = "break")]
+        breakpoints: Vec<usize>,
+        /// Maximum number of values on the VM stack
+        #[arg(long)]
+        stack_size: Option<usize>,
+    },
+    #[command(
+        about = "Evaluate declarations & statements interactively",
+        long_about = "Evaluate declarations & statements interactively. Functions, classes & variables stay defined for the next inputs, `:vars` lists the variables & `:quit` leaves."
+    )]
+    Repl,
+    #[command(
+        about = "Start the language server",
+        long_about = "Start the language server, speaking LSP over stdio. It publishes diagnostics as documents change, and answers hovers, go-to-definition & completions."
+    )]
+    Lsp,
+    #[command(
+        about = "Run the tests of Atlas source files",
+        long_about = "Run the functions marked `#[test]` in Atlas source files, each in a reset VM. `std/test` provides `assert` & `assert_eq`. Directories are searched recursively for `.atlas` files, the current directory is used if no path is given."
+    )]
+    Test {
+        /// Files or directories to test
+        paths: Vec<PathBuf>,
+    },
+    #[command(
+        about = "Format Atlas source files",
+        long_about = "Format Atlas source files in place, comments included. Directories are searched recursively for `.atlas` files, the current directory is used if no path is given."
+    )]
+    Fmt {
+        /// Files or directories to format
+        paths: Vec<PathBuf>,
+        /// Don't write anything, list the files that aren't formatted & exit with 1 if there are some
+        #[arg(long)]
+        check: bool,
+    },
 }
 
 
@@ -42,12 +122,17 @@ fn main() -> miette::Result<()> {
     //Set Backtrace to 1
     std::env::set_var("RUST_BACKTRACE", "1");
     match AtlasRuntimeCLI::parse() {
-        AtlasRuntimeCLI::Run { file_path, release, debug } => {
+        AtlasRuntimeCLI::Run { file_path, release, debug, stack_size, time, profile, assembled } => {
             if release && debug {
                 eprintln!("Cannot run in both release and debug mode");
                 std::process::exit(1);
             }
-            run(file_path, if release { CompilationFlag::Release } else { CompilationFlag::Debug })
+            let code = run(
+                file_path,
+                if release { CompilationFlag::Release } else { CompilationFlag::Debug },
+                run_options(stack_size, time, profile, assembled),
+            )?;
+            exit(code)
         }
         AtlasRuntimeCLI::Build { file_path, release, debug } => {
             if release && debug {
@@ -56,5 +141,52 @@ fn main() -> miette::Result<()> {
             }
             build(file_path, if release { CompilationFlag::Release } else { CompilationFlag::Debug })
         }
+        AtlasRuntimeCLI::Exec { file_path, stack_size, time, profile, assembled } => {
+            let code = exec(file_path, run_options(stack_size, time, profile, assembled))?;
+            exit(code)
+        }
+        AtlasRuntimeCLI::Debug { file_path, breakpoints, stack_size } => {
+            let code = debug(file_path, breakpoints, run_options(stack_size, false, None, false))?;
+            exit(code)
+        }
+        AtlasRuntimeCLI::Repl => repl(),
+        AtlasRuntimeCLI::Lsp => lsp(),
+        AtlasRuntimeCLI::Test { mut paths } => {
+            if paths.is_empty() {
+                paths.push(PathBuf::from("."));
+            }
+            let code = test(paths)?;
+            exit(code)
+        }
+        AtlasRuntimeCLI::Fmt { mut paths, check } => {
+            if paths.is_empty() {
+                paths.push(PathBuf::from("."));
+            }
+            let code = fmt(paths, check)?;
+            exit(code)
+        }
+    }
+}
+
+fn run_options(stack_size: Option<usize>, show_time: bool, profile: Option<PathBuf>, assembled: bool) -> RunOptions {
+    let mut options = RunOptions {
+        show_time,
+        profile,
+        assembled,
+        ..RunOptions::default()
+    };
+    if let Some(stack_size) = stack_size {
+        options.stack_size = stack_size;
+    }
+    options
+}
+
+/// Forward the exit code of the program to the shell
+fn exit(code: i32) -> miette::Result<()> {
+    if code != 0 {
+        //`process::exit` doesn't flush stdout
+        std::io::stdout().flush().into_diagnostic()?;
+        std::process::exit(code);
     }
+    Ok(())
 }
@@ -0,0 +1,458 @@
+//! Session behind `atlas_77 repl`.
+//!
+//! Declarations (`import`, `func`, `class`...) are kept & compiled again with every later input.
+//! Statements are wrapped in a function taking the variables defined so far as arguments,
+//! the values they end up with are handed back to the session by a native call at the end of it.
+//! Every input runs in a new VM, but they all share the session's heap.
+//! Diagnostics point in the input itself, the source it was compiled as isn't shown.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use bumpalo::Bump;
+use miette::{Diagnostic, LabeledSpan, NamedSource, SourceCode};
+use thiserror::Error;
+
+use crate::atlas_c::atlas_hir::{expr::{HirExpr, UnaryOpExpr}, stmt::HirStatement, HirModule};
+use crate::atlas_vm::errors::{RuntimeError, RuntimeErrorReport, TraceFrame};
+use crate::atlas_vm::memory::{object_map::Memory, vm_data::VMData};
+use crate::atlas_vm::native::NativeModule;
+use crate::atlas_vm::runtime::{arena::RuntimeArena, binary};
+use crate::atlas_vm::sandbox::Capabilities;
+use crate::atlas_vm::Atlas77VM;
+use crate::engine::{AtlasError, Engine};
+
+/// Function the statements of an input are wrapped in
+const ENTRY: &str = "repl_input";
+/// Native receiving the variables once the statements ran
+const KEEP: &str = "repl_keep";
+/// Variable holding the value of a trailing expression
+const VALUE: &str = "repl_value";
+
+const DECLARATION_KEYWORDS: [&str; 10] =
+    ["import", "func", "class", "struct", "extern", "public", "private", "enum", "union", "concept"];
+
+struct Variable {
+    name: String,
+    ty: String,
+    value: VMData,
+}
+
+pub struct Repl<'run> {
+    arena: &'run Bump,
+    engine: Engine,
+    /// Every declaration accepted so far
+    declarations: String,
+    variables: Vec<Variable>,
+    memory: Memory<'run>,
+    inputs: usize,
+}
+
+//Diagnostics carry their source code, they are only built once per failed input
+#[allow(clippy::result_large_err)]
+impl<'run> Repl<'run> {
+    /// Programs of every input are allocated in `arena`, so the heap can outlive their VM
+    pub fn new(arena: &'run Bump) -> Self {
+        Self {
+            arena,
+            engine: Engine::new(),
+            declarations: String::new(),
+            variables: Vec::new(),
+            memory: Memory::new(256),
+            inputs: 0,
+        }
+    }
+
+    /// Whether `input` still has unclosed braces, the next line should be appended to it
+    pub fn is_incomplete(input: &str) -> bool {
+        let open = input.chars().filter(|c| *c == '{').count();
+        let close = input.chars().filter(|c| *c == '}').count();
+        open > close
+    }
+
+    /// Names & types of the variables defined so far
+    pub fn variables(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.variables.iter().map(|v| (v.name.as_str(), v.ty.as_str()))
+    }
+
+    /// Declare or run `input`, returns the value of a trailing expression if there is one
+    ///
+    /// Nothing is kept from an input that fails
+    pub fn eval(&mut self, input: &str) -> Result<Option<String>, InputError> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(None);
+        }
+        self.inputs += 1;
+        let name = format!("<repl:{}>", self.inputs);
+        let mut placement = Placement {
+            name: &name,
+            input,
+            start: self.declarations.len(),
+            inserted: None,
+        };
+        let first_word = input.split(|c: char| !c.is_alphanumeric()).next().unwrap_or_default();
+        if DECLARATION_KEYWORDS.contains(&first_word) {
+            let source = format!("{}{}\n", self.declarations, input);
+            self.engine.analyse(&name, &source, |_| Ok(())).map_err(|e| placement.locate(e))?;
+            self.declarations = source;
+            return Ok(None);
+        }
+
+        let mut statements = input.to_string();
+        if !statements.ends_with(';') && !statements.ends_with('}') {
+            statements.push(';');
+        }
+        //A trailing expression is stored in `VALUE` to be printed
+        let (source, offset) = self.wrap(&statements, "");
+        placement.start = offset;
+        let trailing = self
+            .engine
+            .analyse(&name, &source, |hir| Ok(trailing_expr(&hir)))
+            .map_err(|e| placement.locate(e))?;
+        if let Some(start) = trailing {
+            let binding = format!("let {} = ", VALUE);
+            placement.inserted = Some((start - offset, binding.len()));
+            statements.insert_str(start - offset, &binding);
+            //A trailing block expression, e.g. a `match`, has no `;` yet
+            if !statements.ends_with(';') {
+                statements.push(';');
+            }
+        }
+        let (source, _) = self.wrap(&statements, "");
+        let defined = self
+            .engine
+            .analyse(&name, &source, |hir| Ok(defined_variables(&hir)))
+            .map_err(|e| placement.locate(e))?;
+
+        //Variables of this input shadow the previous ones
+        let mut variables: Vec<(String, String)> = self
+            .variables
+            .iter()
+            .filter(|v| !defined.iter().any(|(name, _)| *name == v.name))
+            .map(|v| (v.name.clone(), v.ty.clone()))
+            .collect();
+        variables.extend(defined);
+        let params = variables
+            .iter()
+            .map(|(name, ty)| format!("{}: {}", name, ty))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let args = variables.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(", ");
+
+        let kept = Rc::new(RefCell::new(None));
+        let keep = {
+            let kept = kept.clone();
+            let count = variables.len();
+            NativeModule::new("repl").raw(KEEP, &format!("({}) -> unit", params), move |state| {
+                //The references of the arguments are moved to the session
+                let mut values = (0..count).map(|_| state.stack.pop()).collect::<Result<Vec<_>, _>>()?;
+                values.reverse();
+                *kept.borrow_mut() = Some(values);
+                Ok(VMData::new_unit())
+            })
+        };
+        let mut engine = self.engine.clone();
+        engine.register(keep);
+        let (source, _) = self.wrap(&statements, &format!("{}({});\n", KEEP, args));
+        let import = "import \"repl\"\n";
+        let source = format!("{}{}", import, source);
+        placement.start += import.len();
+        let script = engine.compile(&name, &source).map_err(|e| placement.locate(e))?;
+
+        let program = binary::deserialize(script.bytecode(), &RuntimeArena::new(self.arena))?;
+        let mut vm = Atlas77VM::with_natives(
+            program,
+            RuntimeArena::new(self.arena),
+            engine.natives(),
+            &Capabilities::all(),
+        )
+        .map_err(report)?;
+        std::mem::swap(&mut vm.object_map, &mut self.memory);
+        //The session keeps its own reference to the arguments
+        let args = self.variables.iter().map(|v| v.value).collect::<Vec<_>>();
+        for arg in args.iter().filter(|arg| arg.is_object()) {
+            vm.object_map.rc_inc(arg.as_object());
+        }
+        let res = vm.invoke(ENTRY, &args);
+        std::mem::swap(&mut vm.object_map, &mut self.memory);
+        res.map_err(|e| placement.raised(e, vm.backtrace()))?;
+
+        let Some(values) = kept.borrow_mut().take() else {
+            //`exit` was called before the end of the statements
+            return Ok(None);
+        };
+        for old in std::mem::take(&mut self.variables) {
+            if old.value.is_object() {
+                self.memory.rc_dec(old.value.as_object()).map_err(report)?;
+            }
+        }
+        let mut printed = None;
+        for ((name, ty), value) in variables.into_iter().zip(values) {
+            if name == VALUE {
+                printed = Some(self.memory.describe(value));
+                if value.is_object() {
+                    self.memory.rc_dec(value.as_object()).map_err(report)?;
+                }
+            } else {
+                self.variables.push(Variable { name, ty, value });
+            }
+        }
+        Ok(printed)
+    }
+
+    /// Source of the declarations followed by the function running `statements`
+    ///
+    /// Returns it with the offset of `statements` in it
+    fn wrap(&self, statements: &str, epilogue: &str) -> (String, usize) {
+        let params = self
+            .variables
+            .iter()
+            .map(|v| format!("{}: {}", v.name, v.ty))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let header = format!("{}func {}({}) {{\n", self.declarations, ENTRY, params);
+        let offset = header.len();
+        (format!("{}{}\n{}}}", header, statements, epilogue), offset)
+    }
+}
+
+/// Runtime errors happening outside of the statements
+fn report(error: RuntimeError) -> RuntimeErrorReport {
+    RuntimeErrorReport::new(error, &[], None)
+}
+
+/// An [`AtlasError`] raised by an input, rendered against that input
+#[derive(Error, Debug)]
+#[error("{error}")]
+pub struct InputError {
+    pub error: AtlasError,
+    src: Option<NamedSource<String>>,
+    /// Labels of the error falling in the input, moved to their place in it
+    labels: Vec<LabeledSpan>,
+}
+
+impl Diagnostic for InputError {
+    fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        self.error.code()
+    }
+
+    fn severity(&self) -> Option<miette::Severity> {
+        self.error.severity()
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        self.error.help()
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.src.as_ref().map(|src| src as &dyn SourceCode)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        if self.labels.is_empty() {
+            return None;
+        }
+        Some(Box::new(self.labels.iter().cloned()))
+    }
+}
+
+//Errors happening outside of the statements have nothing to point at
+impl<E: Into<AtlasError>> From<E> for InputError {
+    fn from(error: E) -> Self {
+        Self { error: error.into(), src: None, labels: Vec::new() }
+    }
+}
+
+/// Where an input is in the source it was compiled as
+struct Placement<'a> {
+    name: &'a str,
+    input: &'a str,
+    /// Offset of the input in the source
+    start: usize,
+    /// Offset in the input & length of the text inserted in it
+    inserted: Option<(usize, usize)>,
+}
+
+impl Placement<'_> {
+    /// Offset in the input of `offset` in the source, if it's in the input
+    fn offset(&self, offset: usize) -> Option<usize> {
+        let mut offset = offset.checked_sub(self.start)?;
+        if let Some((at, len)) = self.inserted {
+            if offset >= at + len {
+                offset -= len;
+            } else if offset >= at {
+                offset = at;
+            }
+        }
+        (offset <= self.input.len()).then_some(offset)
+    }
+
+    fn source(&self) -> NamedSource<String> {
+        NamedSource::new(self.name, self.input.to_string())
+    }
+
+    /// `error` pointing in the input, labels outside of it are dropped
+    fn locate(&self, error: AtlasError) -> InputError {
+        let labels = error
+            .labels()
+            .into_iter()
+            .flatten()
+            .filter_map(|label| {
+                let start = self.offset(label.offset())?;
+                let len = label.len().min(self.input.len() - start);
+                Some(LabeledSpan::new(label.label().map(String::from), start, len))
+            })
+            .collect();
+        InputError { error, src: Some(self.source()), labels }
+    }
+
+    /// Report of a runtime error, frames outside of the input don't get a location
+    fn raised(&self, error: RuntimeError, trace: Vec<TraceFrame>) -> InputError {
+        let trace = trace
+            .into_iter()
+            .map(|frame| TraceFrame {
+                function: if frame.function == ENTRY { self.name.to_string() } else { frame.function },
+                span: frame.span.and_then(|span| Some((self.offset(span.offset())?, span.len()).into())),
+            })
+            .collect::<Vec<_>>();
+        let report = RuntimeErrorReport::new(error, &trace, Some((self.name.to_string(), self.input.to_string())));
+        let labels = report.labels().into_iter().flatten().collect();
+        InputError { error: AtlasError::Runtime(report), src: Some(self.source()), labels }
+    }
+}
+
+/// Start of the last statement of the entry function, if it's an expression worth printing
+fn trailing_expr(hir: &HirModule) -> Option<usize> {
+    let HirStatement::Expr(stmt) = hir.body.functions.get(ENTRY)?.body.statements.last()? else {
+        return None;
+    };
+    //The parser wraps every operand in a unary expression, even without an operator
+    let mut expr = &stmt.expr;
+    while let HirExpr::Unary(UnaryOpExpr { op: None, expr: inner, .. }) = expr {
+        expr = inner;
+    }
+    match expr {
+        HirExpr::Assign(_) | HirExpr::Delete(_) => None,
+        _ => Some(stmt.expr.span().start),
+    }
+}
+
+/// Variables declared at the top of the entry function, with the type they were given
+///
+/// `unit` values aren't worth keeping
+fn defined_variables(hir: &HirModule) -> Vec<(String, String)> {
+    let Some(function) = hir.body.functions.get(ENTRY) else {
+        return Vec::new();
+    };
+    let mut variables: Vec<(String, String)> = Vec::new();
+    for statement in function.body.statements.iter() {
+        if let HirStatement::Let(l) | HirStatement::Const(l) = statement {
+            let ty = l.ty.map(|ty| ty.to_string()).unwrap_or_default();
+            variables.retain(|(name, _)| name != l.name);
+            if ty != "unit" && ty != "uninitialized" {
+                variables.push((l.name.to_string(), ty));
+            }
+        }
+    }
+    variables
+}
+
+#[cfg(test)]
+mod tests {
+    use bumpalo::Bump;
+    use miette::Diagnostic;
+
+    use super::{InputError, Repl};
+
+    #[test]
+    fn session_keeps_declarations_and_variables() {
+        let bump = Bump::new();
+        let mut repl = Repl::new(&bump);
+        let mut eval = |input: &str| repl.eval(input).unwrap();
+        assert_eq!(eval("func square(x: int64) -> int64 {\n    return x * x;\n}"), None);
+        assert_eq!(eval("let a = 3;"), None);
+        assert_eq!(eval("square(a) + 1").as_deref(), Some("10"));
+        assert_eq!(eval("a = a + 1;"), None);
+        assert_eq!(eval("let names = new [str; 2];\nnames[1] = \"b\";"), None);
+        assert_eq!(eval("names[1]").as_deref(), Some("`String`: \"b\""));
+        assert_eq!(eval("a").as_deref(), Some("4"));
+
+        assert!(repl.eval("let b = a / 0;").is_err());
+        assert!(repl.eval("let c = missing(a);").is_err());
+        let variables = repl.variables().collect::<Vec<_>>();
+        assert_eq!(variables, [("a", "int64"), ("names", "[str]")]);
+        assert_eq!(repl.eval("a * 2").unwrap().as_deref(), Some("8"));
+    }
+
+    #[test]
+    fn enums_can_be_declared() {
+        let bump = Bump::new();
+        let mut repl = Repl::new(&bump);
+        assert_eq!(repl.eval("enum Color { Red, Green = 4 }").unwrap(), None);
+        assert_eq!(repl.eval("let c = Color::Green;").unwrap(), None);
+        assert_eq!(repl.eval("c == Color::Green").unwrap().as_deref(), Some("true"));
+        let arm = repl.eval("match c {\n    Color::Red => 1,\n    Color::Green => 2,\n};").unwrap();
+        assert_eq!(arm.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn unions_can_be_declared() {
+        let bump = Bump::new();
+        let mut repl = Repl::new(&bump);
+        assert_eq!(repl.eval("union Shape { Square(side: int64), Empty }").unwrap(), None);
+        assert_eq!(repl.eval("let s = Shape::Square(3);").unwrap(), None);
+        let area = repl.eval("match s {\n    Shape::Square(side) => side * side,\n    Shape::Empty => 0,\n};").unwrap();
+        assert_eq!(area.as_deref(), Some("9"));
+        assert_eq!(repl.eval("let o = Option::Some(2);").unwrap(), None);
+        let variables = repl.variables().collect::<Vec<_>>();
+        assert_eq!(variables, [("s", "Shape"), ("o", "Option<int64>")]);
+    }
+
+    #[test]
+    fn concepts_can_be_declared() {
+        let bump = Bump::new();
+        let mut repl = Repl::new(&bump);
+        assert_eq!(repl.eval("concept Shape { func area(self) -> int64; }").unwrap(), None);
+        let square = "class Square : impl Shape {\npublic:\n    side: int64;\n    func area(self) -> int64 { return self.side * self.side; }\n}";
+        assert_eq!(repl.eval(square).unwrap(), None);
+        let total = "func total<S>(a: S, b: S) -> int64 where S: impl Shape { return a.area() + b.area(); }";
+        assert_eq!(repl.eval(total).unwrap(), None);
+        assert_eq!(repl.eval("total(new Square(2), new Square(3))").unwrap().as_deref(), Some("13"));
+    }
+
+    #[test]
+    fn diagnostics_point_in_the_input() {
+        let bump = Bump::new();
+        let mut repl = Repl::new(&bump);
+        assert_eq!(repl.eval("func square(x: int64) -> int64 { return x * x; }").unwrap(), None);
+        assert_eq!(repl.eval("let a = 3;").unwrap(), None);
+        let labelled = |input: &str, err: &InputError| {
+            let mut spans = err
+                .labels()
+                .into_iter()
+                .flatten()
+                .map(|label| input[label.offset()..label.offset() + label.len()].to_string())
+                .collect::<Vec<_>>();
+            spans.sort();
+            spans
+        };
+
+        let input = "let b = a + 1;\nlet c: str = square(b);";
+        let err = repl.eval(input).unwrap_err();
+        assert_eq!(labelled(input, &err), ["c", "square(b);"]);
+        let input = "func f() -> int64 { return \"a\"; }";
+        let err = repl.eval(input).unwrap_err();
+        assert_eq!(labelled(input, &err), ["\"a\";", "int64 {"]);
+        let input = "let d = a / 0;";
+        let err = repl.eval(input).unwrap_err();
+        assert_eq!(labelled(input, &err), [input]);
+        assert!(!err.help().unwrap().to_string().contains("repl_input"));
+
+        //A block ending the input needs no `;`
+        let input = "match a {\n    3 => square(a),\n    _ => 0,\n}";
+        assert_eq!(repl.eval(input).unwrap().as_deref(), Some("9"));
+        assert_eq!(repl.eval("if a > 0 {\n    a = 0;\n}").unwrap(), None);
+        assert_eq!(repl.eval("a").unwrap().as_deref(), Some("0"));
+    }
+}
@@ -0,0 +1,128 @@
+//! Test runner behind `atlas_77 test`.
+//!
+//! Functions marked `#[test]` are called one after the other, the VM is reset between them.
+//! A test passes if it returns, `std/test`'s `assert` & `assert_eq` make it fail.
+
+use bumpalo::Bump;
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::atlas_c::atlas_frontend::parse;
+use crate::atlas_c::atlas_frontend::parser::{arena::AstArena, ast::AstItem, error::ParseResult};
+use crate::atlas_vm::errors::RuntimeErrorReport;
+use crate::engine::Engine;
+
+#[derive(Error, Diagnostic, Debug)]
+#[diagnostic(code(test::invalid_test), help("Tests are called without arguments"))]
+#[error("test `{name}` can't take parameters")]
+pub struct InvalidTestError {
+    pub name: String,
+    #[label = "this test takes parameters"]
+    pub span: SourceSpan,
+    #[source_code]
+    pub src: String,
+}
+
+/// A function marked `#[test]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestFunction {
+    pub name: String,
+    /// Span of the name
+    pub span: SourceSpan,
+    pub takes_params: bool,
+}
+
+/// How a test ended
+pub struct TestResult {
+    pub name: String,
+    pub failure: Option<RuntimeErrorReport>,
+}
+
+/// Functions of `source` marked `#[test]`, in the order they were written
+pub fn test_functions(path: &str, source: &str) -> ParseResult<Vec<TestFunction>> {
+    let bump = Bump::new();
+    let arena = AstArena::new(&bump);
+    let program = parse(path, &arena, source.to_string())?;
+    let tests = program
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            AstItem::Func(f) if f.attributes.iter().any(|a| a.name.name == "test") => Some(TestFunction {
+                name: f.name.name.to_string(),
+                span: SourceSpan::from(f.name.span.clone()),
+                takes_params: !f.args.is_empty(),
+            }),
+            _ => None,
+        })
+        .collect();
+    Ok(tests)
+}
+
+/// Compile `source` & run each of its tests in turn
+pub fn run_tests(engine: &Engine, path: &str, source: &str) -> miette::Result<Vec<TestResult>> {
+    let tests = test_functions(path, source)?;
+    if let Some(test) = tests.iter().find(|test| test.takes_params) {
+        return Err(InvalidTestError {
+            name: test.name.clone(),
+            span: test.span,
+            src: source.to_string(),
+        }
+        .into());
+    }
+    if tests.is_empty() {
+        return Ok(Vec::new());
+    }
+    let script = engine.compile(path, source)?;
+    let names = tests.iter().map(|test| test.name.as_str()).collect::<Vec<_>>();
+    let results = script.call_each(&names)?;
+    Ok(tests
+        .into_iter()
+        .zip(results)
+        .map(|(test, result)| TestResult {
+            name: test.name,
+            failure: result.err(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run_tests;
+    use crate::atlas_vm::errors::RuntimeError;
+    use crate::engine::Engine;
+
+    #[test]
+    fn passing_and_failing_tests() {
+        let source = "import \"std/test\"
+func square(x: int64) -> int64 {
+    return x * x;
+}
+#[test]
+func squares() {
+    assert_eq(square(3), 9);
+    assert(square(2) == 4);
+}
+#[test]
+func wrong_square() {
+    let s = square(2);
+    assert_eq(s, 5);
+}
+#[test]
+func strings() {
+    assert_eq(\"ab\", \"ab\");
+}
+func main() {}";
+        let results = run_tests(&Engine::new(), "square.atlas", source).unwrap();
+        let names = results.iter().map(|r| (r.name.as_str(), r.failure.is_none())).collect::<Vec<_>>();
+        assert_eq!(names, [("squares", true), ("wrong_square", false), ("strings", true)]);
+
+        let failure = results[1].failure.as_ref().unwrap();
+        assert!(matches!(&failure.error, RuntimeError::AssertionFailed(message) if message == "`4` isn't equal to `5`"));
+        //The failing assertion is pointed at
+        let span = failure.span.unwrap();
+        assert_eq!(&source[span.offset()..span.offset() + 9], "assert_eq");
+
+        let invalid = "#[test]\nfunc takes(x: int64) {}\nfunc main() {}";
+        assert!(run_tests(&Engine::new(), "invalid.atlas", invalid).is_err());
+    }
+}